@@ -0,0 +1,34 @@
+//! Compare the closed-form 2D solver with the generic SVD path:
+//! `cargo bench --bench estimate_2d`.
+use kabsch_umeyama::{estimate, estimate_2d, Array2};
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERATIONS: u32 = 100_000;
+
+fn time(name: &str, mut f: impl FnMut()) {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    println!("{name:<12} {elapsed:?} per call");
+}
+
+fn main() {
+    let src = Array2::from([[0., 0.], [1., 0.], [0., 2.], [3., 1.], [-1., 4.], [2., -2.]]);
+    let dst = Array2::from([
+        [1., 1.],
+        [1., 3.],
+        [-3., 1.],
+        [-1., 7.],
+        [-7., -1.],
+        [5., 5.],
+    ]);
+    time("estimate_2d", || {
+        black_box(estimate_2d(black_box(src), black_box(dst), true));
+    });
+    time("estimate", || {
+        black_box(estimate(black_box(src), black_box(dst), true));
+    });
+}
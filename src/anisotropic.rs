@@ -0,0 +1,202 @@
+use crate::{solver, DefaultSolver, KabschError, Solver};
+use alloc::vec::Vec;
+#[cfg(not(any(feature = "std", test)))]
+use nalgebra::ComplexField;
+use nalgebra::{DMatrix, DVector, SMatrix, SVector, SymmetricEigen};
+
+/// Iterations of the alternating minimisation of [`estimate_anisotropic`].
+const MAX_ITERATIONS: usize = 100;
+/// Change of the rotation and scales under which the alternating minimisation stops.
+const TOLERANCE: f64 = 1e-12;
+
+/// Transformation `x ↦ R S x + t` in `C` dimensions, with a diagonal matrix `S` scaling each axis
+/// of the source frame before the rotation.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnisotropicTransform<const C: usize> {
+    rotation: SMatrix<f64, C, C>,
+    scales: SVector<f64, C>,
+    translation: SVector<f64, C>,
+}
+
+impl<const C: usize> AnisotropicTransform<C> {
+    /// New AnisotropicTransform from its rotation, per-axis scales and translation
+    pub fn new(
+        rotation: SMatrix<f64, C, C>,
+        scales: SVector<f64, C>,
+        translation: SVector<f64, C>,
+    ) -> Self {
+        Self {
+            rotation,
+            scales,
+            translation,
+        }
+    }
+
+    /// Rotation `R`
+    pub fn rotation(&self) -> &SMatrix<f64, C, C> {
+        &self.rotation
+    }
+
+    /// Scales along the axes of the source frame, the diagonal of `S`
+    pub fn scales(&self) -> &SVector<f64, C> {
+        &self.scales
+    }
+
+    /// Translation `t`
+    pub fn translation(&self) -> &SVector<f64, C> {
+        &self.translation
+    }
+
+    /// Homogeneous `(C+1)x(C+1)` matrix
+    pub fn to_homogeneous(&self) -> DMatrix<f64> {
+        let mut t = DMatrix::identity(C + 1, C + 1);
+        t.view_mut((0, 0), (C, C))
+            .copy_from(&(self.rotation * SMatrix::from_diagonal(&self.scales)));
+        t.view_mut((0, C), (C, 1)).copy_from(&self.translation);
+        t
+    }
+
+    /// Map each point of `points` through the transformation in place.
+    pub fn apply_mut(&self, points: &mut [[f64; C]]) {
+        for point in points {
+            let p = SVector::from(*point).component_mul(&self.scales);
+            *point = (self.rotation * p + self.translation).into();
+        }
+    }
+}
+
+/// Estimate a rotation, a scale per axis and a translation between two matrices, for targets
+/// stretched differently along each axis which a single isotropic scale would fit poorly: the
+/// extended Procrustes problem `min Σ |R S pᵢ + t - qᵢ|²` with a diagonal `S`.
+///
+/// There is no closed form, so the rotation and the scales are solved for alternately, from the
+/// estimate of [`crate::estimate`], which decreases the residuals at every step until they stop
+/// changing. The source points must spread along every axis, see
+/// [`crate::anisotropic_scale_recoverability`]; a [`KabschError::DegenerateConfiguration`] error
+/// is returned otherwise, and other failures are reported like [`crate::strict::estimate`].
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_anisotropic, Array2};
+/// use nalgebra::{Matrix2, Vector2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [2., 1.], [-1., 3.]]);
+/// // stretched 3 times along x and 2 times along y, rotated by a quarter turn and shifted
+/// let dst = Array2::from([[1., 1.], [1., 4.], [-1., 1.], [-1., 7.], [-5., -2.]]);
+///
+/// let t = estimate_anisotropic(src, dst).unwrap();
+/// assert!((t.scales() - Vector2::new(3., 2.)).abs().max() < 1e-9);
+/// assert!((t.rotation() - Matrix2::new(0., -1., 1., 0.)).abs().max() < 1e-9);
+/// assert!((t.translation() - Vector2::new(1., 1.)).abs().max() < 1e-9);
+///
+/// // along a diagonal, stretching x and shrinking y is a rotation
+/// let diagonal = Array2::from([[0., 0.], [1., 1.], [2., 2.], [3., 3.]]);
+/// let doubled = Array2::from([[0., 0.], [2., 2.], [4., 4.], [6., 6.]]);
+/// assert_eq!(
+///     estimate_anisotropic(diagonal, doubled),
+///     Err(kabsch_umeyama::KabschError::DegenerateConfiguration)
+/// );
+/// ```
+pub fn estimate_anisotropic<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+) -> Result<AnisotropicTransform<C>, KabschError> {
+    if R == 0 || C == 0 {
+        return Err(KabschError::Empty);
+    }
+    let src = src.into();
+    let dst = dst.into();
+    if src.iter().chain(dst.iter()).any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    fit_anisotropic(&src, &dst, &DVector::repeat(R, 1.), &DefaultSolver)
+}
+
+/// Weighted alternating minimisation of [`estimate_anisotropic`], the weights having a positive
+/// sum.
+pub(crate) fn fit_anisotropic<const R: usize, const C: usize>(
+    src: &SMatrix<f64, R, C>,
+    dst: &SMatrix<f64, R, C>,
+    weights: &DVector<f64>,
+    solver: &dyn Solver,
+) -> Result<AnisotropicTransform<C>, KabschError> {
+    let num = weights.sum();
+    let src_mean = (weights.transpose() * src / num).transpose();
+    let dst_mean = (weights.transpose() * dst / num).transpose();
+    // Σ wᵢ qᵢ pᵢᵀ and Σ wᵢ pᵢ² per axis, of the centered points
+    let mut a = SMatrix::<f64, C, C>::zeros();
+    let mut spread = SVector::<f64, C>::zeros();
+    for i in 0..R {
+        let p = src.row(i).transpose() - src_mean;
+        let q = dst.row(i).transpose() - dst_mean;
+        a += q * p.transpose() * weights[i];
+        spread += p.component_mul(&p) * weights[i];
+    }
+    let centered = (0..R).map(|i| (src.row(i).transpose() - src_mean, weights[i]));
+    let information = scale_information(centered);
+    if !solver::is_finite(&information) {
+        return Err(KabschError::NonFinite);
+    }
+    let eigenvalues = SymmetricEigen::new(information).eigenvalues;
+    if eigenvalues.min() <= solver::RANK_TOL * eigenvalues.max() {
+        return Err(KabschError::DegenerateConfiguration);
+    }
+    let rotation_of = |b: SMatrix<f64, C, C>| {
+        let b = DMatrix::from_column_slice(C, C, b.as_slice());
+        let m = solver::rotation_or_error(solver, &b, solver::RANK_TOL)?;
+        Ok(SMatrix::<f64, C, C>::from_column_slice(m.as_slice()))
+    };
+    // each scale minimises the residuals along its axis for the current rotation
+    let scales_of = |rotation: &SMatrix<f64, C, C>| {
+        (rotation.transpose() * a).diagonal().component_div(&spread)
+    };
+
+    let mut rotation = rotation_of(a)?;
+    let mut scales = scales_of(&rotation);
+    for _ in 0..MAX_ITERATIONS {
+        let next_rotation = rotation_of(a * SMatrix::from_diagonal(&scales))?;
+        let next_scales = scales_of(&next_rotation);
+        let change = (next_rotation - rotation).abs().max()
+            + (next_scales - scales).abs().max() / scales.abs().max().max(1.);
+        rotation = next_rotation;
+        scales = next_scales;
+        if change <= TOLERANCE {
+            break;
+        }
+    }
+    let translation = dst_mean - rotation * src_mean.component_mul(&scales);
+    if scales
+        .iter()
+        .chain(translation.iter())
+        .any(|v| !v.is_finite())
+    {
+        return Err(KabschError::NonFinite);
+    }
+    Ok(AnisotropicTransform::new(rotation, scales, translation))
+}
+
+/// Information matrix `Σ wᵢ Jᵢᵀ Jᵢ` of the rotation generators, one per pair of axes, and the
+/// per-axis scales, for weighted points centered on their mean: its null directions are the
+/// combinations of rotation and scales that the points do not constrain.
+pub(crate) fn scale_information<const C: usize>(
+    points: impl Iterator<Item = (SVector<f64, C>, f64)>,
+) -> DMatrix<f64> {
+    let generators = (0..C)
+        .flat_map(|a| (a + 1..C).map(move |b| (a, b)))
+        .collect::<Vec<_>>();
+    let k = generators.len() + C;
+    let mut information = DMatrix::zeros(k, k);
+    for (p, weight) in points {
+        // derivative of the moved point with respect to each parameter
+        let mut jacobian = DMatrix::zeros(C, k);
+        for (column, &(a, b)) in generators.iter().enumerate() {
+            jacobian[(a, column)] = -p[b];
+            jacobian[(b, column)] = p[a];
+        }
+        for j in 0..C {
+            jacobian[(j, generators.len() + j)] = p[j];
+        }
+        information += jacobian.transpose() * &jacobian * weight;
+    }
+    information
+}
@@ -0,0 +1,106 @@
+//! Data augmentation for landmark-regression models: landmark sets in plausible poses, sampled by
+//! applying random similarity transformations and noise to a reference configuration.
+use crate::{Array2, Rng, Transform};
+use nalgebra::{DMatrix, SMatrix, SVector};
+
+/// Range of the sampled transformations.
+#[derive(Clone, Copy, Debug)]
+pub struct AugmentOptions {
+    /// Largest rotation angle (radians), about the centroid of the landmarks.
+    pub max_rotation: f64,
+    /// Range of the scale factor.
+    pub scale_range: (f64, f64),
+    /// Largest translation along each axis.
+    pub max_translation: f64,
+    /// Standard deviation of the Gaussian noise added to each coordinate.
+    pub noise: f64,
+}
+
+impl Default for AugmentOptions {
+    fn default() -> Self {
+        Self {
+            max_rotation: 0.3,
+            scale_range: (0.9, 1.1),
+            max_translation: 0.1,
+            noise: 0.01,
+        }
+    }
+}
+
+/// Random rotation, the exponential of a random skew-symmetric matrix, by an angle at most
+/// `max_angle` (uniform in `[0, max_angle]` in 2 and 3 dimensions).
+fn bounded_rotation<const C: usize>(rng: &mut Rng, max_angle: f64) -> SMatrix<f64, C, C> {
+    let gaussian = DMatrix::<f64>::from_fn(C, C, |_, _| rng.normal());
+    let skew = (&gaussian - gaussian.transpose()) / 2.;
+    // the largest rotation angle of exp(K) is the spectral norm of K, bounded by ‖K‖_F / √2
+    let norm = skew.norm() / std::f64::consts::SQRT_2;
+    let angle = rng.range(0., max_angle);
+    let skew = if norm > 0. {
+        skew * (angle / norm)
+    } else {
+        skew
+    };
+    SMatrix::from_column_slice(skew.exp().as_slice())
+}
+
+/// Augmented copy of the `reference` landmarks, along with the transformation applied before the
+/// noise.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::augment::{augment, AugmentOptions};
+/// use kabsch_umeyama::{estimate_transform, Array2, Rng};
+///
+/// let reference = Array2::from([[0., 0.], [1., 0.], [1., 1.], [0., 1.], [0.5, 1.5]]);
+/// let mut rng = Rng::new(3);
+/// let options = AugmentOptions { noise: 0., ..Default::default() };
+///
+/// for _ in 0..10 {
+///     let (sample, applied) = augment(&reference, &mut rng, &options);
+///     // without noise, the applied transformation is recovered exactly
+///     let recovered = estimate_transform(reference, sample, true).unwrap();
+///     assert!((recovered.scale() - applied.scale()).abs() < 1e-9);
+///     assert!((0.9..1.1).contains(&applied.scale()));
+/// }
+/// ```
+pub fn augment<const R: usize, const C: usize>(
+    reference: &Array2<R, C>,
+    rng: &mut Rng,
+    options: &AugmentOptions,
+) -> (Array2<R, C>, Transform<C>) {
+    let rotation = bounded_rotation::<C>(rng, options.max_rotation);
+    let scale = rng.range(options.scale_range.0, options.scale_range.1);
+    let shift = SVector::<f64, C>::from_fn(|_, _| {
+        rng.range(-options.max_translation, options.max_translation)
+    });
+    let centroid = reference
+        .iter()
+        .map(|p| SVector::<f64, C>::from(*p))
+        .sum::<SVector<f64, C>>()
+        / R.max(1) as f64;
+    // x ↦ s R (x - c) + c + shift
+    let transform = Transform::new(
+        rotation,
+        centroid + shift - rotation * centroid * scale,
+        scale,
+    );
+    let mut points = [[0.; C]; R];
+    for (out, p) in points.iter_mut().zip(reference.iter()) {
+        let moved = rotation * SVector::<f64, C>::from(*p) * scale + transform.translation();
+        for (o, v) in out.iter_mut().zip(moved.iter()) {
+            *o = v + options.noise * rng.normal();
+        }
+    }
+    (Array2::from(points), transform)
+}
+
+/// `count` augmented copies of the `reference` landmarks, see [`augment`].
+pub fn augment_batch<const R: usize, const C: usize>(
+    reference: &Array2<R, C>,
+    rng: &mut Rng,
+    options: &AugmentOptions,
+    count: usize,
+) -> Vec<(Array2<R, C>, Transform<C>)> {
+    (0..count)
+        .map(|_| augment(reference, rng, options))
+        .collect()
+}
@@ -0,0 +1,224 @@
+use crate::{
+    estimate_with_options, solver, DefaultSolver, EstimateOptions, KabschError, Solver, Transform,
+};
+use alloc::vec::Vec;
+use nalgebra::{DMatrix, SMatrix, SVector};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Estimate the similarity transformation of each pair of point sets like
+/// [`crate::estimate_transform`], e.g. thousands of small landmark sets per second, in parallel
+/// across the pairs with the `rayon` feature.
+///
+/// The point sets stay in fixed-size matrices on the stack, only the `C×C` cross-covariance
+/// reaching the heap for the solver, so that allocations do not dominate for small sets.
+/// The `None` value is returned for the pairs whose problem is not well-conditioned, or whose
+/// coordinates are not finite.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_batch, estimate_transform, Array2};
+///
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 2., 0.], [0., 0., 3.]]);
+/// let pairs = (0..1000)
+///     .map(|i| {
+///         let shift = i as f64;
+///         (src, Array2::from(src.map(|[x, y, z]| [-y + shift, x, z])))
+///     })
+///     .chain([(src, Array2::from([[1., 1., 1.]; 4]))])
+///     .chain([(src, Array2::from([[f64::NAN, 1., 1.]; 4]))])
+///     .collect::<Vec<_>>();
+///
+/// let transforms = estimate_batch(&pairs, true);
+/// assert_eq!(transforms.len(), 1002);
+/// let expected = estimate_transform(pairs[7].0, pairs[7].1, true).unwrap();
+/// let t = transforms[7].as_ref().unwrap();
+/// assert!((t.to_homogeneous() - expected.to_homogeneous()).abs().max() < 1e-12);
+/// assert!((t.translation()[0] - 7.).abs() < 1e-12);
+/// assert!(transforms[1000].is_none() && transforms[1001].is_none());
+/// ```
+pub fn estimate_batch<S, D, const R: usize, const C: usize>(
+    pairs: &[(S, D)],
+    estimate_scale: bool,
+) -> Vec<Option<Transform<C>>>
+where
+    S: Into<SMatrix<f64, R, C>> + Clone + Sync,
+    D: Into<SMatrix<f64, R, C>> + Clone + Sync,
+{
+    estimate_batch_with_backend(pairs, estimate_scale, BatchBackend::Cpu)
+}
+
+/// Estimate the transformation of each pair of point sets like [`crate::estimate_with_options`],
+/// e.g. with the same weight per landmark for every set, in parallel across the pairs with the
+/// `rayon` feature.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_batch_with_options, Array2, EstimateOptions, TransformKind};
+///
+/// let src = Array2::from([[1., 0.], [0., 1.], [-1., 0.], [0., -1.]]);
+/// let pairs = [
+///     (src, Array2::from([[2., 2.], [-1., 1.], [2., 0.], [5., 1.]])),
+///     (src, Array2::from([[1., 1.]; 4])),
+/// ];
+/// let options = EstimateOptions::builder().kind(TransformKind::Affine).build().unwrap();
+///
+/// let transforms = estimate_batch_with_options(&pairs, &options);
+/// assert!((transforms[0].as_ref().unwrap()[(0, 1)] + 3.).abs() < 1e-12);
+/// assert!(transforms[1].is_ok());
+/// ```
+pub fn estimate_batch_with_options<S, D, const R: usize, const C: usize>(
+    pairs: &[(S, D)],
+    options: &EstimateOptions,
+) -> Vec<Result<DMatrix<f64>, KabschError>>
+where
+    S: Into<SMatrix<f64, R, C>> + Clone + Sync,
+    D: Into<SMatrix<f64, R, C>> + Clone + Sync,
+{
+    let estimate = |(src, dst): &(S, D)| estimate_with_options(src.clone(), dst.clone(), options);
+    #[cfg(feature = "rayon")]
+    return pairs.par_iter().map(estimate).collect();
+    #[cfg(not(feature = "rayon"))]
+    return pairs.iter().map(estimate).collect();
+}
+
+/// Backend computing the moments of each pair in [`estimate_batch_with_backend`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BatchBackend {
+    /// On the CPU, in parallel across the pairs with the `rayon` feature.
+    #[default]
+    Cpu,
+    /// On an NVIDIA GPU through CUDA with the `cuda` feature, one thread per pair, e.g. for
+    /// millions of pairs on HPC nodes; the rotations are still solved on the CPU. The estimation
+    /// falls back to [`BatchBackend::Cpu`] without the feature or without a CUDA device.
+    Cuda,
+}
+
+/// Estimate the similarity transformation of each pair of point sets like [`estimate_batch`],
+/// with the means, cross-covariance and variance of the pairs computed by `backend`.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_batch, estimate_batch_with_backend, Array2, BatchBackend};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 2.], [3., 1.]]);
+/// let pairs = (0..100)
+///     .map(|i| (src, Array2::from(src.map(|[x, y]| [-y + i as f64, x]))))
+///     .collect::<Vec<_>>();
+///
+/// // on the CPU without the `cuda` feature or a CUDA device
+/// let transforms = estimate_batch_with_backend(&pairs, true, BatchBackend::Cuda);
+/// for (t, expected) in transforms.iter().zip(estimate_batch(&pairs, true)) {
+///     let (t, expected) = (t.as_ref().unwrap(), expected.unwrap());
+///     assert!((t.to_homogeneous() - expected.to_homogeneous()).abs().max() < 1e-12);
+/// }
+/// ```
+pub fn estimate_batch_with_backend<S, D, const R: usize, const C: usize>(
+    pairs: &[(S, D)],
+    estimate_scale: bool,
+    backend: BatchBackend,
+) -> Vec<Option<Transform<C>>>
+where
+    S: Into<SMatrix<f64, R, C>> + Clone + Sync,
+    D: Into<SMatrix<f64, R, C>> + Clone + Sync,
+{
+    let solve = |moments: &Moments<R, C>| moments.solve(estimate_scale, &DefaultSolver).ok();
+    #[cfg(feature = "cuda")]
+    if backend == BatchBackend::Cuda {
+        if let Some(moments) = cuda_moments(pairs) {
+            #[cfg(feature = "rayon")]
+            return moments.par_iter().map(solve).collect();
+            #[cfg(not(feature = "rayon"))]
+            return moments.iter().map(solve).collect();
+        }
+    }
+    #[cfg(not(feature = "cuda"))]
+    let _ = backend;
+    let estimate =
+        |(src, dst): &(S, D)| solve(&Moments::new(&src.clone().into(), &dst.clone().into()));
+    #[cfg(feature = "rayon")]
+    return pairs.par_iter().map(estimate).collect();
+    #[cfg(not(feature = "rayon"))]
+    return pairs.iter().map(estimate).collect();
+}
+
+/// Sufficient statistics of the estimation between two sets of `R` points.
+struct Moments<const R: usize, const C: usize> {
+    src_mean: SVector<f64, C>,
+    dst_mean: SVector<f64, C>,
+    /// `Σ (qᵢ - q̄)(pᵢ - p̄)ᵀ / R`
+    covariance: SMatrix<f64, C, C>,
+    /// `Σ |pᵢ - p̄|²`
+    src_variance: f64,
+}
+
+impl<const R: usize, const C: usize> Moments<R, C> {
+    /// Moments of fixed-size point matrices, accumulated without copying the points to the heap.
+    fn new(src: &SMatrix<f64, R, C>, dst: &SMatrix<f64, R, C>) -> Self {
+        let src_mean = src.row_mean().transpose();
+        let dst_mean = dst.row_mean().transpose();
+        let mut covariance = SMatrix::<f64, C, C>::zeros();
+        let mut src_variance = 0.;
+        for i in 0..R {
+            let p = src.row(i).transpose() - src_mean;
+            let q = dst.row(i).transpose() - dst_mean;
+            covariance += q * p.transpose();
+            src_variance += p.norm_squared();
+        }
+        covariance /= R as f64;
+        Self {
+            src_mean,
+            dst_mean,
+            covariance,
+            src_variance,
+        }
+    }
+
+    fn solve(
+        &self,
+        estimate_scale: bool,
+        solver: &dyn Solver,
+    ) -> Result<Transform<C>, KabschError> {
+        if !self.src_variance.is_finite() {
+            return Err(KabschError::NonFinite);
+        }
+        let covariance = DMatrix::from_column_slice(C, C, self.covariance.as_slice());
+        let m = solver::rotation_or_error(solver, &covariance, solver::RANK_TOL)?;
+        let rotation = SMatrix::<f64, C, C>::from_column_slice(m.as_slice());
+        let scale = if estimate_scale {
+            R as f64 / self.src_variance * (rotation.transpose() * self.covariance).trace()
+        } else {
+            1.
+        };
+        let translation = self.dst_mean - rotation * self.src_mean * scale;
+        Ok(Transform::new(rotation, translation, scale))
+    }
+}
+
+/// Moments of every pair computed on the first CUDA device, the `None` value if the device is
+/// unavailable.
+#[cfg(feature = "cuda")]
+fn cuda_moments<S, D, const R: usize, const C: usize>(
+    pairs: &[(S, D)],
+) -> Option<Vec<Moments<R, C>>>
+where
+    S: Into<SMatrix<f64, R, C>> + Clone,
+    D: Into<SMatrix<f64, R, C>> + Clone,
+{
+    // the points of each pair, row-major
+    let mut src = Vec::with_capacity(pairs.len() * R * C);
+    let mut dst = Vec::with_capacity(pairs.len() * R * C);
+    for (s, d) in pairs {
+        let (s, d): (SMatrix<f64, R, C>, SMatrix<f64, R, C>) = (s.clone().into(), d.clone().into());
+        src.extend_from_slice(s.transpose().as_slice());
+        dst.extend_from_slice(d.transpose().as_slice());
+    }
+    let values = crate::cuda::batch_moments(&src, &dst, pairs.len(), R, C)?;
+    let moments = values
+        .chunks(crate::cuda::moments_stride(C))
+        .map(|v| Moments {
+            src_mean: SVector::from_column_slice(&v[..C]),
+            dst_mean: SVector::from_column_slice(&v[C..2 * C]),
+            covariance: SMatrix::from_row_slice(&v[2 * C..2 * C + C * C]),
+            src_variance: v[2 * C + C * C],
+        })
+        .collect();
+    Some(moments)
+}
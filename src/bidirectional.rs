@@ -0,0 +1,67 @@
+use crate::estimate;
+use nalgebra::{DMatrix, SMatrix};
+
+/// Forward (src→dst) and backward (dst→src) estimates with their consistency.
+#[derive(Clone, Debug)]
+pub struct Bidirectional {
+    /// Homogeneous transformation mapping `src` onto `dst`.
+    pub forward: DMatrix<f64>,
+    /// Homogeneous transformation mapping `dst` onto `src`.
+    pub backward: DMatrix<f64>,
+    /// Product of the forward and backward scales, `1` for noise-free data.
+    /// With scale estimation it is the squared correlation of the point sets, and drops below `1` with noise.
+    pub scale_product: f64,
+    /// Frobenius norm of `R_fwd - R_bwdᵀ`, `0` when the rotations are exact inverses.
+    pub rotation_error: f64,
+    /// Score in `[0, 1]`: the scale agreement `min(p, 1/p)` of the scale product `p`,
+    /// times the rotation agreement `1 - rotation_error / (2√C)`. `1` means perfectly consistent.
+    pub consistency: f64,
+}
+
+/// Estimate the similarity transformations in both directions and check their consistency:
+/// `s_fwd * s_bwd ≈ 1` and `R_fwd ≈ R_bwdᵀ`.
+/// The `None` value is returned if either estimation is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_bidirectional, Array2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+/// let dst = Array2::from([[1., 1.], [1., 3.], [-1., 1.], [-1., 3.]]);
+///
+/// let bidirectional = estimate_bidirectional(src, dst, true).unwrap();
+/// assert!((bidirectional.scale_product - 1.).abs() < 1e-9);
+/// assert!(bidirectional.rotation_error < 1e-9);
+/// assert!((bidirectional.consistency - 1.).abs() < 1e-9);
+/// ```
+pub fn estimate_bidirectional<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+) -> Option<Bidirectional> {
+    let src = src.into();
+    let dst = dst.into();
+    let forward = estimate(src, dst, estimate_scale)?;
+    let backward = estimate(dst, src, estimate_scale)?;
+
+    let (scale_fwd, rotation_fwd) = scale_rotation(&forward);
+    let (scale_bwd, rotation_bwd) = scale_rotation(&backward);
+    let scale_product = scale_fwd * scale_bwd;
+    let rotation_error = (rotation_fwd - rotation_bwd.transpose()).norm();
+    let scale_agreement = scale_product.min(1. / scale_product);
+    let rotation_agreement = 1. - rotation_error / (2. * (C as f64).sqrt());
+    Some(Bidirectional {
+        forward,
+        backward,
+        scale_product,
+        rotation_error,
+        consistency: scale_agreement * rotation_agreement,
+    })
+}
+
+/// Scale and rotation of the linear part `sR` of a homogeneous similarity transformation.
+fn scale_rotation(t: &DMatrix<f64>) -> (f64, DMatrix<f64>) {
+    let c = t.nrows() - 1;
+    let linear = t.view((0, 0), (c, c));
+    let scale = linear.column(0).norm();
+    (scale, linear / scale)
+}
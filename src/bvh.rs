@@ -0,0 +1,207 @@
+use crate::mesh::closest_point_on_triangle;
+use nalgebra::Vector3;
+
+/// Maximum number of triangles in a leaf.
+const LEAF_SIZE: usize = 4;
+
+/// Axis-aligned bounding box node. Leaves hold `count > 0` triangles from `start` in the triangle
+/// order; an inner node has its left child right after it and its right child at `right`.
+#[derive(Clone, Debug)]
+struct Node {
+    min: Vector3<f64>,
+    max: Vector3<f64>,
+    start: usize,
+    count: usize,
+    right: usize,
+}
+
+impl Node {
+    fn squared_distance(&self, p: &Vector3<f64>) -> f64 {
+        (self.min - p)
+            .sup(&(p - self.max))
+            .sup(&Vector3::zeros())
+            .norm_squared()
+    }
+
+    /// Entry distance of the ray along `direction`, if it hits the box before `limit`.
+    fn ray_entry(&self, origin: &Vector3<f64>, inverse: &Vector3<f64>, limit: f64) -> Option<f64> {
+        let (mut near, mut far) = (0., limit);
+        for k in 0..3 {
+            let t0 = (self.min[k] - origin[k]) * inverse[k];
+            let t1 = (self.max[k] - origin[k]) * inverse[k];
+            near = t0.min(t1).max(near);
+            far = t0.max(t1).min(far);
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+/// Intersection of a ray with a mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    /// Ray parameter `t` of the hit point `origin + t * direction`.
+    pub t: f64,
+    pub point: Vector3<f64>,
+    /// Index of the triangle hit.
+    pub triangle: usize,
+}
+
+/// Bounding volume hierarchy over the triangles of a mesh.
+#[derive(Clone, Debug)]
+pub(crate) struct Bvh {
+    nodes: Vec<Node>,
+    order: Vec<usize>,
+}
+
+impl Bvh {
+    pub(crate) fn new(vertices: &[[f64; 3]], triangles: &[[usize; 3]]) -> Self {
+        let bounds = triangles
+            .iter()
+            .map(|triangle| {
+                let corners = triangle.map(|i| Vector3::from(vertices[i]));
+                let min = corners[0].inf(&corners[1]).inf(&corners[2]);
+                let max = corners[0].sup(&corners[1]).sup(&corners[2]);
+                (min, max)
+            })
+            .collect::<Vec<_>>();
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * triangles.len() / LEAF_SIZE + 1),
+            order: (0..triangles.len()).collect(),
+        };
+        bvh.build(&bounds, 0, triangles.len());
+        bvh
+    }
+
+    fn build(
+        &mut self,
+        bounds: &[(Vector3<f64>, Vector3<f64>)],
+        start: usize,
+        end: usize,
+    ) -> usize {
+        let (min, max) = self.order[start..end].iter().fold(
+            (
+                Vector3::repeat(f64::INFINITY),
+                Vector3::repeat(f64::NEG_INFINITY),
+            ),
+            |(min, max), &i| (min.inf(&bounds[i].0), max.sup(&bounds[i].1)),
+        );
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min,
+            max,
+            start,
+            count: end - start,
+            right: 0,
+        });
+        if end - start > LEAF_SIZE {
+            // median split of the centroids along the longest axis of the box
+            let axis = (max - min).imax();
+            let centroid = |i: usize| bounds[i].0[axis] + bounds[i].1[axis];
+            let mid = (start + end) / 2;
+            self.order[start..end]
+                .select_nth_unstable_by(mid - start, |a, b| centroid(*a).total_cmp(&centroid(*b)));
+            self.nodes[index].count = 0;
+            self.build(bounds, start, mid);
+            self.nodes[index].right = self.build(bounds, mid, end);
+        }
+        index
+    }
+
+    /// Closest point of the mesh to `p`, with the index of its triangle.
+    pub(crate) fn closest_point(
+        &self,
+        corners: impl Fn(usize) -> [Vector3<f64>; 3],
+        p: &Vector3<f64>,
+    ) -> (Vector3<f64>, usize) {
+        let mut best = (Vector3::repeat(f64::INFINITY), 0, f64::INFINITY);
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.squared_distance(p) >= best.2 {
+                continue;
+            }
+            if node.count > 0 {
+                for &triangle in &self.order[node.start..node.start + node.count] {
+                    let [a, b, c] = corners(triangle);
+                    let q = closest_point_on_triangle(p, &a, &b, &c);
+                    let squared = (q - p).norm_squared();
+                    if squared < best.2 {
+                        best = (q, triangle, squared);
+                    }
+                }
+            } else {
+                let (left, right) = (index + 1, node.right);
+                let (near, far) = if self.nodes[left].squared_distance(p)
+                    <= self.nodes[right].squared_distance(p)
+                {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                stack.push(far);
+                stack.push(near);
+            }
+        }
+        (best.0, best.1)
+    }
+
+    /// First intersection of the ray `origin + t * direction`, `t ≥ 0`, with the mesh.
+    pub(crate) fn raycast(
+        &self,
+        corners: impl Fn(usize) -> [Vector3<f64>; 3],
+        origin: &Vector3<f64>,
+        direction: &Vector3<f64>,
+    ) -> Option<RayHit> {
+        let inverse = direction.map(|d| 1. / d);
+        let mut best: Option<RayHit> = None;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let limit = best.map_or(f64::INFINITY, |hit| hit.t);
+            if node.ray_entry(origin, &inverse, limit).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &triangle in &self.order[node.start..node.start + node.count] {
+                    let [a, b, c] = corners(triangle);
+                    if let Some(t) = ray_triangle(origin, direction, &a, &b, &c) {
+                        if best.map_or(true, |hit| t < hit.t) {
+                            best = Some(RayHit {
+                                t,
+                                point: origin + direction * t,
+                                triangle,
+                            });
+                        }
+                    }
+                }
+            } else {
+                stack.push(node.right);
+                stack.push(index + 1);
+            }
+        }
+        best
+    }
+}
+
+/// Möller-Trumbore ray/triangle intersection, returning the ray parameter `t ≥ 0` of the hit.
+fn ray_triangle(
+    origin: &Vector3<f64>,
+    direction: &Vector3<f64>,
+    a: &Vector3<f64>,
+    b: &Vector3<f64>,
+    c: &Vector3<f64>,
+) -> Option<f64> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(&ac);
+    let det = ab.dot(&p);
+    if det.abs() <= f64::EPSILON * ab.norm() * ac.norm() * direction.norm() {
+        return None;
+    }
+    let s = (origin - a) / det;
+    let u = s.dot(&p);
+    let q = s.cross(&ab);
+    let v = direction.dot(&q);
+    let t = ac.dot(&q);
+    (u >= 0. && v >= 0. && u + v <= 1. && t >= 0.).then_some(t)
+}
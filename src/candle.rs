@@ -0,0 +1,164 @@
+//! Batched alignment of `candle` tensors on the CPU (requires the `candle` feature), differentiable
+//! with respect to both point sets so that it can be used inside training loops.
+use crate::jacobian::jacobian_points;
+use crate::{fit_points, DefaultSolver, Points};
+use candle_core::{CpuStorage, CustomOp2, DType, Error, Layout, Result, Shape, Tensor};
+use nalgebra::{DMatrix, DVector};
+
+/// Estimate the similarity transformations aligning batches of point sets.
+///
+/// `src` and `dst` are `(batch, points, C)` tensors of `f32` or `f64` with `C` equal to 2 or 3,
+/// and the result is the `(batch, C+1, C+1)` tensor of the homogeneous matrices, of the same type.
+/// Gradients flow back to both inputs through the analytic Jacobians of
+/// [`crate::estimate_with_jacobian`].
+/// # Examples
+/// ```
+/// use candle_core::{Device, Tensor};
+/// use kabsch_umeyama::candle::align_batch;
+///
+/// let src = Tensor::new(&[[[0f64, 0.], [1., 0.], [0., 1.]]], &Device::Cpu).unwrap();
+/// let dst = Tensor::new(&[[[1f64, 1.], [1., 3.], [-1., 1.]]], &Device::Cpu).unwrap();
+/// let t = align_batch(&src, &dst, true).unwrap();
+/// assert_eq!(t.dims(), &[1, 3, 3]);
+/// ```
+pub fn align_batch(src: &Tensor, dst: &Tensor, estimate_scale: bool) -> Result<Tensor> {
+    if src.dims() != dst.dims() {
+        return Err(Error::Msg(format!(
+            "shape mismatch: {:?} and {:?}",
+            src.dims(),
+            dst.dims()
+        )));
+    }
+    let (_, _, c) = src.dims3()?;
+    if !(2..=3).contains(&c) {
+        return Err(Error::Msg(format!("expected 2 or 3 dimensions, got {c}")));
+    }
+    src.contiguous()?
+        .apply_op2(&dst.contiguous()?, Align { estimate_scale })
+}
+
+/// Batched alignment as a custom operation of the autograd graph.
+#[derive(Clone, Copy, Debug)]
+struct Align {
+    estimate_scale: bool,
+}
+
+fn to_f64(storage: &CpuStorage, layout: &Layout) -> Result<Vec<f64>> {
+    let (start, end) = layout
+        .contiguous_offsets()
+        .ok_or_else(|| Error::Msg("non-contiguous points".into()))?;
+    match storage {
+        CpuStorage::F64(v) => Ok(v[start..end].to_vec()),
+        CpuStorage::F32(v) => Ok(v[start..end].iter().map(|x| *x as f64).collect()),
+        _ => Err(Error::Msg("expected f32 or f64 points".into())),
+    }
+}
+
+fn points<const C: usize>(coordinates: &[f64]) -> Points<C> {
+    Points::from_row_iterator(coordinates.len() / C, coordinates.iter().copied())
+}
+
+/// Homogeneous matrix aligning one set of `c`-dimensional points.
+fn estimate(src: &[f64], dst: &[f64], c: usize, estimate_scale: bool) -> Option<DMatrix<f64>> {
+    let weights = DVector::repeat(src.len() / c, 1.);
+    let fit = match c {
+        2 => fit_points(
+            points::<2>(src),
+            points::<2>(dst),
+            &weights,
+            estimate_scale,
+            &DefaultSolver,
+        ),
+        3 => fit_points(
+            points::<3>(src),
+            points::<3>(dst),
+            &weights,
+            estimate_scale,
+            &DefaultSolver,
+        ),
+        _ => return None,
+    };
+    fit.ok().map(|fit| fit.transform)
+}
+
+/// Gradients of both point sets from the gradient of the homogeneous matrix (row-major).
+fn backward(
+    src: &[f64],
+    dst: &[f64],
+    c: usize,
+    estimate_scale: bool,
+    grad: &[f64],
+) -> Option<(DVector<f64>, DVector<f64>)> {
+    let (_, jacobian) = match c {
+        2 => jacobian_points(points::<2>(src), points::<2>(dst), estimate_scale),
+        3 => jacobian_points(points::<3>(src), points::<3>(dst), estimate_scale),
+        _ => None,
+    }?;
+    let grad = DVector::from_column_slice(grad);
+    Some((
+        jacobian.src.transpose() * &grad,
+        jacobian.dst.transpose() * &grad,
+    ))
+}
+
+impl CustomOp2 for Align {
+    fn name(&self) -> &'static str {
+        "kabsch-umeyama-align"
+    }
+
+    fn cpu_fwd(
+        &self,
+        s1: &CpuStorage,
+        l1: &Layout,
+        s2: &CpuStorage,
+        l2: &Layout,
+    ) -> Result<(CpuStorage, Shape)> {
+        let (batch, n, c) = l1.shape().dims3()?;
+        let src = to_f64(s1, l1)?;
+        let dst = to_f64(s2, l2)?;
+        let mut out = Vec::with_capacity(batch * (c + 1) * (c + 1));
+        for b in 0..batch {
+            let range = b * n * c..(b + 1) * n * c;
+            let t = estimate(&src[range.clone()], &dst[range], c, self.estimate_scale)
+                .ok_or_else(|| Error::Msg(format!("alignment {b} is not well-conditioned")))?;
+            out.extend(t.transpose().iter());
+        }
+        let storage = match s1 {
+            CpuStorage::F32(_) => CpuStorage::F32(out.into_iter().map(|x| x as f32).collect()),
+            _ => CpuStorage::F64(out),
+        };
+        Ok((storage, Shape::from((batch, c + 1, c + 1))))
+    }
+
+    fn bwd(
+        &self,
+        arg1: &Tensor,
+        arg2: &Tensor,
+        _res: &Tensor,
+        grad_res: &Tensor,
+    ) -> Result<(Option<Tensor>, Option<Tensor>)> {
+        let (batch, n, c) = arg1.dims3()?;
+        let flat = |t: &Tensor| t.to_dtype(DType::F64)?.flatten_all()?.to_vec1::<f64>();
+        let (src, dst, grad) = (flat(arg1)?, flat(arg2)?, flat(grad_res)?);
+        let entries = (c + 1) * (c + 1);
+        let mut grad_src = Vec::with_capacity(src.len());
+        let mut grad_dst = Vec::with_capacity(dst.len());
+        for b in 0..batch {
+            let range = b * n * c..(b + 1) * n * c;
+            let (gs, gd) = backward(
+                &src[range.clone()],
+                &dst[range],
+                c,
+                self.estimate_scale,
+                &grad[b * entries..(b + 1) * entries],
+            )
+            .ok_or_else(|| Error::Msg(format!("alignment {b} is not differentiable")))?;
+            grad_src.extend(gs.iter());
+            grad_dst.extend(gd.iter());
+        }
+        let tensor = |values: Vec<f64>, like: &Tensor| {
+            Tensor::from_vec(values, (batch, n, c), like.device())?.to_dtype(like.dtype())
+        };
+        Ok((Some(tensor(grad_src, arg1)?), Some(tensor(grad_dst, arg2)?)))
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{Array2, Transform};
+use nalgebra::{DMatrix, SMatrix, SVector, SymmetricEigen};
+
+/// Normalize a configuration to a canonical pose: centered on the origin, scaled to a unit
+/// centroid size (root of the sum of squared distances to the centroid) and rotated so that its
+/// principal axes, by decreasing variance, are the coordinate axes.
+///
+/// The sign of each axis is chosen so that the third moment of the coordinates along it is
+/// positive, falling back to the coordinate of the first point off the axis plane; the sign of the
+/// last axis is then fixed by requiring a proper rotation.
+/// Returns the normalized points and the transformation mapping the points to them.
+/// The `None` value is returned if all the points coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{normalize_to_canonical, Array2};
+///
+/// let points = Array2::from([[1., 1.], [1., 5.], [2., 1.], [2., 5.], [1.5, 6.]]);
+/// let (canonical, transform) = normalize_to_canonical(&points).unwrap();
+///
+/// // the long axis of the configuration is now along x, and its size is 1
+/// let size: f64 = canonical.iter().flatten().map(|v| v * v).sum();
+/// assert!((size - 1.).abs() < 1e-12);
+/// let spread = |k: usize| canonical.iter().map(|p| p[k] * p[k]).sum::<f64>();
+/// assert!(spread(0) > spread(1));
+///
+/// // a rotated and scaled copy has the same canonical pose
+/// let moved = Array2::from(points.map(|[x, y]| [3. - 2. * y, 2. * x]));
+/// let (again, _) = normalize_to_canonical(&moved).unwrap();
+/// assert!(canonical.iter().flatten().zip(again.iter().flatten()).all(|(a, b)| (a - b).abs() < 1e-9));
+/// assert!(transform.scale() > 0.);
+/// ```
+pub fn normalize_to_canonical<const R: usize, const C: usize>(
+    points: &Array2<R, C>,
+) -> Option<(Array2<R, C>, Transform<C>)> {
+    let vectors = points
+        .iter()
+        .map(|p| SVector::<f64, C>::from(*p))
+        .collect::<Vec<_>>();
+    let centroid = vectors.iter().sum::<SVector<f64, C>>() / R.max(1) as f64;
+    let centered = vectors.iter().map(|v| v - centroid).collect::<Vec<_>>();
+    let size = centered
+        .iter()
+        .map(|v| v.norm_squared())
+        .sum::<f64>()
+        .sqrt();
+    if !size.is_normal() {
+        return None;
+    }
+
+    let scatter = centered
+        .iter()
+        .map(|v| v * v.transpose())
+        .sum::<SMatrix<f64, C, C>>();
+    let eigen = SymmetricEigen::new(DMatrix::from_column_slice(C, C, scatter.as_slice()));
+    let mut order = (0..C).collect::<Vec<_>>();
+    order.sort_by(|a, b| eigen.eigenvalues[*b].total_cmp(&eigen.eigenvalues[*a]));
+    let mut axes = DMatrix::from_fn(C, C, |i, j| eigen.eigenvectors[(i, order[j])]);
+    for k in 0..C.saturating_sub(1) {
+        let mut axis = axes.column_mut(k);
+        let projections = centered
+            .iter()
+            .map(|v| v.iter().zip(axis.iter()).map(|(a, b)| a * b).sum::<f64>())
+            .collect::<Vec<_>>();
+        let skew = projections.iter().map(|p| p.powi(3)).sum::<f64>();
+        let tol = 1e-9 * size.powi(3);
+        let sign = if skew.abs() > tol {
+            skew
+        } else {
+            projections
+                .iter()
+                .copied()
+                .find(|p| p.abs() > 1e-9 * size)
+                .unwrap_or(1.)
+        };
+        if sign < 0. {
+            axis.neg_mut();
+        }
+    }
+    if C > 0 && axes.determinant() < 0. {
+        axes.column_mut(C - 1).neg_mut();
+    }
+
+    let rotation = SMatrix::<f64, C, C>::from_fn(|i, j| axes[(j, i)]);
+    let scale = 1. / size;
+    let transform = Transform::new(rotation, -(rotation * centroid) * scale, scale);
+    let mut normalized = [[0.; C]; R];
+    for (out, v) in normalized.iter_mut().zip(&centered) {
+        out.copy_from_slice((rotation * v * scale).as_slice());
+    }
+    Some((Array2::from(normalized), transform))
+}
@@ -0,0 +1,94 @@
+use crate::Transform;
+use nalgebra::{Matrix3, Matrix3x6, Matrix6, Vector3};
+
+/// Covariance of a point-to-point ICP solution by Censi's closed form
+/// `cov(x) = H⁻¹ (∂²J/∂x∂z) cov(z) (∂²J/∂x∂z)ᵀ H⁻¹`, for the cost `J` summing the squared
+/// distances between the transformed `src` points and their `dst` correspondences, each
+/// coordinate of which has independent noise of standard deviation `sigma`.
+///
+/// The pose perturbation is `x ↦ exp(δ) x` with `δ` a rotation vector followed by a translation,
+/// the same parametrization as the residuals of [`crate::mapping::PoseGraph`].
+/// The `None` value is returned if the lengths differ or the points do not constrain the pose.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{icp_covariance, Transform};
+///
+/// let src = [[1., 0., 0.], [-1., 0., 0.], [0., 2., 0.], [0., -2., 0.], [0., 0., 1.], [0., 0., -1.]];
+/// let cov = icp_covariance(&src, &src, &Transform::identity(), 0.01).unwrap();
+///
+/// // translation variance 2σ²/n for points centered on the origin
+/// assert!((cov[(3, 3)] - 2. * 0.01f64.powi(2) / 6.).abs() < 1e-12);
+/// // the rotation about z is constrained by the points farthest from the axis
+/// assert!(cov[(2, 2)] < cov[(1, 1)]);
+/// ```
+pub fn icp_covariance(
+    src: &[[f64; 3]],
+    dst: &[[f64; 3]],
+    transform: &Transform<3>,
+    sigma: f64,
+) -> Option<Matrix6<f64>> {
+    if src.len() != dst.len() {
+        return None;
+    }
+    let linear = transform.rotation() * transform.scale();
+    let mut hessian = Matrix6::zeros();
+    let mut noise = Matrix6::zeros();
+    for (p, q) in src.iter().zip(dst) {
+        let moved = linear * Vector3::from(*p) + transform.translation();
+        let r = moved - Vector3::from(*q);
+        let mut jacobian = Matrix3x6::zeros();
+        jacobian
+            .fixed_view_mut::<3, 3>(0, 0)
+            .copy_from(&-moved.cross_matrix());
+        jacobian
+            .fixed_view_mut::<3, 3>(0, 3)
+            .copy_from(&Matrix3::identity());
+
+        // ∂²J/∂x², including the second order rotation term weighted by the residual
+        hessian += jacobian.transpose() * jacobian * 2.;
+        let mut curvature = r * moved.transpose() + moved * r.transpose();
+        curvature -= Matrix3::from_diagonal_element(2. * r.dot(&moved));
+        let mut rotation_block = hessian.fixed_view_mut::<3, 3>(0, 0);
+        rotation_block += curvature;
+
+        // ∂²J/∂x∂q and ∂²J/∂x∂p
+        let by_dst = -jacobian.transpose() * 2.;
+        let mut by_moved = jacobian.transpose() * 2.;
+        let mut top = by_moved.fixed_view_mut::<3, 3>(0, 0);
+        top -= r.cross_matrix() * 2.;
+        let by_src = by_moved * linear;
+        noise += (by_dst * by_dst.transpose() + by_src * by_src.transpose()) * sigma.powi(2);
+    }
+    let inverse = hessian.try_inverse()?;
+    Some(inverse * noise * inverse.transpose())
+}
+
+/// Covariance of a point-to-point ICP solution like [`icp_covariance`], with the noise level
+/// estimated from the residuals by [`crate::residual_sigma`] instead of given. Both point sets are
+/// assumed equally noisy, so a residual coordinate has standard deviation `σ √(1 + s²)`.
+/// The estimated `σ` is returned along with the covariance.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::testing::Rng;
+/// use kabsch_umeyama::{icp_covariance_from_residuals, Transform};
+///
+/// let mut rng = Rng::new(4);
+/// let src = (0..200)
+///     .map(|_| [rng.range(-1., 1.), rng.range(-1., 1.), rng.range(-1., 1.)])
+///     .collect::<Vec<_>>();
+/// let noisy = |rng: &mut Rng, p: &[f64; 3]| p.map(|v| v + 0.01 * rng.normal());
+/// let dst = src.iter().map(|p| noisy(&mut rng, p)).collect::<Vec<_>>();
+/// let src = src.iter().map(|p| noisy(&mut rng, p)).collect::<Vec<_>>();
+///
+/// let (_, sigma) = icp_covariance_from_residuals(&src, &dst, &Transform::identity()).unwrap();
+/// assert!((sigma - 0.01).abs() < 0.002);
+/// ```
+pub fn icp_covariance_from_residuals(
+    src: &[[f64; 3]],
+    dst: &[[f64; 3]],
+    transform: &Transform<3>,
+) -> Option<(Matrix6<f64>, f64)> {
+    let sigma =
+        crate::residual_sigma(src, dst, transform)? / (1. + transform.scale().powi(2)).sqrt();
+    Some((icp_covariance(src, dst, transform, sigma)?, sigma))
+}
@@ -0,0 +1,357 @@
+//! Brute-force nearest-neighbor search and batch moments in CUDA kernels through `cust` (requires
+//! the `cuda` feature), for NVIDIA HPC nodes.
+//!
+//! Like the `wgpu` search, distances are computed in `f32` on points centered on the centroid of
+//! the searched set, whereas the moments of the batch estimator are computed in `f64`. The kernels
+//! are shipped as PTX, JIT-compiled by the driver for the device.
+use crate::icp::{centered_f32, centroid};
+use cust::context::Context;
+use cust::prelude::*;
+
+const PTX: &str = r"
+.version 7.0
+.target sm_50
+.address_size 64
+
+.visible .entry nearest(
+    .param .u64 points_param,
+    .param .u64 queries_param,
+    .param .u64 nearest_param,
+    .param .u32 count_param,
+    .param .u32 queries_count_param
+)
+{
+    .reg .pred %p<4>;
+    .reg .b32 %r<8>;
+    .reg .f32 %f<11>;
+    .reg .b64 %rd<9>;
+
+    ld.param.u64 %rd1, [points_param];
+    ld.param.u64 %rd2, [queries_param];
+    ld.param.u64 %rd3, [nearest_param];
+    ld.param.u32 %r1, [count_param];
+    ld.param.u32 %r2, [queries_count_param];
+    cvta.to.global.u64 %rd1, %rd1;
+    cvta.to.global.u64 %rd2, %rd2;
+    cvta.to.global.u64 %rd3, %rd3;
+    mov.u32 %r3, %ctaid.x;
+    mov.u32 %r4, %ntid.x;
+    mov.u32 %r5, %tid.x;
+    mad.lo.s32 %r3, %r3, %r4, %r5;
+    setp.ge.u32 %p1, %r3, %r2;
+    @%p1 bra DONE;
+
+    mul.wide.u32 %rd4, %r3, 16;
+    add.s64 %rd5, %rd2, %rd4;
+    ld.global.v4.f32 {%f1, %f2, %f3, %f4}, [%rd5];
+    mov.f32 %f5, 0f7F7FFFFF;
+    mov.u32 %r6, 0;
+    mov.u32 %r7, 0;
+    mov.u64 %rd6, %rd1;
+
+LOOP:
+    setp.ge.u32 %p2, %r7, %r1;
+    @%p2 bra STORE;
+    ld.global.v4.f32 {%f6, %f7, %f8, %f9}, [%rd6];
+    sub.f32 %f6, %f6, %f1;
+    sub.f32 %f7, %f7, %f2;
+    sub.f32 %f8, %f8, %f3;
+    sub.f32 %f9, %f9, %f4;
+    mul.f32 %f10, %f6, %f6;
+    fma.rn.f32 %f10, %f7, %f7, %f10;
+    fma.rn.f32 %f10, %f8, %f8, %f10;
+    fma.rn.f32 %f10, %f9, %f9, %f10;
+    setp.lt.f32 %p3, %f10, %f5;
+    @%p3 mov.f32 %f5, %f10;
+    @%p3 mov.u32 %r6, %r7;
+    add.u32 %r7, %r7, 1;
+    add.s64 %rd6, %rd6, 16;
+    bra LOOP;
+
+STORE:
+    mul.wide.u32 %rd7, %r3, 4;
+    add.s64 %rd8, %rd3, %rd7;
+    st.global.u32 [%rd8], %r6;
+
+DONE:
+    ret;
+}
+";
+
+/// One thread per pair of point sets: the means at `[0, 2 cols)`, the row-major cross-covariance
+/// at `[2 cols, 2 cols + cols²)` and the source variance at `2 cols + cols²` of its moments.
+const MOMENTS_PTX: &str = r"
+.version 7.0
+.target sm_50
+.address_size 64
+
+.visible .entry moments(
+    .param .u64 src_param,
+    .param .u64 dst_param,
+    .param .u64 moments_param,
+    .param .u32 pairs_param,
+    .param .u32 rows_param,
+    .param .u32 cols_param
+)
+{
+    .reg .pred %p<5>;
+    .reg .b32 %r<15>;
+    .reg .f64 %fd<7>;
+    .reg .b64 %rd<7>;
+
+    ld.param.u64 %rd1, [src_param];
+    ld.param.u64 %rd2, [dst_param];
+    ld.param.u64 %rd3, [moments_param];
+    ld.param.u32 %r1, [pairs_param];
+    ld.param.u32 %r2, [rows_param];
+    ld.param.u32 %r3, [cols_param];
+    cvta.to.global.u64 %rd1, %rd1;
+    cvta.to.global.u64 %rd2, %rd2;
+    cvta.to.global.u64 %rd3, %rd3;
+    mov.u32 %r4, %ctaid.x;
+    mov.u32 %r5, %ntid.x;
+    mov.u32 %r6, %tid.x;
+    mad.lo.s32 %r4, %r4, %r5, %r6;
+    setp.ge.u32 %p1, %r4, %r1;
+    @%p1 bra DONE;
+
+    // points and moments of the pair
+    mul.lo.s32 %r5, %r2, %r3;
+    mul.wide.u32 %rd4, %r4, %r5;
+    shl.b64 %rd4, %rd4, 3;
+    add.s64 %rd1, %rd1, %rd4;
+    add.s64 %rd2, %rd2, %rd4;
+    mul.lo.s32 %r6, %r3, %r3;
+    shl.b32 %r7, %r3, 1;
+    add.s32 %r8, %r7, %r6;
+    add.s32 %r9, %r8, 1;
+    mul.wide.u32 %rd4, %r4, %r9;
+    shl.b64 %rd4, %rd4, 3;
+    add.s64 %rd3, %rd3, %rd4;
+    cvt.rn.f64.u32 %fd1, %r2;
+
+    // means of the column j = %r10
+    mov.u32 %r10, 0;
+MEAN_COLUMN:
+    setp.ge.u32 %p2, %r10, %r3;
+    @%p2 bra COVARIANCE;
+    mov.f64 %fd2, 0d0000000000000000;
+    mov.f64 %fd3, 0d0000000000000000;
+    mov.u32 %r11, 0;
+MEAN_ROW:
+    setp.ge.u32 %p3, %r11, %r2;
+    @%p3 bra MEAN_STORE;
+    mad.lo.s32 %r12, %r11, %r3, %r10;
+    mul.wide.u32 %rd5, %r12, 8;
+    add.s64 %rd6, %rd1, %rd5;
+    ld.global.f64 %fd4, [%rd6];
+    add.f64 %fd2, %fd2, %fd4;
+    add.s64 %rd6, %rd2, %rd5;
+    ld.global.f64 %fd4, [%rd6];
+    add.f64 %fd3, %fd3, %fd4;
+    add.u32 %r11, %r11, 1;
+    bra MEAN_ROW;
+MEAN_STORE:
+    div.rn.f64 %fd2, %fd2, %fd1;
+    div.rn.f64 %fd3, %fd3, %fd1;
+    mul.wide.u32 %rd5, %r10, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    st.global.f64 [%rd6], %fd2;
+    add.s32 %r12, %r3, %r10;
+    mul.wide.u32 %rd5, %r12, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    st.global.f64 [%rd6], %fd3;
+    add.u32 %r10, %r10, 1;
+    bra MEAN_COLUMN;
+
+    // cross-covariance entry (a, b) = (%r10, %r13)
+COVARIANCE:
+    mov.u32 %r10, 0;
+COVARIANCE_ROW:
+    setp.ge.u32 %p2, %r10, %r3;
+    @%p2 bra VARIANCE;
+    add.s32 %r12, %r3, %r10;
+    mul.wide.u32 %rd5, %r12, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    ld.global.f64 %fd5, [%rd6];
+    mov.u32 %r13, 0;
+COVARIANCE_COLUMN:
+    setp.ge.u32 %p3, %r13, %r3;
+    @%p3 bra COVARIANCE_NEXT;
+    mul.wide.u32 %rd5, %r13, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    ld.global.f64 %fd6, [%rd6];
+    mov.f64 %fd2, 0d0000000000000000;
+    mov.u32 %r11, 0;
+COVARIANCE_SUM:
+    setp.ge.u32 %p4, %r11, %r2;
+    @%p4 bra COVARIANCE_STORE;
+    mul.lo.s32 %r12, %r11, %r3;
+    add.s32 %r14, %r12, %r10;
+    mul.wide.u32 %rd5, %r14, 8;
+    add.s64 %rd6, %rd2, %rd5;
+    ld.global.f64 %fd3, [%rd6];
+    sub.f64 %fd3, %fd3, %fd5;
+    add.s32 %r14, %r12, %r13;
+    mul.wide.u32 %rd5, %r14, 8;
+    add.s64 %rd6, %rd1, %rd5;
+    ld.global.f64 %fd4, [%rd6];
+    sub.f64 %fd4, %fd4, %fd6;
+    fma.rn.f64 %fd2, %fd3, %fd4, %fd2;
+    add.u32 %r11, %r11, 1;
+    bra COVARIANCE_SUM;
+COVARIANCE_STORE:
+    div.rn.f64 %fd2, %fd2, %fd1;
+    mad.lo.s32 %r14, %r10, %r3, %r13;
+    add.s32 %r14, %r14, %r7;
+    mul.wide.u32 %rd5, %r14, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    st.global.f64 [%rd6], %fd2;
+    add.u32 %r13, %r13, 1;
+    bra COVARIANCE_COLUMN;
+COVARIANCE_NEXT:
+    add.u32 %r10, %r10, 1;
+    bra COVARIANCE_ROW;
+
+    // source variance over the elements %r11
+VARIANCE:
+    mov.f64 %fd2, 0d0000000000000000;
+    mov.u32 %r11, 0;
+VARIANCE_SUM:
+    setp.ge.u32 %p2, %r11, %r5;
+    @%p2 bra VARIANCE_STORE;
+    rem.u32 %r12, %r11, %r3;
+    mul.wide.u32 %rd5, %r12, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    ld.global.f64 %fd3, [%rd6];
+    mul.wide.u32 %rd5, %r11, 8;
+    add.s64 %rd6, %rd1, %rd5;
+    ld.global.f64 %fd4, [%rd6];
+    sub.f64 %fd4, %fd4, %fd3;
+    fma.rn.f64 %fd2, %fd4, %fd4, %fd2;
+    add.u32 %r11, %r11, 1;
+    bra VARIANCE_SUM;
+VARIANCE_STORE:
+    mul.wide.u32 %rd5, %r8, 8;
+    add.s64 %rd6, %rd3, %rd5;
+    st.global.f64 [%rd6], %fd2;
+
+DONE:
+    ret;
+}
+";
+
+const BLOCK_SIZE: u32 = 128;
+
+/// Number of moments of each pair of `cols`-dimensional point sets, see [`batch_moments`].
+pub(crate) fn moments_stride(cols: usize) -> usize {
+    2 * cols + cols * cols + 1
+}
+
+/// Moments of `pairs` pairs of `rows × cols` point sets, stored row-major one pair after the other
+/// in `src` and `dst`, computed on the first CUDA device: for each pair, the source and destination
+/// means, the row-major cross-covariance `Σ (qᵢ - q̄)(pᵢ - p̄)ᵀ / rows` and the source variance
+/// `Σ |pᵢ - p̄|²`. The `None` value is returned if there is no device or if it could not complete
+/// the computation.
+pub(crate) fn batch_moments(
+    src: &[f64],
+    dst: &[f64],
+    pairs: usize,
+    rows: usize,
+    cols: usize,
+) -> Option<Vec<f64>> {
+    let stride = moments_stride(cols);
+    let sizes = [pairs, rows.checked_mul(cols)?, stride];
+    if pairs == 0 || rows == 0 || sizes.iter().any(|n| *n > u32::MAX as usize) {
+        return None;
+    }
+    // declared first so that the device resources are released before the context
+    let _context = cust::quick_init().ok()?;
+    let module = Module::from_ptx(MOMENTS_PTX, &[]).ok()?;
+    let stream = Stream::new(StreamFlags::NON_BLOCKING, None).ok()?;
+    let device_src = DeviceBuffer::from_slice(src).ok()?;
+    let device_dst = DeviceBuffer::from_slice(dst).ok()?;
+    let device_moments = DeviceBuffer::<f64>::zeroed(pairs.checked_mul(stride)?).ok()?;
+    let function = module.get_function("moments").ok()?;
+    let blocks = (pairs as u32).div_ceil(BLOCK_SIZE);
+    // SAFETY: the arguments match the parameters of the kernel, `src` and `dst` hold
+    // `pairs * rows * cols` values and the moments `pairs * stride`.
+    unsafe {
+        launch!(function<<<blocks, BLOCK_SIZE, 0, stream>>>(
+            device_src.as_device_ptr(),
+            device_dst.as_device_ptr(),
+            device_moments.as_device_ptr(),
+            pairs as u32,
+            rows as u32,
+            cols as u32
+        ))
+        .ok()?;
+    }
+    stream.synchronize().ok()?;
+    let mut moments = vec![0.; pairs * stride];
+    device_moments.copy_to(&mut moments).ok()?;
+    Some(moments)
+}
+
+/// Points uploaded to a CUDA device for nearest-neighbor queries.
+pub(crate) struct CudaSearch<const C: usize> {
+    // declared first so that the device resources are released before the context
+    module: Module,
+    stream: Stream,
+    points: DeviceBuffer<[f32; 4]>,
+    count: usize,
+    center: [f64; C],
+    _context: Context,
+}
+
+impl<const C: usize> CudaSearch<C> {
+    /// Upload `points` to the first CUDA device, the `None` value if there is none or if they have
+    /// more than 4 dimensions.
+    pub(crate) fn new(points: &[[f64; C]]) -> Option<Self> {
+        if C == 0 || C > 4 || points.is_empty() || points.len() > u32::MAX as usize {
+            return None;
+        }
+        let context = cust::quick_init().ok()?;
+        let module = Module::from_ptx(PTX, &[]).ok()?;
+        let stream = Stream::new(StreamFlags::NON_BLOCKING, None).ok()?;
+        let center = centroid(points);
+        let device_points = DeviceBuffer::from_slice(&centered_f32(points, &center)).ok()?;
+        Some(Self {
+            module,
+            stream,
+            points: device_points,
+            count: points.len(),
+            center,
+            _context: context,
+        })
+    }
+
+    /// Index of the point nearest to each query, the `None` value if the device could not
+    /// complete the search.
+    pub(crate) fn nearest(&self, queries: &[[f64; C]]) -> Option<Vec<u32>> {
+        if queries.len() > u32::MAX as usize {
+            return None;
+        }
+        let device_queries = DeviceBuffer::from_slice(&centered_f32(queries, &self.center)).ok()?;
+        let device_nearest = DeviceBuffer::<u32>::zeroed(queries.len()).ok()?;
+        let function = self.module.get_function("nearest").ok()?;
+        let blocks = (queries.len() as u32).div_ceil(BLOCK_SIZE);
+        let stream = &self.stream;
+        // SAFETY: the arguments match the parameters of the kernel, and every buffer holds the
+        // number of elements passed along with it.
+        unsafe {
+            launch!(function<<<blocks, BLOCK_SIZE, 0, stream>>>(
+                self.points.as_device_ptr(),
+                device_queries.as_device_ptr(),
+                device_nearest.as_device_ptr(),
+                self.count as u32,
+                queries.len() as u32
+            ))
+            .ok()?;
+        }
+        stream.synchronize().ok()?;
+        let mut nearest = vec![0; queries.len()];
+        device_nearest.copy_to(&mut nearest).ok()?;
+        Some(nearest)
+    }
+}
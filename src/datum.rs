@@ -0,0 +1,258 @@
+use crate::solver::RANK_TOL;
+use crate::{estimate_transform, DefaultSolver, Solver, Transform};
+use nalgebra::{DMatrix, Matrix3, Rotation3, SMatrix, SymmetricEigen, Unit, Vector3};
+
+/// Norm under which a datum direction, e.g. between two datum points, is treated as zero.
+const TOLERANCE: f64 = 1e-9;
+
+/// Estimate a rigid transformation in 3 dimensions under hierarchical datum constraints
+/// (3-2-1 / RPS alignment), as done in coordinate metrology.
+///
+/// `datums` lists point indices by decreasing priority:
+/// - the first datum is matched exactly, fixing the translation;
+/// - the second datum fixes the direction of the axis from the first datum exactly;
+/// - the third datum fixes the rotation about that axis exactly.
+///
+/// The degrees of freedom left free by the datums are least-squares fitted on all the points.
+/// Without datums, this is the plain rigid estimate. The `None` value is returned if there are
+/// more than 3 datums, an index is out of bounds, the first two datums coincide in either point
+/// set, or the problem is not well-conditioned. See [`estimate_datum_planes`] for plane datums.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_datum, Array2};
+///
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+/// // the last point is off by 0.1 along x
+/// let dst = Array2::from([[1., 1., 1.], [2., 1., 1.], [1., 2., 1.], [1.1, 1., 2.]]);
+///
+/// let t = estimate_datum(src, dst, &[0, 1]).unwrap();
+/// // the first datum is exact whereas the unconstrained fit would shift it
+/// assert!((t.translation() - nalgebra::Vector3::new(1., 1., 1.)).norm() < 1e-12);
+///
+/// // the first two datums coincide, so they do not fix an axis
+/// let coincident = Array2::from([[0., 0., 0.], [0., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+/// assert!(estimate_datum(coincident, dst, &[0, 1]).is_none());
+/// ```
+pub fn estimate_datum<const R: usize>(
+    src: impl Into<SMatrix<f64, R, 3>>,
+    dst: impl Into<SMatrix<f64, R, 3>>,
+    datums: &[usize],
+) -> Option<Transform<3>> {
+    let src = src.into();
+    let dst = dst.into();
+    if datums.len() > 3 || datums.iter().any(|&i| i >= R) {
+        return None;
+    }
+    let Some(&origin) = datums.first() else {
+        return estimate_transform(src, dst, false);
+    };
+    let p = |i: usize| src.row(i).transpose();
+    let q = |i: usize| dst.row(i).transpose();
+    // coordinates relative to the first datum
+    let a = (0..R).map(|i| p(i) - p(origin)).collect::<Vec<_>>();
+    let b = (0..R).map(|i| q(i) - q(origin)).collect::<Vec<_>>();
+
+    let rotation = match datums.get(1) {
+        None => {
+            let covariance = b
+                .iter()
+                .zip(&a)
+                .map(|(b, a)| b * a.transpose())
+                .sum::<Matrix3<f64>>();
+            let rotation =
+                DefaultSolver.rotation(&DMatrix::from_column_slice(3, 3, covariance.as_slice()))?;
+            Matrix3::from_column_slice(rotation.as_slice())
+        }
+        Some(&second) => {
+            let axis = Unit::try_new(b[second], TOLERANCE)?;
+            let align = align(&Unit::try_new(a[second], TOLERANCE)?, &axis)?;
+            // remaining rotation about the axis, fitted on the free points
+            let fitted = match datums.get(2) {
+                Some(&third) => vec![third],
+                None => (0..R).collect(),
+            };
+            let pairs = fitted.iter().map(|&i| (align * a[i], b[i]));
+            let (sin, cos) = angle_about(&axis, pairs);
+            if datums.len() == 3 && sin == 0. && cos == 0. {
+                return None;
+            }
+            (Rotation3::from_axis_angle(&axis, sin.atan2(cos)) * align).into_inner()
+        }
+    };
+    Some(Transform::new(
+        rotation,
+        q(origin) - rotation * p(origin),
+        1.,
+    ))
+}
+
+/// Estimate a rigid transformation in 3 dimensions under hierarchical datum planes (3-2-1
+/// alignment on planar features), as done in coordinate metrology.
+///
+/// Each datum plane is given by the indices of the points measured on it, at least 3 and not
+/// collinear, and is fitted by least squares in both point sets. By decreasing priority:
+/// - the primary plane is matched exactly, fixing its normal and its offset (3 degrees of
+///   freedom);
+/// - the secondary plane fixes the rotation about the primary normal and its offset (2 degrees of
+///   freedom);
+/// - the tertiary plane fixes its offset (1 degree of freedom).
+///
+/// The normals of the destination planes are oriented like the rigid estimate of all the points,
+/// and the degrees of freedom left free by the planes are least-squares fitted on all the points.
+/// Without planes, this is the plain rigid estimate. The `None` value is returned if there are
+/// more than 3 planes, an index is out of bounds, a plane is not fitted by its points, a plane is
+/// parallel to a plane of higher priority, or the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_datum_planes, estimate_transform, Array2};
+/// use nalgebra::Vector3;
+///
+/// // corner of a block: points on its bottom (z = 0), front (y = 0) and side (x = 0) faces,
+/// // and a point inside
+/// let src = Array2::from([
+///     [0., 0., 0.], [2., 0., 0.], [0., 1., 0.], [2., 1., 0.],
+///     [1., 0., 1.], [2., 0., 1.], [0., 1., 1.], [0., 2., 2.], [1., 1., 1.],
+/// ]);
+/// // shifted by (1, 1, 1), the last point being off by 0.2 along x
+/// let dst = Array2::from([
+///     [1., 1., 1.], [3., 1., 1.], [1., 2., 1.], [3., 2., 1.],
+///     [2., 1., 2.], [3., 1., 2.], [1., 2., 2.], [1., 3., 3.], [2.2, 2., 2.],
+/// ]);
+///
+/// let bottom: &[usize] = &[0, 1, 2, 3];
+/// let front: &[usize] = &[0, 1, 4, 5];
+/// let side: &[usize] = &[0, 2, 6, 7];
+/// let t = estimate_datum_planes(src, dst, &[bottom, front, side]).unwrap();
+/// assert!(t.rotation().is_identity(1e-12));
+/// assert!((t.translation() - Vector3::new(1., 1., 1.)).norm() < 1e-12);
+///
+/// // without the side plane, the offset along x is fitted on all the points
+/// let t = estimate_datum_planes(src, dst, &[bottom, front]).unwrap();
+/// assert!((t.translation() - Vector3::new(1. + 0.2 / 9., 1., 1.)).norm() < 1e-12);
+/// // whereas the plain fit also tilts to spread the error
+/// let plain = estimate_transform(src, dst, false).unwrap();
+/// assert!(!plain.rotation().is_identity(1e-3));
+///
+/// // the bottom plane twice
+/// assert!(estimate_datum_planes(src, dst, &[bottom, &[1, 2, 3]]).is_none());
+/// ```
+pub fn estimate_datum_planes<const R: usize>(
+    src: impl Into<SMatrix<f64, R, 3>>,
+    dst: impl Into<SMatrix<f64, R, 3>>,
+    planes: &[&[usize]],
+) -> Option<Transform<3>> {
+    let src = src.into();
+    let dst = dst.into();
+    if planes.len() > 3 || planes.iter().flat_map(|p| p.iter()).any(|&i| i >= R) {
+        return None;
+    }
+    let plain = estimate_transform(src, dst, false)?;
+    if planes.is_empty() {
+        return Some(plain);
+    }
+    let p = |i: usize| src.row(i).transpose();
+    let q = |i: usize| dst.row(i).transpose();
+    // (source normal, source centroid, destination normal, destination centroid) of each plane
+    let mut fitted = Vec::with_capacity(planes.len());
+    for indices in planes {
+        let (n, c) = fit_plane(indices.iter().map(|&i| p(i)))?;
+        let (m, d) = fit_plane(indices.iter().map(|&i| q(i)))?;
+        let m = if (plain.rotation() * n.as_ref()).dot(&m) < 0. {
+            -m
+        } else {
+            m
+        };
+        fitted.push((n, c, m, d));
+    }
+
+    let src_mean = (0..R).map(p).sum::<Vector3<f64>>() / R as f64;
+    let dst_mean = (0..R).map(q).sum::<Vector3<f64>>() / R as f64;
+    let (n1, _, m1, _) = &fitted[0];
+    let align = align(n1, m1)?;
+    let (sin, cos) = match fitted.get(1) {
+        Some((n2, _, m2, _)) => {
+            let a = (align * n2.as_ref()) - m1.as_ref() * (align * n2.as_ref()).dot(m1);
+            let b = m2.as_ref() - m1.as_ref() * m2.dot(m1);
+            if a.norm() <= TOLERANCE || b.norm() <= TOLERANCE {
+                return None;
+            }
+            angle_about(m1, [(a, b)].into_iter())
+        }
+        None => angle_about(
+            m1,
+            (0..R).map(|i| (align * (p(i) - src_mean), q(i) - dst_mean)),
+        ),
+    };
+    let rotation = (Rotation3::from_axis_angle(m1, sin.atan2(cos)) * align).into_inner();
+
+    // least-squares translation, corrected along the directions fixed by the planes in turn
+    let mut translation = dst_mean - rotation * src_mean;
+    let mut fixed: Vec<Vector3<f64>> = Vec::with_capacity(3);
+    for (_, c, m, d) in &fitted {
+        let m = m.into_inner();
+        let u = fixed.iter().fold(m, |u, f| u - f * f.dot(&m));
+        let norm = u.norm();
+        if norm <= TOLERANCE {
+            return None;
+        }
+        let offset = m.dot(&(d - rotation * c)) - m.dot(&translation);
+        translation += u * (offset / (norm * norm));
+        fixed.push(u / norm);
+    }
+    Some(Transform::new(rotation, translation, 1.))
+}
+
+/// Least-squares plane through points: its unit normal and the centroid of the points.
+/// The `None` value is returned for fewer than 3 points, or collinear points.
+fn fit_plane(
+    points: impl Iterator<Item = Vector3<f64>>,
+) -> Option<(Unit<Vector3<f64>>, Vector3<f64>)> {
+    let points = points.collect::<Vec<_>>();
+    if points.len() < 3 {
+        return None;
+    }
+    let centroid = points.iter().sum::<Vector3<f64>>() / points.len() as f64;
+    let scatter = points
+        .iter()
+        .map(|p| (p - centroid) * (p - centroid).transpose())
+        .sum::<Matrix3<f64>>();
+    if scatter.iter().any(|v| !v.is_finite()) {
+        return None;
+    }
+    let eigen = SymmetricEigen::new(scatter);
+    let mut order = [0, 1, 2];
+    order.sort_by(|a, b| eigen.eigenvalues[*a].total_cmp(&eigen.eigenvalues[*b]));
+    if eigen.eigenvalues[order[1]] <= RANK_TOL * eigen.eigenvalues[order[2]] {
+        return None;
+    }
+    let normal = Unit::try_new(eigen.eigenvectors.column(order[0]).into_owned(), TOLERANCE)?;
+    Some((normal, centroid))
+}
+
+/// Rotation taking the direction `from` to `to`, half a turn about any perpendicular axis if they
+/// are opposite.
+fn align(from: &Unit<Vector3<f64>>, to: &Unit<Vector3<f64>>) -> Option<Rotation3<f64>> {
+    Rotation3::rotation_between(from, to).or_else(|| {
+        let perpendicular = from.cross(&Vector3::x()).try_normalize(f64::EPSILON);
+        let perpendicular =
+            perpendicular.or_else(|| from.cross(&Vector3::y()).try_normalize(0.))?;
+        Some(Rotation3::from_axis_angle(
+            &Unit::new_unchecked(perpendicular),
+            std::f64::consts::PI,
+        ))
+    })
+}
+
+/// Sums of the sines and cosines, weighted by the lengths, of the angles about `axis` from the
+/// first to the second vector of each pair, projected on the plane orthogonal to `axis`: their
+/// `atan2` is the least-squares rotation angle about `axis`.
+fn angle_about(
+    axis: &Unit<Vector3<f64>>,
+    pairs: impl Iterator<Item = (Vector3<f64>, Vector3<f64>)>,
+) -> (f64, f64) {
+    pairs.fold((0., 0.), |(sin, cos), (a, b)| {
+        let a = a - axis.into_inner() * a.dot(axis);
+        let b = b - axis.into_inner() * b.dot(axis);
+        (sin + a.cross(&b).dot(axis), cos + a.dot(&b))
+    })
+}
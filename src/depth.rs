@@ -0,0 +1,195 @@
+//! RGB-D helpers: conversion of depth images to point clouds and projective data association.
+use crate::KabschError;
+
+/// Pinhole camera intrinsics, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Intrinsics {
+    pub fx: f64,
+    pub fy: f64,
+    pub cx: f64,
+    pub cy: f64,
+}
+
+impl Intrinsics {
+    /// New Intrinsics from the focal lengths and the principal point
+    pub fn new(fx: f64, fy: f64, cx: f64, cy: f64) -> Self {
+        Self { fx, fy, cx, cy }
+    }
+
+    /// Point in the camera frame seen at pixel `(u, v)` with depth `z`
+    pub fn unproject(&self, u: f64, v: f64, z: f64) -> [f64; 3] {
+        [(u - self.cx) * z / self.fx, (v - self.cy) * z / self.fy, z]
+    }
+
+    /// Pixel `(u, v)` where a point of the camera frame is seen, `None` if behind the camera
+    pub fn project(&self, point: &[f64; 3]) -> Option<(f64, f64)> {
+        let [x, y, z] = *point;
+        (z > 0.).then(|| (self.fx * x / z + self.cx, self.fy * y / z + self.cy))
+    }
+}
+
+/// Options of [`depth_to_points`].
+#[derive(Clone, Copy, Debug)]
+pub struct DepthOptions<'a> {
+    /// Factor converting raw depth values to metric depth, e.g. `0.001` for millimeters.
+    pub depth_scale: f64,
+    /// Keep one pixel out of `stride` along each axis.
+    pub stride: usize,
+    /// Pixels to keep, in the row-major layout of the depth image.
+    pub mask: Option<&'a [bool]>,
+}
+
+impl Default for DepthOptions<'_> {
+    fn default() -> Self {
+        Self {
+            depth_scale: 1.,
+            stride: 1,
+            mask: None,
+        }
+    }
+}
+
+/// Convert a row-major depth image of the given `width` to points in the camera frame.
+///
+/// Pixels with a zero or non-finite depth are invalid and skipped, as are pixels masked out.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::depth::{depth_to_points, DepthOptions, Intrinsics};
+///
+/// // a 2x2 depth image in millimeters, with one invalid pixel
+/// let depth: [u16; 4] = [1000, 0, 2000, 1000];
+/// let intrinsics = Intrinsics::new(500., 500., 0.5, 0.5);
+/// let options = DepthOptions { depth_scale: 0.001, ..Default::default() };
+///
+/// let points = depth_to_points(&depth, 2, &intrinsics, &options).unwrap();
+/// assert_eq!(points, vec![[-0.001, -0.001, 1.], [-0.002, 0.002, 2.], [0.001, 0.001, 1.]]);
+/// ```
+pub fn depth_to_points<T: Copy + Into<f64>>(
+    depth: &[T],
+    width: usize,
+    intrinsics: &Intrinsics,
+    options: &DepthOptions,
+) -> Result<Vec<[f64; 3]>, KabschError> {
+    if width == 0 || depth.len() % width != 0 {
+        return Err(KabschError::ShapeMismatch {
+            expected: depth.len().next_multiple_of(width.max(1)),
+            actual: depth.len(),
+        });
+    }
+    if let Some(mask) = options.mask.filter(|mask| mask.len() != depth.len()) {
+        return Err(KabschError::ShapeMismatch {
+            expected: depth.len(),
+            actual: mask.len(),
+        });
+    }
+    let stride = options.stride.max(1);
+    let height = depth.len() / width;
+    Ok((0..height)
+        .step_by(stride)
+        .flat_map(|v| (0..width).step_by(stride).map(move |u| (u, v)))
+        .filter_map(|(u, v)| {
+            let index = v * width + u;
+            let z = depth[index].into() * options.depth_scale;
+            let keep = options.mask.map_or(true, |mask| mask[index]);
+            (keep && z.is_finite() && z != 0.).then(|| intrinsics.unproject(u as f64, v as f64, z))
+        })
+        .collect())
+}
+
+/// Point cloud keeping the row-major pixel layout of its depth image, with `None` at invalid pixels.
+#[derive(Clone, Debug)]
+pub struct OrganizedCloud {
+    width: usize,
+    points: Vec<Option<[f64; 3]>>,
+}
+
+impl OrganizedCloud {
+    /// New OrganizedCloud from a row-major depth image of the given `width`, with raw depth values
+    /// multiplied by `depth_scale`. Pixels with a zero or non-finite depth are invalid.
+    pub fn from_depth<T: Copy + Into<f64>>(
+        depth: &[T],
+        width: usize,
+        intrinsics: &Intrinsics,
+        depth_scale: f64,
+    ) -> Result<Self, KabschError> {
+        if width == 0 || depth.len() % width != 0 {
+            return Err(KabschError::ShapeMismatch {
+                expected: depth.len().next_multiple_of(width.max(1)),
+                actual: depth.len(),
+            });
+        }
+        let points = depth
+            .iter()
+            .enumerate()
+            .map(|(index, z)| {
+                let z = (*z).into() * depth_scale;
+                (z.is_finite() && z != 0.).then(|| {
+                    intrinsics.unproject((index % width) as f64, (index / width) as f64, z)
+                })
+            })
+            .collect();
+        Ok(Self { width, points })
+    }
+
+    /// Image width
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Image height
+    pub fn height(&self) -> usize {
+        self.points.len() / self.width
+    }
+
+    /// Point at a row-major pixel index, `None` if the pixel is invalid or out of bounds
+    pub fn point(&self, index: usize) -> Option<[f64; 3]> {
+        self.points.get(index).copied().flatten()
+    }
+
+    /// Point at pixel `(u, v)`, `None` if the pixel is invalid or out of bounds
+    pub fn get(&self, u: usize, v: usize) -> Option<[f64; 3]> {
+        (u < self.width && v < self.height())
+            .then(|| self.point(v * self.width + u))
+            .flatten()
+    }
+}
+
+/// Projective (image-space) data association, as in KinectFusion-style RGB-D ICP.
+///
+/// Each `src` point, expressed in the camera frame of `target`, is projected into the target image
+/// and associated with the target point at that pixel if they are at most `max_distance` apart.
+/// This replaces a nearest neighbor search by a constant-time lookup.
+/// Returns pairs of `src` index and target pixel index.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::depth::{projective_associate, Intrinsics, OrganizedCloud};
+///
+/// let intrinsics = Intrinsics::new(100., 100., 1., 1.);
+/// let target = OrganizedCloud::from_depth(&[1f32; 9], 3, &intrinsics, 1.).unwrap();
+///
+/// // the last point projects far outside the image
+/// let src = [[0.01, 0.001, 1.02], [0., 0., 1.5], [0., 0., -1.], [0., 1., 1e-300]];
+/// let pairs = projective_associate(&src, &target, &intrinsics, 0.1);
+/// assert_eq!(pairs, vec![(0, 5)]);
+/// ```
+pub fn projective_associate(
+    src: &[[f64; 3]],
+    target: &OrganizedCloud,
+    intrinsics: &Intrinsics,
+    max_distance: f64,
+) -> Vec<(usize, usize)> {
+    src.iter()
+        .enumerate()
+        .filter_map(|(i, p)| {
+            let (u, v) = intrinsics.project(p)?;
+            let (u, v) = (u.round(), v.round());
+            if u < 0. || v < 0. {
+                return None;
+            }
+            let (u, v) = (u as usize, v as usize);
+            let q = target.get(u, v)?;
+            let squared = p.iter().zip(&q).map(|(a, b)| (a - b).powi(2)).sum::<f64>();
+            (squared <= max_distance * max_distance).then_some((i, v * target.width() + u))
+        })
+        .collect()
+}
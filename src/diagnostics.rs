@@ -0,0 +1,43 @@
+use crate::solver::RANK_TOL;
+use nalgebra::{DMatrix, DVector};
+
+/// Intermediate quantities of an estimation, kept for auditing the result.
+#[derive(Clone, Debug)]
+pub struct Diagnostics {
+    /// Cross-covariance matrix `A` of the centered point sets.
+    pub covariance: DMatrix<f64>,
+    /// Singular values of `A`, in decreasing order.
+    pub singular_values: DVector<f64>,
+    /// Rank of `A`, counting the singular values above the rank tolerance.
+    pub rank: usize,
+    /// Determinant of `A`.
+    pub determinant: f64,
+    /// Diagonal of `D = Uᵀ R V`, the sign applied to each singular direction by the rotation `R`.
+    /// The entries are `±1` whenever the problem is well-conditioned.
+    pub signs: DVector<f64>,
+    /// Whether the determinant sign correction flipped a singular direction to avoid a reflection.
+    pub reflection_corrected: bool,
+}
+
+impl Diagnostics {
+    pub(crate) fn new(covariance: DMatrix<f64>, rotation: &DMatrix<f64>) -> Self {
+        let mut svd = covariance.clone().svd(true, true);
+        svd.sort_by_singular_values();
+        let u = svd.u.as_ref().unwrap();
+        let v = svd.v_t.as_ref().unwrap().transpose();
+        let signs = (u.transpose() * rotation * v).diagonal();
+        let rank = svd
+            .singular_values
+            .iter()
+            .filter(|s| **s > RANK_TOL)
+            .count();
+        Self {
+            determinant: covariance.determinant(),
+            covariance,
+            singular_values: svd.singular_values,
+            rank,
+            reflection_corrected: signs.iter().any(|s| *s < 0.),
+            signs,
+        }
+    }
+}
@@ -0,0 +1,294 @@
+use crate::{fit_matrix, DefaultSolver, KabschError, Transform};
+use nalgebra::{DMatrix, DVector, SMatrix, SVector};
+
+/// Timestamped batch of corresponding source and destination points.
+pub type TimedPointPair<'a, const C: usize> = (f64, &'a [[f64; C]], &'a [[f64; C]]);
+
+/// Batch of corresponding source and destination points measured at a value of an external
+/// covariate, e.g. the temperature of the rig.
+pub type CovariatePointPair<'a, const C: usize> = (f64, &'a [[f64; C]], &'a [[f64; C]]);
+
+/// Similarity transformation with a constant rotation and scale and a translation drifting
+/// linearly over time, `x ↦ s R x + t + v τ`.
+#[derive(Clone, Debug)]
+pub struct DriftModel<const C: usize> {
+    pub rotation: SMatrix<f64, C, C>,
+    pub scale: f64,
+    /// Translation at time zero.
+    pub translation: SVector<f64, C>,
+    /// Change of the translation per unit of time.
+    pub velocity: SVector<f64, C>,
+}
+
+impl<const C: usize> DriftModel<C> {
+    /// Transformation at `time`
+    pub fn at(&self, time: f64) -> Transform<C> {
+        Transform::new(
+            self.rotation,
+            self.translation + self.velocity * time,
+            self.scale,
+        )
+    }
+}
+
+/// Similarity transformation with a constant rotation, and a scale and a translation depending
+/// linearly on an external covariate `c`, `x ↦ (s + σ c) R x + t + τ c`, e.g. the thermal
+/// expansion and the thermal drift of a metrology rig.
+#[derive(Clone, Debug)]
+pub struct CovariateModel<const C: usize> {
+    pub rotation: SMatrix<f64, C, C>,
+    /// Scale at a zero covariate.
+    pub scale: f64,
+    /// Change of the scale per unit of the covariate.
+    pub scale_slope: f64,
+    /// Translation at a zero covariate.
+    pub translation: SVector<f64, C>,
+    /// Change of the translation per unit of the covariate.
+    pub translation_slope: SVector<f64, C>,
+}
+
+impl<const C: usize> CovariateModel<C> {
+    /// Transformation at the `covariate` value
+    pub fn at(&self, covariate: f64) -> Transform<C> {
+        Transform::new(
+            self.rotation,
+            self.translation + self.translation_slope * covariate,
+            self.scale + self.scale_slope * covariate,
+        )
+    }
+
+    /// Calibration correction mapping destination points measured at the `covariate` value to
+    /// where they would have been measured at the `reference` value.
+    pub fn correction(&self, covariate: f64, reference: f64) -> Transform<C> {
+        let (at, to) = (self.at(covariate), self.at(reference));
+        let ratio = to.scale() / at.scale();
+        Transform::new(
+            SMatrix::identity(),
+            to.translation() - at.translation() * ratio,
+            ratio,
+        )
+    }
+}
+
+/// Correspondences of all the batches, with the covariate of each point centered on its mean.
+struct Samples<const C: usize> {
+    src: Vec<SVector<f64, C>>,
+    dst: Vec<SVector<f64, C>>,
+    centered: Vec<f64>,
+    mean: f64,
+    variance: f64,
+}
+
+impl<const C: usize> Samples<C> {
+    fn new(batches: &[CovariatePointPair<C>]) -> Result<Self, KabschError> {
+        let mut values = Vec::new();
+        let mut src = Vec::new();
+        let mut dst = Vec::new();
+        for &(value, s, d) in batches {
+            if s.len() != d.len() {
+                return Err(KabschError::ShapeMismatch {
+                    expected: s.len() * C,
+                    actual: d.len() * C,
+                });
+            }
+            values.extend(std::iter::repeat(value).take(s.len()));
+            src.extend(s.iter().map(|p| SVector::from(*p)));
+            dst.extend(d.iter().map(|q| SVector::from(*q)));
+        }
+        if src.is_empty() {
+            return Err(KabschError::Empty);
+        }
+        let finite = |v: &SVector<f64, C>| v.iter().all(|x| x.is_finite());
+        if !values.iter().all(|t| t.is_finite()) || !src.iter().chain(&dst).all(finite) {
+            return Err(KabschError::NonFinite);
+        }
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        let centered = values.iter().map(|t| t - mean).collect::<Vec<_>>();
+        let variance = centered.iter().map(|c| c * c).sum::<f64>();
+        if variance <= 0. {
+            return Err(KabschError::DegenerateConfiguration);
+        }
+        Ok(Self {
+            src,
+            dst,
+            centered,
+            mean,
+            variance,
+        })
+    }
+
+    /// Value at the mean covariate and slope of the regression of `points` over the covariate.
+    fn regression(&self, points: &[SVector<f64, C>]) -> (SVector<f64, C>, SVector<f64, C>) {
+        let mean = points.iter().sum::<SVector<f64, C>>() / points.len() as f64;
+        let slope = points
+            .iter()
+            .zip(&self.centered)
+            .map(|(p, c)| p * *c)
+            .sum::<SVector<f64, C>>()
+            / self.variance;
+        (mean, slope)
+    }
+
+    /// Rotation and scale of the estimation on the points detrended by their regression over the
+    /// covariate.
+    fn detrended_fit(
+        &self,
+        estimate_scale: bool,
+    ) -> Result<(SMatrix<f64, C, C>, f64), KabschError> {
+        let detrended = |points: &[SVector<f64, C>]| {
+            let (mean, slope) = self.regression(points);
+            DMatrix::from_fn(points.len(), C, |i, j| {
+                points[i][j] - mean[j] - slope[j] * self.centered[i]
+            })
+        };
+        let fit = fit_matrix(
+            detrended(&self.src),
+            detrended(&self.dst),
+            &DVector::repeat(self.src.len(), 1.),
+            estimate_scale,
+            &DefaultSolver,
+            None,
+        )?;
+        Ok((
+            SMatrix::from_column_slice(fit.rotation.as_slice()),
+            fit.scale,
+        ))
+    }
+}
+
+/// Estimate a slowly time-varying transformation from timestamped batches of correspondences, e.g.
+/// to monitor the mechanical drift of a mounted sensor over hours: the rotation and the scale are
+/// constant and the translation drifts linearly.
+///
+/// For a fixed rotation and scale, the best translation and velocity are the linear regression of
+/// the residuals over time, so both point sets are detrended by that regression instead of just
+/// centered before the usual estimation. Times are centered internally, so absolute timestamps
+/// such as Unix times are fine.
+/// The [`KabschError::Empty`] error is returned if there are no points, [`KabschError::ShapeMismatch`]
+/// if the points of a batch differ in number, [`KabschError::NonFinite`] for non-finite inputs and
+/// [`KabschError::DegenerateConfiguration`] if all the batches share the same time, leaving the
+/// drift unobservable.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::estimate_drift;
+/// use nalgebra::{Matrix2, Vector2};
+///
+/// let quarter_turn = Matrix2::new(0., -1., 1., 0.);
+/// let src = [[0., 0.], [1., 0.], [0., 1.], [2., 1.]];
+/// let moved = |time: f64| {
+///     src.map(|p| (quarter_turn * Vector2::from(p) + Vector2::new(1. + 0.1 * time, 0.)).into())
+/// };
+/// let (dst_0, dst_1, dst_2) = (moved(0.), moved(1.), moved(2.));
+///
+/// let batches = [(0., &src[..], &dst_0[..]), (1., &src[..], &dst_1[..]), (2., &src[..], &dst_2[..])];
+/// let drift = estimate_drift(&batches, false).unwrap();
+/// assert!((drift.velocity - Vector2::new(0.1, 0.)).norm() < 1e-12);
+/// assert!((drift.at(10.).translation() - Vector2::new(2., 0.)).norm() < 1e-12);
+/// ```
+pub fn estimate_drift<const C: usize>(
+    batches: &[TimedPointPair<C>],
+    estimate_scale: bool,
+) -> Result<DriftModel<C>, KabschError> {
+    let samples = Samples::new(batches)?;
+    let (rotation, scale) = samples.detrended_fit(estimate_scale)?;
+    let residuals = samples
+        .src
+        .iter()
+        .zip(&samples.dst)
+        .map(|(p, q)| q - rotation * p * scale)
+        .collect::<Vec<_>>();
+    let (mean, velocity) = samples.regression(&residuals);
+    Ok(DriftModel {
+        rotation,
+        scale,
+        translation: mean - velocity * samples.mean,
+        velocity,
+    })
+}
+
+/// Estimate a calibration model regressing the transformation against an external covariate
+/// stream, e.g. the temperature of a precision metrology rig: the rotation is constant, and the
+/// scale (if estimated) and the translation depend linearly on the covariate.
+///
+/// The rotation is estimated as in [`estimate_drift`], on the point sets detrended over the
+/// covariate. For that rotation, the model is linear in the scale, the translation and their
+/// slopes, which are then solved for by least squares.
+/// The errors are those of [`estimate_drift`], with [`KabschError::DegenerateConfiguration`] also
+/// returned if the scale slope is not constrained, e.g. all the source points coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::estimate_covariate_model;
+/// use nalgebra::Vector2;
+///
+/// // the rig expands by 1e-3 and shifts by 0.02 along x per degree above 20°C
+/// let src = [[0., 0.], [10., 0.], [0., 10.], [10., 10.]];
+/// let measured = |temperature: f64| {
+///     let delta = temperature - 20.;
+///     src.map(|[x, y]| [(1. + 1e-3 * delta) * x + 0.02 * delta, (1. + 1e-3 * delta) * y])
+/// };
+/// let (cold, room, hot) = (measured(15.), measured(20.), measured(30.));
+/// let batches = [(15., &src[..], &cold[..]), (20., &src[..], &room[..]), (30., &src[..], &hot[..])];
+///
+/// let model = estimate_covariate_model(&batches, true).unwrap();
+/// assert!((model.scale_slope - 1e-3).abs() < 1e-12);
+/// assert!((model.translation_slope - Vector2::new(0.02, 0.)).norm() < 1e-12);
+///
+/// // bring a measurement taken at 30°C back to 20°C
+/// let correction = model.correction(30., 20.);
+/// let corrected = correction.apply(nalgebra::SMatrix::<f64, 4, 2>::from_row_slice(hot.as_flattened()));
+/// let expected = nalgebra::SMatrix::<f64, 4, 2>::from_row_slice(room.as_flattened());
+/// assert!((corrected - expected).abs().max() < 1e-12);
+/// ```
+pub fn estimate_covariate_model<const C: usize>(
+    batches: &[CovariatePointPair<C>],
+    estimate_scale: bool,
+) -> Result<CovariateModel<C>, KabschError> {
+    let samples = Samples::new(batches)?;
+    let (rotation, _) = samples.detrended_fit(estimate_scale)?;
+    let rotated = samples.src.iter().map(|p| rotation * p).collect::<Vec<_>>();
+    if !estimate_scale {
+        let residuals = samples
+            .dst
+            .iter()
+            .zip(&rotated)
+            .map(|(q, p)| q - p)
+            .collect::<Vec<_>>();
+        let (mean, slope) = samples.regression(&residuals);
+        return Ok(CovariateModel {
+            rotation,
+            scale: 1.,
+            scale_slope: 0.,
+            translation: mean - slope * samples.mean,
+            translation_slope: slope,
+        });
+    }
+    // unknowns [s, σ, t, τ] at the centered covariate, one equation per coordinate of each point
+    let n = rotated.len();
+    let mut design = DMatrix::zeros(n * C, 2 + 2 * C);
+    let mut observed = DVector::zeros(n * C);
+    for (i, (p, c)) in rotated.iter().zip(&samples.centered).enumerate() {
+        for d in 0..C {
+            let row = i * C + d;
+            design[(row, 0)] = p[d];
+            design[(row, 1)] = p[d] * c;
+            design[(row, 2 + d)] = 1.;
+            design[(row, 2 + C + d)] = *c;
+            observed[row] = samples.dst[i][d];
+        }
+    }
+    let normal = design.transpose() * &design;
+    let solution = normal
+        .lu()
+        .solve(&(design.transpose() * observed))
+        .ok_or(KabschError::DegenerateConfiguration)?;
+    let (scale, scale_slope) = (solution[0], solution[1]);
+    let translation = SVector::from_fn(|d, _| solution[2 + d]);
+    let translation_slope = SVector::from_fn(|d, _| solution[2 + C + d]);
+    Ok(CovariateModel {
+        rotation,
+        scale: scale - scale_slope * samples.mean,
+        scale_slope,
+        translation: translation - translation_slope * samples.mean,
+        translation_slope,
+    })
+}
@@ -0,0 +1,99 @@
+//! Parity with Eigen's `Eigen::umeyama()`, to validate a port of C++ code built on it.
+//!
+//! Eigen's conventions differ from [`crate::estimate`] in its layout and its edge cases: the
+//! points are the columns of the matrices, the reflection is always corrected from the
+//! determinants of the singular vectors, whatever the rank of the cross-covariance, and nothing
+//! is reported as a failure, degenerate inputs giving NaN or infinite entries instead.
+#[cfg(not(any(feature = "std", test)))]
+use nalgebra::ComplexField;
+use nalgebra::{DMatrix, DVector};
+
+/// Port of `Eigen::umeyama(src, dst, with_scaling)`: the `(m+1)x(m+1)` homogeneous matrix of the
+/// similarity transformation between the `n` columns of the `m×n` matrices `src` and `dst`.
+///
+/// Like Eigen:
+///
+/// - the last singular direction is flipped if `det(U) det(V) < 0`, even for a rank-deficient
+///   cross-covariance, whose singular vectors are then arbitrary;
+/// - a zero cross-covariance, e.g. from coincident points, keeps the identity rotation, and the
+///   scale `0 / 0` of coincident source points is NaN;
+/// - without points, every entry is NaN.
+///
+/// Up to the singular vectors of repeated singular values, which Eigen's `JacobiSVD` may pick
+/// differently, the result matches Eigen's to rounding.
+///
+/// # Panics
+/// Panics if `src` and `dst` have different shapes, where Eigen asserts.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::eigen::umeyama;
+/// use kabsch_umeyama::{estimate, Array2};
+/// use nalgebra::DMatrix;
+///
+/// // one point per column
+/// let src = DMatrix::from_row_slice(2, 4, &[0., 1., 0., 3., 0., 0., 2., 1.]);
+/// let dst = DMatrix::from_row_slice(2, 4, &[1., 1., -3., -1., 1., 3., 1., 7.2]);
+/// let rows = |m: &DMatrix<f64>| std::array::from_fn(|i| [m[(0, i)], m[(1, i)]]);
+/// let expected = estimate(Array2::<4, 2>::from(rows(&src)), Array2::from(rows(&dst)), true);
+/// let expected = expected.unwrap();
+/// assert!((umeyama(&src, &dst, true) - expected).abs().max() < 1e-12);
+///
+/// // coincident source points: identity rotation, and a NaN scale
+/// let same = DMatrix::from_element(2, 4, 1.);
+/// let shift = umeyama(&same, &dst, false);
+/// let expected = DMatrix::from_row_slice(3, 3, &[1., 0., -1.5, 0., 1., 2.05, 0., 0., 1.]);
+/// assert!((shift - expected).abs().max() < 1e-12);
+/// assert!(umeyama(&same, &dst, true)[(0, 0)].is_nan());
+///
+/// // no points
+/// let empty = DMatrix::zeros(2, 0);
+/// assert!(umeyama(&empty, &empty, true).iter().all(|v| v.is_nan()));
+/// ```
+pub fn umeyama(src: &DMatrix<f64>, dst: &DMatrix<f64>, with_scaling: bool) -> DMatrix<f64> {
+    assert_eq!(
+        src.shape(),
+        dst.shape(),
+        "umeyama: src and dst must have the same shape"
+    );
+    let (m, n) = src.shape();
+    let one_over_n = 1. / n as f64;
+    let src_mean = src.column_sum() * one_over_n;
+    let dst_mean = dst.column_sum() * one_over_n;
+    let src_demean = DMatrix::from_fn(m, n, |i, j| src[(i, j)] - src_mean[i]);
+    let dst_demean = DMatrix::from_fn(m, n, |i, j| dst[(i, j)] - dst_mean[i]);
+    let sigma = &dst_demean * src_demean.transpose() * one_over_n;
+
+    if sigma.iter().any(|v| !v.is_finite()) {
+        // JacobiSVD rejects the input
+        return DMatrix::from_element(m + 1, m + 1, f64::NAN);
+    }
+    let mut s = DVector::from_element(m, 1.);
+    let (rotation, singular_values) = if sigma.iter().all(|v| *v == 0.) {
+        // JacobiSVD leaves a diagonal matrix as is
+        (DMatrix::identity(m, m), DVector::zeros(m))
+    } else {
+        let Some(mut svd) = sigma.clone().try_svd(true, true, f64::EPSILON, 0) else {
+            return DMatrix::from_element(m + 1, m + 1, f64::NAN);
+        };
+        svd.sort_by_singular_values();
+        let (Some(u), Some(v_t)) = (svd.u, svd.v_t) else {
+            return DMatrix::from_element(m + 1, m + 1, f64::NAN);
+        };
+        if m > 0 && u.determinant() * v_t.determinant() < 0. {
+            s[m - 1] = -1.;
+        }
+        (u * DMatrix::from_diagonal(&s) * v_t, svd.singular_values)
+    };
+
+    let mut rt = DMatrix::identity(m + 1, m + 1);
+    let c = if with_scaling {
+        let src_var = src_demean.norm_squared() * one_over_n;
+        1. / src_var * singular_values.dot(&s)
+    } else {
+        1.
+    };
+    let translation = dst_mean - &rotation * src_mean * c;
+    rt.view_mut((0, 0), (m, m)).copy_from(&(rotation * c));
+    rt.view_mut((0, m), (m, 1)).copy_from(&translation);
+    rt
+}
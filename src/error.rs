@@ -0,0 +1,69 @@
+use alloc::string::String;
+use core::fmt::{Display, Formatter};
+
+/// Errors reported by the fallible APIs of this crate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KabschError {
+    /// The number of values does not match the expected shape.
+    ShapeMismatch { expected: usize, actual: usize },
+    /// There are no points (or no dimensions) to align.
+    Empty,
+    /// An input value, or a value computed from the inputs, is `NaN` or infinite.
+    NonFinite,
+    /// The points do not constrain a rotation, e.g. all of them coincide.
+    DegenerateConfiguration,
+    /// The decomposition of the cross-covariance matrix failed.
+    SvdFailed,
+    /// The matrix is not a homogeneous similarity transformation within tolerance.
+    NotSimilarity,
+    /// An index refers past the end of a collection.
+    IndexOutOfBounds { index: usize, len: usize },
+    /// A point cloud or transformation is expressed in another coordinate frame than expected.
+    FrameMismatch { expected: String, actual: String },
+    /// No chain of known transformations connects the two frames.
+    NoFramePath { source: String, target: String },
+    /// No candidate transformation is supported by enough inlier correspondences.
+    TooFewInliers { found: usize, required: usize },
+    /// A replay bundle cannot be read, at the given 1-based line.
+    InvalidBundle { line: usize },
+    /// A weight is negative, or the weights sum to zero.
+    InvalidWeights,
+    /// A tolerance is negative or `NaN`.
+    InvalidTolerance,
+}
+
+impl Display for KabschError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::ShapeMismatch { expected, actual } => {
+                write!(f, "expected {expected} values, got {actual}")
+            }
+            Self::Empty => write!(f, "no points to align"),
+            Self::NonFinite => write!(f, "non-finite value"),
+            Self::DegenerateConfiguration => write!(f, "degenerate point configuration"),
+            Self::SvdFailed => write!(f, "decomposition of the cross-covariance matrix failed"),
+            Self::NotSimilarity => write!(f, "not a similarity transformation"),
+            Self::IndexOutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for length {len}")
+            }
+            Self::FrameMismatch { expected, actual } => {
+                write!(f, "expected frame {expected:?}, got {actual:?}")
+            }
+            Self::NoFramePath { source, target } => {
+                write!(
+                    f,
+                    "no transformation path from frame {source:?} to {target:?}"
+                )
+            }
+            Self::TooFewInliers { found, required } => {
+                write!(f, "found {found} inliers, {required} required")
+            }
+            Self::InvalidBundle { line } => write!(f, "invalid replay bundle at line {line}"),
+            Self::InvalidWeights => write!(f, "negative weight or zero sum of weights"),
+            Self::InvalidTolerance => write!(f, "negative or NaN tolerance"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KabschError {}
@@ -0,0 +1,133 @@
+//! C API (requires the `ffi` feature), e.g. for C++ vision pipelines. The functions are
+//! `extern "C"` with `#[repr(C)]` types only, so that `cbindgen` can generate the header, and the
+//! crate can be built as a shared or static library with
+//! `cargo rustc --release --features ffi --crate-type cdylib` (or `staticlib`).
+//!
+//! Points are passed as `n` rows of contiguous coordinates, and matrices are written in row-major
+//! order.
+use crate::{estimate_dyn, KabschError};
+use core::ffi::c_int;
+use nalgebra::DMatrix;
+
+/// Status code returned by every function of the C API, stable across releases.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KuStatus {
+    Ok = 0,
+    /// A pointer argument is null.
+    NullPointer = 1,
+    /// There are no points.
+    Empty = 2,
+    /// A coordinate, or a value computed from them, is `NaN` or infinite.
+    NonFinite = 3,
+    /// The points do not constrain a rotation.
+    Degenerate = 4,
+    /// The decomposition of the cross-covariance matrix failed.
+    SvdFailed = 5,
+    /// Any other error.
+    Other = 255,
+}
+
+impl From<&KabschError> for KuStatus {
+    fn from(error: &KabschError) -> Self {
+        match error {
+            KabschError::Empty => Self::Empty,
+            KabschError::NonFinite => Self::NonFinite,
+            KabschError::DegenerateConfiguration => Self::Degenerate,
+            KabschError::SvdFailed => Self::SvdFailed,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// Estimate the similarity transformation between `n` points of 2 dimensions like
+/// [`crate::estimate`], writing the `3x3` homogeneous matrix to `out_matrix` in row-major order.
+/// A non-zero `estimate_scale` estimates the scale. `out_matrix` is left untouched on failure.
+///
+/// # Safety
+/// `src` and `dst` must be valid for reading `2 * n` doubles, and `out_matrix` for writing 9
+/// doubles.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::ffi::{ku_estimate_2d, KuStatus};
+///
+/// let src = [0., 0., 1., 0., 0., 1.];
+/// let dst = [1., 1., 1., 3., -1., 1.];
+/// let mut out = [0.; 9];
+/// let status = unsafe { ku_estimate_2d(src.as_ptr(), dst.as_ptr(), 3, 1, out.as_mut_ptr()) };
+/// assert_eq!(status, KuStatus::Ok);
+/// assert!((out[3] - 2.).abs() < 1e-12 && (out[2] - 1.).abs() < 1e-12);
+///
+/// let status = unsafe { ku_estimate_2d(src.as_ptr(), dst.as_ptr(), 0, 1, out.as_mut_ptr()) };
+/// assert_eq!(status, KuStatus::Empty);
+///
+/// let huge = [0., 0., 1e300, 0., 0., -1e300];
+/// let status = unsafe { ku_estimate_2d(huge.as_ptr(), huge.as_ptr(), 3, 0, out.as_mut_ptr()) };
+/// assert_eq!(status, KuStatus::NonFinite);
+/// ```
+#[no_mangle]
+pub unsafe extern "C" fn ku_estimate_2d(
+    src: *const f64,
+    dst: *const f64,
+    n: usize,
+    estimate_scale: c_int,
+    out_matrix: *mut f64,
+) -> KuStatus {
+    estimate_raw::<2>(src, dst, n, estimate_scale, out_matrix)
+}
+
+/// Estimate the similarity transformation between `n` points of 3 dimensions like
+/// [`ku_estimate_2d`], writing the `4x4` homogeneous matrix to `out_matrix`.
+///
+/// # Safety
+/// `src` and `dst` must be valid for reading `3 * n` doubles, and `out_matrix` for writing 16
+/// doubles.
+#[no_mangle]
+pub unsafe extern "C" fn ku_estimate_3d(
+    src: *const f64,
+    dst: *const f64,
+    n: usize,
+    estimate_scale: c_int,
+    out_matrix: *mut f64,
+) -> KuStatus {
+    estimate_raw::<3>(src, dst, n, estimate_scale, out_matrix)
+}
+
+/// # Safety
+/// See [`ku_estimate_2d`], for `C` dimensions.
+unsafe fn estimate_raw<const C: usize>(
+    src: *const f64,
+    dst: *const f64,
+    n: usize,
+    estimate_scale: c_int,
+    out_matrix: *mut f64,
+) -> KuStatus {
+    if src.is_null() || dst.is_null() || out_matrix.is_null() {
+        return KuStatus::NullPointer;
+    }
+    if n == 0 {
+        return KuStatus::Empty;
+    }
+    let Some(len) = n.checked_mul(C) else {
+        return KuStatus::Other;
+    };
+    // SAFETY: the caller guarantees that both pointers are valid for `len` reads
+    let (src, dst) = unsafe {
+        (
+            core::slice::from_raw_parts(src, len),
+            core::slice::from_raw_parts(dst, len),
+        )
+    };
+    let src = DMatrix::from_row_slice(n, C, src);
+    let dst = DMatrix::from_row_slice(n, C, dst);
+    match estimate_dyn(&src, &dst, estimate_scale != 0) {
+        Ok(matrix) => {
+            let size = (C + 1) * (C + 1);
+            // SAFETY: the caller guarantees that `out_matrix` is valid for `size` writes
+            let out = unsafe { core::slice::from_raw_parts_mut(out_matrix, size) };
+            out.copy_from_slice(matrix.transpose().as_slice());
+            KuStatus::Ok
+        }
+        Err(error) => KuStatus::from(&error),
+    }
+}
@@ -0,0 +1,400 @@
+//! Coordinate-frame metadata for point clouds and transformations, checked at runtime to catch
+//! transformations applied in the wrong direction or chained in the wrong order.
+use crate::{fit_points, rows_from_slice, DefaultSolver, KabschError, Transform};
+use nalgebra::{DMatrix, DVector, Matrix2, Matrix3, Rotation2, Rotation3, SMatrix, SVector};
+use std::collections::VecDeque;
+
+/// Points expressed in a named coordinate frame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PointCloud<const C: usize> {
+    frame: String,
+    points: Vec<[f64; C]>,
+}
+
+impl<const C: usize> PointCloud<C> {
+    /// New PointCloud from its frame and points
+    pub fn new(frame: impl Into<String>, points: Vec<[f64; C]>) -> Self {
+        Self {
+            frame: frame.into(),
+            points,
+        }
+    }
+
+    /// Frame the points are expressed in
+    pub fn frame(&self) -> &str {
+        &self.frame
+    }
+
+    /// Points
+    pub fn points(&self) -> &[[f64; C]] {
+        &self.points
+    }
+}
+
+/// Transformation mapping coordinates in the `source` frame to the `target` frame.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::frames::{FramedTransform, PointCloud};
+/// use kabsch_umeyama::KabschError;
+///
+/// let lidar = PointCloud::new("lidar", vec![[0., 0.], [1., 0.], [0., 1.]]);
+/// let camera = PointCloud::new("camera", vec![[1., 1.], [1., 2.], [0., 1.]]);
+/// let lidar_to_camera = FramedTransform::estimate(&lidar, &camera, false).unwrap();
+/// assert_eq!((lidar_to_camera.source(), lidar_to_camera.target()), ("lidar", "camera"));
+///
+/// let moved = lidar_to_camera.apply(&lidar).unwrap();
+/// assert_eq!(moved.frame(), "camera");
+///
+/// // applying it to camera points is the classic wrong-direction mistake
+/// assert_eq!(
+///     lidar_to_camera.apply(&camera).unwrap_err(),
+///     KabschError::FrameMismatch { expected: "lidar".into(), actual: "camera".into() }
+/// );
+/// assert!(lidar_to_camera.inverse().apply(&camera).is_ok());
+/// ```
+#[derive(Clone, Debug)]
+pub struct FramedTransform<const C: usize> {
+    source: String,
+    target: String,
+    transform: Transform<C>,
+}
+
+fn check(expected: &str, actual: &str) -> Result<(), KabschError> {
+    if expected == actual {
+        Ok(())
+    } else {
+        Err(KabschError::FrameMismatch {
+            expected: expected.into(),
+            actual: actual.into(),
+        })
+    }
+}
+
+impl<const C: usize> FramedTransform<C> {
+    /// New FramedTransform mapping the `source` frame to the `target` frame
+    pub fn new(
+        source: impl Into<String>,
+        target: impl Into<String>,
+        transform: Transform<C>,
+    ) -> Self {
+        Self {
+            source: source.into(),
+            target: target.into(),
+            transform,
+        }
+    }
+
+    /// Estimate the transformation from the frame of `src` to the frame of `dst` from
+    /// corresponding points.
+    pub fn estimate(
+        src: &PointCloud<C>,
+        dst: &PointCloud<C>,
+        estimate_scale: bool,
+    ) -> Result<Self, KabschError> {
+        if src.points.len() != dst.points.len() {
+            return Err(KabschError::ShapeMismatch {
+                expected: src.points.len() * C,
+                actual: dst.points.len() * C,
+            });
+        }
+        if src.points.is_empty() {
+            return Err(KabschError::Empty);
+        }
+        let fit = fit_points(
+            rows_from_slice(&src.points),
+            rows_from_slice(&dst.points),
+            &DVector::repeat(src.points.len(), 1.),
+            estimate_scale,
+            &DefaultSolver,
+        )?;
+        Ok(Self::new(&src.frame, &dst.frame, fit.to_transform()))
+    }
+
+    /// Source frame
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Target frame
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// Transformation
+    pub fn transform(&self) -> &Transform<C> {
+        &self.transform
+    }
+
+    /// Inverse transformation, from the target frame to the source frame
+    pub fn inverse(&self) -> Self {
+        Self::new(&self.target, &self.source, self.transform.inverse().clone())
+    }
+
+    /// Map a point cloud of the source frame to the target frame.
+    pub fn apply(&self, cloud: &PointCloud<C>) -> Result<PointCloud<C>, KabschError> {
+        check(&self.source, &cloud.frame)?;
+        let t = &self.transform;
+        let points = cloud
+            .points
+            .iter()
+            .map(|p| {
+                let q = t.rotation() * SVector::from(*p) * t.scale() + t.translation();
+                q.into()
+            })
+            .collect();
+        Ok(PointCloud::new(&self.target, points))
+    }
+
+    /// Chain `next` after this transformation: `A → B` then `B → C` gives `A → C`.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::frames::FramedTransform;
+    /// use kabsch_umeyama::Transform;
+    ///
+    /// let a_to_b = FramedTransform::<3>::new("a", "b", Transform::identity());
+    /// let b_to_c = FramedTransform::new("b", "c", Transform::identity());
+    /// assert_eq!(a_to_b.then(&b_to_c).unwrap().target(), "c");
+    /// assert!(b_to_c.then(&a_to_b).is_err());
+    /// ```
+    pub fn then(&self, next: &FramedTransform<C>) -> Result<Self, KabschError> {
+        check(&self.target, &next.source)?;
+        let (first, second) = (&self.transform, &next.transform);
+        let transform = Transform::new(
+            second.rotation() * first.rotation(),
+            second.rotation() * first.translation() * second.scale() + second.translation(),
+            second.scale() * first.scale(),
+        );
+        Ok(Self::new(&self.source, &next.target, transform))
+    }
+}
+
+/// Deviation of a transformation from the identity, e.g. of a chain of transformations around a
+/// loop of frames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Discrepancy {
+    /// Rotation angle in radians.
+    pub rotation: f64,
+    /// Norm of the translation.
+    pub translation: f64,
+    /// Absolute logarithm of the scale.
+    pub scale: f64,
+}
+
+impl Discrepancy {
+    /// Deviation of `transform` from the identity
+    pub fn of<const C: usize>(transform: &Transform<C>) -> Self {
+        // ‖R - I‖ = 2√2 sin(θ/2) for a rotation by θ in a plane
+        let chord = (transform.rotation() - SMatrix::<f64, C, C>::identity()).norm();
+        Self {
+            rotation: 2. * (chord / 8f64.sqrt()).min(1.).asin(),
+            translation: transform.translation().norm(),
+            scale: transform.scale().ln().abs(),
+        }
+    }
+}
+
+/// Registry of transformations between named frames, resolving the transformation between any two
+/// connected frames by chaining the known ones, in either direction.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::frames::{FrameRegistry, FramedTransform, PointCloud};
+/// use kabsch_umeyama::{KabschError, Transform};
+/// use nalgebra::{Matrix2, Vector2};
+///
+/// let quarter_turn = Matrix2::new(0., -1., 1., 0.);
+/// let mut registry = FrameRegistry::new();
+/// registry.insert(FramedTransform::new("lidar", "base", Transform::new(quarter_turn, Vector2::new(1., 0.), 1.)));
+/// registry.insert(FramedTransform::new("camera", "base", Transform::new(Matrix2::identity(), Vector2::new(0., 2.), 1.)));
+///
+/// let lidar_to_camera = registry.resolve("lidar", "camera").unwrap();
+/// let point = lidar_to_camera.apply(&PointCloud::new("lidar", vec![[1., 0.]])).unwrap();
+/// assert!((Vector2::from(point.points()[0]) - Vector2::new(1., -1.)).norm() < 1e-12);
+///
+/// // a third, slightly inconsistent path closes a loop
+/// let direct = FramedTransform::new("lidar", "camera", Transform::new(quarter_turn, Vector2::new(1., -2.01), 1.));
+/// let discrepancy = registry.insert(direct).unwrap();
+/// assert!((discrepancy.translation - 0.01).abs() < 1e-9);
+///
+/// assert!(matches!(registry.resolve("lidar", "gps"), Err(KabschError::NoFramePath { .. })));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FrameRegistry<const C: usize> {
+    transforms: Vec<FramedTransform<C>>,
+}
+
+impl<const C: usize> FrameRegistry<C> {
+    /// New empty FrameRegistry
+    pub fn new() -> Self {
+        Self {
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Known transformations, in insertion order
+    pub fn transforms(&self) -> &[FramedTransform<C>] {
+        &self.transforms
+    }
+
+    /// Add a transformation. If its frames were already connected, it closes a loop and the
+    /// discrepancy between it and the chain it bypasses is returned, which is zero for perfectly
+    /// consistent estimates.
+    pub fn insert(&mut self, transform: FramedTransform<C>) -> Option<Discrepancy> {
+        let discrepancy = self
+            .resolve(&transform.source, &transform.target)
+            .ok()
+            .and_then(|chain| transform.then(&chain.inverse()).ok())
+            .map(|round_trip| Discrepancy::of(&round_trip.transform));
+        self.transforms.push(transform);
+        discrepancy
+    }
+
+    /// Transformation from `source` to `target`, chaining the fewest known transformations.
+    pub fn resolve(&self, source: &str, target: &str) -> Result<FramedTransform<C>, KabschError> {
+        // breadth-first search from the source, remembering how each frame was reached
+        let mut reached = vec![(
+            source.to_string(),
+            FramedTransform::new(source, source, Transform::identity()),
+        )];
+        let mut queue = VecDeque::from([0]);
+        while let Some(current) = queue.pop_front() {
+            if reached[current].0 == target {
+                return Ok(reached.swap_remove(current).1);
+            }
+            let frame = reached[current].0.clone();
+            for t in &self.transforms {
+                let step = if t.source == frame {
+                    t.clone()
+                } else if t.target == frame {
+                    t.inverse()
+                } else {
+                    continue;
+                };
+                if reached.iter().any(|(f, _)| *f == step.target) {
+                    continue;
+                }
+                let chained = reached[current].1.then(&step)?;
+                reached.push((step.target.clone(), chained));
+                queue.push_back(reached.len() - 1);
+            }
+        }
+        Err(KabschError::NoFramePath {
+            source: source.into(),
+            target: target.into(),
+        })
+    }
+}
+
+/// Loop-closure analysis of a cycle of independently estimated transformations.
+#[derive(Clone, Debug)]
+pub struct CycleConsistency<const C: usize> {
+    /// Composition of the whole cycle, which is the identity for perfectly consistent estimates.
+    pub loop_transform: Transform<C>,
+    /// Deviation of the loop transformation from the identity.
+    pub error: Discrepancy,
+    /// Transformations of the cycle corrected by an equal share of the loop error, so that their
+    /// composition is the identity. Only computed in 2 and 3 dimensions.
+    pub corrected: Option<Vec<FramedTransform<C>>>,
+}
+
+/// `n`-th root of a similarity transformation: the similarity whose `n`-fold composition it is,
+/// with the rotation angle divided by `n`. Only available in 2 and 3 dimensions.
+fn root<const C: usize>(t: &Transform<C>, n: usize) -> Option<Transform<C>> {
+    let r = t.rotation();
+    let rotation = match C {
+        2 => {
+            let root = Rotation2::from_matrix_unchecked(Matrix2::from_column_slice(r.as_slice()))
+                .powf(1. / n as f64);
+            SMatrix::from_column_slice(root.matrix().as_slice())
+        }
+        3 => {
+            let root = Rotation3::from_matrix_unchecked(Matrix3::from_column_slice(r.as_slice()))
+                .powf(1. / n as f64);
+            SMatrix::from_column_slice(root.matrix().as_slice())
+        }
+        _ => return None,
+    };
+    let scale = t.scale().powf(1. / n as f64);
+    // the translation b of the root satisfies (I + A + … + Aⁿ⁻¹) b = t for its linear part A
+    let linear = rotation * scale;
+    let mut power = SMatrix::<f64, C, C>::identity();
+    let mut sum = SMatrix::<f64, C, C>::zeros();
+    for _ in 0..n {
+        sum += power;
+        power *= linear;
+    }
+    let sum = DMatrix::from_column_slice(C, C, sum.as_slice());
+    let translation = sum
+        .lu()
+        .solve(&DVector::from_column_slice(t.translation().as_slice()))?;
+    Some(Transform::new(
+        rotation,
+        SVector::from_column_slice(translation.as_slice()),
+        scale,
+    ))
+}
+
+/// Evaluate the consistency of a cycle of transformations estimated independently, e.g.
+/// `A → B`, `B → C` and `C → A` from pairwise calibrations, whose composition should be the
+/// identity: the residual quantifies the systematic calibration error.
+///
+/// The loop error is also distributed evenly over the cycle on the manifold: expressed in the first
+/// frame, each transformation is corrected by the `n`-th root of the inverse loop transformation.
+/// The [`KabschError::FrameMismatch`] error is returned if the transformations are not chained
+/// or the cycle does not close, and [`KabschError::Empty`] if there are none.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::frames::{cycle_consistency, FramedTransform};
+/// use kabsch_umeyama::Transform;
+/// use nalgebra::{Rotation3, Vector3};
+///
+/// let turn = |angle: f64| *Rotation3::from_axis_angle(&Vector3::z_axis(), angle).matrix();
+/// let cycle = [
+///     FramedTransform::new("a", "b", Transform::new(turn(0.5), Vector3::new(1., 0., 0.), 1.)),
+///     FramedTransform::new("b", "c", Transform::new(turn(0.7), Vector3::new(0., 1., 0.), 1.)),
+///     // slightly off the exact closure
+///     FramedTransform::new("c", "a", Transform::new(turn(-1.23), Vector3::new(-0.2, -1.3, 0.05), 1.)),
+/// ];
+/// let consistency = cycle_consistency(&cycle).unwrap();
+/// assert!((consistency.error.rotation - 0.03).abs() < 1e-9);
+///
+/// let corrected = consistency.corrected.unwrap();
+/// let closed = corrected[0].then(&corrected[1]).unwrap().then(&corrected[2]).unwrap();
+/// let residual = kabsch_umeyama::frames::Discrepancy::of(closed.transform());
+/// assert!(residual.rotation < 1e-9 && residual.translation < 1e-9);
+/// ```
+pub fn cycle_consistency<const C: usize>(
+    cycle: &[FramedTransform<C>],
+) -> Result<CycleConsistency<C>, KabschError> {
+    let (first, rest) = cycle.split_first().ok_or(KabschError::Empty)?;
+    // cumulative transformations from the first frame
+    let mut cumulative = vec![first.clone()];
+    for t in rest {
+        cumulative.push(cumulative[cumulative.len() - 1].then(t)?);
+    }
+    let closing = &cumulative[cumulative.len() - 1];
+    check(&closing.source, &closing.target)?;
+    let loop_transform = closing.transform.clone();
+
+    let n = cycle.len();
+    let corrected = root(loop_transform.inverse(), n).map(|share| {
+        let share = FramedTransform::new(&first.source, &first.source, share);
+        cycle
+            .iter()
+            .enumerate()
+            .map(|(k, t)| {
+                // Tₖ ∘ Pₖ₋₁ ∘ share ∘ Pₖ₋₁⁻¹ with Pₖ₋₁ the composition of the previous ones
+                let previous = match k {
+                    0 => FramedTransform::new(&t.source, &t.source, Transform::identity()),
+                    _ => cumulative[k - 1].clone(),
+                };
+                let local = previous.inverse().then(&share)?.then(&previous)?;
+                local.then(t)
+            })
+            .collect::<Result<Vec<_>, _>>()
+    });
+    Ok(CycleConsistency {
+        error: Discrepancy::of(&loop_transform),
+        loop_transform,
+        corrected: corrected.transpose()?,
+    })
+}
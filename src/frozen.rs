@@ -0,0 +1,121 @@
+use crate::{estimate_transform, Transform};
+use nalgebra::{DMatrix, DVector, Matrix3, Rotation3, SMatrix, Vector3};
+
+/// Degrees of freedom of a rigid transformation in 3 dimensions held at zero during estimation.
+///
+/// The rotation is parametrized by the angles `(rx, ry, rz)` of `R = Rz(rz) Ry(ry) Rx(rx)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrozenDofs {
+    /// Frozen translations along x, y and z.
+    pub translation: [bool; 3],
+    /// Frozen rotations about x, y and z.
+    pub rotation: [bool; 3],
+}
+
+impl FrozenDofs {
+    /// In-plane alignment in the xy-plane: translation along z and rotations about x and y are frozen.
+    pub const IN_PLANE: Self = Self {
+        translation: [false, false, true],
+        rotation: [true, true, false],
+    };
+
+    fn free(&self) -> Vec<usize> {
+        (0..6)
+            .filter(|&k| {
+                !if k < 3 {
+                    self.translation[k]
+                } else {
+                    self.rotation[k - 3]
+                }
+            })
+            .collect()
+    }
+}
+
+/// Estimate a rigid transformation in 3 dimensions with some degrees of freedom frozen, e.g. for
+/// fixtured parts where only in-plane alignment is physically meaningful.
+///
+/// The free parameters are refined by Gauss-Newton from the unconstrained estimate with its frozen
+/// parameters zeroed. The `None` value is returned if the free parameters are not constrained by
+/// the points.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_frozen, Array2, FrozenDofs};
+///
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 1.]]);
+/// // shifted in-plane by (1, 2), but the last point was measured 0.3 too high
+/// let dst = Array2::from([[1., 2., 0.], [2., 2., 0.], [1., 3., 0.], [2., 3., 1.3]]);
+///
+/// let t = estimate_frozen(src, dst, FrozenDofs::IN_PLANE).unwrap();
+/// assert_eq!(t.translation().z, 0.);
+/// assert!((t.translation().xy() - nalgebra::Vector2::new(1., 2.)).norm() < 1e-9);
+/// ```
+pub fn estimate_frozen<const R: usize>(
+    src: impl Into<SMatrix<f64, R, 3>>,
+    dst: impl Into<SMatrix<f64, R, 3>>,
+    frozen: FrozenDofs,
+) -> Option<Transform<3>> {
+    const MAX_ITERATIONS: usize = 50;
+    let src = src.into();
+    let dst = dst.into();
+    let free = frozen.free();
+
+    let mut params = [0.; 6];
+    if let Some(initial) = estimate_transform(src, dst, false) {
+        let (rx, ry, rz) = Rotation3::from_matrix_unchecked(*initial.rotation()).euler_angles();
+        params[..3].copy_from_slice(initial.translation().as_slice());
+        params[3..].copy_from_slice(&[rx, ry, rz]);
+    }
+    (0..6)
+        .filter(|k| !free.contains(k))
+        .for_each(|k| params[k] = 0.);
+
+    for _ in 0..MAX_ITERATIONS {
+        if free.is_empty() {
+            break;
+        }
+        let [tx, ty, tz, rx, ry, rz] = params;
+        let (rot_x, rot_y, rot_z) = (
+            Rotation3::from_euler_angles(rx, 0., 0.).into_inner(),
+            Rotation3::from_euler_angles(0., ry, 0.).into_inner(),
+            Rotation3::from_euler_angles(0., 0., rz).into_inner(),
+        );
+        let rotation = rot_z * rot_y * rot_x;
+        let derivatives = [
+            rotation * Vector3::<f64>::x().cross_matrix(),
+            rot_z * rot_y * Vector3::<f64>::y().cross_matrix() * rot_x,
+            Vector3::<f64>::z().cross_matrix() * rotation,
+        ];
+        let translation = Vector3::new(tx, ty, tz);
+
+        let mut jacobian = DMatrix::<f64>::zeros(3 * R, free.len());
+        let mut residuals = DVector::<f64>::zeros(3 * R);
+        for i in 0..R {
+            let p = src.row(i).transpose();
+            let r = rotation * p + translation - dst.row(i).transpose();
+            residuals.rows_mut(3 * i, 3).copy_from(&r);
+            for (column, &k) in free.iter().enumerate() {
+                let derivative = if k < 3 {
+                    Vector3::ith(k, 1.)
+                } else {
+                    derivatives[k - 3] * p
+                };
+                jacobian
+                    .view_mut((3 * i, column), (3, 1))
+                    .copy_from(&derivative);
+            }
+        }
+        let step = (jacobian.transpose() * &jacobian)
+            .cholesky()?
+            .solve(&(-jacobian.transpose() * residuals));
+        free.iter()
+            .zip(step.iter())
+            .for_each(|(&k, delta)| params[k] += delta);
+        if step.norm() < 1e-12 {
+            break;
+        }
+    }
+    let [tx, ty, tz, rx, ry, rz] = params;
+    let rotation: Matrix3<f64> = Rotation3::from_euler_angles(rx, ry, rz).into_inner();
+    Some(Transform::new(rotation, Vector3::new(tx, ty, tz), 1.))
+}
@@ -0,0 +1,194 @@
+//! Brute-force nearest-neighbor search in a `wgpu` compute shader (requires the `gpu` feature).
+//!
+//! The distances are computed in `f32` on points centered on the centroid of the searched set,
+//! so that georeferenced coordinates keep their precision.
+use crate::icp::{centered_f32, centroid};
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r"
+struct Params {
+    points: u32,
+    queries: u32,
+    padding: vec2<u32>,
+}
+
+@group(0) @binding(0) var<storage, read> points: array<vec4<f32>>;
+@group(0) @binding(1) var<storage, read> queries: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> nearest: array<u32>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= params.queries) {
+        return;
+    }
+    let query = queries[i];
+    var best = 0u;
+    var best_distance = 3.4e38;
+    for (var j = 0u; j < params.points; j++) {
+        let offset = points[j] - query;
+        let distance = dot(offset, offset);
+        if (distance < best_distance) {
+            best_distance = distance;
+            best = j;
+        }
+    }
+    nearest[i] = best;
+}
+";
+
+const WORKGROUP_SIZE: usize = 64;
+
+/// Queries of a single dispatch, within the limit of 65535 workgroups along an axis.
+const MAX_QUERIES: usize = 65535 * WORKGROUP_SIZE;
+
+/// Points uploaded to the GPU for nearest-neighbor queries.
+pub(crate) struct GpuSearch<const C: usize> {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    gpu_points: wgpu::Buffer,
+    count: usize,
+    center: [f64; C],
+}
+
+impl<const C: usize> GpuSearch<C> {
+    /// Upload `points` to the first high-performance adapter, the `None` value if there is no
+    /// adapter, if they do not fit in a storage buffer or if they have more than 4 dimensions.
+    pub(crate) fn new(points: &[[f64; C]]) -> Option<Self> {
+        if C == 0 || C > 4 || points.is_empty() {
+            return None;
+        }
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: None,
+        }))?;
+        let limits = adapter.limits();
+        if (points.len() * 16) as u64 > limits.max_storage_buffer_binding_size as u64 {
+            return None;
+        }
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits: limits,
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: None,
+            layout: None,
+            module: &module,
+            entry_point: "main",
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        let center = centroid(points);
+        let gpu_points = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&centered_f32(points, &center)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        Some(Self {
+            device,
+            queue,
+            pipeline,
+            gpu_points,
+            count: points.len(),
+            center,
+        })
+    }
+
+    /// Index of the point nearest to each query, the `None` value if the GPU could not complete
+    /// the search.
+    pub(crate) fn nearest(&self, queries: &[[f64; C]]) -> Option<Vec<u32>> {
+        let mut nearest = Vec::with_capacity(queries.len());
+        for chunk in queries.chunks(MAX_QUERIES) {
+            nearest.extend(self.dispatch(chunk)?);
+        }
+        Some(nearest)
+    }
+
+    fn dispatch(&self, queries: &[[f64; C]]) -> Option<Vec<u32>> {
+        let device = &self.device;
+        let gpu_queries = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&centered_f32(queries, &self.center)),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let params = [self.count as u32, queries.len() as u32, 0, 0];
+        let gpu_params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&params),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let size = (queries.len() * std::mem::size_of::<u32>()) as u64;
+        let output = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: None,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.gpu_points.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: gpu_queries.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: output.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: gpu_params.as_entire_binding(),
+                },
+            ],
+        });
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups(queries.len().div_ceil(WORKGROUP_SIZE) as u32, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&output, 0, &staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let slice = staging.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+        let indices = bytemuck::cast_slice::<u8, u32>(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        Some(indices)
+    }
+}
@@ -0,0 +1,347 @@
+//! Iterative Closest Point (ICP): registration of point sets without known correspondences, by
+//! alternating nearest-neighbor matching and estimation.
+use crate::{fit_points, rows_from_slice, DefaultSolver, KabschError, Transform};
+use nalgebra::{DVector, SVector};
+use std::sync::OnceLock;
+
+/// Kd-tree over points for nearest-neighbor queries.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::icp::KdTree;
+///
+/// let tree = KdTree::new(vec![[0., 0.], [2., 0.], [0., 3.], [5., 5.]]);
+/// let (index, distance) = tree.nearest(&[1.8, 0.4]).unwrap();
+/// assert_eq!(index, 1);
+/// assert!((distance - 0.2f64.hypot(0.4)).abs() < 1e-12);
+/// ```
+#[derive(Clone, Debug)]
+pub struct KdTree<const C: usize> {
+    points: Vec<[f64; C]>,
+    /// Indices of the points in tree order: the median of a range splits it along the axis of its
+    /// depth.
+    order: Vec<usize>,
+}
+
+impl<const C: usize> KdTree<C> {
+    /// New KdTree from points
+    pub fn new(points: Vec<[f64; C]>) -> Self {
+        let mut order = (0..points.len()).collect::<Vec<_>>();
+        build(&points, &mut order, 0);
+        Self { points, order }
+    }
+
+    /// Points of the tree, in their original order
+    pub fn points(&self) -> &[[f64; C]] {
+        &self.points
+    }
+
+    /// Index of the point nearest to `query` and its distance, the `None` value if the tree is empty
+    pub fn nearest(&self, query: &[f64; C]) -> Option<(usize, f64)> {
+        let mut best = None;
+        self.search(0, self.order.len(), 0, query, &mut best);
+        best.map(|(index, squared): (usize, f64)| (index, squared.sqrt()))
+    }
+
+    /// Nearest point of each query like [`KdTree::nearest`], the queries being split in chunks
+    /// searched on up to `threads` threads.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::icp::KdTree;
+    ///
+    /// let tree = KdTree::new((0..100).map(|i| [i as f64, 0.]).collect());
+    /// let queries = (0..1000).map(|i| [i as f64 * 0.1 + 0.01, 1.]).collect::<Vec<_>>();
+    /// let nearest = tree.nearest_batch(&queries, 4);
+    /// assert_eq!(nearest.len(), 1000);
+    /// assert!(nearest.iter().zip(&queries).all(|(n, q)| *n == tree.nearest(q)));
+    /// ```
+    pub fn nearest_batch(&self, queries: &[[f64; C]], threads: usize) -> Vec<Option<(usize, f64)>> {
+        let chunk = queries.len().div_ceil(threads.max(1)).max(MIN_CHUNK);
+        if chunk >= queries.len() {
+            return queries.iter().map(|q| self.nearest(q)).collect();
+        }
+        std::thread::scope(|scope| {
+            let handles = queries
+                .chunks(chunk)
+                .map(|chunk| {
+                    scope.spawn(move || chunk.iter().map(|q| self.nearest(q)).collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    fn search(
+        &self,
+        start: usize,
+        end: usize,
+        depth: usize,
+        query: &[f64; C],
+        best: &mut Option<(usize, f64)>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let mid = (start + end) / 2;
+        let index = self.order[mid];
+        let point = &self.points[index];
+        let squared = (SVector::from(*point) - SVector::from(*query)).norm_squared();
+        if best.map_or(true, |(_, b)| squared < b) {
+            *best = Some((index, squared));
+        }
+        let axis = depth % C.max(1);
+        let offset = query[axis] - point[axis];
+        let (near, far) = if offset < 0. {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+        self.search(near.0, near.1, depth + 1, query, best);
+        if best.map_or(true, |(_, b)| offset * offset < b) {
+            self.search(far.0, far.1, depth + 1, query, best);
+        }
+    }
+}
+
+/// Smallest number of queries worth a thread of their own.
+const MIN_CHUNK: usize = 256;
+
+fn build<const C: usize>(points: &[[f64; C]], order: &mut [usize], depth: usize) {
+    if order.len() <= 1 {
+        return;
+    }
+    let axis = depth % C.max(1);
+    let mid = order.len() / 2;
+    order.select_nth_unstable_by(mid, |a, b| points[*a][axis].total_cmp(&points[*b][axis]));
+    let (left, right) = order.split_at_mut(mid);
+    build(points, left, depth + 1);
+    build(points, &mut right[1..], depth + 1);
+}
+
+/// Nearest-neighbor search backend of [`icp`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SearchBackend {
+    /// [`KdTree`] on the CPU.
+    #[default]
+    KdTree,
+    /// Brute-force search on the GPU with the `gpu` feature, for clouds of millions of points.
+    /// The search falls back to the [`KdTree`] without the feature, without a GPU adapter, or for
+    /// more than 4 dimensions.
+    Gpu,
+    /// Brute-force search on an NVIDIA GPU through CUDA with the `cuda` feature, e.g. on HPC nodes,
+    /// falling back to the [`KdTree`] like [`SearchBackend::Gpu`].
+    Cuda,
+}
+
+/// Destination points searched for the correspondences, with the [`KdTree`] only built if the
+/// selected device is unavailable.
+struct Correspondences<'a, const C: usize> {
+    dst: &'a [[f64; C]],
+    tree: OnceLock<KdTree<C>>,
+    #[cfg(feature = "gpu")]
+    gpu: Option<crate::gpu::GpuSearch<C>>,
+    #[cfg(feature = "cuda")]
+    cuda: Option<crate::cuda::CudaSearch<C>>,
+}
+
+impl<'a, const C: usize> Correspondences<'a, C> {
+    fn new(dst: &'a [[f64; C]], backend: SearchBackend) -> Self {
+        #[cfg(not(any(feature = "gpu", feature = "cuda")))]
+        let _ = backend;
+        Self {
+            dst,
+            tree: OnceLock::new(),
+            #[cfg(feature = "gpu")]
+            gpu: (backend == SearchBackend::Gpu)
+                .then(|| crate::gpu::GpuSearch::new(dst))
+                .flatten(),
+            #[cfg(feature = "cuda")]
+            cuda: (backend == SearchBackend::Cuda)
+                .then(|| crate::cuda::CudaSearch::new(dst))
+                .flatten(),
+        }
+    }
+
+    fn nearest_batch(&self, queries: &[[f64; C]], threads: usize) -> Vec<Option<(usize, f64)>> {
+        #[cfg(feature = "gpu")]
+        if let Some(indices) = self.gpu.as_ref().and_then(|gpu| gpu.nearest(queries)) {
+            return self.with_distances(queries, indices);
+        }
+        #[cfg(feature = "cuda")]
+        if let Some(indices) = self.cuda.as_ref().and_then(|cuda| cuda.nearest(queries)) {
+            return self.with_distances(queries, indices);
+        }
+        self.tree
+            .get_or_init(|| KdTree::new(self.dst.to_vec()))
+            .nearest_batch(queries, threads)
+    }
+
+    /// Nearest points found on a device, with their distances recomputed in `f64`.
+    #[cfg(any(feature = "gpu", feature = "cuda"))]
+    fn with_distances(&self, queries: &[[f64; C]], indices: Vec<u32>) -> Vec<Option<(usize, f64)>> {
+        queries
+            .iter()
+            .zip(indices)
+            .map(|(query, index)| {
+                let point = SVector::from(self.dst[index as usize]);
+                Some((index as usize, (point - SVector::from(*query)).norm()))
+            })
+            .collect()
+    }
+}
+
+/// Centroid of points searched on a device.
+#[cfg(any(feature = "gpu", feature = "cuda"))]
+pub(crate) fn centroid<const C: usize>(points: &[[f64; C]]) -> [f64; C] {
+    std::array::from_fn(|j| points.iter().map(|p| p[j]).sum::<f64>() / points.len() as f64)
+}
+
+/// Points relative to `center`, padded to 4 dimensions in `f32` for a device.
+#[cfg(any(feature = "gpu", feature = "cuda"))]
+pub(crate) fn centered_f32<const C: usize>(
+    points: &[[f64; C]],
+    center: &[f64; C],
+) -> Vec<[f32; 4]> {
+    points
+        .iter()
+        .map(|p| std::array::from_fn(|j| if j < C { (p[j] - center[j]) as f32 } else { 0. }))
+        .collect()
+}
+
+/// Options of [`icp`].
+#[derive(Clone, Copy, Debug)]
+pub struct IcpOptions {
+    /// Maximum number of iterations.
+    pub max_iterations: usize,
+    /// Convergence is reached when the RMSE decreases by less than this between iterations.
+    pub tolerance: f64,
+    /// Fraction of the matches with the largest distances discarded at each iteration (trimmed
+    /// ICP), for partially overlapping point sets.
+    pub trim_fraction: f64,
+    pub estimate_scale: bool,
+    /// Threads of the correspondence search, which dominates the runtime on large point sets; all
+    /// the available cores by default.
+    pub threads: usize,
+    pub backend: SearchBackend,
+}
+
+impl Default for IcpOptions {
+    fn default() -> Self {
+        Self {
+            max_iterations: 50,
+            tolerance: 1e-9,
+            trim_fraction: 0.,
+            estimate_scale: false,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            backend: SearchBackend::KdTree,
+        }
+    }
+}
+
+/// Result of [`icp`].
+#[derive(Clone, Debug)]
+pub struct IcpRegistration<const C: usize> {
+    /// Transformation mapping the source points onto the destination points.
+    pub transform: Transform<C>,
+    /// Root-mean-square distance of the kept matches before each iteration.
+    pub rmse: Vec<f64>,
+    pub converged: bool,
+}
+
+/// Register `src` onto `dst` without known correspondences, starting from the identity: each
+/// source point is matched to its nearest destination point (through a [`KdTree`], in parallel
+/// chunks), the worst matches are trimmed, and the transformation is estimated from the kept
+/// matches, until the RMSE stops decreasing.
+/// The [`KabschError::Empty`] error is returned if either point set is empty, or if trimming
+/// leaves no match.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::icp::{icp, IcpOptions};
+/// use nalgebra::{Rotation2, Vector2};
+///
+/// let dst = (0..40)
+///     .map(|i| {
+///         let t = i as f64 * 0.25;
+///         [t.cos() * (2. + t), t.sin() * (1. + 0.5 * t)]
+///     })
+///     .collect::<Vec<_>>();
+/// // the same curve, slightly rotated and shifted
+/// let rotation = Rotation2::new(0.05);
+/// let src = dst
+///     .iter()
+///     .map(|p| (rotation * Vector2::from(*p) + Vector2::new(0.1, -0.05)).into())
+///     .collect::<Vec<[f64; 2]>>();
+///
+/// let registration = icp(&src, &dst, &IcpOptions::default()).unwrap();
+/// assert!(registration.converged);
+/// assert!(registration.rmse.last().unwrap() < &1e-6);
+/// assert!(registration.rmse[0] > 0.1);
+/// ```
+pub fn icp<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    options: &IcpOptions,
+) -> Result<IcpRegistration<C>, KabschError> {
+    icp_from(src, dst, Transform::identity(), options)
+}
+
+/// Register `src` onto `dst` like [`icp`], starting from the `initial` transformation.
+pub fn icp_from<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    initial: Transform<C>,
+    options: &IcpOptions,
+) -> Result<IcpRegistration<C>, KabschError> {
+    if src.is_empty() || dst.is_empty() {
+        return Err(KabschError::Empty);
+    }
+    let search = Correspondences::new(dst, options.backend);
+    let kept = src.len() - (src.len() as f64 * options.trim_fraction.clamp(0., 1.)) as usize;
+    if kept == 0 {
+        return Err(KabschError::Empty);
+    }
+    let mut transform = initial;
+    let mut rmse = Vec::with_capacity(options.max_iterations);
+    let mut converged = false;
+    for _ in 0..options.max_iterations {
+        let mut moved = src.to_vec();
+        transform.apply_mut(&mut moved);
+        let mut matches = search
+            .nearest_batch(&moved, options.threads)
+            .into_iter()
+            .zip(src)
+            .map(|(nearest, p)| {
+                // the destination points are not empty
+                let (index, distance) = nearest.unwrap();
+                (distance, *p, dst[index])
+            })
+            .collect::<Vec<_>>();
+        if kept < matches.len() {
+            matches.select_nth_unstable_by(kept, |a, b| a.0.total_cmp(&b.0));
+            matches.truncate(kept);
+        }
+        let error = (matches.iter().map(|m| m.0 * m.0).sum::<f64>() / kept as f64).sqrt();
+        let previous = rmse.last().copied();
+        rmse.push(error);
+        if previous.is_some_and(|previous: f64| previous - error < options.tolerance) {
+            converged = true;
+            break;
+        }
+        let (src, dst): (Vec<_>, Vec<_>) = matches.iter().map(|m| (m.1, m.2)).unzip();
+        transform = fit_points(
+            rows_from_slice(&src),
+            rows_from_slice(&dst),
+            &DVector::repeat(kept, 1.),
+            options.estimate_scale,
+            &DefaultSolver,
+        )?
+        .to_transform();
+    }
+    Ok(IcpRegistration {
+        transform,
+        rmse,
+        converged,
+    })
+}
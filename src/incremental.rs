@@ -0,0 +1,151 @@
+use crate::{solver, DefaultSolver, KabschError, Transform};
+use nalgebra::{DMatrix, SMatrix, SVector};
+
+/// Estimator accumulating correspondences one at a time, e.g. from a tracking loop, in `O(C²)`
+/// memory: the running weighted means, cross-covariance and source variance are the sufficient
+/// statistics of the similarity transformation, which can be solved for at any time.
+///
+/// The statistics are updated with Welford's algorithm, and estimators accumulated separately,
+/// e.g. on several threads, are combined with [`IncrementalEstimator::merge`].
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_transform, Array2, IncrementalEstimator, KabschError};
+///
+/// let src = [[0., 0.], [1., 0.], [0., 1.], [2., 3.]];
+/// let dst = [[1., 1.], [1., 3.], [-1., 1.], [-5., 5.1]];
+///
+/// let mut first = IncrementalEstimator::new();
+/// let mut second = IncrementalEstimator::new();
+/// first.push(src[0], dst[0]).unwrap();
+/// first.push(src[1], dst[1]).unwrap();
+/// second.push(src[2], dst[2]).unwrap();
+/// second.push_weighted(src[3], dst[3], 1.).unwrap();
+/// first.merge(&second);
+/// assert_eq!(first.total_weight(), 4.);
+///
+/// let t = first.solve(true).unwrap();
+/// let expected = estimate_transform(Array2::from(src), Array2::from(dst), true).unwrap();
+/// assert!((t.to_homogeneous() - expected.to_homogeneous()).abs().max() < 1e-12);
+///
+/// assert!(IncrementalEstimator::<2>::new().solve(true).is_err());
+///
+/// // rejected correspondences leave the estimator untouched
+/// assert_eq!(first.push([f64::NAN, 0.], [0., 0.]), Err(KabschError::NonFinite));
+/// let infinite = first.push_weighted([0., 0.], [0., 0.], f64::INFINITY);
+/// assert_eq!(infinite, Err(KabschError::NonFinite));
+/// assert_eq!(first.total_weight(), 4.);
+/// ```
+#[derive(Clone, Debug)]
+pub struct IncrementalEstimator<const C: usize> {
+    weight: f64,
+    src_mean: SVector<f64, C>,
+    dst_mean: SVector<f64, C>,
+    /// `Σ wᵢ (qᵢ - q̄)(pᵢ - p̄)ᵀ`
+    comoment: SMatrix<f64, C, C>,
+    /// `Σ wᵢ |pᵢ - p̄|²`
+    src_variance: f64,
+}
+
+impl<const C: usize> Default for IncrementalEstimator<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const C: usize> IncrementalEstimator<C> {
+    /// New IncrementalEstimator without any correspondence
+    pub fn new() -> Self {
+        Self {
+            weight: 0.,
+            src_mean: SVector::zeros(),
+            dst_mean: SVector::zeros(),
+            comoment: SMatrix::zeros(),
+            src_variance: 0.,
+        }
+    }
+
+    /// Sum of the weights of the correspondences
+    pub fn total_weight(&self) -> f64 {
+        self.weight
+    }
+
+    /// Add the correspondence of `src` to `dst`.
+    /// A [`KabschError::NonFinite`] error is returned, and the correspondence ignored, if a
+    /// coordinate is not finite.
+    pub fn push(&mut self, src: [f64; C], dst: [f64; C]) -> Result<(), KabschError> {
+        self.push_weighted(src, dst, 1.)
+    }
+
+    /// Add the correspondence of `src` to `dst` with the given weight, ignored if it is zero or
+    /// negative.
+    /// A [`KabschError::NonFinite`] error is returned, and the correspondence ignored, if a
+    /// coordinate or the weight is not finite.
+    pub fn push_weighted(
+        &mut self,
+        src: [f64; C],
+        dst: [f64; C],
+        weight: f64,
+    ) -> Result<(), KabschError> {
+        let mut values = src.iter().chain(&dst).chain([&weight]);
+        if values.any(|v| !v.is_finite()) {
+            return Err(KabschError::NonFinite);
+        }
+        if weight <= 0. {
+            return Ok(());
+        }
+        let (p, q) = (SVector::from(src), SVector::from(dst));
+        self.weight += weight;
+        let ratio = weight / self.weight;
+        let src_offset = p - self.src_mean;
+        self.src_mean += src_offset * ratio;
+        self.dst_mean += (q - self.dst_mean) * ratio;
+        self.comoment += (q - self.dst_mean) * src_offset.transpose() * weight;
+        self.src_variance += src_offset.dot(&(p - self.src_mean)) * weight;
+        Ok(())
+    }
+
+    /// Add the correspondences accumulated by `other`.
+    pub fn merge(&mut self, other: &Self) {
+        if other.weight <= 0. {
+            return;
+        }
+        let weight = self.weight + other.weight;
+        let src_offset = other.src_mean - self.src_mean;
+        let dst_offset = other.dst_mean - self.dst_mean;
+        let cross = self.weight * other.weight / weight;
+        self.comoment += other.comoment + dst_offset * src_offset.transpose() * cross;
+        self.src_variance += other.src_variance + src_offset.norm_squared() * cross;
+        self.src_mean += src_offset * (other.weight / weight);
+        self.dst_mean += dst_offset * (other.weight / weight);
+        self.weight = weight;
+    }
+
+    /// Similarity transformation of the correspondences accumulated so far, like
+    /// [`crate::estimate_transform`].
+    /// A [`KabschError::Empty`] error is returned without correspondences, and the failures of the
+    /// estimation are reported like [`crate::try_estimate`].
+    pub fn solve(&self, estimate_scale: bool) -> Result<Transform<C>, KabschError> {
+        if self.weight <= 0. || C == 0 {
+            return Err(KabschError::Empty);
+        }
+        let covariance = DMatrix::from_column_slice(C, C, self.comoment.as_slice()) / self.weight;
+        // finite correspondences may still overflow the moments
+        let moments = [self.weight, self.src_variance].into_iter();
+        let mut moments = moments.chain(self.src_mean.iter().chain(&self.dst_mean).copied());
+        if moments.any(|v| !v.is_finite()) {
+            return Err(KabschError::NonFinite);
+        }
+        let m = solver::rotation_or_error(&DefaultSolver, &covariance, solver::RANK_TOL)?;
+        let rotation = SMatrix::<f64, C, C>::from_column_slice(m.as_slice());
+        let scale = if estimate_scale {
+            (rotation.transpose() * self.comoment).trace() / self.src_variance
+        } else {
+            1.
+        };
+        let translation = self.dst_mean - rotation * self.src_mean * scale;
+        if !scale.is_finite() || translation.iter().any(|v| !v.is_finite()) {
+            return Err(KabschError::NonFinite);
+        }
+        Ok(Transform::new(rotation, translation, scale))
+    }
+}
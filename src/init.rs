@@ -0,0 +1,93 @@
+//! Initial pose guesses for alignment without known correspondences.
+use crate::Transform;
+use nalgebra::{Matrix2, Vector2};
+
+/// Best rotation found by [`grid_search_rotation_2d`].
+#[derive(Clone, Debug)]
+pub struct GridSearch {
+    /// Rigid transformation rotating `src` about its centroid by `angle` onto the centroid of `dst`.
+    pub transform: Transform<2>,
+    /// Rotation angle, in radians.
+    pub angle: f64,
+    /// Chamfer distance of the transformed `src` to `dst`.
+    pub chamfer: f64,
+}
+
+/// Symmetric chamfer distance: the mean squared distance from each point of `a` to its nearest
+/// point of `b`, plus the same from `b` to `a`.
+pub fn chamfer_distance<const C: usize>(a: &[[f64; C]], b: &[[f64; C]]) -> f64 {
+    mean_nearest_squared(a, b) + mean_nearest_squared(b, a)
+}
+
+fn mean_nearest_squared<const C: usize>(from: &[[f64; C]], to: &[[f64; C]]) -> f64 {
+    from.iter()
+        .map(|p| {
+            to.iter()
+                .map(|q| p.iter().zip(q).map(|(x, y)| (x - y).powi(2)).sum::<f64>())
+                .fold(f64::INFINITY, f64::min)
+        })
+        .sum::<f64>()
+        / from.len() as f64
+}
+
+/// Coarse search of the rotation aligning two 2D point sets of unknown correspondences.
+///
+/// After matching the centroids, `steps` rotations evenly spaced over a full turn are scored by
+/// their chamfer distance. Unlike principal axes, this initializer does not break down on
+/// symmetric shapes. The `None` value is returned if either set is empty or `steps` is 0.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::init::grid_search_rotation_2d;
+///
+/// // an L-shape, and the same shape rotated by a quarter turn with shuffled points
+/// let src = [[0., 0.], [1., 0.], [2., 0.], [0., 1.], [0., 2.], [0., 3.]];
+/// let dst = [[5., 4.], [5., 6.], [3., 4.], [2., 4.], [5., 5.], [4., 4.]];
+///
+/// let best = grid_search_rotation_2d(&src, &dst, 36).unwrap();
+/// assert!((best.angle - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+/// assert!(best.chamfer < 1e-9);
+/// ```
+pub fn grid_search_rotation_2d(
+    src: &[[f64; 2]],
+    dst: &[[f64; 2]],
+    steps: usize,
+) -> Option<GridSearch> {
+    if src.is_empty() || dst.is_empty() || steps == 0 {
+        return None;
+    }
+    let src_mean = centroid(src);
+    let dst_mean = centroid(dst);
+    let mut rotated = vec![[0.; 2]; src.len()];
+    (0..steps)
+        .map(|k| {
+            let angle = std::f64::consts::TAU * k as f64 / steps as f64;
+            let rotation = rotation_2d(angle);
+            rotated.iter_mut().zip(src).for_each(|(r, p)| {
+                let q = rotation * (Vector2::from(*p) - src_mean) + dst_mean;
+                *r = [q.x, q.y];
+            });
+            (angle, chamfer_distance(&rotated, dst))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(angle, chamfer)| {
+            let rotation = rotation_2d(angle);
+            GridSearch {
+                transform: Transform::new(rotation, dst_mean - rotation * src_mean, 1.),
+                angle,
+                chamfer,
+            }
+        })
+}
+
+fn centroid(points: &[[f64; 2]]) -> Vector2<f64> {
+    points
+        .iter()
+        .map(|p| Vector2::from(*p))
+        .sum::<Vector2<f64>>()
+        / points.len() as f64
+}
+
+fn rotation_2d(angle: f64) -> Matrix2<f64> {
+    let (sin, cos) = angle.sin_cos();
+    Matrix2::new(cos, -sin, sin, cos)
+}
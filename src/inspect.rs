@@ -0,0 +1,293 @@
+//! Dimensional inspection: deviations of an aligned measured cloud from a reference surface.
+use crate::KabschError;
+
+/// Reference surface against which measured points are inspected.
+pub trait Surface<const C: usize> {
+    /// Signed distance from `point` to the surface, positive on the side the normals point to.
+    fn signed_distance(&self, point: &[f64; C]) -> f64;
+}
+
+/// Reference surface sampled as points, with optional outward normals.
+///
+/// The deviation of a measured point is its distance to the nearest reference point, signed by the
+/// normal of that reference point when normals are given, unsigned otherwise.
+#[derive(Clone, Copy, Debug)]
+pub struct PointSurface<'a, const C: usize> {
+    points: &'a [[f64; C]],
+    normals: Option<&'a [[f64; C]]>,
+}
+
+impl<'a, const C: usize> PointSurface<'a, C> {
+    /// New PointSurface, checking there is one normal per point
+    pub fn new(
+        points: &'a [[f64; C]],
+        normals: Option<&'a [[f64; C]]>,
+    ) -> Result<Self, KabschError> {
+        if points.is_empty() {
+            return Err(KabschError::Empty);
+        }
+        if let Some(normals) = normals.filter(|normals| normals.len() != points.len()) {
+            return Err(KabschError::ShapeMismatch {
+                expected: points.len(),
+                actual: normals.len(),
+            });
+        }
+        Ok(Self { points, normals })
+    }
+}
+
+impl<const C: usize> Surface<C> for PointSurface<'_, C> {
+    fn signed_distance(&self, point: &[f64; C]) -> f64 {
+        let (nearest, squared) = self
+            .points
+            .iter()
+            .map(|q| {
+                point
+                    .iter()
+                    .zip(q)
+                    .map(|(a, b)| (a - b).powi(2))
+                    .sum::<f64>()
+            })
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        let distance = squared.sqrt();
+        match self.normals {
+            Some(normals) => {
+                let side = point
+                    .iter()
+                    .zip(&self.points[nearest])
+                    .zip(&normals[nearest])
+                    .map(|((p, q), n)| (p - q) * n)
+                    .sum::<f64>();
+                if side < 0. {
+                    -distance
+                } else {
+                    distance
+                }
+            }
+            None => distance,
+        }
+    }
+}
+
+/// Summary statistics of the deviations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DeviationStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    /// Root mean square of the deviations.
+    pub rms: f64,
+    /// Largest absolute deviation.
+    pub max_abs: f64,
+}
+
+impl DeviationStats {
+    /// Statistics of a non-empty set of deviations, `None` if empty.
+    pub fn new(deviations: &[f64]) -> Option<Self> {
+        if deviations.is_empty() {
+            return None;
+        }
+        let count = deviations.len() as f64;
+        let mean = deviations.iter().sum::<f64>() / count;
+        let mean_square = deviations.iter().map(|d| d * d).sum::<f64>() / count;
+        Some(Self {
+            count: deviations.len(),
+            min: deviations.iter().copied().fold(f64::INFINITY, f64::min),
+            max: deviations.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            mean,
+            std_dev: (mean_square - mean * mean).max(0.).sqrt(),
+            rms: mean_square.sqrt(),
+            max_abs: deviations.iter().fold(0., |m: f64, d| m.max(d.abs())),
+        })
+    }
+}
+
+/// Per-point deviations of a measured cloud and their statistics.
+#[derive(Clone, Debug)]
+pub struct Inspection {
+    /// Signed deviation of each measured point, in input order.
+    pub deviations: Vec<f64>,
+    pub stats: DeviationStats,
+}
+
+/// Compute the deviations of an aligned measured cloud from a reference surface.
+/// The `None` value is returned if `measured` is empty.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::inspect::{inspect, PointSurface};
+///
+/// // reference plane z = 0 sampled on a grid, with upward normals
+/// let reference: Vec<[f64; 3]> = (0..25).map(|i| [(i % 5) as f64, (i / 5) as f64, 0.]).collect();
+/// let normals = vec![[0., 0., 1.]; reference.len()];
+/// let surface = PointSurface::new(&reference, Some(&normals)).unwrap();
+///
+/// let measured = [[1., 1., 0.1], [2., 3., -0.2], [4., 0., 0.]];
+/// let inspection = inspect(&measured, &surface).unwrap();
+/// assert_eq!(inspection.deviations, vec![0.1, -0.2, 0.]);
+/// assert_eq!(inspection.stats.max_abs, 0.2);
+/// ```
+pub fn inspect<const C: usize>(
+    measured: &[[f64; C]],
+    surface: &impl Surface<C>,
+) -> Option<Inspection> {
+    let deviations = measured
+        .iter()
+        .map(|p| surface.signed_distance(p))
+        .collect::<Vec<_>>();
+    let stats = DeviationStats::new(&deviations)?;
+    Some(Inspection { deviations, stats })
+}
+
+/// Acceptable range `[lower, upper]` of the signed deviations.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Tolerance {
+    /// New Tolerance from its bounds
+    pub fn new(lower: f64, upper: f64) -> Self {
+        Self { lower, upper }
+    }
+
+    /// Symmetric tolerance `[-tol, tol]`
+    pub fn symmetric(tol: f64) -> Self {
+        Self::new(-tol.abs(), tol.abs())
+    }
+
+    /// Whether a deviation lies within the tolerance
+    pub fn contains(&self, deviation: f64) -> bool {
+        (self.lower..=self.upper).contains(&deviation)
+    }
+}
+
+/// Machine-readable pass/fail summary of an [`Inspection`] against a [`Tolerance`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct InspectionReport {
+    pub tolerance: Tolerance,
+    /// Number of points within tolerance.
+    pub in_tolerance: usize,
+    /// Number of points deviating above the upper bound.
+    pub above: usize,
+    /// Number of points deviating below the lower bound (or with a `NaN` deviation).
+    pub below: usize,
+    /// Index and deviation of the points with the largest absolute deviations, worst first.
+    pub worst: Vec<(usize, f64)>,
+    pub stats: DeviationStats,
+    /// Whether every point is within tolerance.
+    pub pass: bool,
+}
+
+impl Inspection {
+    /// Check the deviations against a tolerance band, keeping the `worst` largest absolute deviations.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::inspect::{inspect, PointSurface, Tolerance};
+    ///
+    /// let reference = [[0., 0.], [1., 0.], [2., 0.], [3., 0.]];
+    /// let normals = [[0., 1.]; 4];
+    /// let surface = PointSurface::new(&reference, Some(&normals)).unwrap();
+    ///
+    /// let measured = [[0., 0.05], [1., -0.3], [2., 0.01], [3., 0.2]];
+    /// let report = inspect(&measured, &surface)
+    ///     .unwrap()
+    ///     .report(Tolerance::new(-0.1, 0.1), 2);
+    /// assert!(!report.pass);
+    /// assert_eq!((report.in_tolerance, report.above, report.below), (2, 1, 1));
+    /// assert_eq!(report.worst, vec![(1, -0.3), (3, 0.2)]);
+    /// ```
+    pub fn report(&self, tolerance: Tolerance, worst: usize) -> InspectionReport {
+        let above = self
+            .deviations
+            .iter()
+            .filter(|d| **d > tolerance.upper)
+            .count();
+        let in_tolerance = self
+            .deviations
+            .iter()
+            .filter(|d| tolerance.contains(**d))
+            .count();
+        let below = self.deviations.len() - above - in_tolerance;
+        let mut ranked = self
+            .deviations
+            .iter()
+            .copied()
+            .enumerate()
+            .collect::<Vec<_>>();
+        ranked.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        ranked.truncate(worst);
+        InspectionReport {
+            tolerance,
+            in_tolerance,
+            above,
+            below,
+            worst: ranked,
+            stats: self.stats,
+            pass: in_tolerance == self.deviations.len(),
+        }
+    }
+}
+
+impl InspectionReport {
+    /// Serialize the report as a JSON object.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::inspect::{inspect, PointSurface, Tolerance};
+    ///
+    /// let reference = [[0., 0.], [1., 0.]];
+    /// let surface = PointSurface::new(&reference, None).unwrap();
+    /// let report = inspect(&[[0., 0.5]], &surface)
+    ///     .unwrap()
+    ///     .report(Tolerance::symmetric(1.), 1);
+    /// assert!(report.to_json().starts_with(r#"{"pass":true,"tolerance":{"lower":-1,"upper":1}"#));
+    /// ```
+    pub fn to_json(&self) -> String {
+        let worst = self
+            .worst
+            .iter()
+            .map(|(index, deviation)| {
+                format!(
+                    r#"{{"index":{index},"deviation":{}}}"#,
+                    json_number(*deviation)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let stats = &self.stats;
+        format!(
+            concat!(
+                r#"{{"pass":{},"tolerance":{{"lower":{},"upper":{}}},"#,
+                r#""in_tolerance":{},"above":{},"below":{},"worst":[{}],"#,
+                r#""stats":{{"count":{},"min":{},"max":{},"mean":{},"std_dev":{},"rms":{},"max_abs":{}}}}}"#
+            ),
+            self.pass,
+            json_number(self.tolerance.lower),
+            json_number(self.tolerance.upper),
+            self.in_tolerance,
+            self.above,
+            self.below,
+            worst,
+            stats.count,
+            json_number(stats.min),
+            json_number(stats.max),
+            json_number(stats.mean),
+            json_number(stats.std_dev),
+            json_number(stats.rms),
+            json_number(stats.max_abs),
+        )
+    }
+}
+
+/// JSON has no representation for non-finite numbers, which are written as `null`.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
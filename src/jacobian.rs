@@ -0,0 +1,141 @@
+use crate::{fit_points, rows, DefaultSolver, Points};
+use nalgebra::{DMatrix, DVector, SMatrix, SymmetricEigen};
+
+/// Derivatives of an estimated homogeneous matrix with respect to the input point coordinates.
+///
+/// Each matrix has one row per entry of the `(C+1)x(C+1)` homogeneous matrix, in row-major order,
+/// and one column per coordinate of the points, `i * C + j` being coordinate `j` of point `i`.
+#[derive(Clone, Debug)]
+pub struct TransformJacobian {
+    /// Derivatives with respect to the `src` coordinates.
+    pub src: DMatrix<f64>,
+    /// Derivatives with respect to the `dst` coordinates.
+    pub dst: DMatrix<f64>,
+}
+
+/// Estimate a similarity transformation like [`crate::estimate`], along with its analytic
+/// derivatives with respect to the input points, for backpropagating through the alignment.
+///
+/// The rotation `R` maximizing `trace(Rᵀ A)` keeps `M = Rᵀ A` symmetric; differentiating that
+/// condition gives `dR = R Ω` with the skew-symmetric `Ω` solving `Ω M + M Ω = Rᵀ dA - dAᵀ R`,
+/// which is solved in the eigenbasis of `M`.
+/// The `None` value is returned if the problem is not well-conditioned or the rotation is not
+/// differentiable, i.e. two singular values of the cross-covariance vanish.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_with_jacobian, Array2};
+///
+/// let src = [[0., 0., 0.], [1., 0., 0.], [0., 2., 0.], [0., 0., 3.]];
+/// let dst = [[1., 1., 1.], [1., 2., 1.], [-1., 1., 1.2], [1., 1., 4.]];
+/// let (t, jacobian) = estimate_with_jacobian(Array2::from(src), Array2::from(dst), true).unwrap();
+///
+/// // compare with a central finite difference on the y coordinate of the third src point
+/// let h = 1e-6;
+/// let shifted = |d: f64| {
+///     let mut src = src;
+///     src[2][1] += d;
+///     estimate(Array2::from(src), Array2::from(dst), true).unwrap()
+/// };
+/// let numeric = (shifted(h) - shifted(-h)) / (2. * h);
+/// let analytic = jacobian.src.column(2 * 3 + 1);
+/// assert!((numeric.transpose().as_slice().iter().zip(analytic.iter()))
+///     .all(|(n, a)| (n - a).abs() < 1e-6));
+/// assert_eq!(t.shape(), (4, 4));
+/// ```
+pub fn estimate_with_jacobian<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+) -> Option<(DMatrix<f64>, TransformJacobian)> {
+    jacobian_points(rows(src.into()), rows(dst.into()), estimate_scale)
+}
+
+/// Estimation with derivatives like [`estimate_with_jacobian`], for a number of points only known
+/// at runtime.
+pub(crate) fn jacobian_points<const C: usize>(
+    src: Points<C>,
+    dst: Points<C>,
+    estimate_scale: bool,
+) -> Option<(DMatrix<f64>, TransformJacobian)> {
+    let r = src.nrows();
+    let fit = fit_points(
+        src.clone(),
+        dst.clone(),
+        &DVector::repeat(r, 1.),
+        estimate_scale,
+        &DefaultSolver,
+    )
+    .ok()?;
+    let n = r as f64;
+    let rotation = &fit.rotation;
+    let a = &fit.covariance;
+    let src_mean = DVector::from_fn(C, |j, _| src.column(j).mean());
+    let dst_mean = DVector::from_fn(C, |j, _| dst.column(j).mean());
+    let variance = (0..r)
+        .map(|i| {
+            (0..C)
+                .map(|j| (src[(i, j)] - src_mean[j]).powi(2))
+                .sum::<f64>()
+        })
+        .sum::<f64>()
+        / n;
+
+    // eigenbasis of the symmetric M = Rᵀ A, in which the Sylvester equation is diagonal
+    let m = rotation.transpose() * a;
+    let eigen = SymmetricEigen::new((&m + m.transpose()) / 2.);
+    let basis = eigen.eigenvectors;
+    let values = eigen.eigenvalues;
+    let tol = crate::solver::RANK_TOL * values.amax().max(1.);
+    if (0..C).any(|i| (i + 1..C).any(|j| (values[i] + values[j]).abs() <= tol)) {
+        return None;
+    }
+
+    // derivative of the homogeneous matrix for a perturbation of the cross-covariance, the source
+    // variance and the means
+    let derivative =
+        |da: &DMatrix<f64>, dvariance: f64, dsrc_mean: &DVector<f64>, ddst_mean: &DVector<f64>| {
+            let b = basis.transpose()
+                * (rotation.transpose() * da - da.transpose() * rotation)
+                * &basis;
+            let omega = DMatrix::from_fn(C, C, |i, j| b[(i, j)] / (values[i] + values[j]));
+            let drotation = rotation * &basis * omega * basis.transpose();
+            let (scale, dscale) = if estimate_scale {
+                (
+                    fit.scale,
+                    ((rotation.transpose() * da).trace() - fit.scale * dvariance) / variance,
+                )
+            } else {
+                (1., 0.)
+            };
+            let dlinear = &drotation * scale + rotation * dscale;
+            let dtranslation = ddst_mean - &dlinear * &src_mean - rotation * dsrc_mean * scale;
+            let mut d = DMatrix::zeros(C + 1, C + 1);
+            d.view_mut((0, 0), (C, C)).copy_from(&dlinear);
+            d.view_mut((0, C), (C, 1)).copy_from(&dtranslation);
+            DVector::from_iterator((C + 1) * (C + 1), d.transpose().iter().copied())
+        };
+
+    let mut jacobian = TransformJacobian {
+        src: DMatrix::zeros((C + 1) * (C + 1), r * C),
+        dst: DMatrix::zeros((C + 1) * (C + 1), r * C),
+    };
+    let zero = DVector::zeros(C);
+    for i in 0..r {
+        let src_centered = DVector::from_fn(C, |j, _| src[(i, j)] - src_mean[j]);
+        let dst_centered = DVector::from_fn(C, |j, _| dst[(i, j)] - dst_mean[j]);
+        for j in 0..C {
+            let unit = DVector::from_fn(C, |k, _| if k == j { 1. / n } else { 0. });
+            // A = (1/n) Σ (qᵢ - q̄)(pᵢ - p̄)ᵀ and the centered sums vanish
+            let da = DMatrix::from_fn(C, C, |r, c| if c == j { dst_centered[r] / n } else { 0. });
+            let dvariance = 2. * src_centered[j] / n;
+            jacobian
+                .src
+                .set_column(i * C + j, &derivative(&da, dvariance, &unit, &zero));
+            let da = DMatrix::from_fn(C, C, |r, c| if r == j { src_centered[c] / n } else { 0. });
+            jacobian
+                .dst
+                .set_column(i * C + j, &derivative(&da, 0., &zero, &unit));
+        }
+    }
+    Some((fit.transform, jacobian))
+}
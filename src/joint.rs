@@ -0,0 +1,157 @@
+use crate::{DefaultSolver, Solver, Transform};
+use nalgebra::{DMatrix, SMatrix, SVector};
+
+/// Corresponding source and destination points of one pair of a joint estimation.
+pub type PointPair<'a, const C: usize> = (&'a [[f64; C]], &'a [[f64; C]]);
+
+/// Centered statistics of a point-set pair: the means, the summed cross products `Σ (q-q̄)(p-p̄)ᵀ`
+/// and the summed squared norms `Σ |p-p̄|²`.
+struct PairStats<const C: usize> {
+    src_mean: SVector<f64, C>,
+    dst_mean: SVector<f64, C>,
+    covariance: SMatrix<f64, C, C>,
+    src_squared: f64,
+}
+
+fn pair_stats<const C: usize>(src: &[[f64; C]], dst: &[[f64; C]]) -> Option<PairStats<C>> {
+    if src.is_empty() || src.len() != dst.len() {
+        return None;
+    }
+    let n = src.len() as f64;
+    let src_mean = src
+        .iter()
+        .map(|p| SVector::from(*p))
+        .sum::<SVector<f64, C>>()
+        / n;
+    let dst_mean = dst
+        .iter()
+        .map(|q| SVector::from(*q))
+        .sum::<SVector<f64, C>>()
+        / n;
+    let mut covariance = SMatrix::zeros();
+    let mut src_squared = 0.;
+    for (p, q) in src.iter().zip(dst) {
+        let p = SVector::from(*p) - src_mean;
+        let q = SVector::from(*q) - dst_mean;
+        covariance += q * p.transpose();
+        src_squared += p.norm_squared();
+    }
+    Some(PairStats {
+        src_mean,
+        dst_mean,
+        covariance,
+        src_squared,
+    })
+}
+
+fn rotation<const C: usize>(covariance: &SMatrix<f64, C, C>) -> Option<SMatrix<f64, C, C>> {
+    let rotation =
+        DefaultSolver.rotation(&DMatrix::from_column_slice(C, C, covariance.as_slice()))?;
+    Some(SMatrix::from_column_slice(rotation.as_slice()))
+}
+
+/// Estimate one similarity transformation per point-set pair, all sharing a single scale, e.g.
+/// several checkerboard captures from the same camera pair.
+///
+/// The rotation of each pair does not depend on the scale and is fitted independently; the shared
+/// scale then minimizes the summed squared residuals of all the pairs,
+/// `s = Σₖ tr(Rₖᵀ Aₖ) / Σₖ Σ |p-p̄ₖ|²`, weighting each pair by its number of points and spread.
+/// The `None` value is returned if there are no pairs, the points of a pair differ in number or
+/// are empty, or the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::estimate_shared_scale;
+///
+/// // two captures scaled by 2, with different poses
+/// let src_a = [[0., 0.], [1., 0.], [0., 1.]];
+/// let dst_a = [[1., 1.], [3., 1.], [1., 3.]];
+/// let src_b = [[0., 0.], [2., 0.], [0., 1.]];
+/// let dst_b = [[0., 0.], [0., 4.], [-2., 0.]];
+///
+/// let transforms = estimate_shared_scale(&[(&src_a, &dst_a), (&src_b, &dst_b)]).unwrap();
+/// assert!(transforms.iter().all(|t| (t.scale() - 2.).abs() < 1e-12));
+/// assert!((transforms[0].translation() - nalgebra::Vector2::new(1., 1.)).norm() < 1e-12);
+/// ```
+pub fn estimate_shared_scale<const C: usize>(pairs: &[PointPair<C>]) -> Option<Vec<Transform<C>>> {
+    let stats = pairs
+        .iter()
+        .map(|(src, dst)| pair_stats(src, dst))
+        .collect::<Option<Vec<_>>>()?;
+    let rotations = stats
+        .iter()
+        .map(|s| rotation(&s.covariance))
+        .collect::<Option<Vec<_>>>()?;
+    let numerator = stats
+        .iter()
+        .zip(&rotations)
+        .map(|(s, r)| (r.transpose() * s.covariance).trace())
+        .sum::<f64>();
+    let denominator = stats.iter().map(|s| s.src_squared).sum::<f64>();
+    let scale = numerator / denominator;
+    if stats.is_empty() || !scale.is_normal() {
+        return None;
+    }
+    Some(
+        stats
+            .iter()
+            .zip(rotations)
+            .map(|(s, r)| Transform::new(r, s.dst_mean - r * s.src_mean * scale, scale))
+            .collect(),
+    )
+}
+
+/// Estimate one similarity transformation per point-set pair, all sharing a single rotation (and
+/// a single scale if `estimate_scale`) but with their own translations, e.g. repeated captures of
+/// a rigid rig.
+///
+/// The rotation is computed from the summed cross-covariances of the pairs, each centered on its
+/// own means, which is better conditioned than averaging independent fits: pairs that are
+/// degenerate on their own, such as collinear points, still contribute to the constraints.
+/// The `None` value is returned if there are no pairs, the points of a pair differ in number or
+/// are empty, or the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::estimate_shared_rotation;
+///
+/// // two collinear captures, each unable to fix the rotation alone
+/// let src_a = [[0., 0.], [1., 0.], [2., 0.]];
+/// let dst_a = [[5., 0.], [5., 1.], [5., 2.]];
+/// let src_b = [[0., 0.], [0., 1.]];
+/// let dst_b = [[0., 3.], [-1., 3.]];
+///
+/// let transforms = estimate_shared_rotation(&[(&src_a, &dst_a), (&src_b, &dst_b)], false).unwrap();
+/// let quarter_turn = nalgebra::Matrix2::new(0., -1., 1., 0.);
+/// assert!((transforms[1].rotation() - quarter_turn).norm() < 1e-12);
+/// assert!((transforms[0].translation() - nalgebra::Vector2::new(5., 0.)).norm() < 1e-12);
+/// assert!((transforms[1].translation() - nalgebra::Vector2::new(0., 3.)).norm() < 1e-12);
+/// ```
+pub fn estimate_shared_rotation<const C: usize>(
+    pairs: &[PointPair<C>],
+    estimate_scale: bool,
+) -> Option<Vec<Transform<C>>> {
+    let stats = pairs
+        .iter()
+        .map(|(src, dst)| pair_stats(src, dst))
+        .collect::<Option<Vec<_>>>()?;
+    if stats.is_empty() {
+        return None;
+    }
+    let covariance = stats
+        .iter()
+        .map(|s| s.covariance)
+        .sum::<SMatrix<f64, C, C>>();
+    let r = rotation(&covariance)?;
+    let scale = if estimate_scale {
+        let scale =
+            (r.transpose() * covariance).trace() / stats.iter().map(|s| s.src_squared).sum::<f64>();
+        scale.is_normal().then_some(scale)?
+    } else {
+        1.
+    };
+    Some(
+        stats
+            .iter()
+            .map(|s| Transform::new(r, s.dst_mean - r * s.src_mean * scale, scale))
+            .collect(),
+    )
+}
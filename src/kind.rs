@@ -0,0 +1,329 @@
+use crate::anisotropic::fit_anisotropic;
+use crate::solver::{self, TolerantSvd, RANK_TOL};
+use crate::{fit_points, rows, KabschError};
+use alloc::vec::Vec;
+use nalgebra::{DMatrix, DVector, SMatrix};
+
+/// Kind of transformation estimated by [`estimate_with_options`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransformKind {
+    /// Rotation about the origin `x ↦ R x`, without translation: the orthogonal Procrustes problem.
+    RotationOnly,
+    /// Rotation and translation `x ↦ R x + t`.
+    Rigid,
+    /// Rotation, translation and uniform scale `x ↦ s R x + t`, as [`crate::estimate`].
+    #[default]
+    Similarity,
+    /// Rotation, scale per axis and translation `x ↦ R S x + t`, as
+    /// [`crate::estimate_anisotropic`].
+    Anisotropic,
+    /// Any linear map and translation `x ↦ A x + t`, by least squares.
+    Affine,
+}
+
+/// Options of [`estimate_with_options`], [`estimate_detailed`] and
+/// [`crate::estimate_batch_with_options`], built field by field or with
+/// [`EstimateOptions::builder`]. A `bool` converts to the options estimating a similarity if
+/// `true`, like the `estimate_scale` argument of the other estimators, and a rigid transformation
+/// otherwise.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EstimateOptions {
+    pub kind: TransformKind,
+    /// Singular values of the cross-covariance below this are treated as zero when correcting
+    /// reflections, `1e-5` by default.
+    pub rank_tol: f64,
+    /// Non-negative weight of each correspondence, all equal if `None`.
+    pub weights: Option<Vec<f64>>,
+    /// Skip the determinant sign correction, so that the rotation may be a reflection if it fits
+    /// better, e.g. to compare shapes up to mirroring; `false` by default.
+    pub allow_reflection: bool,
+}
+
+impl Default for EstimateOptions {
+    fn default() -> Self {
+        Self {
+            kind: TransformKind::default(),
+            rank_tol: RANK_TOL,
+            weights: None,
+            allow_reflection: false,
+        }
+    }
+}
+
+impl From<bool> for EstimateOptions {
+    fn from(estimate_scale: bool) -> Self {
+        EstimateOptions::builder().scale(estimate_scale).options
+    }
+}
+
+impl EstimateOptions {
+    /// Builder starting from the default options.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{EstimateOptions, TransformKind};
+    ///
+    /// let options = EstimateOptions::builder()
+    ///     .scale(false)
+    ///     .rank_tol(1e-6)
+    ///     .weights([1., 2., 1.])
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(options.kind, TransformKind::Rigid);
+    /// assert_eq!(options.weights, Some(vec![1., 2., 1.]));
+    /// assert_eq!(EstimateOptions::from(false).kind, TransformKind::Rigid);
+    /// ```
+    pub fn builder() -> EstimateOptionsBuilder {
+        EstimateOptionsBuilder::default()
+    }
+}
+
+/// Builder of [`EstimateOptions`].
+#[derive(Clone, Debug, Default)]
+pub struct EstimateOptionsBuilder {
+    options: EstimateOptions,
+}
+
+impl EstimateOptionsBuilder {
+    /// Kind of transformation to estimate, [`TransformKind::Similarity`] by default.
+    pub fn kind(mut self, kind: TransformKind) -> Self {
+        self.options.kind = kind;
+        self
+    }
+
+    /// Estimate a [`TransformKind::Similarity`] if `true`, a [`TransformKind::Rigid`]
+    /// transformation otherwise.
+    pub fn scale(self, estimate_scale: bool) -> Self {
+        self.kind(if estimate_scale {
+            TransformKind::Similarity
+        } else {
+            TransformKind::Rigid
+        })
+    }
+
+    /// Tolerance of [`EstimateOptions::rank_tol`], which must be non-negative.
+    pub fn rank_tol(mut self, rank_tol: f64) -> Self {
+        self.options.rank_tol = rank_tol;
+        self
+    }
+
+    /// Weight of each correspondence, checked against the points by the estimation.
+    pub fn weights(mut self, weights: impl Into<Vec<f64>>) -> Self {
+        self.options.weights = Some(weights.into());
+        self
+    }
+
+    /// Allow the rotation to be a reflection, see [`EstimateOptions::allow_reflection`].
+    pub fn allow_reflection(mut self, allow_reflection: bool) -> Self {
+        self.options.allow_reflection = allow_reflection;
+        self
+    }
+
+    /// Options built so far.
+    /// A [`KabschError::InvalidTolerance`] error is returned if the rank tolerance is negative or
+    /// `NaN`.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{EstimateOptions, KabschError};
+    ///
+    /// let invalid = EstimateOptions::builder().rank_tol(f64::NAN).build();
+    /// assert_eq!(invalid, Err(KabschError::InvalidTolerance));
+    /// ```
+    pub fn build(self) -> Result<EstimateOptions, KabschError> {
+        check_rank_tol(self.options.rank_tol)?;
+        Ok(self.options)
+    }
+}
+
+/// Estimate the transformation of the given kind between two matrices, as a `(C+1)x(C+1)`
+/// homogeneous matrix like [`crate::estimate`], through a single entry point for every kind.
+///
+/// Failures are reported like [`crate::strict::estimate`]; an affine fit also requires the source
+/// points to span all the dimensions. A [`KabschError::ShapeMismatch`] error is returned if the
+/// number of weights is not `R`, a [`KabschError::InvalidWeights`] error if a weight is
+/// negative or they sum to zero, and a [`KabschError::InvalidTolerance`] error if the rank
+/// tolerance is negative or `NaN`.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_with_options, Array2, EstimateOptions, TransformKind};
+///
+/// let src = Array2::from([[1., 0.], [0., 1.], [-1., 0.], [0., -1.]]);
+/// // rotated by a quarter turn, then stretched along x and shifted
+/// let dst = Array2::from([[2., 2.], [-1., 1.], [2., 0.], [5., 1.]]);
+///
+/// let options = |kind| EstimateOptions { kind, ..Default::default() };
+/// let affine = estimate_with_options(src, dst, &options(TransformKind::Affine)).unwrap();
+/// let expected = [0., -3., 2., 1., 0., 1., 0., 0., 1.];
+/// assert!((affine - nalgebra::Matrix3::from_row_slice(&expected)).abs().max() < 1e-12);
+///
+/// let rigid = estimate_with_options(src, dst, &options(TransformKind::Rigid)).unwrap();
+/// assert!((rigid[(0, 2)] - 2.).abs() < 1e-12 && (rigid[(1, 2)] - 1.).abs() < 1e-12);
+///
+/// // the quarter turn about the origin, without translation
+/// let rotation = estimate_with_options(src, dst, &options(TransformKind::RotationOnly)).unwrap();
+/// assert_eq!((rotation[(0, 2)], rotation[(1, 2)]), (0., 0.));
+/// assert!((rotation[(1, 0)] - 1.).abs() < 1e-12);
+///
+/// // a quarter turn shifted by (1, 1), but the last correspondence is an outlier with no weight
+/// let dst = Array2::from([[1., 2.], [0., 1.], [1., 0.], [9., 9.]]);
+/// let options = EstimateOptions::builder().scale(false).weights([1., 1., 1., 0.]);
+/// let options = options.build().unwrap();
+/// let rigid = estimate_with_options(src, dst, &options).unwrap();
+/// assert!((rigid[(0, 2)] - 1.).abs() < 1e-12 && (rigid[(1, 2)] - 1.).abs() < 1e-12);
+/// ```
+pub fn estimate_with_options<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    options: &EstimateOptions,
+) -> Result<DMatrix<f64>, KabschError> {
+    estimate_detailed(src, dst, options).map(|estimation| estimation.transform)
+}
+
+/// Result of [`estimate_detailed`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimation {
+    /// Homogeneous `(C+1)x(C+1)` matrix, as returned by [`estimate_with_options`].
+    pub transform: DMatrix<f64>,
+    /// Whether the linear part reverses orientation, i.e. has a negative determinant, which only
+    /// happens with [`EstimateOptions::allow_reflection`] or an affine fit.
+    pub reflection: bool,
+}
+
+/// Estimate the transformation like [`estimate_with_options`], reporting whether it is a
+/// reflection.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_detailed, Array2, EstimateOptions};
+///
+/// let src = Array2::from([[0., 0.], [2., 0.], [0., 1.], [3., 2.]]);
+/// // the mirror image across the y axis
+/// let dst = Array2::from(src.map(|[x, y]| [-x, y]));
+///
+/// let proper = estimate_detailed(src, dst, &EstimateOptions::default()).unwrap();
+/// assert!(!proper.reflection);
+///
+/// let options = EstimateOptions::builder().scale(false).allow_reflection(true);
+/// let mirrored = estimate_detailed(src, dst, &options.build().unwrap()).unwrap();
+/// assert!(mirrored.reflection);
+/// let expected = nalgebra::Matrix3::new(-1., 0., 0., 0., 1., 0., 0., 0., 1.);
+/// assert!((mirrored.transform - expected).abs().max() < 1e-12);
+/// ```
+pub fn estimate_detailed<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    options: &EstimateOptions,
+) -> Result<Estimation, KabschError> {
+    check_rank_tol(options.rank_tol)?;
+    if R == 0 || C == 0 {
+        return Err(KabschError::Empty);
+    }
+    let src = src.into();
+    let dst = dst.into();
+    if src.iter().chain(dst.iter()).any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    let weights = match &options.weights {
+        Some(weights) => weights_vector::<R>(weights)?,
+        None => DVector::repeat(R, 1.),
+    };
+    let solver = TolerantSvd {
+        rank_tol: options.rank_tol,
+        allow_reflection: options.allow_reflection,
+    };
+    let transform = match options.kind {
+        TransformKind::RotationOnly => rotation_only(&src, &dst, &weights, &solver)?,
+        TransformKind::Rigid => {
+            fit_points(rows(src), rows(dst), &weights, false, &solver)?.transform
+        }
+        TransformKind::Similarity => {
+            fit_points(rows(src), rows(dst), &weights, true, &solver)?.transform
+        }
+        TransformKind::Anisotropic => {
+            fit_anisotropic(&src, &dst, &weights, &solver)?.to_homogeneous()
+        }
+        TransformKind::Affine => affine(&src, &dst, &weights, options.rank_tol)?,
+    };
+    if transform.iter().any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    let reflection = transform.view((0, 0), (C, C)).determinant() < 0.;
+    Ok(Estimation {
+        transform,
+        reflection,
+    })
+}
+
+fn check_rank_tol(rank_tol: f64) -> Result<(), KabschError> {
+    if rank_tol.is_nan() || rank_tol < 0. {
+        return Err(KabschError::InvalidTolerance);
+    }
+    Ok(())
+}
+
+fn weights_vector<const R: usize>(weights: &[f64]) -> Result<DVector<f64>, KabschError> {
+    if weights.len() != R {
+        return Err(KabschError::ShapeMismatch {
+            expected: R,
+            actual: weights.len(),
+        });
+    }
+    if weights.iter().any(|w| !w.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    if weights.iter().any(|w| *w < 0.) || weights.iter().sum::<f64>() <= 0. {
+        return Err(KabschError::InvalidWeights);
+    }
+    Ok(DVector::from_column_slice(weights))
+}
+
+/// Rotation maximising `trace(Rᵀ A)` for the weighted cross-covariance `A` of the uncentered
+/// points.
+fn rotation_only<const R: usize, const C: usize>(
+    src: &SMatrix<f64, R, C>,
+    dst: &SMatrix<f64, R, C>,
+    weights: &DVector<f64>,
+    solver: &TolerantSvd,
+) -> Result<DMatrix<f64>, KabschError> {
+    let mut a = SMatrix::<f64, C, C>::zeros();
+    for i in 0..R {
+        a += dst.row(i).transpose() * src.row(i) * weights[i];
+    }
+    a /= weights.sum();
+    let a = DMatrix::from_column_slice(C, C, a.as_slice());
+    let m = solver::rotation_or_error(solver, &a, solver.rank_tol)?;
+    let mut t = DMatrix::identity(C + 1, C + 1);
+    t.view_mut((0, 0), (C, C)).copy_from(&m);
+    Ok(t)
+}
+
+/// Weighted least-squares `A = Σ_yx Σ_xx⁻¹` of the centered points, and `t` mapping the means.
+fn affine<const R: usize, const C: usize>(
+    src: &SMatrix<f64, R, C>,
+    dst: &SMatrix<f64, R, C>,
+    weights: &DVector<f64>,
+    rank_tol: f64,
+) -> Result<DMatrix<f64>, KabschError> {
+    let num = weights.sum();
+    let src_mean = weights.transpose() * src / num;
+    let dst_mean = weights.transpose() * dst / num;
+    let (mut sxx, mut syx) = (SMatrix::<f64, C, C>::zeros(), SMatrix::<f64, C, C>::zeros());
+    for i in 0..R {
+        let p = src.row(i) - src_mean;
+        let q = dst.row(i) - dst_mean;
+        sxx += p.transpose() * p * weights[i];
+        syx += q.transpose() * p * weights[i];
+    }
+    let scale = sxx.trace() / C as f64;
+    let sxx_dyn = DMatrix::from_column_slice(C, C, sxx.as_slice());
+    if scale <= 0. || sxx_dyn.rank(rank_tol * scale) < C {
+        return Err(KabschError::DegenerateConfiguration);
+    }
+    let a = syx
+        * sxx
+            .try_inverse()
+            .ok_or(KabschError::DegenerateConfiguration)?;
+    let translation = dst_mean.transpose() - a * src_mean.transpose();
+    let mut t = DMatrix::identity(C + 1, C + 1);
+    t.view_mut((0, 0), (C, C)).copy_from(&a);
+    t.view_mut((0, C), (C, 1)).copy_from(&translation);
+    Ok(t)
+}
@@ -0,0 +1,452 @@
+//! Registration cost over a grid of perturbations around a solution, to visualize the local minima
+//! that trap ICP on a dataset, e.g. along the rotation about the axis of a symmetric part, and the
+//! basin of initializations from which ICP converges to the solution.
+use crate::icp::{icp_from, IcpOptions, KdTree};
+use crate::{KabschError, Transform};
+use nalgebra::{SMatrix, SVector};
+
+/// Degree of freedom perturbed along an axis of the grid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// Rotation in the plane of the two coordinate axes, about the centroid of the transformed
+    /// source points, in radians.
+    Rotation(usize, usize),
+    /// Translation along a coordinate axis.
+    Translation(usize),
+}
+
+/// Options of [`cost_landscape`].
+#[derive(Clone, Debug)]
+pub struct LandscapeOptions {
+    /// Largest rotation on each side of the solution, in radians.
+    pub rotation_range: f64,
+    /// Largest translation on each side of the solution, as a fraction of the root-mean-square
+    /// radius of the transformed source points.
+    pub translation_range: f64,
+    /// Number of values along each axis of the grid, the solution being at the middle one when odd.
+    pub steps: usize,
+    /// Threads of the nearest-neighbor search.
+    pub threads: usize,
+}
+
+impl Default for LandscapeOptions {
+    fn default() -> Self {
+        Self {
+            rotation_range: std::f64::consts::FRAC_PI_2,
+            translation_range: 0.5,
+            steps: 41,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+/// Cost of the perturbed solutions over a `steps × steps` grid.
+#[derive(Clone, Debug)]
+pub struct Landscape {
+    pub x: Axis,
+    pub y: Axis,
+    /// Perturbations along [`Landscape::x`], in increasing order.
+    pub x_values: Vec<f64>,
+    /// Perturbations along [`Landscape::y`], in increasing order.
+    pub y_values: Vec<f64>,
+    /// Root-mean-square distance from each transformed source point to its nearest destination
+    /// point, the cost minimized by ICP, row-major with one row per value of `y`.
+    pub costs: Vec<f64>,
+}
+
+impl Landscape {
+    /// Cost at the `i`-th value of `x` and the `j`-th value of `y`
+    pub fn cost(&self, i: usize, j: usize) -> f64 {
+        self.costs[j * self.x_values.len() + i]
+    }
+
+    /// Grid indices `(i, j)` of the local minima, lower than all their neighbors including the
+    /// diagonal ones, in increasing cost.
+    pub fn local_minima(&self) -> Vec<(usize, usize)> {
+        let (nx, ny) = (self.x_values.len(), self.y_values.len());
+        let mut minima = (0..ny)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .filter(|&(i, j)| {
+                let cost = self.cost(i, j);
+                (i.saturating_sub(1)..(i + 2).min(nx))
+                    .flat_map(|k| (j.saturating_sub(1)..(j + 2).min(ny)).map(move |l| (k, l)))
+                    .all(|(k, l)| (k, l) == (i, j) || cost < self.cost(k, l))
+            })
+            .collect::<Vec<_>>();
+        minima.sort_by(|a, b| self.cost(a.0, a.1).total_cmp(&self.cost(b.0, b.1)));
+        minima
+    }
+
+    /// Costs as CSV with a header row, one grid point per row, e.g. for a heat map.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("x,y,cost\n");
+        for (j, y) in self.y_values.iter().enumerate() {
+            for (i, x) in self.x_values.iter().enumerate() {
+                csv += &format!("{},{},{}\n", x, y, self.cost(i, j));
+            }
+        }
+        csv
+    }
+}
+
+/// Evaluate the ICP cost of registering `src` onto `dst` over a grid of perturbations of the
+/// `solution` along the `x` and `y` degrees of freedom, the perturbations being applied after the
+/// solution.
+///
+/// A [`KabschError::Empty`] error is returned if a point set is empty, and a
+/// [`KabschError::IndexOutOfBounds`] error if an axis refers to a missing coordinate.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::landscape::{cost_landscape, Axis, LandscapeOptions};
+/// use kabsch_umeyama::Transform;
+/// use std::collections::BTreeSet;
+/// use std::f64::consts::{FRAC_PI_2, PI};
+///
+/// // a square has a local minimum every quarter turn
+/// let square = [[1., 1.], [-1., 1.], [-1., -1.], [1., -1.]];
+/// let options = LandscapeOptions { rotation_range: PI, steps: 33, ..Default::default() };
+/// let landscape = cost_landscape(
+///     &square,
+///     &square,
+///     &Transform::identity(),
+///     Axis::Rotation(0, 1),
+///     Axis::Translation(0),
+///     &options,
+/// )
+/// .unwrap();
+/// assert_eq!(landscape.costs.len(), 33 * 33);
+/// assert!(landscape.cost(16, 16) < 1e-12);
+///
+/// let minima = landscape.local_minima();
+/// let turns = minima.iter().map(|&(i, j)| {
+///     assert!(j == 16 && landscape.cost(i, j) < 1e-12);
+///     (landscape.x_values[i] / FRAC_PI_2).round() as i32
+/// });
+/// assert_eq!(turns.collect::<BTreeSet<_>>().len(), 5);
+/// assert!(landscape.to_csv().starts_with("x,y,cost\n-3.14159"));
+/// ```
+pub fn cost_landscape<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    solution: &Transform<C>,
+    x: Axis,
+    y: Axis,
+    options: &LandscapeOptions,
+) -> Result<Landscape, KabschError> {
+    let Grid {
+        moved,
+        center,
+        x_values,
+        y_values,
+        ..
+    } = Grid::new(src, dst, solution, x, y, options)?;
+    let tree = KdTree::new(dst.to_vec());
+    let mut costs = Vec::with_capacity(x_values.len() * y_values.len());
+    let mut queries = Vec::with_capacity(moved.len());
+    for &b in &y_values {
+        for &a in &x_values {
+            let (rotation, translation) = perturbation(x, a, y, b);
+            queries.clear();
+            queries.extend(moved.iter().map(|p| {
+                <[f64; C]>::from(rotation * (SVector::from(*p) - center) + center + translation)
+            }));
+            let squared = tree
+                .nearest_batch(&queries, options.threads)
+                .into_iter()
+                .flatten()
+                .map(|(_, distance)| distance * distance)
+                .sum::<f64>();
+            costs.push((squared / queries.len() as f64).sqrt());
+        }
+    }
+    Ok(Landscape {
+        x,
+        y,
+        x_values,
+        y_values,
+        costs,
+    })
+}
+
+/// Outcome of ICP started from each perturbed solution of a `steps × steps` grid.
+#[derive(Clone, Debug)]
+pub struct Basin {
+    pub x: Axis,
+    pub y: Axis,
+    /// Perturbations along [`Basin::x`], in increasing order.
+    pub x_values: Vec<f64>,
+    /// Perturbations along [`Basin::y`], in increasing order.
+    pub y_values: Vec<f64>,
+    /// Whether ICP recovered the solution, row-major with one row per value of `y`.
+    pub recovered: Vec<bool>,
+    /// Iterations run by ICP, row-major with one row per value of `y`.
+    pub iterations: Vec<usize>,
+}
+
+impl Basin {
+    /// Whether ICP recovered the solution from the `i`-th value of `x` and the `j`-th value of `y`
+    pub fn is_recovered(&self, i: usize, j: usize) -> bool {
+        self.recovered[j * self.x_values.len() + i]
+    }
+
+    /// Fraction of the initializations recovering the solution
+    pub fn success_rate(&self) -> f64 {
+        self.recovered.iter().filter(|r| **r).count() as f64 / self.recovered.len() as f64
+    }
+
+    /// Mean iterations of the initializations recovering the solution, the `None` value if none does
+    pub fn mean_iterations(&self) -> Option<f64> {
+        let (count, total) = self
+            .recovered
+            .iter()
+            .zip(&self.iterations)
+            .filter(|(r, _)| **r)
+            .fold((0, 0), |(c, t), (_, i)| (c + 1, t + i));
+        (count > 0).then(|| total as f64 / count as f64)
+    }
+
+    /// Extent of the basin along `x` and `y`: the largest perturbation such that all the smaller
+    /// ones along the same axis, through the unperturbed value of the other, recover the solution.
+    pub fn extent(&self) -> (f64, f64) {
+        let closest = |values: &[f64]| {
+            (0..values.len())
+                .min_by(|&a, &b| values[a].abs().total_cmp(&values[b].abs()))
+                .unwrap_or(0)
+        };
+        let extent = |values: &[f64], recovered: &dyn Fn(usize) -> bool| {
+            let mut order = (0..values.len()).collect::<Vec<_>>();
+            order.sort_by(|&a, &b| values[a].abs().total_cmp(&values[b].abs()));
+            let failure = order
+                .iter()
+                .find(|&&k| !recovered(k))
+                .map_or(f64::INFINITY, |&k| values[k].abs());
+            order
+                .iter()
+                .map(|&k| values[k].abs())
+                .take_while(|v| *v < failure)
+                .fold(0., f64::max)
+        };
+        let (i0, j0) = (closest(&self.x_values), closest(&self.y_values));
+        (
+            extent(&self.x_values, &|i| self.is_recovered(i, j0)),
+            extent(&self.y_values, &|j| self.is_recovered(i0, j)),
+        )
+    }
+
+    /// Outcomes as CSV with a header row, one initialization per row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("x,y,recovered,iterations\n");
+        for (j, y) in self.y_values.iter().enumerate() {
+            for (i, x) in self.x_values.iter().enumerate() {
+                let k = j * self.x_values.len() + i;
+                csv += &format!("{},{},{},{}\n", x, y, self.recovered[k], self.iterations[k]);
+            }
+        }
+        csv
+    }
+}
+
+/// Options of [`convergence_basin`].
+#[derive(Clone, Debug)]
+pub struct BasinOptions {
+    /// Grid of the initializations, like the grid of [`cost_landscape`].
+    pub grid: LandscapeOptions,
+    /// Options of ICP, including the threads of its nearest-neighbor search.
+    pub icp: IcpOptions,
+    /// An initialization recovers the solution if ICP moves the source points within this
+    /// root-mean-square distance of where the solution moves them, as a fraction of their
+    /// root-mean-square radius.
+    pub tolerance: f64,
+}
+
+impl Default for BasinOptions {
+    fn default() -> Self {
+        Self {
+            grid: LandscapeOptions::default(),
+            icp: IcpOptions::default(),
+            tolerance: 1e-6,
+        }
+    }
+}
+
+/// Run ICP from each perturbation of the `solution` over a grid like [`cost_landscape`], and
+/// record which initializations recover it, e.g. to tune [`IcpOptions`] or the accuracy required
+/// from an initial alignment.
+///
+/// The same errors as [`cost_landscape`] are returned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::landscape::{convergence_basin, Axis, BasinOptions, LandscapeOptions};
+/// use kabsch_umeyama::Transform;
+/// use std::f64::consts::PI;
+///
+/// // an L shape, without rotational symmetry
+/// let mut shape = (0..8).map(|i| [i as f64, 0.]).collect::<Vec<_>>();
+/// shape.extend((1..4).map(|i| [0., i as f64]));
+/// let grid = LandscapeOptions { rotation_range: PI / 4., steps: 9, ..Default::default() };
+/// let options = BasinOptions { grid, ..Default::default() };
+/// let basin = convergence_basin(
+///     &shape,
+///     &shape,
+///     &Transform::identity(),
+///     Axis::Rotation(0, 1),
+///     Axis::Translation(1),
+///     &options,
+/// )
+/// .unwrap();
+/// assert!(basin.is_recovered(4, 4));
+/// assert!(!basin.is_recovered(0, 4));
+/// assert!(basin.success_rate() > 0. && basin.success_rate() < 0.5);
+/// assert!(basin.mean_iterations().unwrap() >= 2.);
+/// let (rotation, translation) = basin.extent();
+/// assert!(rotation < PI / 4. && translation > 0.);
+/// ```
+pub fn convergence_basin<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    solution: &Transform<C>,
+    x: Axis,
+    y: Axis,
+    options: &BasinOptions,
+) -> Result<Basin, KabschError> {
+    let grid = Grid::new(src, dst, solution, x, y, &options.grid)?;
+    let mut recovered = Vec::with_capacity(grid.x_values.len() * grid.y_values.len());
+    let mut iterations = Vec::with_capacity(recovered.capacity());
+    let mut result = Vec::with_capacity(src.len());
+    for &b in &grid.y_values {
+        for &a in &grid.x_values {
+            let (rotation, translation) = perturbation(x, a, y, b);
+            let initial = Transform::new(
+                rotation * solution.rotation(),
+                rotation * (solution.translation() - grid.center) + grid.center + translation,
+                solution.scale(),
+            );
+            match icp_from(src, dst, initial, &options.icp) {
+                Ok(registration) => {
+                    result.clear();
+                    result.extend_from_slice(src);
+                    registration.transform.apply_mut(&mut result);
+                    let squared = result
+                        .iter()
+                        .zip(&grid.moved)
+                        .map(|(p, q)| (SVector::from(*p) - SVector::from(*q)).norm_squared())
+                        .sum::<f64>();
+                    let distance = (squared / src.len() as f64).sqrt();
+                    recovered.push(distance <= options.tolerance * grid.radius);
+                    iterations.push(registration.rmse.len());
+                }
+                Err(_) => {
+                    recovered.push(false);
+                    iterations.push(0);
+                }
+            }
+        }
+    }
+    Ok(Basin {
+        x,
+        y,
+        x_values: grid.x_values,
+        y_values: grid.y_values,
+        recovered,
+        iterations,
+    })
+}
+
+/// Perturbations of a solution along two axes.
+struct Grid<const C: usize> {
+    /// Source points transformed by the solution
+    moved: Vec<[f64; C]>,
+    center: SVector<f64, C>,
+    /// Root-mean-square distance of the transformed source points to their centroid
+    radius: f64,
+    x_values: Vec<f64>,
+    y_values: Vec<f64>,
+}
+
+impl<const C: usize> Grid<C> {
+    fn new(
+        src: &[[f64; C]],
+        dst: &[[f64; C]],
+        solution: &Transform<C>,
+        x: Axis,
+        y: Axis,
+        options: &LandscapeOptions,
+    ) -> Result<Self, KabschError> {
+        if src.is_empty() || dst.is_empty() || C == 0 {
+            return Err(KabschError::Empty);
+        }
+        for axis in [x, y] {
+            let index = match axis {
+                Axis::Rotation(i, j) => i.max(j),
+                Axis::Translation(i) => i,
+            };
+            if index >= C {
+                return Err(KabschError::IndexOutOfBounds { index, len: C });
+            }
+        }
+        let mut moved = src.to_vec();
+        solution.apply_mut(&mut moved);
+        let center = moved
+            .iter()
+            .map(|p| SVector::from(*p))
+            .sum::<SVector<f64, C>>()
+            / moved.len() as f64;
+        let radius = (moved
+            .iter()
+            .map(|p| (SVector::from(*p) - center).norm_squared())
+            .sum::<f64>()
+            / moved.len() as f64)
+            .sqrt();
+        let values = |axis| {
+            let range = match axis {
+                Axis::Rotation(..) => options.rotation_range,
+                Axis::Translation(_) => options.translation_range * radius,
+            };
+            let steps = options.steps.max(1);
+            (0..steps)
+                .map(|k| {
+                    if steps == 1 {
+                        0.
+                    } else {
+                        range * (2. * k as f64 / (steps - 1) as f64 - 1.)
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        Ok(Self {
+            x_values: values(x),
+            y_values: values(y),
+            moved,
+            center,
+            radius,
+        })
+    }
+}
+
+/// Rotation and translation of the perturbation by `a` along `x` then `b` along `y`
+fn perturbation<const C: usize>(
+    x: Axis,
+    a: f64,
+    y: Axis,
+    b: f64,
+) -> (SMatrix<f64, C, C>, SVector<f64, C>) {
+    let mut rotation = SMatrix::<f64, C, C>::identity();
+    let mut translation = SVector::<f64, C>::zeros();
+    for (axis, value) in [(x, a), (y, b)] {
+        match axis {
+            Axis::Rotation(i, j) if i != j => {
+                let mut givens = SMatrix::<f64, C, C>::identity();
+                let (sin, cos) = value.sin_cos();
+                givens[(i, i)] = cos;
+                givens[(j, j)] = cos;
+                givens[(j, i)] = sin;
+                givens[(i, j)] = -sin;
+                rotation = givens * rotation;
+                translation = givens * translation;
+            }
+            Axis::Rotation(..) => {}
+            Axis::Translation(i) => translation[i] += value,
+        }
+    }
+    (rotation, translation)
+}
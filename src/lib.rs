@@ -2,65 +2,261 @@
 //!
 //! The Kabsch-Umeyama algorithm is a method for aligning and comparing the similarity between two sets of points.
 //! It finds the optimal translation, rotation and scaling by minimizing the root-mean-square deviation (RMSD) of the point pairs.
-use nalgebra::{
-    allocator::Allocator, Const, DMatrix, DVector, DefaultAllocator, Dim, DimDiff, DimMin, DimSub,
-    SMatrix, U1,
+//! The [`prelude`] imports the common types and functions at once.
+//! The [`metrics`] module computes the RMSD of an alignment, its per-point residuals and the Procrustes disparity of two point sets.
+//!
+//! Without the default `std` feature, the crate is `#![no_std]` with `alloc`, keeping the core
+//! estimation ([`estimate`], [`estimate_transform`], [`strict`], [`estimate_batch`], the pure-Rust
+//! solvers) for embedded targets.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod anisotropic;
+#[cfg(feature = "std")]
+pub mod augment;
+mod batch;
+#[cfg(feature = "std")]
+mod bidirectional;
+#[cfg(feature = "std")]
+mod bvh;
+#[cfg(feature = "candle")]
+pub mod candle;
+#[cfg(feature = "std")]
+mod canonical;
+#[cfg(feature = "std")]
+mod covariance;
+#[cfg(feature = "cuda")]
+mod cuda;
+#[cfg(feature = "std")]
+mod datum;
+#[cfg(feature = "std")]
+pub mod depth;
+mod diagnostics;
+#[cfg(feature = "std")]
+mod drift;
+pub mod eigen;
+mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod frames;
+#[cfg(feature = "std")]
+mod frozen;
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "std")]
+pub mod icp;
+mod incremental;
+#[cfg(feature = "std")]
+pub mod init;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+mod jacobian;
+#[cfg(feature = "std")]
+mod joint;
+mod kind;
+#[cfg(feature = "std")]
+pub mod landscape;
+#[cfg(feature = "std")]
+pub mod mapping;
+#[cfg(feature = "std")]
+pub mod matching;
+pub mod matlab;
+#[cfg(feature = "std")]
+pub mod mesh;
+#[cfg(feature = "std")]
+pub mod metrics;
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
+#[cfg(feature = "std")]
+mod observability;
+#[cfg(feature = "std")]
+pub mod opencv;
+#[cfg(feature = "plot")]
+pub mod plot;
+#[cfg(feature = "std")]
+pub mod pointfile;
+#[cfg(feature = "std")]
+mod pose_graph;
+pub mod prelude;
+#[cfg(feature = "std")]
+mod prior;
+#[cfg(feature = "std")]
+pub mod quality;
+#[cfg(feature = "std")]
+pub mod ransac;
+#[cfg(feature = "std")]
+mod register;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+mod rng;
+#[cfg(feature = "std")]
+mod robust;
+#[cfg(feature = "std")]
+mod robust_scale;
+mod scalar;
+#[cfg(feature = "std")]
+pub mod schema;
+#[cfg(feature = "std")]
+mod sdf;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+pub mod shape;
+#[cfg(feature = "skimage-compat")]
+pub mod skimage;
+mod solver;
+mod stack;
+pub mod strict;
+#[cfg(feature = "std")]
+mod symmetry;
+#[cfg(feature = "std")]
+pub mod testdata;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod trajectory;
+mod transform;
+#[cfg(feature = "std")]
+pub mod tre;
+#[cfg(feature = "std")]
+pub mod tuning;
+#[cfg(feature = "wasm-bindgen")]
+pub mod wasm;
+#[cfg(feature = "std")]
+mod weighted;
+
+pub use anisotropic::{estimate_anisotropic, AnisotropicTransform};
+pub use batch::{
+    estimate_batch, estimate_batch_with_backend, estimate_batch_with_options, BatchBackend,
+};
+#[cfg(feature = "std")]
+pub use bidirectional::{estimate_bidirectional, Bidirectional};
+#[cfg(feature = "std")]
+pub use canonical::normalize_to_canonical;
+#[cfg(feature = "std")]
+pub use covariance::{icp_covariance, icp_covariance_from_residuals};
+#[cfg(feature = "std")]
+pub use datum::{estimate_datum, estimate_datum_planes};
+pub use diagnostics::Diagnostics;
+#[cfg(feature = "std")]
+pub use drift::{
+    estimate_covariate_model, estimate_drift, CovariateModel, CovariatePointPair, DriftModel,
+    TimedPointPair,
+};
+pub use error::KabschError;
+#[cfg(feature = "std")]
+pub use frozen::{estimate_frozen, FrozenDofs};
+pub use incremental::IncrementalEstimator;
+#[cfg(feature = "std")]
+pub use jacobian::{estimate_with_jacobian, TransformJacobian};
+#[cfg(feature = "std")]
+pub use joint::{estimate_shared_rotation, estimate_shared_scale, PointPair};
+pub use kind::{
+    estimate_detailed, estimate_with_options, EstimateOptions, EstimateOptionsBuilder, Estimation,
+    TransformKind,
+};
+#[cfg(feature = "std")]
+pub use observability::{
+    anisotropic_scale_recoverability, observability, Observability, ScaleRecoverability,
 };
-use nalgebra_lapack::SVD;
-use std::ops::{Deref, MulAssign};
+#[cfg(feature = "std")]
+pub use prior::{estimate_with_prior, OrientationPrior};
+#[cfg(feature = "std")]
+pub use register::{register, Pipeline, Registration, RegistrationHints};
+#[cfg(feature = "std")]
+pub use rng::Rng;
+#[cfg(feature = "std")]
+pub use robust::{auto_threshold, residual_sigma, robust_sigma, x84_threshold, X84};
+#[cfg(feature = "std")]
+pub use robust_scale::median_ratio_scale;
+pub use scalar::{estimate_f32, estimate_fixed, estimate_generic, Real};
+#[cfg(feature = "lapack")]
+pub use solver::LapackSvd;
+pub use solver::{Analytic3x3, DefaultSolver, NalgebraSvd, QuaternionEigen, Solver};
+pub use stack::{estimate_2d, estimate_static};
+#[cfg(feature = "std")]
+pub use symmetry::{detect_symmetry, Symmetry};
+pub use transform::{SimilarityTransform, Transform};
+#[cfg(feature = "std")]
+pub use weighted::{estimate_weighted, estimate_with_sigma};
 
-pub type NestedArray<const R: usize, const C: usize> = [[f64; C]; R];
+use core::ops::{Deref, MulAssign};
+use nalgebra::{Const, DMatrix, DVector, Dyn, OMatrix, SMatrix, SVector, Scalar};
 
+pub type NestedArray<const R: usize, const C: usize, T = f64> = [[T; C]; R];
+
+/// Points stored as the rows of a nested array, in `f64` unless another scalar type `T` is given.
 #[derive(Clone, Copy, Debug)]
-pub struct Array2<const R: usize, const C: usize>(NestedArray<R, C>);
+pub struct Array2<const R: usize, const C: usize, T = f64>(NestedArray<R, C, T>);
 
-impl<const R: usize, const C: usize> From<NestedArray<R, C>> for Array2<R, C> {
-    fn from(nested_array: NestedArray<R, C>) -> Self {
+impl<const R: usize, const C: usize, T> From<NestedArray<R, C, T>> for Array2<R, C, T> {
+    fn from(nested_array: NestedArray<R, C, T>) -> Self {
         Self(nested_array)
     }
 }
 
-impl<const R: usize, const C: usize> From<&[f64]> for Array2<R, C> {
-    fn from(slice: &[f64]) -> Self {
+impl<const R: usize, const C: usize, T: Copy> TryFrom<&[T]> for Array2<R, C, T> {
+    type Error = KabschError;
+
+    /// New Array2 from a flat row-major slice, which must hold `R × C` values.
+    fn try_from(slice: &[T]) -> Result<Self, Self::Error> {
         if slice.len() != R * C {
-            panic!("The lengths do not match!")
+            return Err(KabschError::ShapeMismatch {
+                expected: R * C,
+                actual: slice.len(),
+            });
         }
-        let mut nested_array = [[0.; C]; R];
-        nested_array
-            .as_flattened_mut()
-            .into_iter()
-            .zip(slice)
-            .for_each(|(a, v)| *a = *v);
-        Self(nested_array)
+        Ok(Self::from_flat(slice))
     }
 }
 
-impl<const R: usize, const C: usize, const RC: usize> From<&[f64; RC]> for Array2<R, C> {
-    fn from(array: &[f64; RC]) -> Self {
-        if RC != R * C {
-            panic!("The lengths do not match!")
-        }
-        Self::from(array.as_slice())
+impl<const R: usize, const C: usize, const RC: usize, T: Copy> From<&[T; RC]> for Array2<R, C, T> {
+    /// New Array2 from a flat row-major array, whose length is checked at compile time.
+    fn from(array: &[T; RC]) -> Self {
+        const { assert!(RC == R * C, "The lengths do not match!") };
+        Self::from_flat(array)
+    }
+}
+
+impl<const R: usize, const C: usize, T: Scalar> From<Array2<R, C, T>> for SMatrix<T, R, C> {
+    fn from(array: Array2<R, C, T>) -> Self {
+        SMatrix::<T, R, C>::from_row_slice(array.0.as_flattened())
     }
 }
 
-impl<const R: usize, const C: usize> Into<SMatrix<f64, R, C>> for Array2<R, C> {
-    fn into(self) -> SMatrix<f64, R, C> {
-        SMatrix::<f64, R, C>::from_row_slice(self.0.as_flattened())
+impl<const R: usize, const C: usize, T: Scalar> From<SMatrix<T, R, C>> for Array2<R, C, T> {
+    fn from(matrix: SMatrix<T, R, C>) -> Self {
+        Self(core::array::from_fn(|i| {
+            core::array::from_fn(|j| matrix[(i, j)].clone())
+        }))
     }
 }
 
-impl<const R: usize, const C: usize> Deref for Array2<R, C> {
-    type Target = NestedArray<R, C>;
+impl<const R: usize, const C: usize, T> Deref for Array2<R, C, T> {
+    type Target = NestedArray<R, C, T>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl<const R: usize, const C: usize> Array2<R, C> {
+impl<const R: usize, const C: usize, T: Copy> Array2<R, C, T> {
+    fn from_flat(values: &[T]) -> Self {
+        Self(core::array::from_fn(|i| {
+            core::array::from_fn(|j| values[i * C + j])
+        }))
+    }
+}
+
+impl<const R: usize, const C: usize, T> Array2<R, C, T> {
     /// New Array2 from a nested array
-    pub fn new(nested_array: NestedArray<R, C>) -> Self {
+    pub fn new(nested_array: NestedArray<R, C, T>) -> Self {
         Self(nested_array)
     }
 
@@ -95,72 +291,316 @@ pub fn estimate<const R: usize, const C: usize>(
     src: impl Into<SMatrix<f64, R, C>>,
     dst: impl Into<SMatrix<f64, R, C>>,
     estimate_scale: bool,
-) -> Option<DMatrix<f64>>
-where
-    Const<C>: DimMin<Const<C>, Output = Const<C>> + DimSub<U1> + Dim,
-    DefaultAllocator: Allocator<DimDiff<Const<C>, U1>> + Allocator<Const<C>>,
-{
-    let mut src = src.into();
-    let mut dst = dst.into();
-    let num = R as f64;
-    let src_mean = src.row_mean();
-    let dst_mean = dst.row_mean();
+) -> Option<DMatrix<f64>> {
+    estimate_with_solver(src, dst, estimate_scale, &DefaultSolver)
+}
+
+/// Estimate a similarity transformation like [`estimate`], reporting the cause of a failure as a
+/// [`KabschError`] instead of `None`, e.g. to log why a registration was rejected. See [`strict`]
+/// for the other non-panicking variants.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{try_estimate, Array2, KabschError};
+///
+/// let src = Array2::<3, 2>::try_from([0., 0., 1., 0., 0., 1.].as_slice()).unwrap();
+/// let dst = Array2::from([[1., 1.], [1., 2.], [0., 1.]]);
+/// assert!(try_estimate(src, dst, true).is_ok());
+///
+/// let coincident = Array2::from([[1., 1.]; 3]);
+/// assert_eq!(try_estimate(coincident, dst, true), Err(KabschError::DegenerateConfiguration));
+/// assert_eq!(
+///     Array2::<3, 2>::try_from([0., 0., 1.].as_slice()).unwrap_err(),
+///     KabschError::ShapeMismatch { expected: 6, actual: 3 }
+/// );
+/// ```
+pub fn try_estimate<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+) -> Result<DMatrix<f64>, KabschError> {
+    strict::estimate(src, dst, estimate_scale)
+}
+
+/// Estimate a similarity transformation like [`estimate`], computing the rotation with the given [`Solver`].
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_with_solver, Array2, QuaternionEigen};
+///
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 2., 0.], [0., 0., 3.]]);
+/// let dst = Array2::from([[1., 1., 1.], [1., 2., 1.], [-1., 1., 1.], [1., 1., 4.]]);
+///
+/// let lapack = estimate(src, dst, true).unwrap();
+/// let horn = estimate_with_solver(src, dst, true, &QuaternionEigen).unwrap();
+/// assert!((lapack - horn).abs().max() < 1e-9);
+/// ```
+pub fn estimate_with_solver<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+) -> Option<DMatrix<f64>> {
+    fit(src.into(), dst.into(), estimate_scale, solver)
+        .ok()
+        .map(|fit| fit.transform)
+}
+
+/// Estimate a similarity transformation in 3 dimensions like [`estimate`] with Horn's closed-form
+/// quaternion method ([`QuaternionEigen`]) instead of an SVD.
+///
+/// The rotation is the eigenvector of the largest eigenvalue of a symmetric `4×4` matrix, which is
+/// always a proper rotation without any reflection correction, and which degrades gracefully for
+/// nearly planar or collinear points. Prefer it for 3D rigid alignment in tight loops or on
+/// near-degenerate configurations; prefer the SVD of [`estimate`] in other dimensions, or to get
+/// the rank and reflection [`Diagnostics`] of the covariance.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_horn_3d, Array2};
+///
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 2., 0.], [0., 0., 3.]]);
+/// let dst = Array2::from([[1., 1., 1.], [1., 2., 1.], [-1., 1., 1.], [1., 1., 4.]]);
+///
+/// let horn = estimate_horn_3d(src, dst, true).unwrap();
+/// assert!((horn - estimate(src, dst, true).unwrap()).abs().max() < 1e-9);
+/// ```
+pub fn estimate_horn_3d<const R: usize>(
+    src: impl Into<SMatrix<f64, R, 3>>,
+    dst: impl Into<SMatrix<f64, R, 3>>,
+    estimate_scale: bool,
+) -> Option<DMatrix<f64>> {
+    estimate_with_solver(src, dst, estimate_scale, &QuaternionEigen)
+}
+
+/// Estimate a similarity transformation like [`estimate_with_solver`], also returning the
+/// intermediate quantities of the estimation as [`Diagnostics`].
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_with_diagnostics, Array2, DefaultSolver};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+/// let dst = Array2::from([[0., 0.], [-1., 0.], [0., 1.]]);
+///
+/// // the mirror image can only be reached through a reflection, which is corrected
+/// let (_, diagnostics) = estimate_with_diagnostics(src, dst, false, &DefaultSolver).unwrap();
+/// assert_eq!(diagnostics.rank, 2);
+/// assert!(diagnostics.reflection_corrected);
+/// ```
+pub fn estimate_with_diagnostics<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+) -> Option<(DMatrix<f64>, Diagnostics)> {
+    let fit = fit(src.into(), dst.into(), estimate_scale, solver).ok()?;
+    let diagnostics = Diagnostics::new(fit.covariance, &fit.rotation);
+    Some((fit.transform, diagnostics))
+}
+
+/// Estimate a similarity transformation like [`estimate`], returned as a [`Transform`].
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_transform, Array2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+/// let dst = Array2::from([[1., 1.], [1., 3.], [-1., 1.]]);
+///
+/// let t = estimate_transform(src, dst, true).unwrap();
+/// assert!((t.scale() - 2.).abs() < 1e-12);
+/// assert!((t.inverse().scale() - 0.5).abs() < 1e-12);
+/// ```
+pub fn estimate_transform<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+) -> Option<Transform<C>> {
+    fit(src.into(), dst.into(), estimate_scale, &DefaultSolver)
+        .ok()
+        .map(|fit| fit.to_transform())
+}
+
+/// Estimate a similarity transformation like [`estimate`] between point sets whose number of points
+/// and dimensions are only known at runtime, e.g. read from files or sensors: one point per row of
+/// `src` and `dst`, which must have the same shape.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_dyn, KabschError};
+/// use nalgebra::DMatrix;
+///
+/// let src = DMatrix::from_row_slice(3, 2, &[0., 0., 1., 0., 0., 1.]);
+/// let dst = DMatrix::from_row_slice(3, 2, &[1., 1., 1., 3., -1., 1.]);
+/// let t = estimate_dyn(&src, &dst, true).unwrap();
+/// assert_eq!(t.shape(), (3, 3));
+/// assert!((t[(1, 0)] - 2.).abs() < 1e-12);
+///
+/// let short = DMatrix::from_row_slice(2, 2, &[0., 0., 1., 0.]);
+/// assert_eq!(
+///     estimate_dyn(&src, &short, true),
+///     Err(KabschError::ShapeMismatch { expected: 6, actual: 4 })
+/// );
+/// ```
+pub fn estimate_dyn(
+    src: &DMatrix<f64>,
+    dst: &DMatrix<f64>,
+    estimate_scale: bool,
+) -> Result<DMatrix<f64>, KabschError> {
+    if src.shape() != dst.shape() {
+        return Err(KabschError::ShapeMismatch {
+            expected: src.len(),
+            actual: dst.len(),
+        });
+    }
+    if src.is_empty() {
+        return Err(KabschError::Empty);
+    }
+    if src.iter().chain(dst.iter()).any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    let weights = DVector::repeat(src.nrows(), 1.);
+    let fit = fit_matrix(
+        src.clone(),
+        dst.clone(),
+        &weights,
+        estimate_scale,
+        &DefaultSolver,
+        None,
+    )?;
+    Ok(fit.transform)
+}
+
+/// Result of the estimation along with the intermediate quantities it was derived from.
+struct Fit {
+    transform: DMatrix<f64>,
+    covariance: DMatrix<f64>,
+    rotation: DMatrix<f64>,
+    scale: f64,
+}
+
+impl Fit {
+    fn to_transform<const C: usize>(&self) -> Transform<C> {
+        Transform::new(
+            SMatrix::from_column_slice(self.rotation.as_slice()),
+            SVector::from_fn(|i, _| self.transform[(i, C)]),
+            self.scale,
+        )
+    }
+}
+
+fn fit<const R: usize, const C: usize>(
+    src: SMatrix<f64, R, C>,
+    dst: SMatrix<f64, R, C>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+) -> Result<Fit, KabschError> {
+    fit_points(
+        rows(src),
+        rows(dst),
+        &DVector::repeat(R, 1.),
+        estimate_scale,
+        solver,
+    )
+}
+
+/// Points stored as the rows of a matrix, with a number of points only known at runtime.
+pub(crate) type Points<const C: usize> = OMatrix<f64, Dyn, Const<C>>;
+
+fn rows<const R: usize, const C: usize>(points: SMatrix<f64, R, C>) -> Points<C> {
+    Points::from_column_slice_generic(Dyn(R), Const::<C>, points.as_slice())
+}
+
+#[cfg(feature = "std")]
+fn rows_from_slice<const C: usize>(points: &[[f64; C]]) -> Points<C> {
+    Points::from_row_iterator(points.len(), points.iter().flatten().copied())
+}
+
+/// Weighted estimation: the means, the cross-covariance and the source variance are weighted by
+/// `weights`, which must have a positive sum.
+fn fit_points<const C: usize>(
+    src: Points<C>,
+    dst: Points<C>,
+    weights: &DVector<f64>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+) -> Result<Fit, KabschError> {
+    fit_points_with_prior(src, dst, weights, estimate_scale, solver, None)
+}
+
+/// Weighted estimation like [`fit_points`], with the rotation computed from the cross-covariance
+/// plus the `C×C` term of an orientation `prior`.
+fn fit_points_with_prior<const C: usize>(
+    src: Points<C>,
+    dst: Points<C>,
+    weights: &DVector<f64>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+    prior: Option<&DMatrix<f64>>,
+) -> Result<Fit, KabschError> {
+    let r = src.nrows();
+    fit_matrix(
+        DMatrix::from_column_slice(r, C, src.as_slice()),
+        DMatrix::from_column_slice(r, C, dst.as_slice()),
+        weights,
+        estimate_scale,
+        solver,
+        prior,
+    )
+}
+
+/// Core of every estimation, for points stored as the rows of matrices whose number of rows and
+/// columns are both only known at runtime.
+fn fit_matrix(
+    mut src: DMatrix<f64>,
+    mut dst: DMatrix<f64>,
+    weights: &DVector<f64>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+    prior: Option<&DMatrix<f64>>,
+) -> Result<Fit, KabschError> {
+    let c = src.ncols();
+    let num = weights.sum();
+    let src_mean = weights.transpose() * &src / num;
+    let dst_mean = weights.transpose() * &dst / num;
     src.row_iter_mut().for_each(|mut row| {
         row.iter_mut()
-            .zip(src_mean.data.as_slice())
+            .zip(src_mean.iter())
             .for_each(|(v, mean)| *v -= *mean);
     });
     dst.row_iter_mut().for_each(|mut row| {
         row.iter_mut()
-            .zip(dst_mean.data.as_slice())
+            .zip(dst_mean.iter())
             .for_each(|(v, mean)| *v -= *mean)
     });
     let src_demean = src;
     let dst_demean = dst;
+    let src_weighted = DMatrix::from_fn(src_demean.nrows(), c, |i, j| {
+        src_demean[(i, j)] * weights[i]
+    });
 
-    let a = dst_demean.transpose() * src_demean / num;
-    let mut d = DVector::<f64>::from_element(C, 1.);
-
-    if a.determinant() < 0. {
-        d[C - 1] = -1.;
+    let a = dst_demean.transpose() * &src_weighted / num;
+    let fused = match prior {
+        Some(prior) => &a + prior,
+        None => a.clone(),
+    };
+    // finite coordinates may still overflow the moments
+    let variance = src_weighted.component_mul(&src_demean).sum();
+    if estimate_scale && !variance.is_finite() {
+        return Err(KabschError::NonFinite);
     }
-    let mut t = DMatrix::from_diagonal(&DVector::<f64>::from_element(C + 1, 1.));
-    if let Some(svd) = SVD::new(a) {
-        let s = svd.singular_values;
-        let v = svd.vt;
-        let u = svd.u;
-
-        let rank = a.rank(1e-5f64);
-        if rank == 0 {
-            return None;
-        }
-        let m = if rank == C - 1 {
-            if u.determinant() * v.determinant() > 0. {
-                u * v
-            } else {
-                let cache = d[C - 1];
-                d[C - 1] = -1.;
-                let d_diag = DMatrix::from_diagonal(&d);
-                let m = u * d_diag * v;
-                d[C - 1] = cache;
-                m
-            }
-        } else {
-            let d_diag = DMatrix::from_diagonal(&d);
-            u * d_diag * v
-        };
-        t.view_mut((0, 0), (C, C)).copy_from_slice(m.as_slice());
-
-        let scale = if estimate_scale {
-            1. / src_demean.row_variance().sum() * s.dot(&d)
-        } else {
-            1.
-        };
-        let mx = dst_mean - (t.view((0, 0), (C, C)) * src_mean.transpose()).transpose() * scale;
-        t.view_mut((0, C), (C, 1)).copy_from_slice(mx.as_slice());
-        t.view_mut((0, 0), (C, C)).mul_assign(scale);
-        Some(t)
+    let m = solver::rotation_or_error(solver, &fused, solver::RANK_TOL)?;
+
+    let mut t = DMatrix::from_diagonal(&DVector::<f64>::from_element(c + 1, 1.));
+    t.view_mut((0, 0), (c, c)).copy_from(&m);
+
+    let scale = if estimate_scale {
+        num / variance * (m.transpose() * &a).trace()
     } else {
-        None
-    }
-}
\ No newline at end of file
+        1.
+    };
+    let mx = dst_mean - (t.view((0, 0), (c, c)) * src_mean.transpose()).transpose() * scale;
+    t.view_mut((0, c), (c, 1)).copy_from_slice(mx.as_slice());
+    t.view_mut((0, 0), (c, c)).mul_assign(scale);
+    Ok(Fit {
+        transform: t,
+        covariance: a,
+        rotation: m,
+        scale,
+    })
+}
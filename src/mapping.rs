@@ -0,0 +1,476 @@
+//! Continuous scan-to-map registration: incoming scans are aligned to a voxel-downsampled map of
+//! the previous ones by ICP, then merged into it.
+use crate::{fit_points, rows_from_slice, DefaultSolver, Transform};
+use nalgebra::{DVector, Vector3};
+use std::collections::HashMap;
+
+pub use crate::pose_graph::{Edge, PoseGraph};
+
+/// Point cloud downsampled on a voxel grid, keeping the centroid of the points in each voxel.
+#[derive(Clone, Debug)]
+pub struct VoxelMap {
+    voxel_size: f64,
+    voxels: HashMap<[i64; 3], (Vector3<f64>, usize)>,
+}
+
+impl VoxelMap {
+    /// New empty VoxelMap
+    /// The `None` value is returned if `voxel_size` is not positive and finite.
+    pub fn new(voxel_size: f64) -> Option<Self> {
+        valid_voxel_size(voxel_size).then(|| Self::empty(voxel_size))
+    }
+
+    /// New VoxelMap from points
+    /// The `None` value is returned if `voxel_size` is not positive and finite.
+    pub fn from_points(points: &[[f64; 3]], voxel_size: f64) -> Option<Self> {
+        valid_voxel_size(voxel_size).then(|| Self::with_points(points, voxel_size))
+    }
+
+    fn empty(voxel_size: f64) -> Self {
+        Self {
+            voxel_size,
+            voxels: HashMap::new(),
+        }
+    }
+
+    fn with_points(points: &[[f64; 3]], voxel_size: f64) -> Self {
+        let mut map = Self::empty(voxel_size);
+        points.iter().for_each(|p| map.insert(&Vector3::from(*p)));
+        map
+    }
+
+    fn key(&self, p: &Vector3<f64>) -> [i64; 3] {
+        [0, 1, 2].map(|k| (p[k] / self.voxel_size).floor() as i64)
+    }
+
+    /// Add a point to the centroid of its voxel
+    pub fn insert(&mut self, p: &Vector3<f64>) {
+        let voxel = self
+            .voxels
+            .entry(self.key(p))
+            .or_insert((Vector3::zeros(), 0));
+        voxel.0 += p;
+        voxel.1 += 1;
+    }
+
+    /// Number of occupied voxels
+    pub fn len(&self) -> usize {
+        self.voxels.len()
+    }
+
+    /// Whether no voxel is occupied
+    pub fn is_empty(&self) -> bool {
+        self.voxels.is_empty()
+    }
+
+    /// Edge length of a voxel
+    pub fn voxel_size(&self) -> f64 {
+        self.voxel_size
+    }
+
+    /// Centroids of the occupied voxels
+    pub fn points(&self) -> Vec<[f64; 3]> {
+        self.voxels
+            .values()
+            .map(|(sum, count)| (sum / *count as f64).into())
+            .collect()
+    }
+
+    /// Centroid nearest to `p` within `max_distance`, searching the neighbouring voxels only, or
+    /// every occupied voxel when there are fewer of them than neighbouring voxels.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::mapping::VoxelMap;
+    /// use nalgebra::Vector3;
+    ///
+    /// let map = VoxelMap::from_points(&[[0., 0., 0.], [30., 0., 0.]], 0.1).unwrap();
+    /// let far = map.nearest(&Vector3::new(20., 0., 0.), f64::INFINITY).unwrap();
+    /// assert_eq!(far, Vector3::new(30., 0., 0.));
+    /// assert_eq!(map.nearest(&Vector3::new(20., 0., 0.), 5.), None);
+    /// assert!(VoxelMap::new(0.).is_none());
+    /// ```
+    pub fn nearest(&self, p: &Vector3<f64>, max_distance: f64) -> Option<Vector3<f64>> {
+        if !(0. ..).contains(&max_distance) {
+            return None;
+        }
+        let mut best = None;
+        let mut best_squared = max_distance * max_distance;
+        let mut consider = |(sum, count): &(Vector3<f64>, usize)| {
+            let centroid = sum / *count as f64;
+            let squared = (centroid - p).norm_squared();
+            if squared <= best_squared {
+                best = Some(centroid);
+                best_squared = squared;
+            }
+        };
+        let reach = (max_distance / self.voxel_size).ceil();
+        if (2. * reach + 1.).powi(3) > self.len() as f64 {
+            self.voxels.values().for_each(consider);
+            return best;
+        }
+        // bounded by the cube root of the number of occupied voxels
+        let reach = reach as i64;
+        let [x, y, z] = self.key(p);
+        for i in x.saturating_sub(reach)..=x.saturating_add(reach) {
+            for j in y.saturating_sub(reach)..=y.saturating_add(reach) {
+                for k in z.saturating_sub(reach)..=z.saturating_add(reach) {
+                    self.voxels
+                        .get(&[i, j, k])
+                        .into_iter()
+                        .for_each(&mut consider);
+                }
+            }
+        }
+        best
+    }
+}
+
+fn valid_voxel_size(voxel_size: f64) -> bool {
+    voxel_size > 0. && voxel_size.is_finite()
+}
+
+/// Options of the [`Mapper`].
+#[derive(Clone, Copy, Debug)]
+pub struct MapperOptions {
+    /// Edge length of the voxels of the map and of the downsampled scans.
+    pub voxel_size: f64,
+    /// Scan points farther than this from the map are left out of the estimation.
+    pub max_correspondence_distance: f64,
+    pub max_iterations: usize,
+    /// ICP stops once the root-mean-square error decreases by less than this.
+    pub tolerance: f64,
+    /// A scan is merged into the map as a keyframe once the sensor moved farther than this since
+    /// the last keyframe,
+    pub keyframe_translation: f64,
+    /// or rotated by more than this angle (radians),
+    pub keyframe_rotation: f64,
+    /// or when the fraction of its points matched to the map falls below this overlap ratio.
+    pub keyframe_overlap: f64,
+}
+
+impl Default for MapperOptions {
+    fn default() -> Self {
+        Self {
+            voxel_size: 0.5,
+            max_correspondence_distance: 1.,
+            max_iterations: 30,
+            tolerance: 1e-6,
+            keyframe_translation: 1.,
+            keyframe_rotation: 0.2,
+            keyframe_overlap: 0.8,
+        }
+    }
+}
+
+/// Incremental scan-to-map registration.
+///
+/// Each scan is downsampled and registered by point-to-point ICP against the map starting from the
+/// previous pose. Only keyframes are merged into the map at their estimated pose, so that the map
+/// does not grow with every scan of a slow or static sensor. The first scan is a keyframe and
+/// defines the map frame.
+///
+/// Keyframes are the nodes of a [`PoseGraph`] linked by their relative odometry. Through
+/// [`Mapper::register_with`], a [`LoopClosure`] detector can propose earlier keyframes revisited
+/// by a new one: each candidate confirmed by registration adds a loop constraint, after which the
+/// graph is re-optimized and the map rebuilt from the corrected keyframe poses.
+#[derive(Clone, Debug)]
+pub struct Mapper {
+    options: MapperOptions,
+    map: VoxelMap,
+    trajectory: Vec<Transform<3>>,
+    keyframes: Vec<usize>,
+    /// Downsampled keyframe scans, in the sensor frame.
+    keyframe_points: Vec<Vec<[f64; 3]>>,
+    graph: PoseGraph,
+}
+
+/// Place recognition supplied by the user to detect loop closures.
+pub trait LoopClosure {
+    /// Indices (in [`Mapper::keyframes`] order) of the earlier keyframes likely to observe the same
+    /// place as the new keyframe `keyframe`, given its downsampled points and estimated pose.
+    fn candidates(
+        &mut self,
+        keyframe: usize,
+        points: &[[f64; 3]],
+        pose: &Transform<3>,
+    ) -> Vec<usize>;
+}
+
+/// No loop closure detection.
+impl LoopClosure for () {
+    fn candidates(&mut self, _: usize, _: &[[f64; 3]], _: &Transform<3>) -> Vec<usize> {
+        Vec::new()
+    }
+}
+
+impl Mapper {
+    /// New Mapper with an empty map
+    /// The `None` value is returned if the voxel size of the `options` is not positive and finite.
+    pub fn new(options: MapperOptions) -> Option<Self> {
+        Some(Self {
+            map: VoxelMap::new(options.voxel_size)?,
+            options,
+            trajectory: Vec::new(),
+            keyframes: Vec::new(),
+            keyframe_points: Vec::new(),
+            graph: PoseGraph::new(),
+        })
+    }
+
+    /// Register a scan to the map, merging it if it is a keyframe, and return its pose in the map
+    /// frame.
+    /// The `None` value is returned, and the scan discarded, if fewer than 3 of its points are
+    /// within the correspondence distance of the map or the estimation is not well-conditioned.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::mapping::{Mapper, MapperOptions};
+    /// use kabsch_umeyama::testing::Rng;
+    ///
+    /// // a corner made of three walls
+    /// let mut rng = Rng::new(1);
+    /// let scene = (0..3000)
+    ///     .map(|i| {
+    ///         let (u, v) = (rng.range(0., 2.), rng.range(0., 2.));
+    ///         [[0., u, v], [u, 0., v], [u, v, 0.]][i % 3]
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// let options = MapperOptions {
+    ///     voxel_size: 0.1,
+    ///     max_correspondence_distance: 0.3,
+    ///     ..Default::default()
+    /// };
+    /// let mut mapper = Mapper::new(options).unwrap();
+    /// mapper.register(&scene).unwrap();
+    ///
+    /// // the sensor moved by (0.05, 0.1, 0): the scene appears shifted the other way
+    /// let moved = scene.iter().map(|p| [p[0] - 0.05, p[1] - 0.1, p[2]]).collect::<Vec<_>>();
+    /// let pose = mapper.register(&moved).unwrap();
+    /// assert!((pose.translation() - nalgebra::Vector3::new(0.05, 0.1, 0.)).norm() < 0.02);
+    /// assert_eq!(mapper.trajectory().len(), 2);
+    ///
+    /// // small motion with full overlap: not a keyframe, the map is left unchanged
+    /// assert_eq!(mapper.keyframes(), &[0]);
+    /// ```
+    pub fn register(&mut self, scan: &[[f64; 3]]) -> Option<Transform<3>> {
+        self.register_with(scan, &mut ())
+    }
+
+    /// Register a scan like [`Mapper::register`], checking the loop closures proposed by
+    /// `loop_closure` when the scan becomes a keyframe.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::mapping::{LoopClosure, Mapper, MapperOptions};
+    /// use kabsch_umeyama::testing::Rng;
+    /// use kabsch_umeyama::Transform;
+    ///
+    /// // every keyframe is a candidate to close a loop with the first one
+    /// struct First;
+    /// impl LoopClosure for First {
+    ///     fn candidates(&mut self, _: usize, _: &[[f64; 3]], _: &Transform<3>) -> Vec<usize> {
+    ///         vec![0]
+    ///     }
+    /// }
+    ///
+    /// let mut rng = Rng::new(1);
+    /// let scene = (0..3000)
+    ///     .map(|i| {
+    ///         let (u, v) = (rng.range(0., 2.), rng.range(0., 2.));
+    ///         [[0., u, v], [u, 0., v], [u, v, 0.]][i % 3]
+    ///     })
+    ///     .collect::<Vec<_>>();
+    ///
+    /// let options = MapperOptions {
+    ///     voxel_size: 0.1,
+    ///     max_correspondence_distance: 0.3,
+    ///     keyframe_translation: 0.05,
+    ///     ..Default::default()
+    /// };
+    /// let mut mapper = Mapper::new(options).unwrap();
+    /// for shift in [0., 0.1, 0.2, 0.1, 0.] {
+    ///     let scan = scene.iter().map(|p| [p[0] - shift, p[1], p[2]]).collect::<Vec<_>>();
+    ///     mapper.register_with(&scan, &mut First).unwrap();
+    /// }
+    /// // back at the start: consecutive odometry edges plus the loop closures
+    /// assert!(mapper.pose_graph().edges().len() > mapper.keyframes().len() - 1);
+    /// assert!(mapper.pose().unwrap().translation().norm() < 0.02);
+    /// ```
+    pub fn register_with(
+        &mut self,
+        scan: &[[f64; 3]],
+        loop_closure: &mut impl LoopClosure,
+    ) -> Option<Transform<3>> {
+        let points = VoxelMap::with_points(scan, self.options.voxel_size).points();
+        let (pose, keyframe) = match self.keyframes.last() {
+            None => (Transform::identity(), true),
+            Some(&last) => {
+                let (pose, overlap) = icp(
+                    &points,
+                    &self.map,
+                    self.trajectory.last()?.clone(),
+                    &self.options,
+                )?;
+                let last = &self.trajectory[last];
+                let translation = (pose.translation() - last.translation()).norm();
+                let cos = ((last.rotation().transpose() * pose.rotation()).trace() - 1.) / 2.;
+                let keyframe = translation > self.options.keyframe_translation
+                    || cos.clamp(-1., 1.).acos() > self.options.keyframe_rotation
+                    || overlap < self.options.keyframe_overlap;
+                (pose, keyframe)
+            }
+        };
+        self.trajectory.push(pose.clone());
+        if keyframe {
+            points
+                .iter()
+                .for_each(|p| self.map.insert(&transform_point(&pose, p)));
+            self.add_keyframe(points, loop_closure);
+        }
+        self.trajectory.last().cloned()
+    }
+
+    /// Add the latest scan as a keyframe node with its odometry edge, then close the loops.
+    fn add_keyframe(&mut self, points: Vec<[f64; 3]>, loop_closure: &mut impl LoopClosure) {
+        let pose = self.trajectory.last().unwrap().clone();
+        let node = self.graph.add_node(pose.clone());
+        if let Some(&previous) = self.keyframes.last() {
+            self.graph
+                .add_edge(Edge {
+                    from: node - 1,
+                    to: node,
+                    measurement: relative(&self.trajectory[previous], &pose),
+                    weight: 1.,
+                })
+                .expect("the previous keyframe is a node");
+        }
+        self.keyframes.push(self.trajectory.len() - 1);
+
+        let mut closed = false;
+        for candidate in loop_closure.candidates(node, &points, &pose) {
+            if candidate + 1 >= node {
+                continue;
+            }
+            let target =
+                VoxelMap::with_points(&self.keyframe_points[candidate], self.options.voxel_size);
+            let initial = relative(&self.graph.poses()[candidate], &pose);
+            let Some((measurement, overlap)) = icp(&points, &target, initial, &self.options) else {
+                continue;
+            };
+            if overlap >= self.options.keyframe_overlap {
+                self.graph
+                    .add_edge(Edge {
+                        from: candidate,
+                        to: node,
+                        measurement,
+                        weight: 1.,
+                    })
+                    .expect("loop closure candidates precede the new node");
+                closed = true;
+            }
+        }
+        self.keyframe_points.push(points);
+        if closed {
+            self.graph.optimize(10);
+            self.rebuild_map();
+        }
+    }
+
+    /// Rebuild the map from the keyframes at their optimized poses.
+    fn rebuild_map(&mut self) {
+        self.map = VoxelMap::empty(self.options.voxel_size);
+        for ((&index, pose), points) in self
+            .keyframes
+            .iter()
+            .zip(self.graph.poses())
+            .zip(&self.keyframe_points)
+        {
+            points
+                .iter()
+                .for_each(|p| self.map.insert(&transform_point(pose, p)));
+            self.trajectory[index] = pose.clone();
+        }
+    }
+
+    /// Map of the scans registered so far
+    pub fn map(&self) -> &VoxelMap {
+        &self.map
+    }
+
+    /// Pose of every registered scan, in order
+    pub fn trajectory(&self) -> &[Transform<3>] {
+        &self.trajectory
+    }
+
+    /// Indices in the trajectory of the scans merged into the map
+    pub fn keyframes(&self) -> &[usize] {
+        &self.keyframes
+    }
+
+    /// Graph of the keyframe poses
+    pub fn pose_graph(&self) -> &PoseGraph {
+        &self.graph
+    }
+
+    /// Pose of the latest registered scan
+    pub fn pose(&self) -> Option<&Transform<3>> {
+        self.trajectory.last()
+    }
+}
+
+/// Point-to-point ICP of points against a map from an initial pose, returning the pose and the
+/// fraction of points matched to the map.
+fn icp(
+    points: &[[f64; 3]],
+    map: &VoxelMap,
+    initial: Transform<3>,
+    options: &MapperOptions,
+) -> Option<(Transform<3>, f64)> {
+    let mut pose = initial;
+    let mut previous = f64::INFINITY;
+    let mut overlap = 0.;
+    for _ in 0..options.max_iterations {
+        let mut squared = 0.;
+        let (src, dst): (Vec<[f64; 3]>, Vec<[f64; 3]>) = points
+            .iter()
+            .filter_map(|p| {
+                let moved = transform_point(&pose, p);
+                let q = map.nearest(&moved, options.max_correspondence_distance)?;
+                squared += (q - moved).norm_squared();
+                Some((*p, <[f64; 3]>::from(q)))
+            })
+            .unzip();
+        if src.len() < 3 {
+            return None;
+        }
+        overlap = src.len() as f64 / points.len() as f64;
+        let error = (squared / src.len() as f64).sqrt();
+        if previous - error < options.tolerance {
+            break;
+        }
+        previous = error;
+        pose = fit_points(
+            rows_from_slice(&src),
+            rows_from_slice(&dst),
+            &DVector::repeat(src.len(), 1.),
+            false,
+            &DefaultSolver,
+        )
+        .ok()?
+        .to_transform();
+    }
+    Some((pose, overlap))
+}
+
+fn transform_point(pose: &Transform<3>, p: &[f64; 3]) -> Vector3<f64> {
+    pose.rotation() * Vector3::from(*p) * pose.scale() + pose.translation()
+}
+
+/// Pose of `to` in the frame of the rigid pose `from`.
+fn relative(from: &Transform<3>, to: &Transform<3>) -> Transform<3> {
+    let rotation = from.rotation().transpose();
+    Transform::new(
+        rotation * to.rotation(),
+        rotation * (to.translation() - from.translation()),
+        1.,
+    )
+}
@@ -0,0 +1,99 @@
+//! Correspondence matching upstream of the estimation: which source point matches which
+//! destination point, by nearest-neighbor search in a [`KdTree`], with mutual-consistency
+//! filtering and a distance threshold.
+pub use crate::icp::KdTree;
+
+/// Options of [`nearest_neighbor_matches`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MatchOptions {
+    /// Largest distance between matched points, unbounded by default.
+    pub max_distance: f64,
+    /// Keep only the matches whose destination point also has the source point as its nearest
+    /// neighbor, `true` by default.
+    pub mutual: bool,
+    /// Threads searching the nearest neighbors, `1` by default.
+    pub threads: usize,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            max_distance: f64::INFINITY,
+            mutual: true,
+            threads: 1,
+        }
+    }
+}
+
+/// Correspondence of the source point `src` to the destination point `dst`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Match {
+    pub src: usize,
+    pub dst: usize,
+    pub distance: f64,
+}
+
+/// Match each source point to its nearest destination point, dropping the matches farther than
+/// the threshold and, if `mutual`, those that are not also the nearest source point of their
+/// destination point, so that each destination point is matched at most once.
+///
+/// The matches are in the order of the source points; [`matched_points`] gathers their points for
+/// the estimation.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::matching::{matched_points, nearest_neighbor_matches, MatchOptions};
+/// use kabsch_umeyama::{estimate_weighted, Array2};
+///
+/// let src = [[0., 0.], [1., 0.], [0., 1.], [1., 1.], [5., 5.]];
+/// // slightly shifted, with a spurious point and without the last source point
+/// let dst = [[0.1, 0.], [1.1, 0.], [0.1, 1.], [1.1, 1.], [0.6, 0.5]];
+///
+/// let options = MatchOptions { max_distance: 1., ..Default::default() };
+/// let matches = nearest_neighbor_matches(&src, &dst, &options);
+/// let pairs = matches.iter().map(|m| (m.src, m.dst)).collect::<Vec<_>>();
+/// assert_eq!(pairs, [(0, 0), (1, 1), (2, 2), (3, 3)]);
+///
+/// let (from, to) = matched_points(&matches, &src, &dst);
+/// let weights = std::array::from_fn(|i| 1. / (1. + matches[i].distance));
+/// let from = Array2::<4, 2>::from(<[_; 4]>::try_from(from).unwrap());
+/// let to = Array2::<4, 2>::from(<[_; 4]>::try_from(to).unwrap());
+/// let t = estimate_weighted(from, to, &weights, false).unwrap();
+/// assert!((t[(0, 2)] - 0.1).abs() < 1e-12);
+/// ```
+pub fn nearest_neighbor_matches<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    options: &MatchOptions,
+) -> Vec<Match> {
+    let threads = options.threads.max(1);
+    let forward = KdTree::new(dst.to_vec()).nearest_batch(src, threads);
+    let backward = if options.mutual {
+        KdTree::new(src.to_vec()).nearest_batch(dst, threads)
+    } else {
+        Vec::new()
+    };
+    forward
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, nearest)| {
+            let (j, distance) = nearest?;
+            let consistent = !options.mutual || backward[j].is_some_and(|(k, _)| k == i);
+            (consistent && distance <= options.max_distance).then_some(Match {
+                src: i,
+                dst: j,
+                distance,
+            })
+        })
+        .collect()
+}
+
+/// Source and destination points of the matches, in the same order, e.g. for
+/// [`crate::estimate_dyn`] or, once converted to arrays, [`crate::estimate`] and
+/// [`crate::estimate_weighted`].
+pub fn matched_points<const C: usize>(
+    matches: &[Match],
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+) -> (Vec<[f64; C]>, Vec<[f64; C]>) {
+    matches.iter().map(|m| (src[m.src], dst[m.dst])).unzip()
+}
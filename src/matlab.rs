@@ -0,0 +1,182 @@
+//! Port of MATLAB's `procrustes(X, Y)`, for analysis scripts migrated from MATLAB: the same
+//! outputs, with MATLAB's row-vector convention `Z = b Y T + c`, i.e. points as rows multiplied on
+//! the right by `T`, which is the transpose of the rotation of [`crate::estimate`].
+use crate::KabschError;
+#[cfg(not(any(feature = "std", test)))]
+use nalgebra::ComplexField;
+use nalgebra::{DMatrix, RowDVector};
+
+/// Reflection option of [`procrustes`], like its `'reflection'` argument.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Reflection {
+    /// `'best'`: a reflection if it fits better.
+    #[default]
+    Best,
+    /// `true`: always a reflection.
+    Force,
+    /// `false`: never a reflection.
+    Forbid,
+}
+
+/// Options of [`procrustes`], like its name-value arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcrustesOptions {
+    /// `'scaling'`, `true` by default.
+    pub scaling: bool,
+    /// `'reflection'`, [`Reflection::Best`] by default.
+    pub reflection: Reflection,
+}
+
+impl Default for ProcrustesOptions {
+    fn default() -> Self {
+        Self {
+            scaling: true,
+            reflection: Reflection::Best,
+        }
+    }
+}
+
+/// The `transform` struct of [`procrustes`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProcrustesTransform {
+    /// Orthogonal `T`, applied on the right of the row vectors, with as many rows as `Y` has
+    /// columns.
+    pub t: DMatrix<f64>,
+    /// Scale `b`.
+    pub b: f64,
+    /// Translation `c`, repeated on each of the `n` rows.
+    pub c: DMatrix<f64>,
+}
+
+/// Outputs `[d, Z, transform]` of [`procrustes`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Procrustes {
+    /// Sum of the squared errors of `Z`, standardized by the sum of the squared deviations of `X`
+    /// from its mean.
+    pub d: f64,
+    /// Transformed `Y`.
+    pub z: DMatrix<f64>,
+    pub transform: ProcrustesTransform,
+}
+
+/// Port of `[d, Z, transform] = procrustes(X, Y, ...)`: the transformation of the `n×k` points
+/// `Y` best fitting the `n×k` target points `X`, with the same edge cases as MATLAB: a constant
+/// `X` or `Y` gives `Z` equal to the mean of `X`, `b = 0` and `d` equal to `0` or `1` respectively,
+/// and `Y` may have fewer columns than `X`, padded with zeros.
+///
+/// A [`KabschError::ShapeMismatch`] error is returned if the numbers of points differ or `Y` has
+/// more columns than `X`, where MATLAB raises an error, and [`KabschError::Empty`] or
+/// [`KabschError::NonFinite`] errors for empty or non-finite points.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::matlab::{procrustes, ProcrustesOptions};
+/// use nalgebra::DMatrix;
+///
+/// let x = DMatrix::from_row_slice(4, 2, &[1., 1., 1., 3., -1., 1., -3., 7.]);
+/// let y = DMatrix::from_row_slice(4, 2, &[0., 0., 1., 0., 0., 1., 3., 2.]);
+/// let fit = procrustes(&x, &y, &ProcrustesOptions::default()).unwrap();
+/// // x is y rotated by a quarter turn, scaled by 2 and shifted by (1, 1)
+/// assert!(fit.d < 1e-12);
+/// assert!((fit.transform.b - 2.).abs() < 1e-12);
+/// let quarter_turn = DMatrix::from_row_slice(2, 2, &[0., 1., -1., 0.]);
+/// assert!((&fit.transform.t - quarter_turn).abs().max() < 1e-12);
+/// let z = &y * &fit.transform.t * fit.transform.b + &fit.transform.c;
+/// assert!((&z - &fit.z).abs().max() < 1e-12 && (&z - &x).abs().max() < 1e-12);
+/// ```
+pub fn procrustes(
+    x: &DMatrix<f64>,
+    y: &DMatrix<f64>,
+    options: &ProcrustesOptions,
+) -> Result<Procrustes, KabschError> {
+    let (n, m) = x.shape();
+    if y.nrows() != n || y.ncols() > m {
+        return Err(KabschError::ShapeMismatch {
+            expected: x.len(),
+            actual: y.len(),
+        });
+    }
+    if n == 0 || m == 0 {
+        return Err(KabschError::Empty);
+    }
+    if x.iter().chain(y.iter()).any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    let my = y.ncols();
+    let mut padded = DMatrix::zeros(n, m);
+    padded.columns_mut(0, my).copy_from(y);
+    let y = padded;
+
+    let mu_x = x.row_mean();
+    let mu_y = y.row_mean();
+    let x0 = DMatrix::from_fn(n, m, |i, j| x[(i, j)] - mu_x[j]);
+    let y0 = DMatrix::from_fn(n, m, |i, j| y[(i, j)] - mu_y[j]);
+    // all(ssqX <= abs(eps * n * muX).^2)
+    let constant = |centered: &DMatrix<f64>, mean: &RowDVector<f64>| {
+        (0..m).all(|j| {
+            let ssq = centered.column(j).norm_squared();
+            ssq <= (f64::EPSILON * n as f64 * mean[j]).powi(2)
+        })
+    };
+    let (const_x, const_y) = (constant(&x0, &mu_x), constant(&y0, &mu_y));
+    if const_x || const_y {
+        let z = DMatrix::from_fn(n, m, |_, j| mu_x[j]);
+        return Ok(Procrustes {
+            d: if const_x { 0. } else { 1. },
+            z: z.clone(),
+            transform: ProcrustesTransform {
+                t: DMatrix::identity(my, m),
+                b: 0.,
+                c: z,
+            },
+        });
+    }
+
+    let (ssq_x, ssq_y) = (x0.norm_squared(), y0.norm_squared());
+    let (norm_x, norm_y) = (ssq_x.sqrt(), ssq_y.sqrt());
+    let x0 = x0 / norm_x;
+    let y0 = y0 / norm_y;
+    // [L, D, M] = svd(X0' * Y0), T = M * L'
+    let mut svd = (x0.transpose() * &y0)
+        .try_svd(true, true, f64::EPSILON, 0)
+        .ok_or(KabschError::SvdFailed)?;
+    svd.sort_by_singular_values();
+    let (l, m_t) = (
+        svd.u.ok_or(KabschError::SvdFailed)?,
+        svd.v_t.ok_or(KabschError::SvdFailed)?,
+    );
+    let mut big_m = m_t.transpose();
+    let mut singular_values = svd.singular_values;
+    let mut t = &big_m * l.transpose();
+    let want_reflection = match options.reflection {
+        Reflection::Best => None,
+        Reflection::Force => Some(true),
+        Reflection::Forbid => Some(false),
+    };
+    if let Some(want) = want_reflection {
+        if (t.determinant() < 0.) != want {
+            big_m.column_mut(m - 1).neg_mut();
+            singular_values[m - 1] = -singular_values[m - 1];
+            t = &big_m * l.transpose();
+        }
+    }
+    let trace_ta = singular_values.sum();
+
+    let (b, dissimilarity, z) = if options.scaling {
+        let b = trace_ta * norm_x / norm_y;
+        (b, 1. - trace_ta * trace_ta, &y0 * &t * (norm_x * trace_ta))
+    } else {
+        let dissimilarity = 1. + ssq_y / ssq_x - 2. * trace_ta * norm_y / norm_x;
+        (1., dissimilarity, &y0 * &t * norm_y)
+    };
+    let z = DMatrix::from_fn(n, m, |i, j| z[(i, j)] + mu_x[j]);
+    let c = mu_x - mu_y * &t * b;
+    Ok(Procrustes {
+        d: dissimilarity,
+        z,
+        transform: ProcrustesTransform {
+            t: t.rows(0, my).into_owned(),
+            b,
+            c: DMatrix::from_fn(n, m, |_, j| c[j]),
+        },
+    })
+}
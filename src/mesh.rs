@@ -0,0 +1,367 @@
+//! Triangle meshes and point-to-mesh registration, for aligning scans to CAD reference models.
+use crate::bvh::Bvh;
+use crate::inspect::Surface;
+use crate::{fit_points, rows_from_slice, DefaultSolver, KabschError, Transform};
+use nalgebra::{DVector, Vector3};
+use std::collections::HashMap;
+
+pub use crate::bvh::RayHit;
+pub use crate::sdf::VoxelSdf;
+
+/// Reference geometry a point cloud can be registered to.
+pub trait Reference {
+    /// Point of the reference closest to `point`, or `None` where the reference cannot tell.
+    fn closest(&self, point: &[f64; 3]) -> Option<Vector3<f64>>;
+}
+
+/// Triangle mesh given by its vertices and the vertex indices of its triangles.
+///
+/// A bounding volume hierarchy over the triangles is built on construction, so that closest point
+/// and ray queries take logarithmic time in the number of triangles, along with the angle-weighted
+/// pseudo-normals of the vertices and edges which sign the distances.
+#[derive(Clone, Debug)]
+pub struct TriangleMesh {
+    vertices: Vec<[f64; 3]>,
+    triangles: Vec<[usize; 3]>,
+    bvh: Bvh,
+    /// Sum of the normals of the triangles around each vertex, weighted by their angle at it.
+    vertex_normals: Vec<Vector3<f64>>,
+    /// Sum of the normals of the triangles along each edge, keyed by its sorted vertex indices.
+    edge_normals: HashMap<[usize; 2], Vector3<f64>>,
+}
+
+impl TriangleMesh {
+    /// New TriangleMesh, checking the vertex indices
+    pub fn new(vertices: Vec<[f64; 3]>, triangles: Vec<[usize; 3]>) -> Result<Self, KabschError> {
+        if triangles.is_empty() {
+            return Err(KabschError::Empty);
+        }
+        if let Some(&index) = triangles.iter().flatten().find(|&&i| i >= vertices.len()) {
+            return Err(KabschError::IndexOutOfBounds {
+                index,
+                len: vertices.len(),
+            });
+        }
+        let mut vertex_normals = vec![Vector3::zeros(); vertices.len()];
+        let mut edge_normals = HashMap::<[usize; 2], Vector3<f64>>::new();
+        for triangle in &triangles {
+            let corners = triangle.map(|i| Vector3::from(vertices[i]));
+            let normal = (corners[1] - corners[0]).cross(&(corners[2] - corners[0]));
+            let Some(normal) = normal.try_normalize(0.) else {
+                continue;
+            };
+            for k in 0..3 {
+                let (i, j, l) = (k, (k + 1) % 3, (k + 2) % 3);
+                let angle = (corners[j] - corners[i]).angle(&(corners[l] - corners[i]));
+                vertex_normals[triangle[i]] += normal * angle;
+                let (a, b) = (triangle[i], triangle[j]);
+                *edge_normals
+                    .entry([a.min(b), a.max(b)])
+                    .or_insert_with(Vector3::zeros) += normal;
+            }
+        }
+        Ok(Self {
+            bvh: Bvh::new(&vertices, &triangles),
+            vertices,
+            triangles,
+            vertex_normals,
+            edge_normals,
+        })
+    }
+
+    /// Vertices
+    pub fn vertices(&self) -> &[[f64; 3]] {
+        &self.vertices
+    }
+
+    /// Vertex indices of the triangles
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    /// Corners of a triangle
+    pub fn corners(&self, triangle: usize) -> [Vector3<f64>; 3] {
+        self.triangles[triangle].map(|i| Vector3::from(self.vertices[i]))
+    }
+
+    /// Closest point of the mesh to `point`, with the index of the triangle it lies on.
+    pub fn closest_point(&self, point: &[f64; 3]) -> (Vector3<f64>, usize) {
+        self.bvh
+            .closest_point(|triangle| self.corners(triangle), &Vector3::from(*point))
+    }
+
+    /// First intersection of the ray `origin + t * direction`, `t ≥ 0`, with the mesh.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::mesh::TriangleMesh;
+    ///
+    /// let square = TriangleMesh::new(
+    ///     vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+    ///     vec![[0, 1, 2], [0, 2, 3]],
+    /// )
+    /// .unwrap();
+    /// let hit = square.raycast(&[0.2, 0.7, 2.], &[0., 0., -1.]).unwrap();
+    /// assert_eq!((hit.t, hit.triangle), (2., 1));
+    /// assert!(square.raycast(&[0.2, 0.7, 2.], &[0., 0., 1.]).is_none());
+    /// ```
+    pub fn raycast(&self, origin: &[f64; 3], direction: &[f64; 3]) -> Option<RayHit> {
+        self.bvh.raycast(
+            |triangle| self.corners(triangle),
+            &Vector3::from(*origin),
+            &Vector3::from(*direction),
+        )
+    }
+
+    /// Unit normal of a triangle, oriented by the counter-clockwise winding of its corners
+    pub fn normal(&self, triangle: usize) -> Vector3<f64> {
+        let [a, b, c] = self.corners(triangle);
+        (b - a).cross(&(c - a)).normalize()
+    }
+}
+
+/// The deviation of a point is its distance to the closest point of the mesh, signed by the
+/// angle-weighted pseudo-normal of the face, edge or vertex it lies on (Bærentzen and Aanæs), which
+/// is positive outside a closed mesh wound counter-clockwise even at sharp or concave edges and
+/// vertices, where the normal of a single triangle is ambiguous.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::inspect::Surface;
+/// use kabsch_umeyama::mesh::TriangleMesh;
+///
+/// // tetrahedron with a sharp edge from (0, 0, 0) to (1, 0, 0)
+/// let wedge = TriangleMesh::new(
+///     vec![[0., 0., 0.], [1., 0., 0.], [0.5, 1., 0.1], [0.5, 1., -0.1]],
+///     vec![[0, 1, 2], [0, 3, 1], [0, 2, 3], [1, 3, 2]],
+/// )
+/// .unwrap();
+/// // outside points closest to the sharp edge, on either side
+/// for point in [[0.5, -1., 0.5], [0.5, -1., -0.5], [-1., -1., 0.]] {
+///     assert!(wedge.signed_distance(&point) > 0.);
+/// }
+/// assert!(wedge.signed_distance(&[0.5, 0.5, 0.]) < 0.);
+/// ```
+impl Surface<3> for TriangleMesh {
+    fn signed_distance(&self, point: &[f64; 3]) -> f64 {
+        let p = Vector3::from(*point);
+        let (q, triangle) = self.closest_point(point);
+        let distance = (p - q).norm();
+        if (p - q).dot(&self.pseudo_normal(triangle, &q)) < 0. {
+            -distance
+        } else {
+            distance
+        }
+    }
+}
+
+impl TriangleMesh {
+    /// Angle-weighted pseudo-normal, not normalized, at the point `q` of a triangle: the normal of
+    /// the triangle inside it, and the sums of the normals around the edge or vertex it lies on.
+    fn pseudo_normal(&self, triangle: usize, q: &Vector3<f64>) -> Vector3<f64> {
+        const TOLERANCE: f64 = 1e-9;
+        let indices = self.triangles[triangle];
+        let [a, b, c] = self.corners(triangle);
+        let (v0, v1, v2) = (b - a, c - a, q - a);
+        let (d00, d01, d11) = (v0.dot(&v0), v0.dot(&v1), v1.dot(&v1));
+        let (d20, d21) = (v2.dot(&v0), v2.dot(&v1));
+        let denom = d00 * d11 - d01 * d01;
+        if denom <= 0. {
+            return self.normal(triangle);
+        }
+        let v = (d11 * d20 - d01 * d21) / denom;
+        let w = (d00 * d21 - d01 * d20) / denom;
+        // barycentric coordinates, zero on the edge opposite to their corner
+        let on = [1. - v - w, v, w].map(|l| l > TOLERANCE);
+        match on {
+            [true, false, false] => self.vertex_normals[indices[0]],
+            [false, true, false] => self.vertex_normals[indices[1]],
+            [false, false, true] => self.vertex_normals[indices[2]],
+            [true, true, false] => self.edge_normal(indices[0], indices[1], triangle),
+            [false, true, true] => self.edge_normal(indices[1], indices[2], triangle),
+            [true, false, true] => self.edge_normal(indices[2], indices[0], triangle),
+            _ => self.normal(triangle),
+        }
+    }
+
+    fn edge_normal(&self, a: usize, b: usize, triangle: usize) -> Vector3<f64> {
+        self.edge_normals
+            .get(&[a.min(b), a.max(b)])
+            .copied()
+            .unwrap_or_else(|| self.normal(triangle))
+    }
+}
+
+impl Reference for TriangleMesh {
+    fn closest(&self, point: &[f64; 3]) -> Option<Vector3<f64>> {
+        Some(self.closest_point(point).0)
+    }
+}
+
+/// Closest point to `p` on the triangle `abc` (Ericson, Real-Time Collision Detection, 5.1.5).
+pub fn closest_point_on_triangle(
+    p: &Vector3<f64>,
+    a: &Vector3<f64>,
+    b: &Vector3<f64>,
+    c: &Vector3<f64>,
+) -> Vector3<f64> {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = p - a;
+    let (d1, d2) = (ab.dot(&ap), ac.dot(&ap));
+    if d1 <= 0. && d2 <= 0. {
+        return *a;
+    }
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(&bp), ac.dot(&bp));
+    if d3 >= 0. && d4 <= d3 {
+        return *b;
+    }
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0. && d1 >= 0. && d3 <= 0. {
+        return a + ab * (d1 / (d1 - d3));
+    }
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(&cp), ac.dot(&cp));
+    if d6 >= 0. && d5 <= d6 {
+        return *c;
+    }
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0. && d2 >= 0. && d6 <= 0. {
+        return a + ac * (d2 / (d2 - d6));
+    }
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0. && d4 - d3 >= 0. && d5 - d6 >= 0. {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+    let denom = 1. / (va + vb + vc);
+    a + ab * (vb * denom) + ac * (vc * denom)
+}
+
+/// Options of [`register_to_mesh`].
+#[derive(Clone, Copy, Debug)]
+pub struct MeshRegistrationOptions {
+    /// Maximum number of iterations.
+    pub max_iterations: usize,
+    /// Convergence is reached when the RMSE decreases by less than this between iterations.
+    pub tolerance: f64,
+    pub estimate_scale: bool,
+}
+
+impl Default for MeshRegistrationOptions {
+    fn default() -> Self {
+        Self {
+            max_iterations: 50,
+            tolerance: 1e-9,
+            estimate_scale: false,
+        }
+    }
+}
+
+/// Result of [`register_to_mesh`].
+#[derive(Clone, Debug)]
+pub struct MeshRegistration {
+    /// Transformation mapping the points onto the mesh.
+    pub transform: Transform<3>,
+    /// Root-mean-square point-to-mesh distance of the matched points before each iteration.
+    pub rmse: Vec<f64>,
+    pub converged: bool,
+}
+
+/// Register a point cloud to a triangle mesh by iterating point-to-triangle closest point queries
+/// and estimations, starting from `initial` (or the identity).
+/// For repeated registrations against the same mesh, a precomputed [`VoxelSdf`] of the mesh can be
+/// given instead, trading accuracy for constant-time closest point queries; points moved outside
+/// its grid are left out of the iteration.
+/// The `None` value is returned if no point has a closest point or an estimation is not
+/// well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::mesh::{register_to_mesh, MeshRegistrationOptions, TriangleMesh};
+///
+/// // unit cube
+/// let vertices = (0..8)
+///     .map(|i| [(i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64])
+///     .collect();
+/// let triangles = vec![
+///     [0, 1, 3], [0, 3, 2], [4, 6, 7], [4, 7, 5], [0, 4, 5], [0, 5, 1],
+///     [2, 3, 7], [2, 7, 6], [0, 2, 6], [0, 6, 4], [1, 5, 7], [1, 7, 3],
+/// ];
+/// let cube = TriangleMesh::new(vertices, triangles).unwrap();
+///
+/// // points sampled on the faces, shifted by (0.05, -0.03, 0.02)
+/// let points = [
+///     [0.55, 0.47, 0.02], [0.3, 0.17, 1.02], [0.05, 0.57, 0.62],
+///     [1.05, 0.27, 0.42], [0.75, -0.03, 0.72], [0.35, 0.97, 0.22],
+/// ];
+/// let options = MeshRegistrationOptions::default();
+/// let registration = register_to_mesh(&points, &cube, None, options).unwrap();
+/// let shift = nalgebra::Vector3::new(-0.05, 0.03, -0.02);
+/// assert!((registration.transform.translation() - shift).norm() < 1e-3);
+/// assert!(registration.rmse.last().unwrap() < &1e-4);
+///
+/// // same registration through a signed distance field sampled every 0.05
+/// let sdf = kabsch_umeyama::mesh::VoxelSdf::from_mesh(&cube, 0.05, 0.2).unwrap();
+/// let registration = register_to_mesh(&points, &sdf, None, options).unwrap();
+/// assert!((registration.transform.translation() - shift).norm() < 1e-2);
+/// ```
+pub fn register_to_mesh(
+    points: &[[f64; 3]],
+    mesh: &impl Reference,
+    initial: Option<&Transform<3>>,
+    options: MeshRegistrationOptions,
+) -> Option<MeshRegistration> {
+    if points.is_empty() {
+        return None;
+    }
+    let src = rows_from_slice(points);
+    let mut transform = initial.cloned().unwrap_or_else(Transform::identity);
+    let mut rmse = Vec::with_capacity(options.max_iterations);
+    let mut converged = false;
+    for _ in 0..options.max_iterations {
+        let mut squared = 0.;
+        let mut weights = DVector::zeros(points.len());
+        let closest = points
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let moved = transform.rotation() * Vector3::from(*p) * transform.scale()
+                    + transform.translation();
+                match mesh.closest(&moved.into()) {
+                    Some(q) => {
+                        squared += (q - moved).norm_squared();
+                        weights[i] = 1.;
+                        q.into()
+                    }
+                    None => moved.into(),
+                }
+            })
+            .collect::<Vec<[f64; 3]>>();
+        let matched = weights.sum();
+        if matched == 0. {
+            return None;
+        }
+        let error = (squared / matched).sqrt();
+        if rmse
+            .last()
+            .is_some_and(|last: &f64| last - error < options.tolerance)
+        {
+            rmse.push(error);
+            converged = true;
+            break;
+        }
+        rmse.push(error);
+        transform = fit_points(
+            src.clone(),
+            rows_from_slice(&closest),
+            &weights,
+            options.estimate_scale,
+            &DefaultSolver,
+        )
+        .ok()?
+        .to_transform();
+    }
+    Some(MeshRegistration {
+        transform,
+        rmse,
+        converged,
+    })
+}
@@ -0,0 +1,104 @@
+//! Quality metrics of an alignment: distances between the transformed source points and the
+//! destination points, and the Procrustes disparity comparing the shapes of two point sets.
+use crate::{fit, DefaultSolver, KabschError, Transform};
+use nalgebra::SMatrix;
+
+/// Distance between each transformed source point and its destination point.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::metrics::residuals;
+/// use kabsch_umeyama::{Array2, Transform};
+/// use nalgebra::{Matrix2, Vector2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+/// let dst = Array2::from([[1., 0.], [2., 0.], [1., 2.]]);
+/// let shift = Transform::new(Matrix2::identity(), Vector2::new(1., 0.), 1.);
+/// assert_eq!(residuals(src, dst, &shift), [0., 0., 1.]);
+/// ```
+pub fn residuals<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    transform: &Transform<C>,
+) -> Vec<f64> {
+    crate::robust::residuals(&src.into(), &dst.into(), transform)
+}
+
+/// Root-mean-square deviation between the transformed source points and the destination points,
+/// the quantity minimized by the estimation.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::metrics::rmsd;
+/// use kabsch_umeyama::{estimate_transform, Array2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+/// let dst = Array2::from([[1., 1.], [1., 2.], [0., 1.], [0., 2.]]);
+/// let t = estimate_transform(src, dst, false).unwrap();
+/// assert!(rmsd(src, dst, &t) < 1e-12);
+///
+/// let noisy = Array2::from([[1., 1.], [1., 2.1], [0., 1.], [0., 2.]]);
+/// let t = estimate_transform(src, noisy, false).unwrap();
+/// // below the RMSD of 0.05 of the exact transformation, which absorbs none of the noise
+/// let error = rmsd(src, noisy, &t);
+/// assert!(0. < error && error < 0.05);
+/// ```
+pub fn rmsd<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    transform: &Transform<C>,
+) -> f64 {
+    let squared = residuals(src, dst, transform)
+        .iter()
+        .map(|d| d * d)
+        .sum::<f64>();
+    (squared / R as f64).sqrt()
+}
+
+/// Procrustes disparity between the shapes of two point sets, as in `scipy.spatial.procrustes`:
+/// both sets are centered and scaled to a unit Frobenius norm, `src` is aligned onto `dst` by a
+/// similarity transformation, and the disparity is the sum of the squared remaining distances.
+///
+/// It lies in `[0, 1]`, is `0` for point sets equal up to a similarity transformation, and is
+/// symmetric in `src` and `dst`. Unlike scipy, reflections are not allowed, so mirror images are
+/// not equal in shape.
+/// The [`KabschError::Empty`] error is returned for empty point sets, [`KabschError::NonFinite`]
+/// for non-finite inputs and [`KabschError::DegenerateConfiguration`] if all the points of a set
+/// coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::metrics::procrustes_disparity;
+/// use kabsch_umeyama::Array2;
+///
+/// let square = Array2::from([[0., 0.], [1., 0.], [1., 1.], [0., 1.]]);
+/// // the same square, rotated, scaled and shifted
+/// let moved = Array2::from([[5., 5.], [5., 7.], [3., 7.], [3., 5.]]);
+/// let kite = Array2::from([[0., 0.], [1., 0.], [2., 2.], [0., 1.]]);
+///
+/// assert!(procrustes_disparity(square, moved).unwrap() < 1e-12);
+/// let disparity = procrustes_disparity(square, kite).unwrap();
+/// assert!(disparity > 0.01);
+/// assert!((disparity - procrustes_disparity(kite, square).unwrap()).abs() < 1e-12);
+/// ```
+pub fn procrustes_disparity<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+) -> Result<f64, KabschError> {
+    if R == 0 || C == 0 {
+        return Err(KabschError::Empty);
+    }
+    let standardize = |points: SMatrix<f64, R, C>| {
+        if points.iter().any(|v| !v.is_finite()) {
+            return Err(KabschError::NonFinite);
+        }
+        let mean = points.row_mean();
+        let centered = SMatrix::<f64, R, C>::from_fn(|i, j| points[(i, j)] - mean[j]);
+        let norm = centered.norm();
+        if norm == 0. {
+            return Err(KabschError::DegenerateConfiguration);
+        }
+        Ok(centered / norm)
+    };
+    let src = standardize(src.into())?;
+    let dst = standardize(dst.into())?;
+    let transform = fit(src, dst, true, &DefaultSolver)?.to_transform();
+    Ok(residuals(src, dst, &transform).iter().map(|d| d * d).sum())
+}
@@ -0,0 +1,66 @@
+//! Interoperability with `ndarray` (requires the `ndarray` feature), e.g. for point sets loaded
+//! from NumPy `.npy` files: conversions between array views and [`Array2`], and estimation
+//! directly from array views.
+use crate::{estimate_dyn, Array2, KabschError};
+use ::ndarray::ArrayView2;
+use nalgebra::DMatrix;
+
+impl<const R: usize, const C: usize> TryFrom<ArrayView2<'_, f64>> for Array2<R, C> {
+    type Error = KabschError;
+
+    /// New Array2 from an `R × C` array view, in any memory layout.
+    fn try_from(view: ArrayView2<'_, f64>) -> Result<Self, Self::Error> {
+        if view.dim() != (R, C) {
+            return Err(KabschError::ShapeMismatch {
+                expected: R * C,
+                actual: view.len(),
+            });
+        }
+        Ok(Self(std::array::from_fn(|i| {
+            std::array::from_fn(|j| view[[i, j]])
+        })))
+    }
+}
+
+impl<const R: usize, const C: usize> From<Array2<R, C>> for ::ndarray::Array2<f64> {
+    fn from(array: Array2<R, C>) -> Self {
+        Self::from_shape_fn((R, C), |(i, j)| array[i][j])
+    }
+}
+
+/// Points of an array view as the rows of a matrix
+fn rows(view: ArrayView2<'_, f64>) -> DMatrix<f64> {
+    DMatrix::from_fn(view.nrows(), view.ncols(), |i, j| view[[i, j]])
+}
+
+/// Estimate a similarity transformation like [`crate::estimate_dyn`] from array views holding one
+/// point per row, whose shapes are only known at runtime, without going through nested arrays.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::ndarray::estimate_view;
+/// use kabsch_umeyama::{Array2, KabschError};
+/// use ndarray::array;
+///
+/// let src = array![[0., 0.], [1., 0.], [0., 1.]];
+/// let dst = array![[1., 1.], [1., 3.], [-1., 1.]];
+/// let t = estimate_view(src.view(), dst.view(), true).unwrap();
+/// assert!((t[(1, 0)] - 2.).abs() < 1e-12);
+///
+/// // transposed views are read in their logical layout
+/// let columns = array![[0., 1., 0.], [0., 0., 1.]];
+/// assert_eq!(estimate_view(columns.t(), dst.view(), true).unwrap(), t);
+///
+/// let points = Array2::<3, 2>::try_from(src.view()).unwrap();
+/// assert_eq!(ndarray::Array2::from(points), src);
+/// assert_eq!(
+///     Array2::<2, 2>::try_from(src.view()).unwrap_err(),
+///     KabschError::ShapeMismatch { expected: 4, actual: 6 }
+/// );
+/// ```
+pub fn estimate_view(
+    src: ArrayView2<'_, f64>,
+    dst: ArrayView2<'_, f64>,
+    estimate_scale: bool,
+) -> Result<DMatrix<f64>, KabschError> {
+    estimate_dyn(&rows(src), &rows(dst), estimate_scale)
+}
@@ -0,0 +1,179 @@
+use crate::anisotropic::scale_information;
+use nalgebra::{DMatrix, DVector, Matrix6, RowVector6, SVector, SymmetricEigen, Vector3, Vector6};
+
+/// Observability of the rigid pose from point-to-plane correspondences.
+///
+/// The information matrix `Σ Jᵢᵀ Jᵢ` of the point-to-plane residuals `nᵢ · (exp(δ) pᵢ - qᵢ)` is
+/// analysed with `δ` a rotation vector about the centroid of the points followed by a translation.
+/// The rotation components are scaled by the root-mean-square distance of the points to their
+/// centroid, so that both blocks are lengths and their eigenvalues are comparable.
+#[derive(Clone, Debug)]
+pub struct Observability {
+    /// Eigenvalues of the information matrix, in increasing order.
+    pub eigenvalues: Vector6<f64>,
+    /// Eigenvectors of the information matrix (columns), in the order of the eigenvalues.
+    pub directions: Matrix6<f64>,
+    /// Number of directions whose eigenvalue is below the threshold relative to the largest one.
+    pub degenerate: usize,
+    /// Ratio of the largest to the smallest eigenvalue.
+    pub condition_number: f64,
+}
+
+impl Observability {
+    /// Whether every direction of the pose is constrained
+    pub fn is_observable(&self) -> bool {
+        self.degenerate == 0
+    }
+
+    /// For each component of the pose (rotation about x, y, z then translation along x, y, z),
+    /// whether it mostly lies in the unobservable subspace and its estimate is unreliable.
+    pub fn unreliable(&self) -> [bool; 6] {
+        let unobservable = self.directions.columns(0, self.degenerate);
+        std::array::from_fn(|k| unobservable.row(k).norm_squared() > 0.5)
+    }
+}
+
+/// Analyse which degrees of freedom of a rigid pose the point-to-plane correspondences constrain,
+/// from the points and the normals of the planes they are matched to. A direction is degenerate
+/// when its eigenvalue is below `threshold` times the largest one.
+/// The `None` value is returned if the lengths differ or there are no points.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::observability;
+///
+/// // corridor: two parallel walls constrain neither the motion along them nor the roll
+/// let mut points = Vec::new();
+/// let mut normals = Vec::new();
+/// for i in 0..50 {
+///     let (y, z) = ((i % 10) as f64, (i / 10) as f64 * 0.5);
+///     points.extend([[-1., y, z], [1., y, z]]);
+///     normals.extend([[1., 0., 0.], [-1., 0., 0.]]);
+/// }
+/// let analysis = observability(&points, &normals, 1e-6).unwrap();
+/// assert!(!analysis.is_observable());
+/// assert_eq!(analysis.unreliable(), [true, false, false, false, true, true]);
+/// ```
+pub fn observability(
+    points: &[[f64; 3]],
+    normals: &[[f64; 3]],
+    threshold: f64,
+) -> Option<Observability> {
+    if points.is_empty() || points.len() != normals.len() {
+        return None;
+    }
+    let centroid = points
+        .iter()
+        .map(|p| Vector3::from(*p))
+        .sum::<Vector3<f64>>()
+        / points.len() as f64;
+    let radius = (points
+        .iter()
+        .map(|p| (Vector3::from(*p) - centroid).norm_squared())
+        .sum::<f64>()
+        / points.len() as f64)
+        .sqrt()
+        .max(f64::MIN_POSITIVE);
+    let information = points
+        .iter()
+        .zip(normals)
+        .map(|(p, n)| {
+            let n = Vector3::from(*n);
+            let lever = (Vector3::from(*p) - centroid).cross(&n) / radius;
+            let row = RowVector6::new(lever.x, lever.y, lever.z, n.x, n.y, n.z);
+            row.transpose() * row
+        })
+        .sum::<Matrix6<f64>>();
+
+    let eigen = SymmetricEigen::new(information);
+    let mut order = [0, 1, 2, 3, 4, 5];
+    order.sort_by(|a, b| eigen.eigenvalues[*a].total_cmp(&eigen.eigenvalues[*b]));
+    let eigenvalues = Vector6::from_fn(|i, _| eigen.eigenvalues[order[i]].max(0.));
+    let directions = Matrix6::from_fn(|i, j| eigen.eigenvectors[(i, order[j])]);
+    let largest = eigenvalues[5];
+    Some(Observability {
+        degenerate: eigenvalues
+            .iter()
+            .filter(|l| **l <= threshold * largest)
+            .count(),
+        condition_number: largest / eigenvalues[0],
+        eigenvalues,
+        directions,
+    })
+}
+
+/// Recoverability of per-axis scales `x ↦ R diag(s) x + t` from a configuration of source points.
+///
+/// The information matrix of the residuals is analysed at the identity with respect to the
+/// `C(C-1)/2` rotation generators followed by the `C` axis scales, the translation being
+/// eliminated by centering the points. Both blocks are lengths, so their eigenvalues are
+/// comparable.
+#[derive(Clone, Debug)]
+pub struct ScaleRecoverability {
+    /// Eigenvalues of the information matrix, in increasing order.
+    pub eigenvalues: DVector<f64>,
+    /// Eigenvectors of the information matrix (columns), in the order of the eigenvalues.
+    pub directions: DMatrix<f64>,
+    /// Number of directions whose eigenvalue is below the threshold relative to the largest one.
+    pub degenerate: usize,
+    /// Ratio of the largest to the smallest eigenvalue.
+    pub condition_number: f64,
+}
+
+impl ScaleRecoverability {
+    /// Whether the rotation and every axis scale are constrained, i.e. the anisotropic model is
+    /// identifiable from these points
+    pub fn is_identifiable(&self) -> bool {
+        self.degenerate == 0
+    }
+}
+
+/// Test whether a configuration of source points constrains per-axis scales on top of the
+/// rotation, before fitting an anisotropic model that would otherwise trade the scales against the
+/// rotation arbitrarily. A direction is degenerate when its eigenvalue is below `threshold` times
+/// the largest one.
+/// The `None` value is returned if there are no points.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::anisotropic_scale_recoverability;
+///
+/// let spread = [[0., 0.], [2., 0.], [0., 1.], [2., 1.]];
+/// assert!(anisotropic_scale_recoverability(&spread, 1e-9).unwrap().is_identifiable());
+///
+/// // along a diagonal, stretching x and shrinking y is a rotation
+/// let diagonal = [[0., 0.], [1., 1.], [2., 2.]];
+/// let analysis = anisotropic_scale_recoverability(&diagonal, 1e-9).unwrap();
+/// assert!(!analysis.is_identifiable());
+/// assert_eq!(analysis.degenerate, 1);
+/// ```
+pub fn anisotropic_scale_recoverability<const C: usize>(
+    points: &[[f64; C]],
+    threshold: f64,
+) -> Option<ScaleRecoverability> {
+    if points.is_empty() {
+        return None;
+    }
+    let centroid = points
+        .iter()
+        .map(|p| SVector::from(*p))
+        .sum::<SVector<f64, C>>()
+        / points.len() as f64;
+    let centered = points.iter().map(|p| (SVector::from(*p) - centroid, 1.));
+    let information = scale_information(centered);
+    let k = information.nrows();
+
+    let eigen = SymmetricEigen::new(information);
+    let mut order = (0..k).collect::<Vec<_>>();
+    order.sort_by(|a, b| eigen.eigenvalues[*a].total_cmp(&eigen.eigenvalues[*b]));
+    let eigenvalues = DVector::from_fn(k, |i, _| eigen.eigenvalues[order[i]].max(0.));
+    let directions = DMatrix::from_fn(k, k, |i, j| eigen.eigenvectors[(i, order[j])]);
+    let largest = eigenvalues[k - 1];
+    Some(ScaleRecoverability {
+        degenerate: eigenvalues
+            .iter()
+            .filter(|l| **l <= threshold * largest)
+            .count(),
+        condition_number: largest / eigenvalues[0],
+        eigenvalues,
+        directions,
+    })
+}
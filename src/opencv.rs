@@ -0,0 +1,214 @@
+//! Wrapper with the semantics of OpenCV's `cv::estimateAffinePartial2D`, for code translated from
+//! C++ or Python vision pipelines: the same parameters with the same meanings and defaults, a
+//! `2x3` matrix and an inlier mask of one byte per correspondence.
+//!
+//! The minimal subsets are drawn from this crate's seeded generator rather than OpenCV's, so the
+//! consensus set may differ on ambiguous data, and the Levenberg-Marquardt refinement of the
+//! inliers is replaced by its converged value: the reprojection error of a partial affine model
+//! is linear in its 4 parameters, so its minimum is the least-squares similarity of
+//! [`crate::estimate`].
+use crate::{fit_points, rows_from_slice, DefaultSolver, Points, Rng};
+use nalgebra::{DVector, Matrix2x3, Vector2};
+
+/// Points of a minimal subset of a partial affine model.
+const MODEL_POINTS: usize = 2;
+/// Outlier ratio assumed by LMedS to select its number of iterations, as in OpenCV.
+const LMEDS_OUTLIER_RATIO: f64 = 0.45;
+
+/// Robust method of [`estimate_affine_partial_2d`], like `cv::RANSAC` and `cv::LMEDS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Method {
+    /// Random sample consensus, counting the correspondences within the reprojection threshold.
+    #[default]
+    Ransac,
+    /// Least median of squares, which needs no threshold but at least half of the correspondences
+    /// to be inliers.
+    Lmeds,
+}
+
+/// Parameters of [`estimate_affine_partial_2d`], with the defaults of OpenCV.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PartialAffineParams {
+    pub method: Method,
+    /// Largest reprojection error, in pixels, of an inlier of RANSAC, `3` by default.
+    pub ransac_reproj_threshold: f64,
+    /// Largest number of subsets sampled, `2000` by default.
+    pub max_iters: usize,
+    /// Confidence that a subset of inliers was sampled when the iterations stop early, `0.99` by
+    /// default.
+    pub confidence: f64,
+    /// Iterations refining the estimate on the inliers, none if `0`, `10` by default.
+    pub refine_iters: usize,
+    /// Seed of the sampling, so that the estimate is reproducible.
+    pub seed: u64,
+}
+
+impl Default for PartialAffineParams {
+    fn default() -> Self {
+        Self {
+            method: Method::Ransac,
+            ransac_reproj_threshold: 3.,
+            max_iters: 2000,
+            confidence: 0.99,
+            refine_iters: 10,
+            seed: 0,
+        }
+    }
+}
+
+/// Estimate the optimal limited affine transformation with 4 degrees of freedom (rotation, uniform
+/// scale and translation) between two 2D point sets, like `cv::estimateAffinePartial2D`.
+///
+/// The `2x3` matrix `[[a, -b, tx], [b, a, ty]]` is returned with the inlier mask, `1` for each
+/// inlier and `0` for each outlier, of the best subset before the refinement. Like the empty
+/// matrix of OpenCV, the `None` value is returned if the point sets have different lengths, fewer
+/// than 2 points or non-finite coordinates, or if no subset has any inlier.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::opencv::{estimate_affine_partial_2d, PartialAffineParams};
+///
+/// let from = (0..20).map(|i| [i as f64, (i * i % 11) as f64]).collect::<Vec<_>>();
+/// // rotated by a quarter turn, scaled by 2 and shifted, with two mismatches
+/// let mut to = from.iter().map(|[x, y]| [5. - 2. * y, 2. * x - 1.]).collect::<Vec<_>>();
+/// to[4] = [100., 100.];
+/// to[9] = [-50., 30.];
+///
+/// let params = PartialAffineParams::default();
+/// let (m, inliers) = estimate_affine_partial_2d(&from, &to, &params).unwrap();
+/// assert_eq!(inliers.iter().filter(|v| **v == 1).count(), 18);
+/// assert_eq!((inliers[4], inliers[9]), (0, 0));
+/// let expected = nalgebra::Matrix2x3::new(0., -2., 5., 2., 0., -1.);
+/// assert!((m - expected).abs().max() < 1e-9);
+/// ```
+pub fn estimate_affine_partial_2d(
+    from: &[[f64; 2]],
+    to: &[[f64; 2]],
+    params: &PartialAffineParams,
+) -> Option<(Matrix2x3<f64>, Vec<u8>)> {
+    let count = from.len();
+    let finite = from.iter().chain(to).flatten().all(|v| v.is_finite());
+    if to.len() != count || count < MODEL_POINTS || !finite {
+        return None;
+    }
+    let (src, dst) = (rows_from_slice(from), rows_from_slice(to));
+    let (model, mask) = match params.method {
+        Method::Ransac => ransac(&src, &dst, params)?,
+        Method::Lmeds => lmeds(&src, &dst, params)?,
+    };
+    if params.refine_iters == 0 {
+        return Some((model, mask));
+    }
+    let weights = DVector::from_fn(count, |i, _| f64::from(mask[i]));
+    let refined = fit(&src, &dst, &weights).unwrap_or(model);
+    Some((refined, mask))
+}
+
+/// Best model of RANSAC and its inlier mask
+fn ransac(
+    src: &Points<2>,
+    dst: &Points<2>,
+    params: &PartialAffineParams,
+) -> Option<(Matrix2x3<f64>, Vec<u8>)> {
+    let count = src.nrows();
+    let threshold = params.ransac_reproj_threshold * params.ransac_reproj_threshold;
+    let mut rng = Rng::new(params.seed);
+    let mut best: Option<(usize, Matrix2x3<f64>, Vec<u8>)> = None;
+    let mut niters = params.max_iters;
+    let mut iter = 0;
+    while iter < niters {
+        iter += 1;
+        let Some(model) = sample_model(src, dst, &mut rng) else {
+            continue;
+        };
+        let mask = errors(src, dst, &model)
+            .map(|e| u8::from(e <= threshold))
+            .collect::<Vec<_>>();
+        let good = mask.iter().filter(|v| **v == 1).count();
+        let max_good = best.as_ref().map_or(0, |(good, _, _)| *good);
+        if good > max_good.max(MODEL_POINTS - 1) {
+            let outlier_ratio = (count - good) as f64 / count as f64;
+            niters = update_num_iters(params.confidence, outlier_ratio, niters);
+            best = Some((good, model, mask));
+        }
+    }
+    best.map(|(_, model, mask)| (model, mask))
+}
+
+/// Model of LMedS with the least median squared error, and the mask of its inliers within the
+/// robust standard deviation of OpenCV
+fn lmeds(
+    src: &Points<2>,
+    dst: &Points<2>,
+    params: &PartialAffineParams,
+) -> Option<(Matrix2x3<f64>, Vec<u8>)> {
+    let count = src.nrows();
+    let mut rng = Rng::new(params.seed);
+    let niters = update_num_iters(params.confidence, LMEDS_OUTLIER_RATIO, params.max_iters);
+    let mut best: Option<(f64, Matrix2x3<f64>)> = None;
+    for _ in 0..niters {
+        let Some(model) = sample_model(src, dst, &mut rng) else {
+            continue;
+        };
+        let mut err = errors(src, dst, &model).collect::<Vec<_>>();
+        let median_index = count / 2;
+        let (_, median, _) = err.select_nth_unstable_by(median_index, f64::total_cmp);
+        let median = *median;
+        if best.as_ref().map_or(true, |(m, _)| median < *m) {
+            best = Some((median, model));
+        }
+    }
+    let (median, model) = best?;
+    let sigma = 2.5 * 1.4826 * (1. + 5. / (count - MODEL_POINTS).max(1) as f64) * median.sqrt();
+    let threshold = sigma.max(0.001).powi(2);
+    let mask = errors(src, dst, &model)
+        .map(|e| u8::from(e <= threshold))
+        .collect();
+    Some((model, mask))
+}
+
+/// Model of a random minimal subset, `None` if its points coincide
+fn sample_model(src: &Points<2>, dst: &Points<2>, rng: &mut Rng) -> Option<Matrix2x3<f64>> {
+    let count = src.nrows();
+    let i = (rng.next_u64() % count as u64) as usize;
+    let j = (i + 1 + (rng.next_u64() % (count - 1) as u64) as usize) % count;
+    let pick =
+        |points: &Points<2>| Points::<2>::from_fn(MODEL_POINTS, |k, c| points[([i, j][k], c)]);
+    fit(&pick(src), &pick(dst), &DVector::repeat(MODEL_POINTS, 1.))
+}
+
+/// Least-squares partial affine model of the weighted correspondences
+fn fit(src: &Points<2>, dst: &Points<2>, weights: &DVector<f64>) -> Option<Matrix2x3<f64>> {
+    let fit = fit_points(src.clone(), dst.clone(), weights, true, &DefaultSolver).ok()?;
+    let model = Matrix2x3::from_fn(|i, j| fit.transform[(i, j)]);
+    model.iter().all(|v| v.is_finite()).then_some(model)
+}
+
+/// Squared reprojection error of each correspondence
+fn errors<'a>(
+    src: &'a Points<2>,
+    dst: &'a Points<2>,
+    model: &'a Matrix2x3<f64>,
+) -> impl Iterator<Item = f64> + 'a {
+    (0..src.nrows()).map(move |i| {
+        let p = Vector2::new(src[(i, 0)], src[(i, 1)]);
+        let q = Vector2::new(dst[(i, 0)], dst[(i, 1)]);
+        (model.fixed_view::<2, 2>(0, 0) * p + model.column(2) - q).norm_squared()
+    })
+}
+
+/// Number of iterations after which a subset of inliers was sampled with the given confidence,
+/// like `cv::RANSACUpdateNumIters`
+fn update_num_iters(confidence: f64, outlier_ratio: f64, max_iters: usize) -> usize {
+    let outlier_ratio = outlier_ratio.clamp(0., 1.);
+    let num = (1. - confidence).max(f64::MIN_POSITIVE);
+    let denom = 1. - (1. - outlier_ratio).powi(MODEL_POINTS as i32);
+    if denom < f64::MIN_POSITIVE {
+        return 0;
+    }
+    let (num, denom) = (num.ln(), denom.ln());
+    if denom >= 0. || -num >= max_iters as f64 * -denom {
+        max_iters
+    } else {
+        (num / denom).round() as usize
+    }
+}
@@ -0,0 +1,102 @@
+//! SVG plots of evaluation results, rendered with `plotters` (requires the `plot` feature).
+use plotters::prelude::*;
+use std::error::Error;
+use std::ops::Range;
+
+const SIZE: (u32, u32) = (800, 480);
+
+/// Range of the values, padded by 5% so that the series do not touch the frame.
+fn padded(values: impl Iterator<Item = f64>) -> Range<f64> {
+    let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+        (min.min(v), max.max(v))
+    });
+    if !min.is_finite() {
+        return 0.0..1.0;
+    }
+    let margin = ((max - min) * 0.05).max(1e-9);
+    min - margin..max + margin
+}
+
+/// Line plot of an error series, e.g. the absolute trajectory error over time or the segment
+/// errors of a [`crate::trajectory::DriftAnalysis`] over the distance traveled.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::plot::error_svg;
+///
+/// let errors = [(0., 0.1), (1., 0.15), (2., 0.12)];
+/// let svg = error_svg("Absolute trajectory error", "time [s]", "error [m]", &errors).unwrap();
+/// assert!(svg.starts_with("<svg"));
+/// ```
+pub fn error_svg(
+    title: &str,
+    x_label: &str,
+    y_label: &str,
+    series: &[(f64, f64)],
+) -> Result<String, Box<dyn Error>> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 20))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(
+                padded(series.iter().map(|p| p.0)),
+                padded(series.iter().map(|p| p.1).chain([0.])),
+            )?;
+        chart
+            .configure_mesh()
+            .x_desc(x_label)
+            .y_desc(y_label)
+            .draw()?;
+        chart.draw_series(LineSeries::new(series.iter().copied(), &RED))?;
+        root.present()?;
+    }
+    Ok(svg)
+}
+
+/// Top view (x, y) of an aligned estimated trajectory over its reference.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::plot::trajectory_svg;
+///
+/// let reference = [[0., 0., 0.], [1., 0., 0.], [2., 1., 0.]];
+/// let estimated = [[0., 0.1, 0.], [1., -0.1, 0.], [2.1, 1., 0.]];
+/// assert!(trajectory_svg(&estimated, &reference).unwrap().contains("reference"));
+/// ```
+pub fn trajectory_svg(
+    estimated: &[[f64; 3]],
+    reference: &[[f64; 3]],
+) -> Result<String, Box<dyn Error>> {
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+        let all = || estimated.iter().chain(reference);
+        let mut chart = ChartBuilder::on(&root)
+            .caption("Trajectory", ("sans-serif", 20))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(padded(all().map(|p| p[0])), padded(all().map(|p| p[1])))?;
+        chart.configure_mesh().x_desc("x").y_desc("y").draw()?;
+        for (name, points, color) in [
+            ("reference", reference, BLACK),
+            ("estimated", estimated, BLUE),
+        ] {
+            chart
+                .draw_series(LineSeries::new(points.iter().map(|p| (p[0], p[1])), &color))?
+                .label(name)
+                .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        }
+        chart
+            .configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root.present()?;
+    }
+    Ok(svg)
+}
@@ -0,0 +1,313 @@
+//! Binary point files for huge datasets: a fixed header followed by packed little-endian `f32` or
+//! `f64` coordinates, so that a file can be memory-mapped and read in place, or streamed in
+//! batches, without any parsing.
+//!
+//! The header is 32 bytes long, keeping the coordinates aligned for both scalar types:
+//!
+//! | offset | size | content                                                   |
+//! |--------|------|-----------------------------------------------------------|
+//! | 0      | 8    | magic `KUPOINTS`                                          |
+//! | 8      | 2    | version of the layout, `1`                                |
+//! | 10     | 1    | bytes per coordinate, `4` for `f32` or `8` for `f64`      |
+//! | 11     | 1    | dimensions of the points                                  |
+//! | 12     | 1    | `1` if each record holds a source and a destination point |
+//! | 13     | 3    | reserved, zero                                            |
+//! | 16     | 8    | number of records                                         |
+//! | 24     | 8    | reserved, zero                                            |
+//!
+//! Each record then holds the coordinates of a point, followed by those of its destination point
+//! for files of correspondences. All the integers are little-endian.
+use std::io::{self, Read, Write};
+
+/// First bytes of a point file.
+pub const MAGIC: &[u8; 8] = b"KUPOINTS";
+
+/// Version of the layout written by this crate.
+pub const VERSION: u16 = 1;
+
+/// Scalar type of the coordinates of a point file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    F64,
+}
+
+impl Precision {
+    /// Bytes per coordinate
+    pub const fn size(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    fn decode(self, bytes: &[u8]) -> f64 {
+        match self {
+            Self::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Self::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        }
+    }
+}
+
+/// Header of a point file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PointHeader {
+    pub precision: Precision,
+    pub dimensions: usize,
+    /// Whether each record holds a source and a destination point.
+    pub paired: bool,
+    /// Number of records.
+    pub count: u64,
+}
+
+/// Point of a record, and its destination point in a file of correspondences.
+pub type PointRecord<const C: usize> = ([f64; C], Option<[f64; C]>);
+
+impl PointHeader {
+    /// Size of the header in bytes.
+    pub const SIZE: usize = 32;
+
+    /// Size of a record in bytes
+    pub fn record_size(&self) -> usize {
+        self.dimensions * self.precision.size() * if self.paired { 2 } else { 1 }
+    }
+
+    /// Header as written at the start of a file
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[..8].copy_from_slice(MAGIC);
+        bytes[8..10].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[10] = self.precision.size() as u8;
+        bytes[11] = self.dimensions as u8;
+        bytes[12] = self.paired as u8;
+        bytes[16..24].copy_from_slice(&self.count.to_le_bytes());
+        bytes
+    }
+
+    /// Parse the header at the start of `bytes`.
+    /// An [`io::ErrorKind::InvalidData`] error is returned if they do not start with a header of a
+    /// supported version.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < Self::SIZE || &bytes[..8] != MAGIC {
+            return Err(invalid("not a point file"));
+        }
+        if u16::from_le_bytes([bytes[8], bytes[9]]) != VERSION {
+            return Err(invalid("unsupported point file version"));
+        }
+        let precision = match bytes[10] {
+            4 => Precision::F32,
+            8 => Precision::F64,
+            _ => return Err(invalid("unsupported coordinate size")),
+        };
+        let paired = match bytes[12] {
+            0 => false,
+            1 => true,
+            _ => return Err(invalid("invalid pairing flag")),
+        };
+        Ok(Self {
+            precision,
+            dimensions: bytes[11] as usize,
+            paired,
+            count: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        })
+    }
+
+    fn check_dimensions<const C: usize>(&self) -> io::Result<()> {
+        if self.dimensions != C || C == 0 {
+            return Err(invalid("points of other dimensions"));
+        }
+        Ok(())
+    }
+
+    fn decode<const C: usize>(&self, record: &[u8]) -> PointRecord<C> {
+        let size = self.precision.size();
+        let coordinate = |i: usize| self.precision.decode(&record[i * size..(i + 1) * size]);
+        let point = std::array::from_fn(coordinate);
+        let destination = self
+            .paired
+            .then(|| std::array::from_fn(|j| coordinate(C + j)));
+        (point, destination)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Write `src` as a point file, with the corresponding `dst` points if any, the coordinates being
+/// rounded to `f32` for [`Precision::F32`].
+/// An [`io::ErrorKind::InvalidInput`] error is returned if `dst` has another number of points, or
+/// if there are more than 255 dimensions.
+pub fn write_points<const C: usize>(
+    mut writer: impl Write,
+    precision: Precision,
+    src: &[[f64; C]],
+    dst: Option<&[[f64; C]]>,
+) -> io::Result<()> {
+    if dst.is_some_and(|dst| dst.len() != src.len()) || C > u8::MAX as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "mismatched point sets",
+        ));
+    }
+    let header = PointHeader {
+        precision,
+        dimensions: C,
+        paired: dst.is_some(),
+        count: src.len() as u64,
+    };
+    writer.write_all(&header.to_bytes())?;
+    let mut record = Vec::with_capacity(header.record_size());
+    for (i, p) in src.iter().enumerate() {
+        record.clear();
+        for v in p.iter().chain(dst.iter().flat_map(|dst| &dst[i])) {
+            match precision {
+                Precision::F32 => record.extend_from_slice(&(*v as f32).to_le_bytes()),
+                Precision::F64 => record.extend_from_slice(&v.to_le_bytes()),
+            }
+        }
+        writer.write_all(&record)?;
+    }
+    writer.flush()
+}
+
+/// Point file read in place from its bytes, e.g. a memory-mapped file.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::pointfile::{write_points, PointView, Precision};
+///
+/// let src = [[0., 0.], [1., 0.], [0., 1.]];
+/// let dst = [[1., 1.], [1., 3.], [-1., 1.]];
+/// let mut bytes = Vec::new();
+/// write_points(&mut bytes, Precision::F32, &src, Some(&dst)).unwrap();
+/// assert_eq!(bytes.len(), 32 + 3 * 4 * 4);
+///
+/// let view = PointView::<2>::new(&bytes).unwrap();
+/// assert_eq!(view.len(), 3);
+/// assert_eq!(view.get(1), Some(([1., 0.], Some([1., 3.]))));
+/// let (s, d): (Vec<_>, Vec<_>) = view.iter().map(|(p, q)| (p, q.unwrap())).unzip();
+/// assert_eq!((&s[..], &d[..]), (&src[..], &dst[..]));
+///
+/// assert!(PointView::<3>::new(&bytes).is_err());
+/// assert!(PointView::<2>::new(&bytes[..40]).is_err());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PointView<'a, const C: usize> {
+    header: PointHeader,
+    records: &'a [u8],
+}
+
+impl<'a, const C: usize> PointView<'a, C> {
+    /// New PointView from the bytes of a point file of `C`-dimensional points.
+    /// An [`io::ErrorKind::InvalidData`] error is returned for an invalid header, other dimensions
+    /// or a truncated file.
+    pub fn new(bytes: &'a [u8]) -> io::Result<Self> {
+        let header = PointHeader::from_bytes(bytes)?;
+        header.check_dimensions::<C>()?;
+        let size = usize::try_from(header.count)
+            .ok()
+            .and_then(|count| count.checked_mul(header.record_size()))
+            .filter(|size| *size <= bytes.len() - PointHeader::SIZE)
+            .ok_or_else(|| invalid("truncated point file"))?;
+        Ok(Self {
+            header,
+            records: &bytes[PointHeader::SIZE..PointHeader::SIZE + size],
+        })
+    }
+
+    /// Header of the file
+    pub fn header(&self) -> &PointHeader {
+        &self.header
+    }
+
+    /// Number of records
+    pub fn len(&self) -> usize {
+        self.header.count as usize
+    }
+
+    /// Whether the file has no record
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Record at `index`, the `None` value if out of bounds
+    pub fn get(&self, index: usize) -> Option<PointRecord<C>> {
+        let size = self.header.record_size();
+        let record = self.records.get(index * size..(index + 1) * size)?;
+        Some(self.header.decode(record))
+    }
+
+    /// Iterator over the records
+    pub fn iter(&self) -> impl Iterator<Item = PointRecord<C>> + '_ {
+        (0..self.len()).filter_map(|i| self.get(i))
+    }
+}
+
+/// Point file streamed in batches from a reader, holding a single batch in memory.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::pointfile::{write_points, PointReader, Precision};
+///
+/// let src = (0..10).map(|i| [i as f64, 0., 1.]).collect::<Vec<_>>();
+/// let mut bytes = Vec::new();
+/// write_points(&mut bytes, Precision::F64, &src, None).unwrap();
+///
+/// let mut reader = PointReader::<_, 3>::new(bytes.as_slice()).unwrap();
+/// assert_eq!(reader.header().count, 10);
+/// let mut sizes = Vec::new();
+/// while let Some(batch) = reader.read_batch(4).unwrap() {
+///     assert!(batch.iter().all(|(_, destination)| destination.is_none()));
+///     sizes.push(batch.len());
+/// }
+/// assert_eq!(sizes, [4, 4, 2]);
+/// ```
+#[derive(Debug)]
+pub struct PointReader<R, const C: usize> {
+    reader: R,
+    header: PointHeader,
+    remaining: u64,
+    buffer: Vec<u8>,
+}
+
+impl<R: Read, const C: usize> PointReader<R, C> {
+    /// New PointReader reading the header of a point file of `C`-dimensional points.
+    /// An [`io::ErrorKind::InvalidData`] error is returned for an invalid header or other
+    /// dimensions.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut bytes = [0; PointHeader::SIZE];
+        reader.read_exact(&mut bytes)?;
+        let header = PointHeader::from_bytes(&bytes)?;
+        header.check_dimensions::<C>()?;
+        Ok(Self {
+            reader,
+            header,
+            remaining: header.count,
+            buffer: Vec::new(),
+        })
+    }
+
+    /// Header of the file
+    pub fn header(&self) -> &PointHeader {
+        &self.header
+    }
+
+    /// Read the next batch of at most `max_records` records, the `None` value once all have been
+    /// read.
+    /// An [`io::ErrorKind::UnexpectedEof`] error is returned if the file is truncated.
+    pub fn read_batch(&mut self, max_records: usize) -> io::Result<Option<Vec<PointRecord<C>>>> {
+        let count = self.remaining.min(max_records.max(1) as u64) as usize;
+        if count == 0 {
+            return Ok(None);
+        }
+        let size = self.header.record_size();
+        self.buffer.resize(count * size, 0);
+        self.reader.read_exact(&mut self.buffer)?;
+        self.remaining -= count as u64;
+        Ok(Some(
+            self.buffer
+                .chunks_exact(size)
+                .map(|record| self.header.decode(record))
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,199 @@
+use crate::{KabschError, Transform};
+use nalgebra::{DMatrix, DVector, Matrix3, Rotation3, Vector3, Vector6};
+
+/// Relative pose constraint between two nodes of a [`PoseGraph`].
+#[derive(Clone, Debug)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    /// Pose of node `to` in the frame of node `from`.
+    pub measurement: Transform<3>,
+    pub weight: f64,
+}
+
+/// Graph of rigid poses linked by relative pose measurements, optimized by Gauss-Newton with the
+/// first pose held fixed.
+#[derive(Clone, Debug, Default)]
+pub struct PoseGraph {
+    poses: Vec<Transform<3>>,
+    edges: Vec<Edge>,
+}
+
+/// Rigid pose as a rotation and a translation.
+type Pose = (Matrix3<f64>, Vector3<f64>);
+
+fn pose(t: &Transform<3>) -> Pose {
+    (*t.rotation(), *t.translation())
+}
+
+/// Residual `log(Z⁻¹ Xᵢ⁻¹ Xⱼ)` as a rotation vector followed by a translation.
+fn residual(from: &Pose, to: &Pose, measurement: &Pose) -> Vector6<f64> {
+    let relative = (
+        from.0.transpose() * to.0,
+        from.0.transpose() * (to.1 - from.1),
+    );
+    let rotation = measurement.0.transpose() * relative.0;
+    let translation = measurement.0.transpose() * (relative.1 - measurement.1);
+    let omega = Rotation3::from_matrix_unchecked(rotation).scaled_axis();
+    Vector6::new(
+        omega.x,
+        omega.y,
+        omega.z,
+        translation.x,
+        translation.y,
+        translation.z,
+    )
+}
+
+/// Left perturbation `exp(δ) X`, with `δ` a rotation vector followed by a translation.
+fn perturb(x: &Pose, delta: &[f64]) -> Pose {
+    let rotation = Rotation3::new(Vector3::new(delta[0], delta[1], delta[2])).into_inner();
+    (
+        rotation * x.0,
+        rotation * x.1 + Vector3::new(delta[3], delta[4], delta[5]),
+    )
+}
+
+impl PoseGraph {
+    /// New empty PoseGraph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a node with its initial pose, returning its index
+    pub fn add_node(&mut self, pose: Transform<3>) -> usize {
+        self.poses.push(pose);
+        self.poses.len() - 1
+    }
+
+    /// Add a relative pose constraint.
+    /// A [`KabschError::IndexOutOfBounds`] error is returned, and the edge ignored, if `from` or
+    /// `to` is not the index of a node.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::mapping::{Edge, PoseGraph};
+    /// use kabsch_umeyama::{KabschError, Transform};
+    ///
+    /// let mut graph = PoseGraph::new();
+    /// graph.add_node(Transform::identity());
+    /// let edge = Edge { from: 0, to: 1, measurement: Transform::identity(), weight: 1. };
+    /// assert_eq!(graph.add_edge(edge), Err(KabschError::IndexOutOfBounds { index: 1, len: 1 }));
+    /// assert!(graph.edges().is_empty());
+    /// ```
+    pub fn add_edge(&mut self, edge: Edge) -> Result<(), KabschError> {
+        let len = self.poses.len();
+        if let Some(index) = [edge.from, edge.to].into_iter().find(|i| *i >= len) {
+            return Err(KabschError::IndexOutOfBounds { index, len });
+        }
+        self.edges.push(edge);
+        Ok(())
+    }
+
+    /// Poses of the nodes
+    pub fn poses(&self) -> &[Transform<3>] {
+        &self.poses
+    }
+
+    /// Edges of the graph
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Weighted sum of the squared residuals of the edges
+    pub fn error(&self) -> f64 {
+        let poses = self.poses.iter().map(pose).collect::<Vec<_>>();
+        self.edges
+            .iter()
+            .map(|e| {
+                e.weight
+                    * residual(&poses[e.from], &poses[e.to], &pose(&e.measurement)).norm_squared()
+            })
+            .sum()
+    }
+
+    /// Run up to `iterations` Gauss-Newton steps, returning the final error.
+    ///
+    /// Each step differentiates the residuals numerically and solves the dense `6(n-1)×6(n-1)`
+    /// normal equations of the `n` poses by Cholesky, in `O(n²)` memory and `O(n³)` time: this is
+    /// meant for graphs of keyframes, up to a few hundred poses, not for every scan of a long
+    /// trajectory.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::mapping::{Edge, PoseGraph};
+    /// use kabsch_umeyama::Transform;
+    /// use nalgebra::{Matrix3, Vector3};
+    ///
+    /// let step = |x: f64, y: f64| Transform::new(Matrix3::identity(), Vector3::new(x, y, 0.), 1.);
+    ///
+    /// // odometry around a unit square drifting along x, closed by a loop edge back to the start
+    /// let mut graph = PoseGraph::new();
+    /// let corners = [(0., 0.), (1.1, 0.), (1.1, 1.), (0.1, 1.), (0.1, 0.)];
+    /// for (x, y) in corners {
+    ///     graph.add_node(step(x, y));
+    /// }
+    /// for (i, (dx, dy)) in [(1.1, 0.), (0., 1.), (-1., 0.), (0., -1.)].into_iter().enumerate() {
+    ///     let edge = Edge { from: i, to: i + 1, measurement: step(dx, dy), weight: 1. };
+    ///     graph.add_edge(edge).unwrap();
+    /// }
+    /// let edge = Edge { from: 4, to: 0, measurement: step(0., 0.), weight: 10. };
+    /// graph.add_edge(edge).unwrap();
+    ///
+    /// graph.optimize(10);
+    /// assert!(graph.poses()[4].translation().norm() < 0.05);
+    /// ```
+    pub fn optimize(&mut self, iterations: usize) -> f64 {
+        const STEP: f64 = 1e-6;
+        let n = self.poses.len();
+        for _ in 0..iterations {
+            if n < 2 {
+                break;
+            }
+            let poses = self.poses.iter().map(pose).collect::<Vec<_>>();
+            let mut h = DMatrix::<f64>::zeros(6 * n, 6 * n);
+            let mut g = DVector::<f64>::zeros(6 * n);
+            for e in &self.edges {
+                let z = pose(&e.measurement);
+                let r = residual(&poses[e.from], &poses[e.to], &z);
+                let mut jacobian = DMatrix::<f64>::zeros(6, 12);
+                for k in 0..6 {
+                    let mut delta = [0.; 6];
+                    delta[k] = STEP;
+                    let from = perturb(&poses[e.from], &delta);
+                    let to = perturb(&poses[e.to], &delta);
+                    jacobian
+                        .column_mut(k)
+                        .copy_from(&((residual(&from, &poses[e.to], &z) - r) / STEP));
+                    jacobian
+                        .column_mut(6 + k)
+                        .copy_from(&((residual(&poses[e.from], &to, &z) - r) / STEP));
+                }
+                let blocks = [(e.from, 0), (e.to, 6)];
+                for (a, ja) in blocks {
+                    let ja = jacobian.columns(ja, 6);
+                    g.rows_mut(6 * a, 6)
+                        .axpy(e.weight, &(ja.transpose() * r), 1.);
+                    for (b, jb) in blocks {
+                        let jb = jacobian.columns(jb, 6);
+                        let mut block = h.view_mut((6 * a, 6 * b), (6, 6));
+                        block += ja.transpose() * jb * e.weight;
+                    }
+                }
+            }
+            // gauge freedom: the first pose is held fixed
+            let h = h.view((6, 6), (6 * (n - 1), 6 * (n - 1))).into_owned();
+            let g = g.rows(6, 6 * (n - 1)).into_owned();
+            let Some(cholesky) = h.cholesky() else {
+                break;
+            };
+            let delta = -cholesky.solve(&g);
+            for (i, t) in self.poses.iter_mut().enumerate().skip(1) {
+                let (rotation, translation) = perturb(&pose(t), &delta.as_slice()[6 * (i - 1)..]);
+                *t = Transform::new(rotation, translation, 1.);
+            }
+            if delta.norm() < 1e-10 {
+                break;
+            }
+        }
+        self.error()
+    }
+}
@@ -0,0 +1,26 @@
+//! The common types and functions in one import, keeping simple uses to a single line while the
+//! specialized APIs stay in their modules.
+//! # Examples
+//! ```
+//! use kabsch_umeyama::prelude::*;
+//!
+//! let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+//! let dst = Array2::from([[1., 1.], [1., 3.], [-1., 1.]]);
+//! let t: Transform<2> = estimate_transform(src, dst, true).unwrap();
+//! assert!((t.scale() - 2.).abs() < 1e-12);
+//!
+//! let hints = RegistrationHints { correspondences: true, estimate_scale: true, ..Default::default() };
+//! let registration = register(&src[..], &dst[..], &hints).unwrap();
+//! assert_eq!(registration.pipeline, Pipeline::Umeyama);
+//! ```
+pub use crate::{
+    estimate, estimate_transform, estimate_with_options, try_estimate, Array2, DefaultSolver,
+    EstimateOptions, KabschError, Solver, Transform, TransformKind,
+};
+#[cfg(feature = "std")]
+pub use crate::{
+    frames::PointCloud,
+    icp::{icp, icp_from, IcpOptions, IcpRegistration},
+    ransac::{estimate_ransac, RansacEstimate, RansacOptions},
+    register, Pipeline, Registration, RegistrationHints,
+};
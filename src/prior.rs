@@ -0,0 +1,56 @@
+use crate::{fit_points_with_prior, rows, DefaultSolver, Transform};
+use nalgebra::{DMatrix, DVector, SMatrix, SVector};
+
+/// Known direction pair constraining the rotation, e.g. the gravity direction measured by an IMU
+/// in both frames.
+#[derive(Clone, Copy, Debug)]
+pub struct OrientationPrior<const C: usize> {
+    /// Direction in the `src` frame.
+    pub src: SVector<f64, C>,
+    /// Direction the rotation should map `src` to, in the `dst` frame.
+    pub dst: SVector<f64, C>,
+    /// Weight of the prior, relative to the cross-covariance of the points (squared length units).
+    pub weight: f64,
+}
+
+/// Estimate a similarity transformation like [`crate::estimate_transform`], fusing an orientation
+/// prior into the rotation: `weight · dst srcᵀ` (with unit directions) is added to the
+/// cross-covariance, so that the rotation maximises `trace(Rᵀ A) + weight · dstᵀ R src`.
+/// This stabilizes the rotation about the in-plane axes of nearly planar clouds.
+/// The `None` value is returned if a direction is zero or the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_transform, estimate_with_prior, Array2, OrientationPrior};
+/// use nalgebra::Vector3;
+///
+/// // nearly planar points with out-of-plane measurement noise
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]]);
+/// let dst = Array2::from([[0., 0., 0.], [1., 0., 0.02], [0., 1., 0.], [1., 1., 0.02]]);
+///
+/// let gravity = OrientationPrior { src: Vector3::z(), dst: Vector3::z(), weight: 1. };
+/// let fused = estimate_with_prior(src, dst, false, &gravity).unwrap();
+/// let plain = estimate_transform(src, dst, false).unwrap();
+///
+/// let tilt = |r: &nalgebra::Matrix3<f64>| (r * Vector3::z()).angle(&Vector3::z());
+/// assert!(tilt(fused.rotation()) < tilt(plain.rotation()));
+/// ```
+pub fn estimate_with_prior<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+    prior: &OrientationPrior<C>,
+) -> Option<Transform<C>> {
+    let direction_src = prior.src.try_normalize(0.)?;
+    let direction_dst = prior.dst.try_normalize(0.)?;
+    let term = direction_dst * direction_src.transpose() * prior.weight;
+    fit_points_with_prior(
+        rows(src.into()),
+        rows(dst.into()),
+        &DVector::repeat(R, 1.),
+        estimate_scale,
+        &DefaultSolver,
+        Some(&DMatrix::from_column_slice(C, C, term.as_slice())),
+    )
+    .ok()
+    .map(|fit| fit.to_transform())
+}
@@ -0,0 +1,145 @@
+//! Quality gates for production pipelines: accept or reject a registration from the metrics of its
+//! [`Report`] against configured limits, with the reasons of the rejection.
+use crate::report::{Report, CONDITION_NUMBER, INLIERS, RMSE, SCALE};
+use std::fmt::{Display, Formatter};
+
+/// Limits a registration must satisfy to be accepted; unset limits are not checked.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QualityGate {
+    pub max_rmse: Option<f64>,
+    pub min_inliers: Option<usize>,
+    pub max_condition_number: Option<f64>,
+    /// Inclusive range of accepted scales.
+    pub scale_bounds: Option<(f64, f64)>,
+}
+
+/// Reason a registration was rejected by a [`QualityGate`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rejection {
+    Rmse {
+        rmse: f64,
+        max: f64,
+    },
+    TooFewInliers {
+        inliers: usize,
+        min: usize,
+    },
+    IllConditioned {
+        condition_number: f64,
+        max: f64,
+    },
+    ScaleOutOfBounds {
+        scale: f64,
+        bounds: (f64, f64),
+    },
+    /// The report has no numeric metric of this name to check a limit against.
+    MissingMetric {
+        name: &'static str,
+    },
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rmse { rmse, max } => write!(f, "RMSE {rmse} exceeds {max}"),
+            Self::TooFewInliers { inliers, min } => {
+                write!(f, "{inliers} inliers, at least {min} required")
+            }
+            Self::IllConditioned {
+                condition_number,
+                max,
+            } => write!(f, "condition number {condition_number} exceeds {max}"),
+            Self::ScaleOutOfBounds { scale, bounds } => {
+                write!(f, "scale {scale} outside [{}, {}]", bounds.0, bounds.1)
+            }
+            Self::MissingMetric { name } => write!(f, "metric {name} missing from the report"),
+        }
+    }
+}
+
+/// Outcome of a [`QualityGate`]: the registration is accepted if there is no rejection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Verdict {
+    pub rejections: Vec<Rejection>,
+}
+
+impl Verdict {
+    /// Whether every limit is satisfied
+    pub fn accepted(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
+impl QualityGate {
+    /// Check the registration metrics of a report, added by [`Report::registration`], against
+    /// every limit, collecting all the violated ones.
+    /// Non-finite metrics violate the limits they are checked against, and missing ones are
+    /// reported as [`Rejection::MissingMetric`].
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::quality::{QualityGate, Rejection};
+    /// use kabsch_umeyama::report::Report;
+    /// use kabsch_umeyama::{estimate_with_diagnostics, Array2, DefaultSolver, Transform};
+    ///
+    /// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+    /// let dst = Array2::from([[1., 0.], [3., 0.], [1., 2.], [3., 2.1]]);
+    /// let (matrix, diagnostics) =
+    ///     estimate_with_diagnostics(src, dst, true, &DefaultSolver).unwrap();
+    /// let transform = Transform::<2>::from_homogeneous(&matrix).unwrap();
+    /// let report =
+    ///     Report::new("Registration").registration(src, dst, &transform, &diagnostics, 0.1);
+    ///
+    /// let gate = QualityGate {
+    ///     max_rmse: Some(0.1),
+    ///     min_inliers: Some(4),
+    ///     scale_bounds: Some((0.9, 1.1)),
+    ///     ..Default::default()
+    /// };
+    /// let verdict = gate.evaluate(&report);
+    /// assert!(!verdict.accepted());
+    /// assert!(matches!(verdict.rejections[..], [Rejection::ScaleOutOfBounds { .. }]));
+    /// assert!(verdict.rejections[0].to_string().starts_with("scale 2.0"));
+    ///
+    /// let gate = QualityGate { max_condition_number: Some(1e6), ..Default::default() };
+    /// let verdict = gate.evaluate(&Report::new("Registration").metric("RMSE", 0.1));
+    /// assert_eq!(verdict.rejections, [Rejection::MissingMetric { name: "condition number" }]);
+    /// ```
+    pub fn evaluate(&self, report: &Report) -> Verdict {
+        let mut rejections = Vec::new();
+        let mut check = |name: &'static str, rejection: &dyn Fn(f64) -> Option<Rejection>| {
+            let Some(value) = report.metric_value(name) else {
+                rejections.push(Rejection::MissingMetric { name });
+                return;
+            };
+            rejections.extend(rejection(value));
+        };
+        if let Some(max) = self.max_rmse {
+            check(RMSE, &|rmse| {
+                (!(..=max).contains(&rmse)).then_some(Rejection::Rmse { rmse, max })
+            });
+        }
+        if let Some(min) = self.min_inliers {
+            check(INLIERS, &|inliers| {
+                (!(min as f64..).contains(&inliers)).then_some(Rejection::TooFewInliers {
+                    inliers: inliers as usize,
+                    min,
+                })
+            });
+        }
+        if let Some(max) = self.max_condition_number {
+            check(CONDITION_NUMBER, &|condition_number| {
+                (!(..=max).contains(&condition_number)).then_some(Rejection::IllConditioned {
+                    condition_number,
+                    max,
+                })
+            });
+        }
+        if let Some(bounds) = self.scale_bounds {
+            check(SCALE, &|scale| {
+                (!(bounds.0..=bounds.1).contains(&scale))
+                    .then_some(Rejection::ScaleOutOfBounds { scale, bounds })
+            });
+        }
+        Verdict { rejections }
+    }
+}
@@ -0,0 +1,156 @@
+//! Robust estimation from correspondences contaminated by gross outliers, e.g. keypoint matches,
+//! by random sample consensus (RANSAC).
+use crate::robust::residuals;
+use crate::{fit_points, rows, DefaultSolver, KabschError, Points, Rng, Transform};
+use nalgebra::{DVector, SMatrix};
+
+/// Options of [`estimate_ransac`].
+#[derive(Clone, Copy, Debug)]
+pub struct RansacOptions {
+    /// Number of minimal subsets sampled.
+    pub iterations: usize,
+    /// Largest distance between a transformed source point and its destination point for the
+    /// correspondence to be an inlier.
+    pub inlier_threshold: f64,
+    /// Smallest consensus set accepted.
+    pub min_inliers: usize,
+    /// Seed of the sampling, so that the estimate is reproducible.
+    pub seed: u64,
+    pub estimate_scale: bool,
+}
+
+impl Default for RansacOptions {
+    fn default() -> Self {
+        Self {
+            iterations: 1000,
+            inlier_threshold: 1.,
+            min_inliers: 3,
+            seed: 0,
+            estimate_scale: true,
+        }
+    }
+}
+
+/// Result of [`estimate_ransac`].
+#[derive(Clone, Debug)]
+pub struct RansacEstimate<const C: usize> {
+    /// Transformation refitted on the consensus set.
+    pub transform: Transform<C>,
+    /// Whether each correspondence is an inlier of the refitted transformation.
+    pub inliers: Vec<bool>,
+}
+
+impl<const C: usize> RansacEstimate<C> {
+    /// Number of inliers
+    pub fn num_inliers(&self) -> usize {
+        self.inliers.iter().filter(|inlier| **inlier).count()
+    }
+}
+
+/// Estimate a similarity transformation robust to gross outliers: minimal subsets of
+/// correspondences (`C` of them, at least 2) are sampled, the estimate of each is scored by its
+/// number of inliers within the threshold (ties broken by the sum of their distances), and the
+/// best one is refitted on its consensus set.
+/// The [`KabschError::TooFewInliers`] error is returned if no sample, or the refitted
+/// transformation, reaches `min_inliers` inliers (or there are fewer correspondences than a
+/// minimal subset).
+/// # Examples
+/// ```
+/// use kabsch_umeyama::ransac::{estimate_ransac, RansacOptions};
+/// use kabsch_umeyama::Array2;
+/// use nalgebra::Vector2;
+///
+/// let src = Array2::<10, 2>::from(std::array::from_fn(|i| [i as f64, (i * i % 7) as f64]));
+/// // rotated by a quarter turn, scaled by 2 and shifted, with three mismatches
+/// let mut points = src.map(|[x, y]| [1. - 2. * y, 2. * x]);
+/// points[2] = [40., 3.];
+/// points[5] = [-7., 12.];
+/// points[8] = [0., 0.];
+/// let dst = Array2::from(points);
+///
+/// let options = RansacOptions { inlier_threshold: 0.1, min_inliers: 5, ..Default::default() };
+/// let estimate = estimate_ransac(src, dst, &options).unwrap();
+/// assert_eq!(estimate.num_inliers(), 7);
+/// assert!(!estimate.inliers[2] && !estimate.inliers[5] && !estimate.inliers[8]);
+/// assert!((estimate.transform.scale() - 2.).abs() < 1e-9);
+/// assert!((estimate.transform.translation() - Vector2::new(1., 0.)).norm() < 1e-9);
+/// ```
+pub fn estimate_ransac<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    options: &RansacOptions,
+) -> Result<RansacEstimate<C>, KabschError> {
+    ransac_points(&rows(src.into()), &rows(dst.into()), options)
+}
+
+/// Estimation of [`estimate_ransac`] for a number of correspondences only known at runtime.
+pub(crate) fn ransac_points<const C: usize>(
+    src: &Points<C>,
+    dst: &Points<C>,
+    options: &RansacOptions,
+) -> Result<RansacEstimate<C>, KabschError> {
+    let r = src.nrows();
+    let sample_size = C.max(2);
+    let required = options.min_inliers.max(sample_size);
+    let mut rng = Rng::new(options.seed);
+    let mut indices = (0..r).collect::<Vec<_>>();
+    // number of inliers and sum of their distances of the best sample so far
+    let mut best: Option<(usize, f64, Vec<bool>)> = None;
+    if r >= sample_size {
+        for _ in 0..options.iterations {
+            // partial Fisher-Yates shuffle of the first `sample_size` indices
+            for k in 0..sample_size {
+                let j = k + (rng.next_u64() % (r - k) as u64) as usize;
+                indices.swap(k, j);
+            }
+            let sample = &indices[..sample_size];
+            let Ok(fit) = fit_points(
+                Points::<C>::from_fn(sample_size, |i, j| src[(sample[i], j)]),
+                Points::<C>::from_fn(sample_size, |i, j| dst[(sample[i], j)]),
+                &DVector::repeat(sample_size, 1.),
+                options.estimate_scale,
+                &DefaultSolver,
+            ) else {
+                continue;
+            };
+            let distances = residuals(src, dst, &fit.to_transform());
+            let inliers = distances
+                .iter()
+                .map(|d| *d <= options.inlier_threshold)
+                .collect::<Vec<_>>();
+            let count = inliers.iter().filter(|inlier| **inlier).count();
+            let spread = distances
+                .iter()
+                .filter(|d| **d <= options.inlier_threshold)
+                .sum();
+            if best
+                .as_ref()
+                .map_or(true, |(c, s, _)| count > *c || (count == *c && spread < *s))
+            {
+                best = Some((count, spread, inliers));
+            }
+        }
+    }
+    let (found, _, consensus) = best.unwrap_or((0, 0., Vec::new()));
+    if found < required {
+        return Err(KabschError::TooFewInliers { found, required });
+    }
+    let weights = DVector::from_fn(r, |i, _| if consensus[i] { 1. } else { 0. });
+    let transform = fit_points(
+        src.clone(),
+        dst.clone(),
+        &weights,
+        options.estimate_scale,
+        &DefaultSolver,
+    )?
+    .to_transform();
+    let inliers = residuals(src, dst, &transform)
+        .iter()
+        .map(|d| *d <= options.inlier_threshold)
+        .collect::<Vec<_>>();
+    let found = inliers.iter().filter(|inlier| **inlier).count();
+    if found < required {
+        return Err(KabschError::TooFewInliers { found, required });
+    }
+    Ok(RansacEstimate { transform, inliers })
+}
@@ -0,0 +1,264 @@
+use crate::icp::{icp_from, IcpOptions};
+use crate::ransac::{ransac_points, RansacOptions};
+use crate::robust::residuals;
+use crate::{fit_points, rows_from_slice, x84_threshold, DefaultSolver, KabschError, Points};
+use crate::{Transform, X84};
+use nalgebra::{DMatrix, DVector, SMatrix, SVector, SymmetricEigen};
+
+/// Source points used to compare the initial poses of the global search.
+const GLOBAL_POINTS: usize = 1000;
+
+/// What is known about the point clouds given to [`register`].
+#[derive(Clone, Debug)]
+pub struct RegistrationHints<const C: usize> {
+    /// Whether each point of the first cloud corresponds to the point of the second one at the
+    /// same index.
+    pub correspondences: bool,
+    /// Initial pose of the first cloud, if known.
+    pub initial: Option<Transform<C>>,
+    /// Fraction of the first cloud overlapping the second one, `1` by default.
+    pub overlap: f64,
+    /// Inlier threshold of RANSAC, selected by the X84 rule if `None`.
+    pub inlier_threshold: Option<f64>,
+    pub estimate_scale: bool,
+}
+
+impl<const C: usize> Default for RegistrationHints<C> {
+    fn default() -> Self {
+        Self {
+            correspondences: false,
+            initial: None,
+            overlap: 1.,
+            inlier_threshold: None,
+            estimate_scale: false,
+        }
+    }
+}
+
+/// Pipeline selected by [`register`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pipeline {
+    /// Closed-form estimation from the correspondences.
+    Umeyama,
+    /// RANSAC on correspondences with outliers, refitted on the consensus set.
+    RansacRefine,
+    /// ICP from the initial pose.
+    Icp,
+    /// ICP from the best of the initial poses aligning the centroids and principal axes.
+    GlobalIcp,
+}
+
+/// Result of [`register`].
+#[derive(Clone, Debug)]
+pub struct Registration<const C: usize> {
+    /// Transformation mapping the first cloud onto the second one.
+    pub transform: Transform<C>,
+    pub pipeline: Pipeline,
+    /// Root-mean-square distance of the inlier correspondences, or of the kept ICP matches.
+    pub rmse: f64,
+}
+
+/// Register the cloud `a` onto the cloud `b` with a pipeline chosen from the `hints`, for users who
+/// do not know which one suits their data:
+///
+/// - known correspondences are fitted in closed form, through RANSAC if the X84 rule flags some of
+///   them as outliers;
+/// - without correspondences, ICP runs from the initial pose, or else from the best of the poses
+///   aligning the centroids and principal axes of the clouds, compared on a subsample of large
+///   clouds; the ICP matches are trimmed to the expected overlap.
+///
+/// A [`KabschError::ShapeMismatch`] error is returned if correspondences are given for clouds of
+/// different sizes, and the errors of the selected pipeline are returned otherwise.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{register, Pipeline, RegistrationHints};
+/// use nalgebra::Vector2;
+///
+/// let a = (0..40).map(|i| [i as f64 * 0.1, (i * i % 7) as f64 * 0.2]).collect::<Vec<_>>();
+/// let mut b = a.iter().map(|[x, y]| [1. - y, 2. + x]).collect::<Vec<_>>();
+///
+/// let hints = RegistrationHints { correspondences: true, ..Default::default() };
+/// assert_eq!(register(&a, &b, &hints).unwrap().pipeline, Pipeline::Umeyama);
+///
+/// // correspondences with a few gross mismatches
+/// let mut mismatched = b.clone();
+/// mismatched[3] = [40., 3.];
+/// mismatched[17] = [-7., 12.];
+/// let registration = register(&a, &mismatched, &hints).unwrap();
+/// assert_eq!(registration.pipeline, Pipeline::RansacRefine);
+/// assert!((registration.transform.translation() - Vector2::new(1., 2.)).norm() < 1e-9);
+///
+/// // no correspondence and no initial pose: the points of b are shuffled
+/// b.reverse();
+/// let registration = register(&a, &b, &RegistrationHints::default()).unwrap();
+/// assert_eq!(registration.pipeline, Pipeline::GlobalIcp);
+/// assert!(registration.rmse < 1e-9);
+/// assert!((registration.transform.rotation()[(1, 0)] - 1.).abs() < 1e-9);
+/// ```
+pub fn register<const C: usize>(
+    a: &[[f64; C]],
+    b: &[[f64; C]],
+    hints: &RegistrationHints<C>,
+) -> Result<Registration<C>, KabschError> {
+    if a.is_empty() || b.is_empty() || C == 0 {
+        return Err(KabschError::Empty);
+    }
+    if hints.correspondences {
+        if a.len() != b.len() {
+            return Err(KabschError::ShapeMismatch {
+                expected: a.len() * C,
+                actual: b.len() * C,
+            });
+        }
+        return register_correspondences(&rows_from_slice(a), &rows_from_slice(b), hints);
+    }
+    let icp = IcpOptions {
+        trim_fraction: 1. - hints.overlap.clamp(0., 1.),
+        estimate_scale: hints.estimate_scale,
+        ..Default::default()
+    };
+    let (initial, pipeline) = match &hints.initial {
+        Some(initial) => (initial.clone(), Pipeline::Icp),
+        None => {
+            let step = a.len().div_ceil(GLOBAL_POINTS);
+            let sample = a.iter().step_by(step).copied().collect::<Vec<_>>();
+            let mut best: Option<(f64, Transform<C>)> = None;
+            for candidate in principal_poses(a, b, hints.estimate_scale) {
+                let Ok(registration) = icp_from(&sample, b, candidate, &icp) else {
+                    continue;
+                };
+                let rmse = registration.rmse.last().copied().unwrap_or(f64::INFINITY);
+                if best.as_ref().map_or(true, |(e, _)| rmse < *e) {
+                    best = Some((rmse, registration.transform));
+                }
+            }
+            let (_, transform) = best.ok_or(KabschError::DegenerateConfiguration)?;
+            (transform, Pipeline::GlobalIcp)
+        }
+    };
+    let registration = icp_from(a, b, initial, &icp)?;
+    Ok(Registration {
+        rmse: registration.rmse.last().copied().unwrap_or(0.),
+        transform: registration.transform,
+        pipeline,
+    })
+}
+
+fn register_correspondences<const C: usize>(
+    src: &Points<C>,
+    dst: &Points<C>,
+    hints: &RegistrationHints<C>,
+) -> Result<Registration<C>, KabschError> {
+    let fit = |weights: &DVector<f64>| {
+        fit_points(
+            src.clone(),
+            dst.clone(),
+            weights,
+            hints.estimate_scale,
+            &DefaultSolver,
+        )
+        .map(|fit| fit.to_transform())
+    };
+    let rmse = |distances: &[f64]| {
+        (distances.iter().map(|d| d * d).sum::<f64>() / distances.len() as f64).sqrt()
+    };
+    let transform = fit(&DVector::repeat(src.nrows(), 1.))?;
+    let distances = residuals(src, dst, &transform);
+    // distances at the rounding level are not outliers
+    let floor = 1e-9 * dst.iter().fold(0., |m: f64, v| m.max(v.abs()));
+    let threshold = x84_threshold(&distances, X84).unwrap_or(0.).max(floor);
+    if distances.iter().all(|d| *d <= threshold) {
+        return Ok(Registration {
+            rmse: rmse(&distances),
+            transform,
+            pipeline: Pipeline::Umeyama,
+        });
+    }
+    let inlier_threshold = match hints.inlier_threshold {
+        Some(threshold) => threshold,
+        None => {
+            // the X84 threshold of a refit on the provisional inliers
+            let weights = DVector::from_iterator(
+                distances.len(),
+                distances
+                    .iter()
+                    .map(|d| if *d <= threshold { 1. } else { 0. }),
+            );
+            let distances = residuals(src, dst, &fit(&weights)?);
+            x84_threshold(&distances, X84).unwrap_or(0.).max(floor)
+        }
+    };
+    let options = RansacOptions {
+        inlier_threshold,
+        estimate_scale: hints.estimate_scale,
+        ..Default::default()
+    };
+    let estimate = ransac_points(src, dst, &options)?;
+    let inliers = residuals(src, dst, &estimate.transform)
+        .into_iter()
+        .zip(&estimate.inliers)
+        .filter_map(|(d, inlier)| inlier.then_some(d))
+        .collect::<Vec<_>>();
+    Ok(Registration {
+        rmse: rmse(&inliers),
+        transform: estimate.transform,
+        pipeline: Pipeline::RansacRefine,
+    })
+}
+
+/// Poses mapping the centroid of `a` onto the centroid of `b`, without rotation or rotating the
+/// principal axes of `a` onto those of `b` with every proper choice of their directions, the
+/// scale matching their root-mean-square radii if estimated.
+fn principal_poses<const C: usize>(
+    a: &[[f64; C]],
+    b: &[[f64; C]],
+    estimate_scale: bool,
+) -> Vec<Transform<C>> {
+    let (a_center, a_axes, a_radius) = principal_axes(a);
+    let (b_center, b_axes, b_radius) = principal_axes(b);
+    let scale = if estimate_scale && a_radius > 0. {
+        b_radius / a_radius
+    } else {
+        1.
+    };
+    let mut rotations = vec![SMatrix::<f64, C, C>::identity()];
+    for signs in 0..1usize << C {
+        let flip = SMatrix::<f64, C, C>::from_fn(|i, j| match (i == j, signs >> i & 1) {
+            (false, _) => 0.,
+            (true, 0) => 1.,
+            (true, _) => -1.,
+        });
+        let rotation = b_axes * flip * a_axes.transpose();
+        if DMatrix::from_column_slice(C, C, rotation.as_slice()).determinant() > 0. {
+            rotations.push(rotation);
+        }
+    }
+    rotations
+        .into_iter()
+        .map(|rotation| Transform::new(rotation, b_center - rotation * a_center * scale, scale))
+        .collect()
+}
+
+/// Centroid, principal axes as columns in decreasing variance, and root-mean-square radius
+fn principal_axes<const C: usize>(
+    points: &[[f64; C]],
+) -> (SVector<f64, C>, SMatrix<f64, C, C>, f64) {
+    let center = points
+        .iter()
+        .map(|p| SVector::from(*p))
+        .sum::<SVector<f64, C>>()
+        / points.len() as f64;
+    let scatter = points
+        .iter()
+        .map(|p| {
+            let v = SVector::from(*p) - center;
+            v * v.transpose()
+        })
+        .sum::<SMatrix<f64, C, C>>()
+        / points.len() as f64;
+    let eigen = SymmetricEigen::new(DMatrix::from_column_slice(C, C, scatter.as_slice()));
+    let mut order = (0..C).collect::<Vec<_>>();
+    order.sort_by(|i, j| eigen.eigenvalues[*j].total_cmp(&eigen.eigenvalues[*i]));
+    let axes = SMatrix::from_fn(|i, j| eigen.eigenvectors[(i, order[j])]);
+    (center, axes, scatter.trace().sqrt())
+}
@@ -0,0 +1,406 @@
+//! Replay bundles: compact text dumps of the inputs, options and seed of a registration, written
+//! when a [`QualityGate`](crate::quality::QualityGate) rejects it so that failures from the field
+//! can be reproduced exactly in the lab.
+//!
+//! A bundle is a line-oriented text file starting with a `kabsch_umeyama-replay` header with the
+//! version of its [`schema`](crate::schema), followed by `key value` lines and one `point` line per
+//! correspondence holding the source then the destination coordinates. Numbers are written in their
+//! shortest exact decimal representation, so they are read back bit for bit.
+//!
+//! A bundle read back with [`RecordedBundle::from_text`] can be replayed through the current code
+//! with [`ReplayBundle::replay`], diffing the result against the recorded one, e.g. when upgrading
+//! the crate in a validated system.
+use crate::quality::Verdict;
+use crate::report::{Report, CONDITION_NUMBER, INLIERS, RMSE, SCALE};
+use crate::schema::migrate;
+use crate::KabschError;
+use nalgebra::DMatrix;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Header line of a replay bundle, with the version of the [`schema`](crate::schema) it is
+/// written in.
+pub const HEADER: &str = "kabsch_umeyama-replay 2";
+
+const PRODUCER: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Inputs, options and seed of a registration.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::quality::QualityGate;
+/// use kabsch_umeyama::replay::ReplayBundle;
+/// use kabsch_umeyama::report::Report;
+///
+/// let src = [[0., 0.], [1., 0.], [0., 1.]];
+/// let dst = [[1., 1.], [1., 3.], [-1., 1.]];
+/// let bundle = ReplayBundle::new(&src, &dst, true)
+///     .seed(7)
+///     .option("inlier_threshold", 0.1);
+///
+/// let report = Report::new("Registration").registration_metrics(0., 3, 1., 2.);
+/// let gate = QualityGate { scale_bounds: Some((0.9, 1.1)), ..Default::default() };
+/// let verdict = gate.evaluate(&report);
+///
+/// let dir = std::env::temp_dir().join("kabsch_umeyama-replay-doctest");
+/// let path = bundle.log_rejection(&verdict, &dir).unwrap().unwrap();
+/// let text = std::fs::read_to_string(&path).unwrap();
+/// assert!(text.starts_with("kabsch_umeyama-replay 2\nproducer kabsch_umeyama "));
+/// assert!(text.contains("\ndimensions 2\n"));
+/// assert!(text.contains("seed 7\noption inlier_threshold 0.1\n"));
+/// assert!(text.contains("rejection scale 2 outside [0.9, 1.1]\npoint 0 0 1 1\n"));
+///
+/// // accepted registrations are not logged
+/// let accepted = QualityGate::default().evaluate(&report);
+/// assert!(bundle.log_rejection(&accepted, &dir).unwrap().is_none());
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayBundle<const C: usize> {
+    pub src: Vec<[f64; C]>,
+    pub dst: Vec<[f64; C]>,
+    pub estimate_scale: bool,
+    /// Seed of the random sampling of the run, if any.
+    pub seed: Option<u64>,
+    /// Named options of the run, in the order they were added.
+    pub options: Vec<(String, String)>,
+    /// Homogeneous `(C+1)x(C+1)` matrix of the recorded result, `None` if the estimation failed.
+    pub result: Option<DMatrix<f64>>,
+    /// Quality report of the recorded result, of which only the registration metrics added by
+    /// [`Report::registration`] are written, and read back into an untitled report.
+    pub summary: Option<Report>,
+}
+
+impl<const C: usize> ReplayBundle<C> {
+    /// New ReplayBundle from the corresponding points of a registration
+    pub fn new(src: &[[f64; C]], dst: &[[f64; C]], estimate_scale: bool) -> Self {
+        Self {
+            src: src.to_vec(),
+            dst: dst.to_vec(),
+            estimate_scale,
+            seed: None,
+            options: Vec::new(),
+            result: None,
+            summary: None,
+        }
+    }
+
+    /// Set the seed of the run
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Add an option of the run
+    pub fn option(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.options.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Set the recorded result of the run
+    pub fn result(mut self, transform: &DMatrix<f64>) -> Self {
+        self.result = Some(transform.clone());
+        self
+    }
+
+    /// Set the quality report of the recorded result
+    pub fn summary(mut self, summary: Report) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    /// Option of the run named `name`
+    pub fn get_option(&self, name: &str) -> Option<&str> {
+        self.options
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Source and destination points as the rows of matrices, e.g. for [`crate::estimate_dyn`]
+    pub fn matrices(&self) -> (DMatrix<f64>, DMatrix<f64>) {
+        let rows = |points: &[[f64; C]]| {
+            DMatrix::from_row_iterator(points.len(), C, points.iter().flatten().copied())
+        };
+        (rows(&self.src), rows(&self.dst))
+    }
+
+    /// Run `estimator` on the bundle and compare its result with the recorded one.
+    pub fn replay(
+        &self,
+        estimator: impl FnOnce(&Self) -> Result<DMatrix<f64>, KabschError>,
+    ) -> ReplayDiff {
+        ReplayDiff {
+            recorded: self.result.clone(),
+            replayed: estimator(self),
+        }
+    }
+
+    /// Serialize the bundle, along with the reasons the registration was rejected.
+    pub fn to_text(&self, verdict: &Verdict) -> String {
+        let mut text = format!("{HEADER}\nproducer {PRODUCER}\ndimensions {C}\n");
+        let _ = writeln!(text, "estimate_scale {}", self.estimate_scale);
+        if let Some(seed) = self.seed {
+            let _ = writeln!(text, "seed {seed}");
+        }
+        for (name, value) in &self.options {
+            let _ = writeln!(text, "option {} {}", escape(name), escape(value));
+        }
+        if let Some(result) = &self.result {
+            text.push_str("result");
+            for v in result.transpose().iter() {
+                let _ = write!(text, " {v}");
+            }
+            text.push('\n');
+        }
+        if let Some(summary) = &self.summary {
+            text.push_str("summary");
+            for name in [RMSE, INLIERS, CONDITION_NUMBER, SCALE] {
+                match summary.metric_value(name) {
+                    Some(value) => {
+                        let _ = write!(text, " {value}");
+                    }
+                    None => text.push_str(" -"),
+                }
+            }
+            text.push('\n');
+        }
+        for rejection in &verdict.rejections {
+            let _ = writeln!(text, "rejection {rejection}");
+        }
+        for (p, q) in self.src.iter().zip(&self.dst) {
+            text.push_str("point");
+            for v in p.iter().chain(q) {
+                let _ = write!(text, " {v}");
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Write the bundle to `dir` if the registration was rejected, returning the path of the file.
+    /// The file is named after a hash of its contents, so that the same failure is only logged once.
+    pub fn log_rejection(&self, verdict: &Verdict, dir: &Path) -> io::Result<Option<PathBuf>> {
+        if verdict.accepted() {
+            return Ok(None);
+        }
+        let text = self.to_text(verdict);
+        // FNV-1a
+        let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3)
+        });
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("rejected-{hash:016x}.replay"));
+        std::fs::write(&path, text)?;
+        Ok(Some(path))
+    }
+}
+
+/// Replay bundle read back from its text, along with the reasons it was rejected and the version
+/// of the crate that wrote it.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::quality::Verdict;
+/// use kabsch_umeyama::replay::{RecordedBundle, ReplayBundle};
+/// use kabsch_umeyama::report::Report;
+/// use kabsch_umeyama::{estimate_dyn, KabschError};
+///
+/// let src = [[0., 0.], [1., 0.], [0., 1.]];
+/// let dst = [[1., 1.], [1., 3.], [-1., 1.]];
+/// let bundle = ReplayBundle::new(&src, &dst, true)
+///     .option("solver", "lapack")
+///     .option("input file", "C:\\scans\\part 1\nrev 2");
+/// let (s, d) = bundle.matrices();
+/// let summary = Report::default().registration_metrics(0., 3, f64::INFINITY, 2.);
+/// let bundle = bundle.result(&estimate_dyn(&s, &d, true).unwrap()).summary(summary);
+///
+/// let text = bundle.to_text(&Verdict { rejections: Vec::new() });
+/// let recorded = RecordedBundle::<2>::from_text(&text).unwrap();
+/// assert_eq!(recorded.bundle, bundle);
+/// assert!(recorded.producer.unwrap().starts_with("kabsch_umeyama "));
+///
+/// // replay through the current code without scaling, which no longer matches
+/// let diff = recorded.bundle.replay(|b| {
+///     let (src, dst) = b.matrices();
+///     estimate_dyn(&src, &dst, false)
+/// });
+/// assert!(!diff.matches(1e-9));
+/// assert!(diff.max_difference().unwrap() > 0.5);
+///
+/// assert_eq!(
+///     RecordedBundle::<3>::from_text(&text).unwrap_err(),
+///     KabschError::InvalidBundle { line: 3 }
+/// );
+///
+/// // bundles archived in version 1 of the schema are migrated
+/// let archived = "kabsch_umeyama-replay 1\ndimensions 2\nestimate_scale true\n\
+///                 transform 1 0 1 0 1 1 0 0 1\npoint 0 0 1 1\npoint 1 0 2 1\n";
+/// let recorded = RecordedBundle::<2>::from_text(archived).unwrap();
+/// assert_eq!(recorded.producer, None);
+/// assert_eq!(recorded.bundle.result.unwrap()[(0, 2)], 1.);
+///
+/// // missing registration metrics are left out of the summary read back
+/// let summary = Report::default().metric("RMSE", 0.1);
+/// let partial = ReplayBundle::new(&src, &dst, true).summary(summary);
+/// let text = partial.to_text(&Verdict { rejections: Vec::new() });
+/// assert!(text.contains("\nsummary 0.1 - - -\n"));
+/// assert_eq!(RecordedBundle::<2>::from_text(&text).unwrap().bundle, partial);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedBundle<const C: usize> {
+    pub bundle: ReplayBundle<C>,
+    /// Reasons the registration was rejected, as written in the bundle.
+    pub rejections: Vec<String>,
+    /// Name and version of the crate that wrote the bundle, unknown before version 2 of the schema.
+    pub producer: Option<String>,
+}
+
+impl<const C: usize> RecordedBundle<C> {
+    /// Parse the text of a bundle written by [`ReplayBundle::to_text`] or
+    /// [`ReplayBundle::log_rejection`].
+    /// Bundles written in an older version of the [`schema`](crate::schema) are migrated first.
+    /// The [`KabschError::InvalidBundle`] error is returned with the first line that cannot be
+    /// read, e.g. because the bundle was recorded in other dimensions.
+    pub fn from_text(text: &str) -> Result<Self, KabschError> {
+        let text = migrate(text)?;
+        let lines = text.lines().enumerate().skip(1);
+        let invalid = |index: usize| KabschError::InvalidBundle { line: index + 1 };
+        let mut recorded = Self {
+            bundle: ReplayBundle::new(&[], &[], false),
+            rejections: Vec::new(),
+            producer: None,
+        };
+        let bundle = &mut recorded.bundle;
+        for (index, line) in lines {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let numbers = || {
+                value
+                    .split_whitespace()
+                    .map(str::parse::<f64>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| invalid(index))
+            };
+            match key {
+                "producer" => recorded.producer = Some(value.into()),
+                "dimensions" if value == C.to_string() => {}
+                "estimate_scale" => {
+                    bundle.estimate_scale = value.parse().map_err(|_| invalid(index))?
+                }
+                "seed" => bundle.seed = Some(value.parse().map_err(|_| invalid(index))?),
+                "option" => {
+                    let (name, value) = value.split_once(' ').unwrap_or((value, ""));
+                    bundle.options.push((unescape(name), unescape(value)));
+                }
+                "result" => {
+                    let values = numbers()?;
+                    if values.len() != (C + 1) * (C + 1) {
+                        return Err(invalid(index));
+                    }
+                    bundle.result = Some(DMatrix::from_row_slice(C + 1, C + 1, &values));
+                }
+                "summary" => {
+                    let fields = value.split_whitespace().collect::<Vec<_>>();
+                    if fields.len() != 4 {
+                        return Err(invalid(index));
+                    }
+                    let mut summary = Report::default();
+                    for (name, field) in [RMSE, INLIERS, CONDITION_NUMBER, SCALE]
+                        .into_iter()
+                        .zip(fields)
+                    {
+                        if field != "-" {
+                            let value = field.parse::<f64>().map_err(|_| invalid(index))?;
+                            summary = summary.metric(name, value);
+                        }
+                    }
+                    bundle.summary = Some(summary);
+                }
+                "rejection" => recorded.rejections.push(value.into()),
+                "point" => {
+                    let values = numbers()?;
+                    if values.len() != 2 * C {
+                        return Err(invalid(index));
+                    }
+                    bundle.src.push(std::array::from_fn(|j| values[j]));
+                    bundle.dst.push(std::array::from_fn(|j| values[C + j]));
+                }
+                _ => return Err(invalid(index)),
+            }
+        }
+        Ok(recorded)
+    }
+
+    /// Read a bundle from a file.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Self::from_text(&std::fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// Option name or value with its backslashes and whitespace escaped, to fit in a `key value` line.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ' ' => escaped.push_str("\\s"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Inverse of [`escape`], keeping unknown escapes as written.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('s') => unescaped.push(' '),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Recorded and replayed results of a [`ReplayBundle`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayDiff {
+    pub recorded: Option<DMatrix<f64>>,
+    pub replayed: Result<DMatrix<f64>, KabschError>,
+}
+
+impl ReplayDiff {
+    /// Largest absolute difference between the entries of the recorded and replayed matrices, the
+    /// `None` value if either is missing or their shapes differ
+    pub fn max_difference(&self) -> Option<f64> {
+        match (&self.recorded, &self.replayed) {
+            (Some(recorded), Ok(replayed)) if recorded.shape() == replayed.shape() => {
+                Some((recorded - replayed).abs().max())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the replay reproduces the recorded result within `tol`, or fails like it
+    pub fn matches(&self, tol: f64) -> bool {
+        match (&self.recorded, &self.replayed) {
+            (None, Err(_)) => true,
+            _ => self.max_difference().is_some_and(|d| d <= tol),
+        }
+    }
+}
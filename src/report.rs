@@ -0,0 +1,340 @@
+//! Self-contained HTML or Markdown summaries of a registration or evaluation run, for sharing
+//! results with people who do not run the code.
+use crate::robust::residuals;
+use crate::{Diagnostics, Transform};
+use nalgebra::SMatrix;
+use std::fmt::Write;
+
+/// Name of the root-mean-square distance metric added by [`Report::registration`].
+pub const RMSE: &str = "RMSE";
+/// Name of the inlier count metric added by [`Report::registration`].
+pub const INLIERS: &str = "inliers";
+/// Name of the condition number metric added by [`Report::registration`].
+pub const CONDITION_NUMBER: &str = "condition number";
+/// Name of the scale metric added by [`Report::registration`].
+pub const SCALE: &str = "scale";
+
+/// Section of a [`Report`].
+#[derive(Clone, Debug, PartialEq)]
+enum Section {
+    Histogram {
+        title: String,
+        /// `(lower bound, upper bound, count)` of each bin.
+        bins: Vec<(f64, f64, usize)>,
+    },
+    Figure {
+        title: String,
+        svg: String,
+    },
+}
+
+/// Report made of parameters, metrics, histograms and figures, in the order they were added.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::report::Report;
+///
+/// let report = Report::new("Scan registration")
+///     .parameter("max iterations", 50)
+///     .metric("RMSE [mm]", 0.42)
+///     .histogram("Deviations [mm]", &[-0.3, 0.1, 0.2, 0.25, 0.9], 4);
+///
+/// let html = report.to_html();
+/// assert!(html.starts_with("<!DOCTYPE html>") && html.contains("RMSE [mm]"));
+/// assert!(report.to_markdown().contains("| max iterations | 50 |"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    title: String,
+    parameters: Vec<(String, String)>,
+    metrics: Vec<(String, String)>,
+    sections: Vec<Section>,
+}
+
+impl Report {
+    /// New empty Report
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Add a parameter of the run
+    pub fn parameter(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.parameters.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Add a resulting metric
+    pub fn metric(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.metrics.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Add the root-mean-square and the robust standard deviation ([`crate::robust_sigma`]) of
+    /// residuals as metrics, named after `name`
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::report::Report;
+    ///
+    /// let report = Report::new("Registration").residuals("Deviation [mm]", &[-0.1, 0.2, 0., 0.1, -0.2]);
+    /// assert!(report.to_markdown().contains("| Deviation [mm] robust σ | 0.1482"));
+    /// ```
+    pub fn residuals(self, name: &str, residuals: &[f64]) -> Self {
+        let rms =
+            (residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len().max(1) as f64).sqrt();
+        let sigma = crate::robust_sigma(residuals).unwrap_or(f64::NAN);
+        self.metric(format!("{name} RMS"), rms)
+            .metric(format!("{name} robust σ"), sigma)
+    }
+
+    /// Add the metrics of the registration of `src` onto `dst` by `transform`, with the
+    /// `diagnostics` of its estimation, counting the correspondences closer than
+    /// `inlier_threshold` as inliers: the root-mean-square distance between the transformed source
+    /// points and the destination points, the number of inliers, the ratio of the largest to the
+    /// smallest singular value of the cross-covariance matrix (infinite for a rank-deficient
+    /// problem) and the scale. These are the metrics checked by a
+    /// [`QualityGate`](crate::quality::QualityGate).
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::report::{Report, INLIERS, SCALE};
+    /// use kabsch_umeyama::{estimate_with_diagnostics, Array2, DefaultSolver, Transform};
+    ///
+    /// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+    /// let dst = Array2::from([[1., 0.], [3., 0.], [1., 2.], [3., 2.1]]);
+    /// let (matrix, diagnostics) =
+    ///     estimate_with_diagnostics(src, dst, true, &DefaultSolver).unwrap();
+    /// let transform = Transform::<2>::from_homogeneous(&matrix).unwrap();
+    ///
+    /// let report =
+    ///     Report::new("Registration").registration(src, dst, &transform, &diagnostics, 0.1);
+    /// assert_eq!(report.metric_value(INLIERS), Some(4.));
+    /// assert!((report.metric_value(SCALE).unwrap() - 2.).abs() < 0.1);
+    /// ```
+    pub fn registration<const R: usize, const C: usize>(
+        self,
+        src: impl Into<SMatrix<f64, R, C>>,
+        dst: impl Into<SMatrix<f64, R, C>>,
+        transform: &Transform<C>,
+        diagnostics: &Diagnostics,
+        inlier_threshold: f64,
+    ) -> Self {
+        let distances = residuals(&src.into(), &dst.into(), transform);
+        let rmse = (distances.iter().map(|d| d * d).sum::<f64>() / R as f64).sqrt();
+        let singular_values = &diagnostics.singular_values;
+        let smallest = singular_values.min();
+        let condition_number = if smallest > 0. {
+            singular_values.max() / smallest
+        } else {
+            f64::INFINITY
+        };
+        self.registration_metrics(
+            rmse,
+            distances.iter().filter(|d| **d <= inlier_threshold).count(),
+            condition_number,
+            transform.scale(),
+        )
+    }
+
+    /// Add registration metrics computed elsewhere, named like [`Report::registration`] does
+    pub fn registration_metrics(
+        self,
+        rmse: f64,
+        inliers: usize,
+        condition_number: f64,
+        scale: f64,
+    ) -> Self {
+        self.metric(RMSE, rmse)
+            .metric(INLIERS, inliers)
+            .metric(CONDITION_NUMBER, condition_number)
+            .metric(SCALE, scale)
+    }
+
+    /// Numeric value of the last metric named `name`, `None` if there is none or it is not a number
+    pub fn metric_value(&self, name: &str) -> Option<f64> {
+        self.metrics
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, value)| value.parse().ok())
+    }
+
+    /// Add a histogram of the finite values over `bins` equal bins spanning their range
+    pub fn histogram(mut self, title: impl Into<String>, values: &[f64], bins: usize) -> Self {
+        let finite = values.iter().copied().filter(|v| v.is_finite());
+        let (min, max) = finite
+            .clone()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                (min.min(v), max.max(v))
+            });
+        let bins = bins.max(1);
+        let width = if max > min {
+            (max - min) / bins as f64
+        } else {
+            1.
+        };
+        let mut counts = vec![0; if min.is_finite() { bins } else { 0 }];
+        for v in finite {
+            let bin = (((v - min) / width) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+        self.sections.push(Section::Histogram {
+            title: title.into(),
+            bins: counts
+                .into_iter()
+                .enumerate()
+                .map(|(i, count)| {
+                    let lower = min + i as f64 * width;
+                    (lower, lower + width, count)
+                })
+                .collect(),
+        });
+        self
+    }
+
+    /// Add a figure given as an SVG document, e.g. from the `plot` module
+    pub fn figure(mut self, title: impl Into<String>, svg: impl Into<String>) -> Self {
+        self.sections.push(Section::Figure {
+            title: title.into(),
+            svg: svg.into(),
+        });
+        self
+    }
+
+    /// Self-contained HTML page, with the histograms drawn as inline SVG
+    pub fn to_html(&self) -> String {
+        let mut html = String::new();
+        let title = escape(&self.title);
+        let _ = write!(
+            html,
+            concat!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n",
+                "<style>body{{font-family:sans-serif;max-width:900px;margin:auto}}",
+                "table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 8px}}</style>\n",
+                "</head>\n<body>\n<h1>{0}</h1>\n"
+            ),
+            title
+        );
+        for (heading, rows) in [("Parameters", &self.parameters), ("Metrics", &self.metrics)] {
+            if rows.is_empty() {
+                continue;
+            }
+            let _ = write!(html, "<h2>{heading}</h2>\n<table>\n");
+            for (name, value) in rows {
+                let _ = writeln!(
+                    html,
+                    "<tr><th>{}</th><td>{}</td></tr>",
+                    escape(name),
+                    escape(value)
+                );
+            }
+            html += "</table>\n";
+        }
+        for section in &self.sections {
+            match section {
+                Section::Histogram { title, bins } => {
+                    let _ = writeln!(html, "<h2>{}</h2>", escape(title));
+                    html += &histogram_svg(bins);
+                }
+                Section::Figure { title, svg } => {
+                    let _ = writeln!(html, "<h2>{}</h2>\n{svg}", escape(title));
+                }
+            }
+        }
+        html += "</body>\n</html>\n";
+        html
+    }
+
+    /// Markdown document, with the histograms as tables and the figures embedded as data URIs
+    pub fn to_markdown(&self) -> String {
+        let mut md = format!("# {}\n", self.title);
+        for (heading, rows) in [("Parameters", &self.parameters), ("Metrics", &self.metrics)] {
+            if rows.is_empty() {
+                continue;
+            }
+            let _ = write!(md, "\n## {heading}\n\n| Name | Value |\n| --- | --- |\n");
+            for (name, value) in rows {
+                let _ = writeln!(md, "| {name} | {value} |");
+            }
+        }
+        for section in &self.sections {
+            match section {
+                Section::Histogram { title, bins } => {
+                    let _ = write!(md, "\n## {title}\n\n| Bin | Count |\n| --- | --- |\n");
+                    for (lower, upper, count) in bins {
+                        let _ = writeln!(md, "| [{lower:.4}, {upper:.4}) | {count} |");
+                    }
+                }
+                Section::Figure { title, svg } => {
+                    let _ = write!(
+                        md,
+                        "\n## {title}\n\n![{title}](data:image/svg+xml;base64,{})\n",
+                        base64(svg.as_bytes())
+                    );
+                }
+            }
+        }
+        md
+    }
+}
+
+/// Bar chart of the histogram bins.
+fn histogram_svg(bins: &[(f64, f64, usize)]) -> String {
+    const WIDTH: f64 = 600.;
+    const HEIGHT: f64 = 200.;
+    let highest = bins.iter().map(|b| b.2).max().unwrap_or(0).max(1) as f64;
+    let bar = WIDTH / bins.len().max(1) as f64;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{}\">\n",
+        HEIGHT + 20.
+    );
+    for (i, (lower, upper, count)) in bins.iter().enumerate() {
+        let height = *count as f64 / highest * HEIGHT;
+        let _ = writeln!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{height:.1}\" fill=\"steelblue\">\
+             <title>[{lower:.4}, {upper:.4}): {count}</title></rect>",
+            i as f64 * bar + 1.,
+            HEIGHT - height,
+            bar - 2.,
+        );
+    }
+    if let (Some(first), Some(last)) = (bins.first(), bins.last()) {
+        let _ = writeln!(
+            svg,
+            "<text x=\"0\" y=\"{0}\" font-size=\"12\">{1:.4}</text>\
+             <text x=\"{WIDTH}\" y=\"{0}\" font-size=\"12\" text-anchor=\"end\">{2:.4}</text>",
+            HEIGHT + 15.,
+            first.0,
+            last.1
+        );
+    }
+    svg += "</svg>\n";
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            out.push(if i <= chunk.len() {
+                ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char
+            } else {
+                '='
+            });
+        }
+    }
+    out
+}
@@ -0,0 +1,153 @@
+use crate::{fit_points, rows, DefaultSolver, Transform};
+use nalgebra::{Const, DVector, Dim, Matrix, SMatrix, SVector, Storage};
+
+/// Number of median absolute deviations from the median beyond which the X84 rule rejects a
+/// residual, equivalent to 3.5 standard deviations for Gaussian noise.
+pub const X84: f64 = 5.2;
+
+/// Ratio of the standard deviation to the median absolute deviation for Gaussian noise.
+pub(crate) const MAD_TO_SIGMA: f64 = 1.4826;
+
+pub(crate) fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n / 2]
+    } else {
+        (values[n / 2 - 1] + values[n / 2]) / 2.
+    }
+}
+
+/// Median absolute deviation of the values from their median.
+fn mad(values: &[f64]) -> f64 {
+    let center = median(&mut values.to_vec());
+    median(
+        &mut values
+            .iter()
+            .map(|v| (v - center).abs())
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Robust estimate of the standard deviation of residuals, `1.4826 × MAD` (median absolute
+/// deviation from the median), unaffected by up to half of the residuals being outliers.
+/// This is the noise scale used by the thresholds, reports and covariance estimates of the crate.
+/// The `None` value is returned if there are no residuals or one is not finite.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::robust_sigma;
+///
+/// let residuals = [-0.1, 0.2, 0., 0.1, -0.2, 40.];
+/// let sigma = robust_sigma(&residuals).unwrap();
+/// assert!((sigma - 1.4826 * 0.15).abs() < 1e-12);
+/// ```
+pub fn robust_sigma(residuals: &[f64]) -> Option<f64> {
+    if residuals.is_empty() || residuals.iter().any(|r| !r.is_finite()) {
+        return None;
+    }
+    Some(MAD_TO_SIGMA * mad(residuals))
+}
+
+/// Robust standard deviation of the coordinates of the residuals `s R p + t - q` of a transformation,
+/// by [`robust_sigma`].
+/// The `None` value is returned if the lengths differ, there are no points or a residual is not
+/// finite.
+pub fn residual_sigma<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    transform: &Transform<C>,
+) -> Option<f64> {
+    if src.len() != dst.len() {
+        return None;
+    }
+    let components = src
+        .iter()
+        .zip(dst)
+        .flat_map(|(p, q)| {
+            let moved = transform.rotation() * SVector::from(*p) * transform.scale()
+                + transform.translation();
+            (moved - SVector::from(*q)).data.0[0]
+        })
+        .collect::<Vec<_>>();
+    robust_sigma(&components)
+}
+
+/// Inlier threshold on residuals by the X84 rule (Hampel): the median residual plus `k` median
+/// absolute deviations, [`X84`] being the usual choice.
+/// The `None` value is returned if there are no residuals or one is not finite.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{x84_threshold, X84};
+///
+/// let residuals = [0.9, 1.1, 1., 0.95, 1.05, 25.];
+/// let threshold = x84_threshold(&residuals, X84).unwrap();
+/// assert!(threshold > 1.1 && threshold < 25.);
+/// ```
+pub fn x84_threshold(residuals: &[f64], k: f64) -> Option<f64> {
+    let sigma = robust_sigma(residuals)?;
+    Some(median(&mut residuals.to_vec()) + k * sigma / MAD_TO_SIGMA)
+}
+
+pub(crate) fn residuals<R: Dim, const C: usize, S: Storage<f64, R, Const<C>>>(
+    src: &Matrix<f64, R, Const<C>, S>,
+    dst: &Matrix<f64, R, Const<C>, S>,
+    transform: &Transform<C>,
+) -> Vec<f64> {
+    (0..src.nrows())
+        .map(|i| {
+            let moved = transform.rotation() * src.row(i).transpose() * transform.scale()
+                + transform.translation();
+            (moved - dst.row(i).transpose()).norm()
+        })
+        .collect()
+}
+
+/// Select an inlier threshold on the correspondence distances without knowing the noise scale:
+/// the residuals of a provisional fit on all the points are thresholded by the X84 rule, the fit
+/// is repeated on the inliers only, and the X84 threshold of its residuals is returned.
+/// The `None` value is returned if the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::testing::Rng;
+/// use kabsch_umeyama::{auto_threshold, Array2};
+///
+/// let mut rng = Rng::new(3);
+/// let src = Array2::<20, 2>::from(std::array::from_fn(|i| [i as f64, (i * i % 7) as f64]));
+/// let mut dst = Array2::<20, 2>::from(std::array::from_fn(|i| {
+///     [src[i][0] + 0.01 * rng.normal(), src[i][1] + 5. + 0.01 * rng.normal()]
+/// }));
+/// // two gross outliers
+/// let mut points = *dst;
+/// points[3] = [50., -20.];
+/// points[11] = [-30., 40.];
+/// dst = Array2::from(points);
+///
+/// let threshold = auto_threshold(src, dst, false).unwrap();
+/// assert!(threshold > 0.01 && threshold < 0.2);
+/// ```
+pub fn auto_threshold<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+) -> Option<f64> {
+    let src = src.into();
+    let dst = dst.into();
+    let mut weights = DVector::repeat(R, 1.);
+    let mut threshold = None;
+    for _ in 0..2 {
+        let transform = fit_points(
+            rows(src),
+            rows(dst),
+            &weights,
+            estimate_scale,
+            &DefaultSolver,
+        )
+        .ok()?
+        .to_transform();
+        let residuals = residuals(&src, &dst, &transform);
+        let limit = x84_threshold(&residuals, X84)?;
+        weights = DVector::from_fn(R, |i, _| if residuals[i] <= limit { 1. } else { 0. });
+        threshold = Some(limit);
+    }
+    threshold
+}
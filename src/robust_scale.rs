@@ -0,0 +1,46 @@
+use nalgebra::SMatrix;
+
+/// Estimate the scale between two point sets as the median of the ratios of their pairwise
+/// distances, `median |qᵢ - qⱼ| / |pᵢ - pⱼ|` over all pairs `i < j`.
+///
+/// Unlike the variance ratio of the Kabsch-Umeyama estimate, a minority of outliers only affects
+/// the ratios of the pairs they belong to and barely moves the median, which makes it a useful
+/// cross-check of the estimated scale. It needs no rotation, but costs `O(R²)`.
+/// The `None` value is returned if all the `src` points coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::report::Report;
+/// use kabsch_umeyama::{estimate_transform, median_ratio_scale, Array2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.], [2., 1.], [1., 2.]]);
+/// // scaled by 3, with the last point an outlier
+/// let dst = Array2::from([[0., 0.], [3., 0.], [0., 3.], [3., 3.], [6., 3.], [30., 30.]]);
+///
+/// let robust = median_ratio_scale(src, dst).unwrap();
+/// let estimated = estimate_transform(src, dst, true).unwrap().scale();
+/// assert!((robust - 3.).abs() < 1e-12);
+/// assert!((estimated - 3.).abs() > 1.);
+///
+/// let report = Report::new("Alignment")
+///     .metric("Scale", estimated)
+///     .metric("Scale (median of ratios)", robust);
+/// assert!(report.to_markdown().contains("median of ratios"));
+/// ```
+pub fn median_ratio_scale<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+) -> Option<f64> {
+    let src = src.into();
+    let dst = dst.into();
+    let mut ratios = (0..R)
+        .flat_map(|i| (i + 1..R).map(move |j| (i, j)))
+        .filter_map(|(i, j)| {
+            let d = (src.row(i) - src.row(j)).norm();
+            d.is_normal().then(|| (dst.row(i) - dst.row(j)).norm() / d)
+        })
+        .collect::<Vec<_>>();
+    if ratios.is_empty() {
+        return None;
+    }
+    Some(crate::robust::median(&mut ratios))
+}
@@ -0,0 +1,167 @@
+use nalgebra::{DMatrix, DVector, RealField, SMatrix, Scalar};
+
+/// Floating-point scalar type the estimation can run in, with the tolerances of its precision.
+pub trait Real: RealField + Copy {
+    /// Singular values of the cross-covariance below this are treated as zero when computing the
+    /// rank.
+    const RANK_TOL: Self;
+}
+
+impl Real for f64 {
+    const RANK_TOL: f64 = crate::solver::RANK_TOL;
+}
+
+impl Real for f32 {
+    // about as many significant digits above the rounding error as the f64 tolerance leaves
+    const RANK_TOL: f32 = 1e-3;
+}
+
+/// Estimate a similarity transformation like [`crate::estimate`] in the precision of the input,
+/// e.g. `f32` buffers on embedded hardware or fed to a GPU, without converting them to `f64`.
+///
+/// The rotation is computed by nalgebra's SVD in pure Rust, as LAPACK solvers are `f64` only.
+/// The `None` value is returned if the problem is not well-conditioned, or if a coordinate, or a
+/// moment computed from them, is not finite.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_generic, Array2};
+///
+/// let src = Array2::<3, 2, f32>::from([[0., 0.], [1., 0.], [0., 1.]]);
+/// let dst = Array2::<3, 2, f32>::from([[1., 1.], [1., 3.], [-1., 1.]]);
+///
+/// let t = estimate_generic(src, dst, true).unwrap();
+/// assert!((t[(1, 0)] - 2f32).abs() < 1e-5);
+/// assert!(estimate_generic(src, Array2::from([[1f32, 1.]; 3]), true).is_none());
+/// assert!(estimate_generic(src, Array2::from([[f32::NAN, 1.]; 3]), true).is_none());
+/// ```
+pub fn estimate_generic<T: Real, const R: usize, const C: usize>(
+    src: impl Into<SMatrix<T, R, C>>,
+    dst: impl Into<SMatrix<T, R, C>>,
+    estimate_scale: bool,
+) -> Option<DMatrix<T>> {
+    let src = src.into();
+    let dst = dst.into();
+    let num = nalgebra::convert::<f64, T>(R as f64);
+    let src_mean = src.row_mean();
+    let dst_mean = dst.row_mean();
+    let src_demean = SMatrix::<T, R, C>::from_fn(|i, j| src[(i, j)] - src_mean[j]);
+    let dst_demean = SMatrix::<T, R, C>::from_fn(|i, j| dst[(i, j)] - dst_mean[j]);
+
+    let a = dst_demean.transpose() * src_demean / num;
+    let a = DMatrix::from_column_slice(C, C, a.as_slice());
+    if a.iter().any(|v| !v.is_finite()) {
+        return None;
+    }
+    let rank = a.rank(T::RANK_TOL);
+    if rank == 0 {
+        return None;
+    }
+    let mut svd = a.clone().try_svd(
+        true,
+        true,
+        T::default_epsilon(),
+        crate::solver::MAX_SVD_ITERATIONS,
+    )?;
+    svd.sort_by_singular_values();
+    let (u, vt) = (svd.u?, svd.v_t?);
+    let mut d = DVector::<T>::from_element(C, T::one());
+    let reflected = if rank == C - 1 {
+        u.determinant() * vt.determinant() <= T::zero()
+    } else {
+        a.determinant() < T::zero()
+    };
+    if reflected {
+        d[C - 1] = -T::one();
+    }
+    let m = u * DMatrix::from_diagonal(&d) * vt;
+
+    let scale = if estimate_scale {
+        num / src_demean.norm_squared() * (m.transpose() * &a).trace()
+    } else {
+        T::one()
+    };
+    let mut t = DMatrix::<T>::identity(C + 1, C + 1);
+    let linear = &m * scale;
+    let mx = DVector::from_fn(C, |i, _| {
+        dst_mean[i] - (0..C).fold(T::zero(), |acc, j| acc + linear[(i, j)] * src_mean[j])
+    });
+    t.view_mut((0, 0), (C, C)).copy_from(&linear);
+    t.view_mut((0, C), (C, 1)).copy_from(&mx);
+    Some(t)
+}
+
+/// Estimate a similarity transformation from `f32` points like [`crate::estimate`], accumulating
+/// the means, the cross-covariance and the variances in `f64`: the inputs and the result stay in
+/// `f32` for GPU-adjacent pipelines, while the accuracy only suffers from their rounding.
+///
+/// Unlike [`estimate_generic`] in `f32`, the error does not grow with the number of points or with
+/// their distance to the origin, e.g. georeferenced coordinates.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_f32, estimate_generic};
+/// use nalgebra::SMatrix;
+///
+/// // a thousand points far from the origin, rotated by 0.3 rad and shifted
+/// let src = SMatrix::<f64, 1000, 2>::from_fn(|i, j| {
+///     let t = i as f64 * 0.01;
+///     if j == 0 { 5000. + 40. * t.cos() } else { 3000. + 25. * (2. * t).sin() }
+/// });
+/// let (c, s) = (0.3f64.cos(), 0.3f64.sin());
+/// let dst = SMatrix::<f64, 1000, 2>::from_fn(|i, j| {
+///     let (x, y) = (src[(i, 0)], src[(i, 1)]);
+///     if j == 0 { c * x - s * y + 10. } else { s * x + c * y - 20. }
+/// });
+///
+/// let reference = estimate(src, dst, false).unwrap();
+/// let accumulated = estimate_f32(src.cast::<f32>(), dst.cast::<f32>(), false).unwrap();
+/// let single = estimate_generic(src.cast::<f32>(), dst.cast::<f32>(), false).unwrap();
+/// let error = |t: nalgebra::DMatrix<f32>| (t.cast::<f64>() - &reference).abs().max();
+///
+/// // about 5e-4 from the f32 rounding of the inputs, against about 4e-3 accumulating in f32
+/// assert!(error(accumulated.clone()) < 1e-3);
+/// assert!(error(accumulated) * 4. < error(single));
+/// ```
+pub fn estimate_f32<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f32, R, C>>,
+    dst: impl Into<SMatrix<f32, R, C>>,
+    estimate_scale: bool,
+) -> Option<DMatrix<f32>> {
+    crate::estimate(
+        src.into().cast::<f64>(),
+        dst.into().cast::<f64>(),
+        estimate_scale,
+    )
+    .map(|t| t.cast())
+}
+
+/// Estimate a similarity transformation like [`crate::estimate`] from integer coordinates, e.g.
+/// the `i32` millimeters of a depth sensor, so that embedded front-ends can pass their buffers
+/// as they are. Each coordinate is converted to `f64` and multiplied by `unit`, the length of one
+/// integer step (`1e-3` to get meters from millimeters), and the translation of the result is in
+/// the converted unit.
+/// The `None` value is returned if `unit` is not positive and finite, or if the problem is not
+/// well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_fixed, Array2};
+///
+/// let src = Array2::<3, 2, i32>::from([[0, 0], [1000, 0], [0, 1000]]);
+/// let dst = Array2::<3, 2, i32>::from([[250, -40], [250, 960], [-750, -40]]);
+///
+/// let t = estimate_fixed(src, dst, 1e-3, false).unwrap();
+/// assert!((t[(0, 2)] - 0.25).abs() < 1e-12);
+/// assert!((t[(1, 2)] + 0.04).abs() < 1e-12);
+/// assert!(estimate_fixed(src, dst, 0., false).is_none());
+/// ```
+pub fn estimate_fixed<I: Scalar + Copy + Into<f64>, const R: usize, const C: usize>(
+    src: impl Into<SMatrix<I, R, C>>,
+    dst: impl Into<SMatrix<I, R, C>>,
+    unit: f64,
+    estimate_scale: bool,
+) -> Option<DMatrix<f64>> {
+    if !(unit > 0. && unit.is_finite()) {
+        return None;
+    }
+    let convert = |points: SMatrix<I, R, C>| points.map(|v| v.into() * unit);
+    crate::estimate(convert(src.into()), convert(dst.into()), estimate_scale)
+}
@@ -0,0 +1,92 @@
+//! Versioned schema of the archived registration results: the [`replay`](crate::replay) bundles,
+//! holding the inputs, the resulting transformation and the quality report of a registration.
+//!
+//! A bundle starts with a `kabsch_umeyama-replay <version>` header, followed by one `key value`
+//! line per item. Numbers are written in their shortest exact decimal representation, with `inf`
+//! and `NaN` for non-finite values. Bundles of an older version are migrated to the current one
+//! with [`migrate`] when they are read, so that results archived by older versions of the crate
+//! remain loadable; migrations keep the line numbers, so errors point at the archived file.
+//!
+//! # Version 2 (current)
+//! In this order, optional lines marked with `?` and repeated lines with `*`:
+//! - `producer <crate> <version>`?: crate version that wrote the bundle
+//! - `dimensions <C>`: dimensions of the points
+//! - `estimate_scale <true|false>`
+//! - `seed <u64>`?: seed of the random sampling of the run
+//! - `option <name> <value>`*: named options of the run, with `\\`, `\s`, `\t`, `\n` and `\r`
+//!   escaping backslashes and whitespace
+//! - `result <(C+1)² numbers>`?: homogeneous matrix of the result, row by row, absent if the
+//!   estimation failed
+//! - `summary <rmse> <inliers> <condition number> <scale>`?: registration metrics of the quality
+//!   report of the result, `-` for a metric missing from the report
+//! - `rejection <reason>`*: reasons the quality gate rejected the result
+//! - `point <C numbers> <C numbers>`*: source then destination coordinates of a correspondence
+//!
+//! # Version 1
+//! As version 2 without the `producer` and `summary` lines, the result being written as
+//! `transform <(C+1)² numbers>`.
+use crate::KabschError;
+
+/// Name starting the header line of a bundle, before the version.
+pub const MAGIC: &str = "kabsch_umeyama-replay";
+
+/// Version of the schema written by this version of the crate.
+pub const VERSION: u32 = 2;
+
+/// Version of the schema of a bundle, read from its header, the `None` value if the text does not
+/// start with a header.
+pub fn version(text: &str) -> Option<u32> {
+    let header = text.lines().next()?;
+    let (magic, version) = header.split_once(' ')?;
+    if magic != MAGIC {
+        return None;
+    }
+    version.parse().ok()
+}
+
+/// Migrate the text of a bundle of any supported version to the [`VERSION`] of this crate, line
+/// for line.
+/// The [`KabschError::InvalidBundle`] error is returned for the header if the text has no header or
+/// a version newer than this crate's.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::schema::{migrate, version, VERSION};
+///
+/// let archived = "kabsch_umeyama-replay 1\ndimensions 1\nestimate_scale false\n\
+///                 transform 1 0.5 0 1\npoint 0 0.5\n";
+/// let migrated = migrate(archived).unwrap();
+/// assert_eq!(version(&migrated), Some(VERSION));
+/// assert!(migrated.contains("\nresult 1 0.5 0 1\n"));
+/// assert_eq!(migrate(&migrated).unwrap(), migrated);
+/// assert!(migrate("kabsch_umeyama-replay 99\n").is_err());
+/// ```
+pub fn migrate(text: &str) -> Result<String, KabschError> {
+    let mut version = match version(text) {
+        Some(version) if (1..=VERSION).contains(&version) => version,
+        _ => return Err(KabschError::InvalidBundle { line: 1 }),
+    };
+    let mut text = text.to_owned();
+    while version < VERSION {
+        text = match version {
+            1 => from_v1(&text),
+            _ => unreachable!("every older version has a migration"),
+        };
+        version += 1;
+    }
+    Ok(text)
+}
+
+/// Version 1 to 2: the `transform` line is renamed `result`.
+fn from_v1(text: &str) -> String {
+    let mut migrated = format!("{MAGIC} 2\n");
+    for line in text.lines().skip(1) {
+        match line.strip_prefix("transform ") {
+            Some(values) => migrated.push_str(&format!("result {values}\n")),
+            None => {
+                migrated.push_str(line);
+                migrated.push('\n');
+            }
+        }
+    }
+    migrated
+}
@@ -0,0 +1,163 @@
+use crate::inspect::Surface;
+use crate::mesh::{Reference, TriangleMesh};
+use nalgebra::Vector3;
+
+/// Signed distance field of a mesh sampled on a regular voxel grid.
+///
+/// Distances are interpolated trilinearly between the grid nodes, and closest points are found by
+/// stepping along the interpolated gradient, so queries cost the same whatever the size of the
+/// mesh. Outside the grid, the distance at the nearest grid point is extended by the distance to
+/// the grid, while closest points are not known there.
+#[derive(Clone, Debug)]
+pub struct VoxelSdf {
+    origin: Vector3<f64>,
+    voxel_size: f64,
+    dims: [usize; 3],
+    values: Vec<f64>,
+}
+
+impl VoxelSdf {
+    /// Largest number of grid nodes sampled by [`VoxelSdf::from_mesh`], 8 GiB of distances.
+    pub const MAX_NODES: usize = 1 << 30;
+
+    /// Sample the signed distance to `mesh` on a grid with the given `voxel_size`, covering the
+    /// bounding box of the mesh enlarged by `padding` on every side.
+    /// The `None` value is returned if `voxel_size` is not positive, `padding` is negative or the
+    /// grid would have more than [`VoxelSdf::MAX_NODES`] nodes.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::inspect::Surface;
+    /// use kabsch_umeyama::mesh::{TriangleMesh, VoxelSdf};
+    ///
+    /// let tetrahedron = TriangleMesh::new(
+    ///     vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+    ///     vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+    /// )
+    /// .unwrap();
+    /// let sdf = VoxelSdf::from_mesh(&tetrahedron, 0.05, 0.2).unwrap();
+    ///
+    /// for point in [[0.2, 0.2, 0.2], [0.5, 0.3, -0.1], [0.6, 0.6, 0.6], [2., 0., 0.]] {
+    ///     let exact = tetrahedron.signed_distance(&point);
+    ///     assert!((sdf.signed_distance(&point) - exact).abs() < 0.05);
+    /// }
+    /// assert!(VoxelSdf::from_mesh(&tetrahedron, 1e-9, 0.).is_none());
+    /// ```
+    pub fn from_mesh(mesh: &TriangleMesh, voxel_size: f64, padding: f64) -> Option<Self> {
+        if !(voxel_size > 0. && padding >= 0.) {
+            return None;
+        }
+        let (min, max) = mesh.vertices().iter().fold(
+            (
+                Vector3::repeat(f64::INFINITY),
+                Vector3::repeat(f64::NEG_INFINITY),
+            ),
+            |(min, max), &v| (min.inf(&v.into()), max.sup(&v.into())),
+        );
+        let origin = min.add_scalar(-padding);
+        let extent = max.add_scalar(padding) - origin;
+        let dims = [0, 1, 2].map(|k| ((extent[k] / voxel_size).ceil() as usize).saturating_add(1));
+        let nodes = dims
+            .iter()
+            .try_fold(1usize, |nodes, &n| nodes.checked_mul(n))
+            .filter(|&nodes| nodes <= Self::MAX_NODES)?;
+        let mut values = Vec::with_capacity(nodes);
+        for k in 0..dims[2] {
+            for j in 0..dims[1] {
+                for i in 0..dims[0] {
+                    let node = origin + Vector3::new(i as f64, j as f64, k as f64) * voxel_size;
+                    values.push(mesh.signed_distance(&node.into()));
+                }
+            }
+        }
+        Some(Self {
+            origin,
+            voxel_size,
+            dims,
+            values,
+        })
+    }
+
+    /// Number of grid nodes along each axis
+    pub fn dims(&self) -> [usize; 3] {
+        self.dims
+    }
+
+    /// Edge length of a voxel
+    pub fn voxel_size(&self) -> f64 {
+        self.voxel_size
+    }
+
+    fn value(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.values[(k * self.dims[1] + j) * self.dims[0] + i]
+    }
+
+    /// Point of the grid nearest to `p`.
+    fn clamp(&self, p: &Vector3<f64>) -> Vector3<f64> {
+        let upper = Vector3::from_fn(|k, _| (self.dims[k] - 1) as f64 * self.voxel_size);
+        self.origin + (p - self.origin).sup(&Vector3::zeros()).inf(&upper)
+    }
+
+    /// Interpolated distance and gradient at a point of the grid.
+    fn sample(&self, p: &Vector3<f64>) -> (f64, Vector3<f64>) {
+        let local = (p - self.origin) / self.voxel_size;
+        let mut cell = [0; 3];
+        let mut f = Vector3::zeros();
+        for k in 0..3 {
+            cell[k] = (local[k].floor().max(0.) as usize).min(self.dims[k].saturating_sub(2));
+            f[k] = (local[k] - cell[k] as f64).clamp(0., 1.);
+        }
+        let mut distance = 0.;
+        let mut gradient = Vector3::zeros();
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+            let [i, j, k] = [0, 1, 2].map(|a| (cell[a] + offset[a]).min(self.dims[a] - 1));
+            let value = self.value(i, j, k);
+            let w = Vector3::from_fn(|a, _| if offset[a] == 1 { f[a] } else { 1. - f[a] });
+            let dw = Vector3::from_fn(|a, _| if offset[a] == 1 { 1. } else { -1. });
+            distance += value * w.product();
+            gradient += Vector3::new(dw.x * w.y * w.z, w.x * dw.y * w.z, w.x * w.y * dw.z) * value;
+        }
+        (distance, gradient / self.voxel_size)
+    }
+}
+
+impl Surface<3> for VoxelSdf {
+    fn signed_distance(&self, point: &[f64; 3]) -> f64 {
+        let p = Vector3::from(*point);
+        let clamped = self.clamp(&p);
+        let (distance, _) = self.sample(&clamped);
+        distance + (p - clamped).norm()
+    }
+}
+
+/// The `None` value is returned for points outside the grid, where the sampled gradient does not
+/// point to the surface.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::mesh::{Reference, TriangleMesh, VoxelSdf};
+///
+/// let tetrahedron = TriangleMesh::new(
+///     vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+///     vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+/// )
+/// .unwrap();
+/// let sdf = VoxelSdf::from_mesh(&tetrahedron, 0.05, 0.2).unwrap();
+///
+/// let closest = sdf.closest(&[0.5, 0.3, -0.1]).unwrap();
+/// assert!((closest - nalgebra::Vector3::new(0.5, 0.3, 0.)).norm() < 0.05);
+/// assert!(sdf.closest(&[3., 0.2, 0.2]).is_none());
+/// ```
+impl Reference for VoxelSdf {
+    fn closest(&self, point: &[f64; 3]) -> Option<Vector3<f64>> {
+        let p = Vector3::from(*point);
+        let local = (p - self.origin) / self.voxel_size;
+        if (0..3).any(|k| !(0. ..=(self.dims[k] - 1) as f64).contains(&local[k])) {
+            return None;
+        }
+        let (distance, gradient) = self.sample(&p);
+        match gradient.try_normalize(f64::EPSILON) {
+            Some(direction) => Some(p - direction * distance),
+            None => Some(p),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Serialization with `serde` (requires the `serde` feature): [`Array2`] as a sequence of rows,
+//! checked against its dimensions when deserialized, and [`crate::Transform`] as its rotation,
+//! translation and scale.
+//! # Examples
+//! ```
+//! use kabsch_umeyama::{estimate_transform, Array2, Transform};
+//!
+//! let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+//! let dst = Array2::from([[1., 1.], [1., 3.], [-1., 1.]]);
+//!
+//! let json = serde_json::to_string(&src).unwrap();
+//! assert_eq!(json, "[[0.0,0.0],[1.0,0.0],[0.0,1.0]]");
+//! let fixture: Array2<3, 2> = serde_json::from_str(&json).unwrap();
+//! assert_eq!(*fixture, *src);
+//! assert!(serde_json::from_str::<Array2<2, 2>>(&json).is_err());
+//!
+//! let t = estimate_transform(src, dst, true).unwrap();
+//! let reloaded: Transform<2> = serde_json::from_str(&serde_json::to_string(&t).unwrap()).unwrap();
+//! assert!((reloaded.to_homogeneous() - t.to_homogeneous()).abs().max() < 1e-12);
+//! ```
+use crate::Array2;
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::ser::{Serialize, Serializer};
+
+impl<const R: usize, const C: usize, T: Serialize> Serialize for Array2<R, C, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|row| &row[..]))
+    }
+}
+
+impl<'de, const R: usize, const C: usize, T: Deserialize<'de>> Deserialize<'de>
+    for Array2<R, C, T>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<T>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|row| {
+                <[T; C]>::try_from(row).map_err(|row| {
+                    D::Error::invalid_length(row.len(), &"as many columns as the array")
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let len = rows.len();
+        rows.try_into()
+            .map(Self)
+            .map_err(|_| D::Error::invalid_length(len, &"as many rows as the array"))
+    }
+}
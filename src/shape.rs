@@ -0,0 +1,899 @@
+//! Geometric morphometrics: comparing configurations of landmarks up to similarity, i.e. by their
+//! shape alone.
+use crate::{Array2, DefaultSolver, Rng, Solver};
+use nalgebra::{DMatrix, SMatrix};
+
+/// Rounds of [`landmark_reliability`], each weighting the alignments by the variances estimated in
+/// the previous one; only the noisiest landmarks are down-weighted, so it settles quickly.
+const RELIABILITY_ROUNDS: usize = 3;
+/// Iterations of the rigid alignment of repeated digitizations to their mean, which differ by
+/// measurement noise only, so that the mean converges within a few iterations.
+const RIGID_GPA_ITERATIONS: usize = 10;
+
+/// Configuration translated to have its centroid at the origin.
+fn centered<const R: usize, const C: usize>(points: &Array2<R, C>) -> SMatrix<f64, R, C> {
+    let mut m = SMatrix::<f64, R, C>::from(*points);
+    for mut column in m.column_iter_mut() {
+        let mean = column.mean();
+        column.add_scalar_mut(-mean);
+    }
+    m
+}
+
+/// Centered configuration scaled to unit centroid size, the `None` value if all points coincide.
+fn preshape<const R: usize, const C: usize>(points: &Array2<R, C>) -> Option<SMatrix<f64, R, C>> {
+    let m = centered(points);
+    let size = m.norm();
+    size.is_normal().then(|| m / size)
+}
+
+/// `tr(Rᵀ A)` for the cross-covariance `A` of two preshapes and the rotation `R` maximising it.
+fn alignment<const R: usize, const C: usize>(
+    a: &SMatrix<f64, R, C>,
+    b: &SMatrix<f64, R, C>,
+) -> f64 {
+    let cross = a.transpose() * b;
+    let cross = DMatrix::from_column_slice(C, C, cross.as_slice());
+    DefaultSolver
+        .rotation(&cross)
+        .map_or(0., |rotation| (rotation.transpose() * cross).trace())
+}
+
+/// Centroid size of a configuration: the square root of the summed squared distances of its points
+/// to their centroid, the size measure under which Procrustes shape coordinates are standardized.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::{baseline_size, centroid_size, mean_centroid_distance};
+/// use kabsch_umeyama::Array2;
+///
+/// let square = Array2::from([[0., 0.], [2., 0.], [2., 2.], [0., 2.]]);
+/// assert!((centroid_size(&square) - 8f64.sqrt()).abs() < 1e-12);
+/// assert!((mean_centroid_distance(&square) - 2f64.sqrt()).abs() < 1e-12);
+/// assert_eq!(baseline_size(&square, 0, 2), Some(8f64.sqrt()));
+/// assert_eq!(baseline_size(&square, 0, 4), None);
+/// ```
+pub fn centroid_size<const R: usize, const C: usize>(shape: &Array2<R, C>) -> f64 {
+    centered(shape).norm()
+}
+
+/// Mean distance of the points of a configuration to their centroid, less sensitive than the
+/// centroid size to a single distant landmark.
+pub fn mean_centroid_distance<const R: usize, const C: usize>(shape: &Array2<R, C>) -> f64 {
+    let m = centered(shape);
+    m.row_iter().map(|row| row.norm()).sum::<f64>() / R.max(1) as f64
+}
+
+/// Baseline size of a configuration: the distance between the landmarks `first` and `second`,
+/// the classic standardization by a reference length.
+/// The `None` value is returned if an index is out of bounds.
+pub fn baseline_size<const R: usize, const C: usize>(
+    shape: &Array2<R, C>,
+    first: usize,
+    second: usize,
+) -> Option<f64> {
+    let (a, b) = (shape.get(first)?, shape.get(second)?);
+    Some(
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f64>()
+            .sqrt(),
+    )
+}
+
+/// Full Procrustes distance between two configurations: the residual distance after scaling,
+/// rotating and translating `b` onto `a`, both scaled to unit centroid size.
+/// It is 0 for identical shapes and at most 1.
+/// The `None` value is returned if all the points of a configuration coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::procrustes_distance;
+/// use kabsch_umeyama::Array2;
+///
+/// let square = Array2::from([[0., 0.], [1., 0.], [1., 1.], [0., 1.]]);
+/// let turned = Array2::from([[5., 5.], [5., 7.], [3., 7.], [3., 5.]]);
+/// let kite = Array2::from([[0., 0.], [1., 0.], [2., 2.], [0., 1.]]);
+///
+/// assert!(procrustes_distance(&square, &turned).unwrap() < 1e-7);
+/// assert!(procrustes_distance(&square, &kite).unwrap() > 0.1);
+/// ```
+pub fn procrustes_distance<const R: usize, const C: usize>(
+    a: &Array2<R, C>,
+    b: &Array2<R, C>,
+) -> Option<f64> {
+    Some(full_distance(&preshape(a)?, &preshape(b)?))
+}
+
+/// Database of configurations queried by full Procrustes distance.
+#[derive(Clone, Debug, Default)]
+pub struct ShapeIndex<const R: usize, const C: usize> {
+    preshapes: Vec<SMatrix<f64, R, C>>,
+}
+
+impl<const R: usize, const C: usize> ShapeIndex<R, C> {
+    /// New empty ShapeIndex
+    pub fn new() -> Self {
+        Self {
+            preshapes: Vec::new(),
+        }
+    }
+
+    /// Add a configuration, returning its index.
+    /// The `None` value is returned, and nothing added, if all its points coincide.
+    pub fn insert(&mut self, shape: &Array2<R, C>) -> Option<usize> {
+        self.preshapes.push(preshape(shape)?);
+        Some(self.preshapes.len() - 1)
+    }
+
+    /// Number of configurations
+    pub fn len(&self) -> usize {
+        self.preshapes.len()
+    }
+
+    /// Whether there are no configurations
+    pub fn is_empty(&self) -> bool {
+        self.preshapes.is_empty()
+    }
+
+    /// The `k` configurations nearest to `shape`, as `(index, distance)` by increasing distance.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::shape::ShapeIndex;
+    /// use kabsch_umeyama::Array2;
+    ///
+    /// let mut index = ShapeIndex::new();
+    /// for height in [1., 2., 3., 4.] {
+    ///     index.insert(&Array2::from([[0., 0.], [1., 0.], [0.5, height]]));
+    /// }
+    /// // a scaled and translated triangle of height 2.9
+    /// let query = Array2::from([[10., 10.], [12., 10.], [11., 15.8]]);
+    /// let nearest = index.query(&query, 2);
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(nearest[0].0, 2);
+    /// assert!(nearest[0].1 < nearest[1].1);
+    /// ```
+    pub fn query(&self, shape: &Array2<R, C>, k: usize) -> Vec<(usize, f64)> {
+        let Some(query) = preshape(shape) else {
+            return Vec::new();
+        };
+        let mut distances = self
+            .preshapes
+            .iter()
+            .map(|p| full_distance(&query, p))
+            .enumerate()
+            .collect::<Vec<_>>();
+        distances.sort_by(|a, b| a.1.total_cmp(&b.1));
+        distances.truncate(k);
+        distances
+    }
+}
+
+/// Configuration as an [`Array2`].
+fn to_array<const R: usize, const C: usize>(m: &SMatrix<f64, R, C>) -> Array2<R, C> {
+    Array2::from(std::array::from_fn(|i| std::array::from_fn(|j| m[(i, j)])))
+}
+
+/// Preshape `b` rotated onto the preshape `a`.
+fn rotate_onto<const R: usize, const C: usize>(
+    a: &SMatrix<f64, R, C>,
+    b: &SMatrix<f64, R, C>,
+) -> SMatrix<f64, R, C> {
+    let cross = a.transpose() * b;
+    match DefaultSolver.rotation(&DMatrix::from_column_slice(C, C, cross.as_slice())) {
+        Some(rotation) => {
+            b * SMatrix::<f64, C, C>::from_column_slice(rotation.as_slice()).transpose()
+        }
+        None => *b,
+    }
+}
+
+fn full_distance<const R: usize, const C: usize>(
+    a: &SMatrix<f64, R, C>,
+    b: &SMatrix<f64, R, C>,
+) -> f64 {
+    let rho = alignment(a, b);
+    (1. - rho * rho).max(0.).sqrt()
+}
+
+/// Result of a generalized Procrustes analysis.
+#[derive(Clone, Debug)]
+pub struct Gpa<const R: usize, const C: usize> {
+    /// Configurations centered, scaled by [`Gpa::scales`] and rotated onto the mean.
+    pub aligned: Vec<Array2<R, C>>,
+    /// Mean shape, of unit centroid size unless computed by [`partial_procrustes`].
+    pub mean: Array2<R, C>,
+    /// Full Procrustes distance of each configuration to the mean, or the Euclidean distance in
+    /// the original units for [`partial_procrustes`].
+    pub distances: Vec<f64>,
+    /// Centroid size of each configuration: the root of the summed squared distances of its points
+    /// to their centroid.
+    pub centroid_sizes: Vec<f64>,
+    /// Scale applied to each configuration: the inverse of its centroid size, or 1 for
+    /// [`partial_procrustes`].
+    pub scales: Vec<f64>,
+    pub iterations: usize,
+}
+
+/// Mean of the configurations rotated onto it, normalized to unit centroid size if `unit`.
+fn procrustes_mean<const R: usize, const C: usize>(
+    preshapes: &[SMatrix<f64, R, C>],
+    max_iterations: usize,
+    tolerance: f64,
+    unit: bool,
+) -> (Vec<SMatrix<f64, R, C>>, SMatrix<f64, R, C>, usize) {
+    let mut mean = preshapes[0];
+    let mut aligned = preshapes.to_vec();
+    let mut iterations = 0;
+    while iterations < max_iterations {
+        iterations += 1;
+        aligned = preshapes.iter().map(|p| rotate_onto(&mean, p)).collect();
+        let sum = aligned.iter().sum::<SMatrix<f64, R, C>>();
+        let next = if unit {
+            sum / sum.norm()
+        } else {
+            sum / preshapes.len() as f64
+        };
+        let change = (next - mean).norm();
+        mean = next;
+        if change < tolerance {
+            break;
+        }
+    }
+    (aligned, mean, iterations)
+}
+
+impl<const R: usize, const C: usize> Gpa<R, C> {
+    /// Indices of the configurations whose distance to the mean exceeds the median distance by more
+    /// than `threshold` robust standard deviations, estimated by [`crate::robust_sigma`], e.g.
+    /// mislabeled or swapped landmarks in an annotation dataset.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::shape::generalized_procrustes;
+    /// use kabsch_umeyama::Array2;
+    ///
+    /// let mut shapes = (0..10)
+    ///     .map(|i| {
+    ///         let h = 1. + 0.01 * i as f64;
+    ///         Array2::from([[0., 0.], [2., 0.], [2., h], [0., h]])
+    ///     })
+    ///     .collect::<Vec<_>>();
+    /// // two landmarks swapped by the annotator
+    /// shapes[4] = Array2::from([[0., 0.], [2., 0.], [0., 1.04], [2., 1.04]]);
+    ///
+    /// let gpa = generalized_procrustes(&shapes, 100, 1e-12).unwrap();
+    /// assert_eq!(gpa.outliers(3.), vec![4]);
+    /// ```
+    pub fn outliers(&self, threshold: f64) -> Vec<usize> {
+        let Some(sigma) = crate::robust_sigma(&self.distances) else {
+            return Vec::new();
+        };
+        let center = crate::robust::median(&mut self.distances.clone());
+        let limit = center + threshold * sigma;
+        self.distances
+            .iter()
+            .enumerate()
+            .filter(|(_, d)| **d > limit)
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// Generalized Procrustes analysis: iteratively rotate every configuration, centered and scaled to
+/// unit centroid size, onto their mean until the mean changes by less than `tolerance`.
+/// The `None` value is returned if there are no configurations or all the points of one coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::generalized_procrustes;
+/// use kabsch_umeyama::Array2;
+///
+/// let shapes = [
+///     Array2::from([[0., 0.], [2., 0.], [1., 1.]]),
+///     Array2::from([[5., 5.], [5., 9.], [3., 7.]]),
+///     Array2::from([[0., 0.], [2., 0.], [1., 1.2]]),
+/// ];
+/// let gpa = generalized_procrustes(&shapes, 100, 1e-12).unwrap();
+/// // the first two are the same shape
+/// assert!((gpa.distances[0] - gpa.distances[1]).abs() < 1e-9);
+/// assert!(gpa.distances[2] > gpa.distances[0]);
+/// ```
+pub fn generalized_procrustes<const R: usize, const C: usize>(
+    shapes: &[Array2<R, C>],
+    max_iterations: usize,
+    tolerance: f64,
+) -> Option<Gpa<R, C>> {
+    let preshapes = shapes.iter().map(preshape).collect::<Option<Vec<_>>>()?;
+    if preshapes.is_empty() {
+        return None;
+    }
+    let (aligned, mean, iterations) = procrustes_mean(&preshapes, max_iterations, tolerance, true);
+    let centroid_sizes = shapes.iter().map(centroid_size).collect::<Vec<_>>();
+    Some(Gpa {
+        distances: preshapes.iter().map(|p| full_distance(&mean, p)).collect(),
+        aligned: aligned.iter().map(to_array).collect(),
+        mean: to_array(&mean),
+        scales: centroid_sizes.iter().map(|s| 1. / s).collect(),
+        centroid_sizes,
+        iterations,
+    })
+}
+
+/// Generalized Procrustes analysis without scaling: configurations are only centered and rotated
+/// onto their mean, keeping their size, which is often the variable of interest.
+/// The `None` value is returned if there are no configurations.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::partial_procrustes;
+/// use kabsch_umeyama::Array2;
+///
+/// let small = Array2::from([[0., 0.], [2., 0.], [1., 1.]]);
+/// let large = Array2::from([[5., 5.], [5., 9.], [3., 7.]]);
+/// let gpa = partial_procrustes(&[small, large], 100, 1e-12).unwrap();
+///
+/// assert!((gpa.centroid_sizes[1] / gpa.centroid_sizes[0] - 2.).abs() < 1e-12);
+/// assert_eq!(gpa.scales, vec![1., 1.]);
+/// // the mean is halfway in size and the shapes are a size difference apart
+/// assert!((gpa.distances[0] - gpa.centroid_sizes[0] / 2.).abs() < 1e-9);
+/// ```
+pub fn partial_procrustes<const R: usize, const C: usize>(
+    shapes: &[Array2<R, C>],
+    max_iterations: usize,
+    tolerance: f64,
+) -> Option<Gpa<R, C>> {
+    if shapes.is_empty() {
+        return None;
+    }
+    let configurations = shapes.iter().map(centered).collect::<Vec<_>>();
+    let (aligned, mean, iterations) =
+        procrustes_mean(&configurations, max_iterations, tolerance, false);
+    Some(Gpa {
+        distances: aligned.iter().map(|a| (a - mean).norm()).collect(),
+        aligned: aligned.iter().map(to_array).collect(),
+        mean: to_array(&mean),
+        centroid_sizes: configurations.iter().map(|c| c.norm()).collect(),
+        scales: vec![1.; shapes.len()],
+        iterations,
+    })
+}
+
+/// Mean shape updated incrementally as configurations arrive (online generalized Procrustes
+/// analysis), in constant memory and time per configuration.
+///
+/// Each configuration is rotated onto the current mean and added to a running sum, whose
+/// normalization is the new mean. Earlier configurations are not realigned to the later means, so
+/// the result approximates [`generalized_procrustes`], closely once the mean has stabilized.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::{generalized_procrustes, procrustes_distance, OnlineGpa};
+/// use kabsch_umeyama::Rng;
+/// use kabsch_umeyama::Array2;
+///
+/// let mut rng = Rng::new(5);
+/// let shapes = (0..200)
+///     .map(|i| {
+///         let (s, c) = (i as f64).sin_cos();
+///         let base = [[0., 0.], [2., 0.], [2., 1.], [0.5, 1.5]];
+///         Array2::<4, 2>::from(base.map(|[x, y]| {
+///             let (x, y) = (x + 0.05 * rng.normal(), y + 0.05 * rng.normal());
+///             [c * x - s * y + i as f64, s * x + c * y]
+///         }))
+///     })
+///     .collect::<Vec<_>>();
+///
+/// let mut online = OnlineGpa::new();
+/// for shape in &shapes {
+///     online.insert(shape).unwrap();
+/// }
+/// let batch = generalized_procrustes(&shapes, 100, 1e-12).unwrap();
+/// assert_eq!(online.len(), 200);
+/// assert!(procrustes_distance(&online.mean().unwrap(), &batch.mean).unwrap() < 1e-3);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OnlineGpa<const R: usize, const C: usize> {
+    sum: Option<SMatrix<f64, R, C>>,
+    count: usize,
+}
+
+impl<const R: usize, const C: usize> OnlineGpa<R, C> {
+    /// New OnlineGpa without configurations
+    pub fn new() -> Self {
+        Self {
+            sum: None,
+            count: 0,
+        }
+    }
+
+    /// Add a configuration, returning its full Procrustes distance to the mean before the update.
+    /// The `None` value is returned, and nothing added, if all its points coincide.
+    pub fn insert(&mut self, shape: &Array2<R, C>) -> Option<f64> {
+        let preshape = preshape(shape)?;
+        let distance = match &mut self.sum {
+            Some(sum) => {
+                let mean = *sum / sum.norm();
+                *sum += rotate_onto(&mean, &preshape);
+                full_distance(&mean, &preshape)
+            }
+            None => {
+                self.sum = Some(preshape);
+                0.
+            }
+        };
+        self.count += 1;
+        Some(distance)
+    }
+
+    /// Number of configurations added
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Whether no configurations were added
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Current mean shape, of unit centroid size, the `None` value if no configurations were added.
+    pub fn mean(&self) -> Option<Array2<R, C>> {
+        self.sum.map(|sum| to_array(&(sum / sum.norm())))
+    }
+}
+
+/// Partition of configurations into clusters.
+#[derive(Clone, Debug)]
+pub struct Clustering<const R: usize, const C: usize> {
+    /// Cluster of each configuration.
+    pub labels: Vec<usize>,
+    /// Procrustes mean shape of each cluster.
+    pub means: Vec<Array2<R, C>>,
+    /// Sum of the squared full Procrustes distances to the cluster means.
+    pub cost: f64,
+}
+
+/// K-means clustering of configurations under the full Procrustes distance, with the cluster
+/// centers updated to the Procrustes means of their members and seeded by k-means++.
+/// The `None` value is returned if `k` is zero or exceeds the number of configurations, or all the
+/// points of a configuration coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::kmeans;
+/// use kabsch_umeyama::Rng;
+/// use kabsch_umeyama::Array2;
+///
+/// // flat and tall triangles, in various poses
+/// let shapes = [
+///     Array2::from([[0., 0.], [4., 0.], [2., 1.]]),
+///     Array2::from([[0., 0.], [0., 8.], [-2.1, 4.]]),
+///     Array2::from([[0., 0.], [1., 0.], [0.5, 2.]]),
+///     Array2::from([[3., 3.], [3., 7.], [2., 5.]]),
+///     Array2::from([[0., 0.], [2., 0.], [1., 4.1]]),
+/// ];
+/// let clustering = kmeans(&shapes, 2, 20, &mut Rng::new(1)).unwrap();
+/// let l = &clustering.labels;
+/// assert!(l[0] == l[1] && l[1] == l[3] && l[2] == l[4] && l[0] != l[2]);
+/// ```
+pub fn kmeans<const R: usize, const C: usize>(
+    shapes: &[Array2<R, C>],
+    k: usize,
+    max_iterations: usize,
+    rng: &mut Rng,
+) -> Option<Clustering<R, C>> {
+    let preshapes = shapes.iter().map(preshape).collect::<Option<Vec<_>>>()?;
+    if k == 0 || k > preshapes.len() {
+        return None;
+    }
+    // k-means++ seeding
+    let mut means = vec![preshapes[(rng.next_u64() % preshapes.len() as u64) as usize]];
+    while means.len() < k {
+        let weights = preshapes
+            .iter()
+            .map(|p| {
+                means
+                    .iter()
+                    .map(|m| full_distance(m, p).powi(2))
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .collect::<Vec<_>>();
+        let mut target = rng.uniform() * weights.iter().sum::<f64>();
+        let chosen = weights
+            .iter()
+            .position(|w| {
+                target -= w;
+                target <= 0.
+            })
+            .unwrap_or(preshapes.len() - 1);
+        means.push(preshapes[chosen]);
+    }
+
+    let assign = |means: &[SMatrix<f64, R, C>]| {
+        preshapes
+            .iter()
+            .map(|p| {
+                means
+                    .iter()
+                    .map(|m| full_distance(m, p))
+                    .enumerate()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut assignment = assign(&means);
+    for _ in 0..max_iterations {
+        for (cluster, mean) in means.iter_mut().enumerate() {
+            let members = preshapes
+                .iter()
+                .zip(&assignment)
+                .filter(|(_, a)| a.0 == cluster)
+                .map(|(p, _)| *p)
+                .collect::<Vec<_>>();
+            if !members.is_empty() {
+                let (_, m, _) = procrustes_mean(&members, 100, 1e-12, true);
+                *mean = m;
+            }
+        }
+        let next = assign(&means);
+        let changed = next.iter().zip(&assignment).any(|(a, b)| a.0 != b.0);
+        assignment = next;
+        if !changed {
+            break;
+        }
+    }
+    Some(Clustering {
+        labels: assignment.iter().map(|a| a.0).collect(),
+        cost: assignment.iter().map(|a| a.1 * a.1).sum(),
+        means: means.iter().map(to_array).collect(),
+    })
+}
+
+/// Merge of two clusters in a [`Dendrogram`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Merge {
+    /// Clusters merged: indices below the number of configurations are single configurations, the
+    /// others refer to the cluster formed by merge `index - n`.
+    pub left: usize,
+    pub right: usize,
+    /// Average full Procrustes distance between the members of the two clusters.
+    pub distance: f64,
+    /// Number of configurations in the merged cluster.
+    pub size: usize,
+}
+
+/// Hierarchy of the merges of an agglomerative clustering, in increasing distance.
+#[derive(Clone, Debug)]
+pub struct Dendrogram {
+    pub merges: Vec<Merge>,
+}
+
+impl Dendrogram {
+    /// Cluster labels (`0..k`) obtained by undoing the last `k - 1` merges.
+    pub fn labels(&self, k: usize) -> Vec<usize> {
+        let n = self.merges.len() + 1;
+        let mut parent = (0..2 * n - 1).collect::<Vec<_>>();
+        for (i, merge) in self
+            .merges
+            .iter()
+            .take(n.saturating_sub(k.max(1)))
+            .enumerate()
+        {
+            parent[merge.left] = n + i;
+            parent[merge.right] = n + i;
+        }
+        let root = |mut i: usize| {
+            while parent[i] != i {
+                i = parent[i];
+            }
+            i
+        };
+        let mut roots: Vec<usize> = Vec::new();
+        (0..n)
+            .map(|i| {
+                let r = root(i);
+                roots.iter().position(|x| *x == r).unwrap_or_else(|| {
+                    roots.push(r);
+                    roots.len() - 1
+                })
+            })
+            .collect()
+    }
+}
+
+/// Average-linkage agglomerative clustering of configurations under the full Procrustes distance.
+/// The `None` value is returned if there are no configurations or all the points of one coincide.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::hierarchical;
+/// use kabsch_umeyama::Array2;
+///
+/// let shapes = [
+///     Array2::from([[0., 0.], [4., 0.], [2., 1.]]),
+///     Array2::from([[0., 0.], [1., 0.], [0.5, 2.]]),
+///     Array2::from([[0., 0.], [0., 8.], [-2.1, 4.]]),
+///     Array2::from([[0., 0.], [2., 0.], [1., 4.1]]),
+/// ];
+/// let dendrogram = hierarchical(&shapes).unwrap();
+/// assert_eq!(dendrogram.merges.len(), 3);
+/// assert_eq!(dendrogram.labels(2), vec![0, 1, 0, 1]);
+/// ```
+pub fn hierarchical<const R: usize, const C: usize>(shapes: &[Array2<R, C>]) -> Option<Dendrogram> {
+    let preshapes = shapes.iter().map(preshape).collect::<Option<Vec<_>>>()?;
+    let n = preshapes.len();
+    if n == 0 {
+        return None;
+    }
+    let pairwise = DMatrix::from_fn(n, n, |i, j| full_distance(&preshapes[i], &preshapes[j]));
+    // active clusters as (id, members)
+    let mut clusters = (0..n).map(|i| (i, vec![i])).collect::<Vec<_>>();
+    let mut merges = Vec::with_capacity(n - 1);
+    while clusters.len() > 1 {
+        let linkage = |a: &[usize], b: &[usize]| {
+            a.iter()
+                .flat_map(|i| b.iter().map(move |j| (*i, *j)))
+                .map(|(i, j)| pairwise[(i, j)])
+                .sum::<f64>()
+                / (a.len() * b.len()) as f64
+        };
+        let (x, y, distance) = (0..clusters.len())
+            .flat_map(|x| (x + 1..clusters.len()).map(move |y| (x, y)))
+            .map(|(x, y)| (x, y, linkage(&clusters[x].1, &clusters[y].1)))
+            .min_by(|a, b| a.2.total_cmp(&b.2))
+            .unwrap();
+        let (right, mut members) = clusters.remove(y);
+        let (left, others) = &clusters[x];
+        members.extend(others);
+        merges.push(Merge {
+            left: *left,
+            right,
+            distance,
+            size: members.len(),
+        });
+        clusters[x] = (n + merges.len() - 1, members);
+    }
+    Some(Dendrogram { merges })
+}
+
+/// Measurement variance of each landmark, estimated from repeated digitizations.
+#[derive(Clone, Copy, Debug)]
+pub struct LandmarkReliability<const R: usize> {
+    /// Variance of each coordinate of each landmark (squared length units).
+    pub variance: [f64; R],
+    /// Degrees of freedom of the estimate: the number of digitizations minus the number of
+    /// specimens.
+    pub degrees_of_freedom: usize,
+}
+
+/// Estimate the measurement variance of each landmark from repeated digitizations of the same
+/// specimens, one slice of repeats per specimen.
+///
+/// The repeats of each specimen are rigidly aligned to their mean, since they are digitized in
+/// arbitrary poses but at the same scale, and the deviations from the mean are pooled across
+/// specimens. Each landmark is measured after aligning on the other landmarks only, so at least
+/// `C + 1` landmarks are needed. The `None` value is returned if no specimen has at least 2
+/// digitizations or an alignment is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::landmark_reliability;
+/// use kabsch_umeyama::Rng;
+/// use kabsch_umeyama::Array2;
+///
+/// // the third landmark is hard to locate
+/// let mut rng = Rng::new(2);
+/// let specimen = [[0., 0.], [10., 0.], [5., 8.], [2., 6.]];
+/// let repeats = (0..20)
+///     .map(|_| {
+///         let sigma = [0.05, 0.05, 0.5, 0.05];
+///         let noisy = |i: usize| specimen[i].map(|v| v + sigma[i] * rng.normal());
+///         Array2::<4, 2>::from(std::array::from_fn(noisy))
+///     })
+///     .collect::<Vec<_>>();
+///
+/// let reliability = landmark_reliability(&[repeats]).unwrap();
+/// let weights = reliability.weights();
+/// assert!(weights[2] < weights[0] / 10.);
+/// assert!((reliability.variance[2].sqrt() - 0.5).abs() < 0.15);
+/// ```
+pub fn landmark_reliability<const R: usize, const C: usize>(
+    specimens: &[Vec<Array2<R, C>>],
+) -> Option<LandmarkReliability<R>> {
+    let specimens = specimens
+        .iter()
+        .filter(|r| r.len() >= 2)
+        .map(|r| r.iter().map(|p| SMatrix::<f64, R, C>::from(*p)).collect())
+        .collect::<Vec<Vec<_>>>();
+    let degrees_of_freedom = specimens.iter().map(|r| r.len() - 1).sum::<usize>();
+    if degrees_of_freedom == 0 {
+        return None;
+    }
+    // a landmark included in the alignments pulls them towards its own noise, which hides part of
+    // its variance, so each landmark is measured after aligning on the others only, weighted by
+    // the estimate of the previous round
+    let mut reliability = LandmarkReliability {
+        variance: [1.; R],
+        degrees_of_freedom,
+    };
+    for _ in 0..RELIABILITY_ROUNDS {
+        let mut variance = [0.; R];
+        for (landmark, v) in variance.iter_mut().enumerate() {
+            let mut weights = nalgebra::DVector::from_row_slice(&reliability.weights());
+            weights[landmark] = 0.;
+            let mut squared = 0.;
+            for points in &specimens {
+                let (aligned, mean) = rigid_gpa(points, &weights)?;
+                squared += aligned
+                    .iter()
+                    .map(|a| (a.row(landmark) - mean.row(landmark)).norm_squared())
+                    .sum::<f64>();
+            }
+            *v = squared / (C * degrees_of_freedom) as f64;
+        }
+        reliability.variance = variance;
+    }
+    Some(reliability)
+}
+
+/// Configurations rigidly aligned to their mean with weighted landmarks, and that mean.
+fn rigid_gpa<const R: usize, const C: usize>(
+    points: &[SMatrix<f64, R, C>],
+    weights: &nalgebra::DVector<f64>,
+) -> Option<(Vec<SMatrix<f64, R, C>>, SMatrix<f64, R, C>)> {
+    let mut mean = points[0];
+    let mut aligned = points.to_vec();
+    for _ in 0..RIGID_GPA_ITERATIONS {
+        aligned = points
+            .iter()
+            .map(|p| {
+                let fit = crate::fit_points(
+                    crate::rows(*p),
+                    crate::rows(mean),
+                    weights,
+                    false,
+                    &DefaultSolver,
+                )
+                .ok()?;
+                let t = fit.to_transform::<C>();
+                let mut moved = p * t.rotation().transpose();
+                moved
+                    .row_iter_mut()
+                    .for_each(|mut row| row += t.translation().transpose());
+                Some(moved)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        mean = aligned.iter().sum::<SMatrix<f64, R, C>>() / aligned.len() as f64;
+    }
+    Some((aligned, mean))
+}
+
+impl<const R: usize> LandmarkReliability<R> {
+    /// Inverse-variance weight of each landmark, normalized to a mean of 1
+    pub fn weights(&self) -> [f64; R] {
+        let inverse = self.variance.map(|v| 1. / v.max(f64::MIN_POSITIVE));
+        let mean = inverse.iter().sum::<f64>() / R.max(1) as f64;
+        inverse.map(|w| w / mean)
+    }
+
+    /// Estimate a similarity transformation like [`crate::estimate_transform`], weighting each
+    /// landmark by its [`LandmarkReliability::weights`].
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::shape::LandmarkReliability;
+    /// use kabsch_umeyama::Array2;
+    ///
+    /// let reliability = LandmarkReliability {
+    ///     variance: [0.01, 0.01, 0.01, 1.],
+    ///     degrees_of_freedom: 10,
+    /// };
+    /// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+    /// // the unreliable landmark is off by 0.3
+    /// let dst = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1.3, 1.]]);
+    /// let t = reliability.estimate(src, dst, false).unwrap();
+    /// assert!(t.translation().norm() < 0.01);
+    /// ```
+    pub fn estimate<const C: usize>(
+        &self,
+        src: impl Into<SMatrix<f64, R, C>>,
+        dst: impl Into<SMatrix<f64, R, C>>,
+        estimate_scale: bool,
+    ) -> Option<crate::Transform<C>> {
+        crate::fit_points(
+            crate::rows(src.into()),
+            crate::rows(dst.into()),
+            &nalgebra::DVector::from_row_slice(&self.weights()),
+            estimate_scale,
+            &DefaultSolver,
+        )
+        .ok()
+        .map(|fit| fit.to_transform())
+    }
+}
+
+/// Full Procrustes distance between two configurations restricted to the landmarks `common`.
+fn partial_distance<const R: usize, const C: usize>(
+    a: &Array2<R, C>,
+    b: &Array2<R, C>,
+    common: &[usize],
+) -> Option<f64> {
+    let subset = |points: &Array2<R, C>| {
+        let mut m = DMatrix::from_fn(common.len(), C, |i, j| points[common[i]][j]);
+        for mut column in m.column_iter_mut() {
+            let mean = column.mean();
+            column.add_scalar_mut(-mean);
+        }
+        let size = m.norm();
+        size.is_normal().then(|| m / size)
+    };
+    let (a, b) = (subset(a)?, subset(b)?);
+    let cross = a.transpose() * b;
+    let rho = DefaultSolver
+        .rotation(&cross)
+        .map_or(0., |rotation| (rotation.transpose() * cross).trace());
+    Some((1. - rho * rho).max(0.).sqrt())
+}
+
+/// Symmetric matrix of the full Procrustes distances between all pairs of configurations, the
+/// input of most multivariate analyses of shape (ordination, clustering, permutation tests).
+///
+/// Missing landmarks are marked by non-finite coordinates; each pair is compared on the landmarks
+/// present in both. Entries are `NaN` for pairs sharing fewer than 3 landmarks or whose shared
+/// points coincide. Rows are computed on all available threads.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::shape::{distance_matrix, procrustes_distance};
+/// use kabsch_umeyama::Array2;
+///
+/// let square = Array2::from([[0., 0.], [1., 0.], [1., 1.], [0., 1.], [0.5, 2.]]);
+/// let kite = Array2::from([[0., 0.], [1., 0.], [2., 2.], [0., 1.], [0.5, 3.]]);
+/// // the square with its last landmark missing
+/// let partial = Array2::from([[3., 3.], [3., 5.], [1., 5.], [1., 3.], [f64::NAN, f64::NAN]]);
+///
+/// let d = distance_matrix(&[square, kite, partial]);
+/// assert_eq!(d[(0, 0)], 0.);
+/// assert_eq!(d[(0, 1)], d[(1, 0)]);
+/// assert!((d[(0, 1)] - procrustes_distance(&square, &kite).unwrap()).abs() < 1e-12);
+/// assert!(d[(0, 2)] < 1e-7);
+/// assert!(d[(1, 2)] > 0.1);
+/// ```
+pub fn distance_matrix<const R: usize, const C: usize>(shapes: &[Array2<R, C>]) -> DMatrix<f64> {
+    let n = shapes.len();
+    let present = shapes
+        .iter()
+        .map(|s| s.map(|p| p.iter().all(|v| v.is_finite())))
+        .collect::<Vec<_>>();
+    let row = |i: usize| {
+        (i + 1..n)
+            .map(|j| {
+                let common = (0..R)
+                    .filter(|&k| present[i][k] && present[j][k])
+                    .collect::<Vec<_>>();
+                if common.len() < 3 {
+                    return f64::NAN;
+                }
+                partial_distance(&shapes[i], &shapes[j], &common).unwrap_or(f64::NAN)
+            })
+            .collect::<Vec<_>>()
+    };
+    let threads = std::thread::available_parallelism().map_or(1, |t| t.get());
+    // rows shrink with their index, so they are dealt out round-robin to balance the threads
+    let rows = std::thread::scope(|scope| {
+        let handles = (0..threads.min(n.max(1)))
+            .map(|t| {
+                let row = &row;
+                scope.spawn(move || {
+                    (t..n)
+                        .step_by(threads)
+                        .map(|i| (i, row(i)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|h| h.join().expect("distance thread panicked"))
+            .collect::<Vec<_>>()
+    });
+    let mut distances = DMatrix::zeros(n, n);
+    for (i, row) in rows {
+        for (j, d) in (i + 1..n).zip(row) {
+            distances[(i, j)] = d;
+            distances[(j, i)] = d;
+        }
+    }
+    distances
+}
@@ -0,0 +1,152 @@
+//! Compatibility shim reproducing `skimage.transform` (requires the `skimage-compat` feature), to
+//! port Python pipelines and cross-validate them against this crate bit for bit where possible.
+//!
+//! Unlike the rest of the crate, failures are not reported as errors: like scikit-image, the
+//! estimated matrix is filled with NaN and `estimate` returns `false`. The rank of the
+//! cross-covariance is computed like `numpy.linalg.matrix_rank`, relative to its largest singular
+//! value, instead of the absolute tolerance of [`crate::estimate`], and a cross-covariance of rank
+//! lower than the dimension minus one keeps the reflection sign given by its determinant.
+use nalgebra::{DMatrix, DVector};
+
+/// Port of `skimage.transform._geometric._umeyama`: the `(D+1)x(D+1)` homogeneous matrix of the
+/// similarity transformation between the rows of `src` and `dst` of `D` columns, filled with NaN
+/// if the cross-covariance has rank 0.
+///
+/// Inputs on which NumPy raises also give NaN: point sets of different shapes, and non-finite
+/// points whose SVD does not converge.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::skimage::umeyama;
+/// use nalgebra::DMatrix;
+///
+/// let src = DMatrix::from_row_slice(3, 2, &[0., 0., 1., 0., 0., 1.]);
+/// let dst = DMatrix::from_row_slice(3, 2, &[1., 1., 1., 3., -1., 1.]);
+/// let t = umeyama(&src, &dst, true);
+/// assert!((t[(1, 0)] - 2.).abs() < 1e-12 && (t[(0, 2)] - 1.).abs() < 1e-12);
+///
+/// let collapsed = DMatrix::from_element(3, 2, 1.);
+/// assert!(umeyama(&src, &collapsed, true).iter().all(|v| v.is_nan()));
+/// ```
+pub fn umeyama(src: &DMatrix<f64>, dst: &DMatrix<f64>, estimate_scale: bool) -> DMatrix<f64> {
+    let (num, dim) = src.shape();
+    let nan = DMatrix::from_element(dim + 1, dim + 1, f64::NAN);
+    let finite = src.iter().chain(dst.iter()).all(|v| v.is_finite());
+    if dst.shape() != (num, dim) || num == 0 || dim == 0 || !finite {
+        return nan;
+    }
+    let src_mean = src.row_mean();
+    let dst_mean = dst.row_mean();
+    let src_demean = DMatrix::from_fn(num, dim, |i, j| src[(i, j)] - src_mean[j]);
+    let dst_demean = DMatrix::from_fn(num, dim, |i, j| dst[(i, j)] - dst_mean[j]);
+    let a = dst_demean.transpose() * &src_demean / num as f64;
+
+    let mut d = DVector::from_element(dim, 1.);
+    if a.determinant() < 0. {
+        d[dim - 1] = -1.;
+    }
+    let Some(mut svd) = a.clone().try_svd(true, true, f64::EPSILON, 0) else {
+        return nan;
+    };
+    svd.sort_by_singular_values();
+    let (Some(u), Some(v)) = (svd.u, svd.v_t) else {
+        return nan;
+    };
+    let s = svd.singular_values;
+    // numpy.linalg.matrix_rank
+    let tol = s.max() * dim as f64 * f64::EPSILON;
+    let rank = s.iter().filter(|v| **v > tol).count();
+
+    let mut t = DMatrix::identity(dim + 1, dim + 1);
+    if rank == 0 {
+        return nan;
+    } else if rank == dim - 1 {
+        if u.determinant() * v.determinant() > 0. {
+            t.view_mut((0, 0), (dim, dim)).copy_from(&(&u * &v));
+        } else {
+            let sign = d[dim - 1];
+            d[dim - 1] = -1.;
+            let rotation = &u * DMatrix::from_diagonal(&d) * &v;
+            t.view_mut((0, 0), (dim, dim)).copy_from(&rotation);
+            d[dim - 1] = sign;
+        }
+    } else {
+        let rotation = &u * DMatrix::from_diagonal(&d) * &v;
+        t.view_mut((0, 0), (dim, dim)).copy_from(&rotation);
+    }
+
+    let scale = if estimate_scale {
+        // src_demean.var(axis=0).sum()
+        let variance = src_demean.norm_squared() / num as f64;
+        1. / variance * s.dot(&d)
+    } else {
+        1.
+    };
+    let translation =
+        dst_mean.transpose() - t.view((0, 0), (dim, dim)) * src_mean.transpose() * scale;
+    t.view_mut((0, dim), (dim, 1)).copy_from(&translation);
+    t.view_mut((0, 0), (dim, dim)).scale_mut(scale);
+    if t.iter().any(|v| !v.is_finite()) {
+        return nan;
+    }
+    t
+}
+
+/// Port of `skimage.transform.SimilarityTransform`, in any number of dimensions.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::skimage::SimilarityTransform;
+/// use nalgebra::DMatrix;
+///
+/// let src = DMatrix::from_row_slice(3, 2, &[0., 0., 1., 0., 0., 1.]);
+/// let dst = DMatrix::from_row_slice(3, 2, &[1., 1., 1., 3., -1., 1.]);
+/// let mut tform = SimilarityTransform::new(2);
+/// assert!(tform.estimate(&src, &dst));
+/// assert!((tform.params[(0, 1)] + 2.).abs() < 1e-12);
+///
+/// assert!(!tform.estimate(&src, &DMatrix::zeros(3, 2)));
+/// assert!(tform.params.iter().all(|v| v.is_nan()));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimilarityTransform {
+    /// Homogeneous matrix, like `SimilarityTransform.params`
+    pub params: DMatrix<f64>,
+}
+
+impl SimilarityTransform {
+    /// Identity transformation in `dimensionality` dimensions
+    pub fn new(dimensionality: usize) -> Self {
+        Self {
+            params: DMatrix::identity(dimensionality + 1, dimensionality + 1),
+        }
+    }
+
+    /// Replace the parameters by the estimate of [`umeyama`] with a scale, returning whether it
+    /// succeeded, i.e. contains no NaN.
+    pub fn estimate(&mut self, src: &DMatrix<f64>, dst: &DMatrix<f64>) -> bool {
+        self.params = umeyama(src, dst, true);
+        !self.params.iter().any(|v| v.is_nan())
+    }
+}
+
+/// Port of `skimage.transform.EuclideanTransform`, in any number of dimensions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EuclideanTransform {
+    /// Homogeneous matrix, like `EuclideanTransform.params`
+    pub params: DMatrix<f64>,
+}
+
+impl EuclideanTransform {
+    /// Identity transformation in `dimensionality` dimensions
+    pub fn new(dimensionality: usize) -> Self {
+        Self {
+            params: DMatrix::identity(dimensionality + 1, dimensionality + 1),
+        }
+    }
+
+    /// Replace the parameters by the estimate of [`umeyama`] without scale, returning whether it
+    /// succeeded, i.e. contains no NaN.
+    pub fn estimate(&mut self, src: &DMatrix<f64>, dst: &DMatrix<f64>) -> bool {
+        self.params = umeyama(src, dst, false);
+        !self.params.iter().any(|v| v.is_nan())
+    }
+}
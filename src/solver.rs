@@ -0,0 +1,305 @@
+//! Solvers for the "nearest rotation from covariance" step of the Kabsch-Umeyama algorithm.
+//!
+//! Given the `C×C` cross-covariance `A` of the centered point sets, a [`Solver`] returns the proper
+//! rotation `R` (`det(R) = 1`) maximising `trace(Rᵀ A)`. Every estimator in this crate accepts any
+//! solver at runtime through `&dyn Solver`.
+//!
+//! The estimators that do not take a solver use [`DefaultSolver`]: LAPACK's SVD with the default
+//! `lapack` feature, nalgebra's SVD otherwise, so that the crate builds without a Fortran toolchain.
+use crate::KabschError;
+#[cfg(not(any(feature = "std", test)))]
+use nalgebra::{ComplexField, RealField};
+use nalgebra::{
+    DMatrix, DVector, Matrix3, Matrix4, Quaternion, SymmetricEigen, UnitQuaternion, Vector3,
+};
+#[cfg(feature = "lapack")]
+use nalgebra_lapack::SVD;
+
+/// Singular values below this threshold are treated as zero when computing the rank.
+pub(crate) const RANK_TOL: f64 = 1e-5;
+
+/// Iteration limit of nalgebra's SVD, which otherwise iterates forever on some inputs.
+pub(crate) const MAX_SVD_ITERATIONS: usize = 1000;
+
+/// Computes the proper rotation closest to a cross-covariance matrix.
+pub trait Solver {
+    /// Proper rotation `R` maximising `trace(Rᵀ A)` for the square cross-covariance `a`.
+    /// The `None` value is returned if the rotation cannot be determined.
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>>;
+}
+
+/// SVD computed by LAPACK (the default solver with the `lapack` feature).
+#[cfg(feature = "lapack")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LapackSvd;
+
+/// SVD backend selected at compile time: [`LapackSvd`] with the `lapack` feature, [`NalgebraSvd`]
+/// otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DefaultSolver;
+
+/// SVD computed by nalgebra in pure Rust.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{NalgebraSvd, Solver};
+/// use nalgebra::DMatrix;
+///
+/// let a = DMatrix::from_row_slice(3, 3, &[2., 0., 0., 0., 1., 0., 0., 0., f64::NAN]);
+/// assert!(NalgebraSvd.rotation(&a).is_none());
+/// let a = DMatrix::from_row_slice(2, 2, &[0., -1., 1., f64::INFINITY]);
+/// assert!(NalgebraSvd.rotation(&a).is_none());
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NalgebraSvd;
+
+/// Closed-form solver for 3 dimensions, based on the analytic eigen-decomposition of `AᵀA`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Analytic3x3;
+
+/// Horn's quaternion eigen-method for 3 dimensions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QuaternionEigen;
+
+#[cfg(feature = "lapack")]
+impl Solver for LapackSvd {
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>> {
+        if !is_finite(a) {
+            return None;
+        }
+        let svd = SVD::new(a.clone())?;
+        rotation_from_svd(a, svd.u, svd.vt, RANK_TOL)
+    }
+}
+
+impl Solver for DefaultSolver {
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>> {
+        #[cfg(feature = "lapack")]
+        return LapackSvd.rotation(a);
+        #[cfg(not(feature = "lapack"))]
+        return NalgebraSvd.rotation(a);
+    }
+}
+
+impl Solver for NalgebraSvd {
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>> {
+        nalgebra_rotation(a, RANK_TOL)
+    }
+}
+
+/// SVD of the [`DefaultSolver`] backend, with singular values below `rank_tol` instead of
+/// [`RANK_TOL`] treated as zero, and without the determinant sign correction if
+/// `allow_reflection`, i.e. the nearest orthogonal matrix.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TolerantSvd {
+    pub(crate) rank_tol: f64,
+    pub(crate) allow_reflection: bool,
+}
+
+impl Solver for TolerantSvd {
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>> {
+        if !is_finite(a) {
+            return None;
+        }
+        #[cfg(feature = "lapack")]
+        let (u, vt) = {
+            let svd = SVD::new(a.clone())?;
+            (svd.u, svd.vt)
+        };
+        #[cfg(not(feature = "lapack"))]
+        let (u, vt) = {
+            let mut svd = a
+                .clone()
+                .try_svd(true, true, f64::EPSILON, MAX_SVD_ITERATIONS)?;
+            svd.sort_by_singular_values();
+            (svd.u?, svd.v_t?)
+        };
+        if !self.allow_reflection {
+            return rotation_from_svd(a, u, vt, self.rank_tol);
+        }
+        (a.rank(self.rank_tol) > 0).then(|| u * vt)
+    }
+}
+
+fn nalgebra_rotation(a: &DMatrix<f64>, rank_tol: f64) -> Option<DMatrix<f64>> {
+    if !is_finite(a) {
+        return None;
+    }
+    let mut svd = a
+        .clone()
+        .try_svd(true, true, f64::EPSILON, MAX_SVD_ITERATIONS)?;
+    svd.sort_by_singular_values();
+    rotation_from_svd(a, svd.u?, svd.v_t?, rank_tol)
+}
+
+/// Rotation of `solver` for the cross-covariance `a`, its failure reported as a
+/// [`KabschError::DegenerateConfiguration`] error if `a` has rank zero within `rank_tol`, and as a
+/// [`KabschError::SvdFailed`] error otherwise. A [`KabschError::NonFinite`] error is returned if
+/// an entry of `a` is not finite.
+pub(crate) fn rotation_or_error(
+    solver: &dyn Solver,
+    a: &DMatrix<f64>,
+    rank_tol: f64,
+) -> Result<DMatrix<f64>, KabschError> {
+    if !is_finite(a) {
+        return Err(KabschError::NonFinite);
+    }
+    solver.rotation(a).ok_or_else(|| {
+        if a.rank(rank_tol) == 0 {
+            KabschError::DegenerateConfiguration
+        } else {
+            KabschError::SvdFailed
+        }
+    })
+}
+
+/// Whether every entry of `a` is finite: the SVD of a NaN or infinite matrix may never converge,
+/// or panic when sorting its singular values.
+pub(crate) fn is_finite(a: &DMatrix<f64>) -> bool {
+    a.iter().all(|v| v.is_finite())
+}
+
+/// Applies the determinant sign correction of Umeyama to the singular vectors `u` and `vt` of `a`,
+/// sorted by decreasing singular values.
+fn rotation_from_svd(
+    a: &DMatrix<f64>,
+    u: DMatrix<f64>,
+    vt: DMatrix<f64>,
+    rank_tol: f64,
+) -> Option<DMatrix<f64>> {
+    let c = a.nrows();
+    let rank = a.rank(rank_tol);
+    if rank == 0 {
+        return None;
+    }
+    let mut d = DVector::<f64>::from_element(c, 1.);
+    let reflected = if rank == c - 1 {
+        u.determinant() * vt.determinant() <= 0.
+    } else {
+        a.determinant() < 0.
+    };
+    if reflected {
+        d[c - 1] = -1.;
+    }
+    Some(u * DMatrix::from_diagonal(&d) * vt)
+}
+
+impl Solver for Analytic3x3 {
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>> {
+        if a.shape() != (3, 3) || !is_finite(a) {
+            return None;
+        }
+        let a = Matrix3::from_column_slice(a.as_slice());
+        let (lambda, v) = symmetric_eigen_3x3(&(a.transpose() * a));
+
+        let sigma = lambda.map(|l| l.max(0.).sqrt());
+        if sigma[0] <= RANK_TOL {
+            return None;
+        }
+        let u1 = (a * v.column(0) / sigma[0]).normalize();
+        let u2 = if sigma[1] > RANK_TOL {
+            let u2 = a * v.column(1) / sigma[1];
+            u2 - u1 * u1.dot(&u2)
+        } else {
+            any_orthogonal(&u1)
+        }
+        .normalize();
+        let u = Matrix3::from_columns(&[u1, u2, u1.cross(&u2)]);
+        let r = u * v.transpose();
+        Some(DMatrix::from_column_slice(3, 3, r.as_slice()))
+    }
+}
+
+/// Eigenvalues (decreasing) and right-handed orthonormal eigenvectors (columns) of a symmetric 3×3 matrix.
+fn symmetric_eigen_3x3(m: &Matrix3<f64>) -> (Vector3<f64>, Matrix3<f64>) {
+    let p1 = m[(0, 1)].powi(2) + m[(0, 2)].powi(2) + m[(1, 2)].powi(2);
+    let q = m.trace() / 3.;
+    let p2 = (m[(0, 0)] - q).powi(2) + (m[(1, 1)] - q).powi(2) + (m[(2, 2)] - q).powi(2) + 2. * p1;
+    let p = (p2 / 6.).sqrt();
+    if p <= f64::EPSILON * q.abs().max(f64::MIN_POSITIVE) {
+        return (Vector3::from_element(q), Matrix3::identity());
+    }
+    let b = (m - Matrix3::from_diagonal_element(q)) / p;
+    let phi = (b.determinant() / 2.).clamp(-1., 1.).acos() / 3.;
+    let l1 = q + 2. * p * phi.cos();
+    let l3 = q + 2. * p * (phi + 2. * core::f64::consts::FRAC_PI_3).cos();
+    let l2 = 3. * q - l1 - l3;
+
+    // The most isolated eigenvalue has a well-conditioned eigenvector; the two others are then
+    // resolved in its orthogonal plane.
+    let largest_isolated = l1 - l2 >= l2 - l3;
+    let isolated = if largest_isolated { l1 } else { l3 };
+    let n = null_vector(&(m - Matrix3::from_diagonal_element(isolated)));
+    let e = any_orthogonal(&n).normalize();
+    let f = n.cross(&e);
+    let (mee, mef, mff) = ((m * e).dot(&e), (m * f).dot(&e), (m * f).dot(&f));
+    let theta: f64 = 0.5 * (2. * mef).atan2(mee - mff);
+    let major = e * theta.cos() + f * theta.sin();
+    let minor = n.cross(&major);
+
+    let (v1, v2) = if largest_isolated {
+        (n, major)
+    } else {
+        (major, minor)
+    };
+    (
+        Vector3::new(l1, l2, l3),
+        Matrix3::from_columns(&[v1, v2, v1.cross(&v2)]),
+    )
+}
+
+/// Unit vector spanning the null space of a rank 2 matrix, from the largest cross product of its rows.
+fn null_vector(m: &Matrix3<f64>) -> Vector3<f64> {
+    let rows = [
+        m.row(0).transpose(),
+        m.row(1).transpose(),
+        m.row(2).transpose(),
+    ];
+    [
+        rows[0].cross(&rows[1]),
+        rows[0].cross(&rows[2]),
+        rows[1].cross(&rows[2]),
+    ]
+    .into_iter()
+    .max_by(|a, b| a.norm_squared().total_cmp(&b.norm_squared()))
+    .map(|v| v.normalize())
+    .unwrap()
+}
+
+/// A vector orthogonal to `v`, not normalized.
+fn any_orthogonal(v: &Vector3<f64>) -> Vector3<f64> {
+    if v.x.abs() < v.y.abs() && v.x.abs() < v.z.abs() {
+        Vector3::new(0., -v.z, v.y)
+    } else if v.y.abs() < v.z.abs() {
+        Vector3::new(-v.z, 0., v.x)
+    } else {
+        Vector3::new(-v.y, v.x, 0.)
+    }
+}
+
+impl Solver for QuaternionEigen {
+    fn rotation(&self, a: &DMatrix<f64>) -> Option<DMatrix<f64>> {
+        if a.shape() != (3, 3) || !is_finite(a) || a.norm() <= RANK_TOL {
+            return None;
+        }
+        // Horn's `S_ij = Σ src_i dst_j` is the transpose of the cross-covariance.
+        let s = a.transpose();
+        let (sxx, sxy, sxz) = (s[(0, 0)], s[(0, 1)], s[(0, 2)]);
+        let (syx, syy, syz) = (s[(1, 0)], s[(1, 1)], s[(1, 2)]);
+        let (szx, szy, szz) = (s[(2, 0)], s[(2, 1)], s[(2, 2)]);
+        #[rustfmt::skip]
+        let n = Matrix4::new(
+            sxx + syy + szz, syz - szy,        szx - sxz,        sxy - syx,
+            syz - szy,       sxx - syy - szz,  sxy + syx,        szx + sxz,
+            szx - sxz,       sxy + syx,        -sxx + syy - szz, syz + szy,
+            sxy - syx,       szx + sxz,        syz + szy,        -sxx - syy + szz,
+        );
+        let eigen = SymmetricEigen::new(n);
+        let q = eigen.eigenvectors.column(eigen.eigenvalues.imax());
+        let q = UnitQuaternion::from_quaternion(Quaternion::new(q[0], q[1], q[2], q[3]));
+        Some(DMatrix::from_column_slice(
+            3,
+            3,
+            q.to_rotation_matrix().matrix().as_slice(),
+        ))
+    }
+}
@@ -0,0 +1,138 @@
+use crate::solver::{MAX_SVD_ITERATIONS, RANK_TOL};
+#[cfg(not(any(feature = "std", test)))]
+use nalgebra::ComplexField;
+use nalgebra::{Matrix2, Matrix3, Matrix4, SMatrix, Vector2, Vector3};
+
+/// Estimate a similarity transformation in 3 dimensions like [`crate::estimate`] without any heap
+/// allocation, e.g. when called per frame: the means, the cross-covariance and its SVD are all
+/// fixed-size, and so is the homogeneous output matrix.
+/// The `None` value is returned only if the problem is not well-conditioned, or if a coordinate,
+/// or a moment computed from them, is not finite.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_static, Array2};
+///
+/// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 2., 0.], [0., 0., 3.]]);
+/// let dst = Array2::from([[1., 1., 1.], [1., 2., 1.], [-1., 1., 1.], [1., 1., 4.]]);
+///
+/// let t: nalgebra::Matrix4<f64> = estimate_static(src, dst, true).unwrap();
+/// assert!((t - estimate(src, dst, true).unwrap()).abs().max() < 1e-9);
+/// assert!(estimate_static(src, Array2::from([[1., 1., 1.]; 4]), true).is_none());
+/// assert!(estimate_static(src, Array2::from([[f64::NAN, 1., 1.]; 4]), true).is_none());
+/// ```
+pub fn estimate_static<const R: usize>(
+    src: impl Into<SMatrix<f64, R, 3>>,
+    dst: impl Into<SMatrix<f64, R, 3>>,
+    estimate_scale: bool,
+) -> Option<Matrix4<f64>> {
+    let src = src.into();
+    let dst = dst.into();
+    let src_mean = src.row_mean().transpose();
+    let dst_mean = dst.row_mean().transpose();
+    let mut a = Matrix3::zeros();
+    let mut src_variance = 0.;
+    for i in 0..R {
+        let p = src.row(i).transpose() - src_mean;
+        let q = dst.row(i).transpose() - dst_mean;
+        a += q * p.transpose();
+        src_variance += p.norm_squared();
+    }
+    a /= R as f64;
+    if !src_variance.is_finite() {
+        return None;
+    }
+    let rotation = rotation(&a)?;
+    let scale = if estimate_scale {
+        R as f64 / src_variance * (rotation.transpose() * a).trace()
+    } else {
+        1.
+    };
+    let translation = dst_mean - rotation * src_mean * scale;
+    let mut t = Matrix4::identity();
+    t.fixed_view_mut::<3, 3>(0, 0)
+        .copy_from(&(rotation * scale));
+    t.fixed_view_mut::<3, 1>(0, 3).copy_from(&translation);
+    Some(t)
+}
+
+/// Estimate a similarity transformation in 2 dimensions like [`crate::estimate`] in closed form:
+/// the rotation angle maximising `trace(Rᵀ A)` for the cross-covariance `A` is the `atan2` of its
+/// antisymmetric and symmetric parts, so no SVD and no heap allocation is needed.
+/// The `None` value is returned if the points are concentrated on their centroid, or if the
+/// destination points mirror the source points so that every rotation fits equally well, or if a
+/// coordinate, or a moment computed from them, is not finite.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_2d, Array2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 2.], [3., 1.]]);
+/// let dst = Array2::from([[1., 1.], [1., 3.], [-3., 1.], [-1., 7.2]]);
+///
+/// let t: nalgebra::Matrix3<f64> = estimate_2d(src, dst, true).unwrap();
+/// assert!((t - estimate(src, dst, true).unwrap()).abs().max() < 1e-12);
+/// assert!(estimate_2d(src, Array2::from([[1., 1.]; 4]), true).is_none());
+/// assert!(estimate_2d(src, Array2::from([[1e300, 1.]; 4]), true).is_none());
+/// ```
+pub fn estimate_2d<const R: usize>(
+    src: impl Into<SMatrix<f64, R, 2>>,
+    dst: impl Into<SMatrix<f64, R, 2>>,
+    estimate_scale: bool,
+) -> Option<Matrix3<f64>> {
+    let src = src.into();
+    let dst = dst.into();
+    let src_mean = src.row_mean().transpose();
+    let dst_mean = dst.row_mean().transpose();
+    let mut a = Matrix2::zeros();
+    let mut src_variance = 0.;
+    for i in 0..R {
+        let p = src.row(i).transpose() - src_mean;
+        let q = dst.row(i).transpose() - dst_mean;
+        a += q * p.transpose();
+        src_variance += p.norm_squared();
+    }
+    a /= R as f64;
+    let (cos, sin) = (a[(0, 0)] + a[(1, 1)], a[(1, 0)] - a[(0, 1)]);
+    // trace(Rᵀ A) at the optimal angle
+    let trace = cos.hypot(sin);
+    if !trace.is_finite() || !src_variance.is_finite() || trace <= RANK_TOL {
+        return None;
+    }
+    let rotation = Matrix2::new(cos, -sin, sin, cos) / trace;
+    let scale = if estimate_scale {
+        R as f64 / src_variance * trace
+    } else {
+        1.
+    };
+    let translation: Vector2<f64> = dst_mean - rotation * src_mean * scale;
+    let mut t = Matrix3::identity();
+    t.fixed_view_mut::<2, 2>(0, 0)
+        .copy_from(&(rotation * scale));
+    t.fixed_view_mut::<2, 1>(0, 2).copy_from(&translation);
+    Some(t)
+}
+
+/// Proper rotation closest to `a`, with the determinant sign correction of Umeyama like the
+/// SVD solvers.
+fn rotation(a: &Matrix3<f64>) -> Option<Matrix3<f64>> {
+    if a.iter().any(|v| !v.is_finite()) {
+        return None;
+    }
+    let mut svd = a.try_svd(true, true, f64::EPSILON, MAX_SVD_ITERATIONS)?;
+    svd.sort_by_singular_values();
+    let rank = svd
+        .singular_values
+        .iter()
+        .filter(|s| **s > RANK_TOL)
+        .count();
+    if rank == 0 {
+        return None;
+    }
+    let (u, v_t) = (svd.u?, svd.v_t?);
+    let reflected = if rank == 2 {
+        u.determinant() * v_t.determinant() <= 0.
+    } else {
+        a.determinant() < 0.
+    };
+    let d = Vector3::new(1., 1., if reflected { -1. } else { 1. });
+    Some(u * Matrix3::from_diagonal(&d) * v_t)
+}
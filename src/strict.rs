@@ -0,0 +1,77 @@
+//! Non-panicking variants of the public API, for inputs that cannot be trusted.
+//!
+//! No input can make these functions panic: mismatched lengths, empty point sets, `NaN` or infinite
+//! coordinates and degenerate configurations are all reported as [`KabschError`].
+use crate::{fit, Array2, DefaultSolver, KabschError, Solver};
+use nalgebra::{DMatrix, SMatrix};
+
+/// New Array2 from a flat row-major slice, checking its length.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{strict, KabschError};
+///
+/// assert!(strict::array_from_slice::<2, 2>(&[1., 2., 3., 4.]).is_ok());
+/// assert_eq!(
+///     strict::array_from_slice::<2, 2>(&[1., 2., 3.]).unwrap_err(),
+///     KabschError::ShapeMismatch { expected: 4, actual: 3 }
+/// );
+/// ```
+pub fn array_from_slice<const R: usize, const C: usize>(
+    slice: &[f64],
+) -> Result<Array2<R, C>, KabschError> {
+    Array2::try_from(slice)
+}
+
+/// Estimate a similarity transformation like [`crate::estimate`], reporting every failure as an error.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{strict, Array2, KabschError};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+/// let dst = Array2::from([[1., 1.], [1., 2.], [0., 1.]]);
+/// assert!(strict::estimate(src, dst, true).is_ok());
+///
+/// let nan = Array2::from([[0., 0.], [1., f64::NAN], [0., 1.]]);
+/// assert_eq!(strict::estimate(nan, dst, true), Err(KabschError::NonFinite));
+///
+/// let coincident = Array2::from([[1., 1.]; 3]);
+/// assert_eq!(
+///     strict::estimate(coincident, dst, true),
+///     Err(KabschError::DegenerateConfiguration)
+/// );
+///
+/// // finite coordinates whose covariance overflows
+/// let huge = Array2::from([[0., 0., 0.], [1e300, 0., 0.], [0., -1e300, 0.], [0., 0., 1e300]]);
+/// assert_eq!(strict::estimate(huge, huge, true), Err(KabschError::NonFinite));
+/// let huge = Array2::from([[0., 0.], [1e300, 0.], [0., -1e300]]);
+/// assert_eq!(strict::estimate(huge, huge, false), Err(KabschError::NonFinite));
+/// ```
+pub fn estimate<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+) -> Result<DMatrix<f64>, KabschError> {
+    estimate_with_solver(src, dst, estimate_scale, &DefaultSolver)
+}
+
+/// Estimate a similarity transformation like [`crate::estimate_with_solver`], reporting every failure as an error.
+pub fn estimate_with_solver<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+    solver: &dyn Solver,
+) -> Result<DMatrix<f64>, KabschError> {
+    if R == 0 || C == 0 {
+        return Err(KabschError::Empty);
+    }
+    let src = src.into();
+    let dst = dst.into();
+    if src.iter().chain(dst.iter()).any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    let transform = fit(src, dst, estimate_scale, solver)?.transform;
+    if transform.iter().any(|v| !v.is_finite()) {
+        return Err(KabschError::NonFinite);
+    }
+    Ok(transform)
+}
@@ -0,0 +1,78 @@
+use crate::estimate_transform;
+use nalgebra::{DMatrix, SMatrix, SVector, SymmetricEigen};
+
+/// Mirror symmetry of a landmark configuration: a plane in 3 dimensions, an axis in 2 dimensions.
+#[derive(Clone, Debug)]
+pub struct Symmetry<const C: usize> {
+    /// Unit normal `n` of the symmetry plane (or axis).
+    pub normal: SVector<f64, C>,
+    /// Offset `d` of the symmetry plane `{x : n·x = d}`.
+    pub offset: f64,
+    /// Root-mean-square distance between the configuration and its aligned reflection, `0` for a
+    /// perfectly symmetric configuration.
+    pub rmsd: f64,
+}
+
+/// Detect the bilateral symmetry of a landmark configuration by aligning it against its own
+/// reflection.
+///
+/// `pairs[i]` is the index of the landmark mirroring landmark `i`; landmarks on the symmetry plane
+/// are paired with themselves. The reflection is relabeled by `pairs` and rigidly aligned onto the
+/// configuration, and the symmetry plane is recovered from the composed improper transformation.
+/// The `None` value is returned if `pairs` is not an involution or the alignment is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{detect_symmetry, Array2};
+///
+/// // a kite, symmetric about the line x = 1
+/// let kite = Array2::from([[1., 3.], [0., 1.], [1., 0.], [2., 1.]]);
+/// let symmetry = detect_symmetry(kite, &[0, 3, 2, 1]).unwrap();
+/// assert!((symmetry.normal.x.abs() - 1.).abs() < 1e-9);
+/// assert!((symmetry.offset * symmetry.normal.x - 1.).abs() < 1e-9);
+/// assert!(symmetry.rmsd < 1e-9);
+/// ```
+pub fn detect_symmetry<const R: usize, const C: usize>(
+    points: impl Into<SMatrix<f64, R, C>>,
+    pairs: &[usize; R],
+) -> Option<Symmetry<C>> {
+    if pairs
+        .iter()
+        .enumerate()
+        .any(|(i, &j)| j >= R || pairs[j] != i)
+    {
+        return None;
+    }
+    let points = points.into();
+    let mut mirror = SMatrix::<f64, C, C>::identity();
+    mirror[(0, 0)] = -1.;
+    let reflected = SMatrix::<f64, R, C>::from_fn(|i, j| points[(pairs[i], j)] * mirror[(j, j)]);
+    let alignment = estimate_transform(reflected, points, false)?;
+
+    // x_i ≈ S x_pairs[i] + t with the improper S = R M, ideally the reflection I - 2nnᵀ.
+    let s = alignment.rotation() * mirror;
+    let t = alignment.translation();
+    let half_gap = (SMatrix::<f64, C, C>::identity() - (s + s.transpose()) / 2.) / 2.;
+    let eigen = SymmetricEigen::new(DMatrix::from_column_slice(C, C, half_gap.as_slice()));
+    let mut normal = SVector::<f64, C>::from_column_slice(
+        eigen
+            .eigenvectors
+            .column(eigen.eigenvalues.imax())
+            .as_slice(),
+    );
+    if normal[normal.iamax()] < 0. {
+        normal.neg_mut();
+    }
+
+    let squared_error = (0..R)
+        .map(|i| {
+            let x = points.row(i).transpose();
+            let y = reflected.row(i).transpose();
+            (x - (alignment.rotation() * y + t)).norm_squared()
+        })
+        .sum::<f64>();
+    Some(Symmetry {
+        normal,
+        offset: normal.dot(t) / 2.,
+        rmsd: (squared_error / R as f64).sqrt(),
+    })
+}
@@ -0,0 +1,219 @@
+//! Canonical input/output cases for verifying the numerical parity of estimators.
+//!
+//! The expected transforms are the ones returned by scikit-image's `SimilarityTransform.estimate`,
+//! where the degenerate cases for which scikit-image returns a `NaN` matrix are expected to be `None`.
+use crate::{Array2, NestedArray};
+use nalgebra::DMatrix;
+
+/// A source/destination pair with the expected homogeneous transformation matrix.
+#[derive(Clone, Copy, Debug)]
+pub struct Case<const R: usize, const C: usize> {
+    pub name: &'static str,
+    pub src: NestedArray<R, C>,
+    pub dst: NestedArray<R, C>,
+    pub estimate_scale: bool,
+    /// Expected `(C+1)x(C+1)` matrix in row-major order, `None` if the problem is degenerate.
+    pub expected: Option<&'static [f64]>,
+}
+
+/// An estimator output that differs from the expected one.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    pub name: &'static str,
+    pub expected: Option<DMatrix<f64>>,
+    pub actual: Option<DMatrix<f64>>,
+}
+
+const SRC_2D: NestedArray<4, 2> = [[0., 0.], [3., 0.], [1., 2.], [-1., 1.]];
+
+const SRC_3D: NestedArray<5, 3> = [
+    [0., 0., 0.],
+    [1., 0., 0.],
+    [0., 2., 0.],
+    [0., 0., 3.],
+    [1., 1., 1.],
+];
+
+/// Cases with 4 points in 2 dimensions.
+pub const CASES_2D: &[Case<4, 2>] = &[
+    Case {
+        name: "identity",
+        src: SRC_2D,
+        dst: SRC_2D,
+        estimate_scale: true,
+        expected: Some(&[1., 0., 0., 0., 1., 0., 0., 0., 1.]),
+    },
+    Case {
+        name: "translation",
+        src: SRC_2D,
+        dst: [[1., -2.], [4., -2.], [2., 0.], [0., -1.]],
+        estimate_scale: false,
+        expected: Some(&[1., 0., 1., 0., 1., -2., 0., 0., 1.]),
+    },
+    Case {
+        name: "rotation",
+        src: SRC_2D,
+        dst: [[0.5, 0.25], [0.5, 3.25], [-1.5, 1.25], [-0.5, -0.75]],
+        estimate_scale: false,
+        expected: Some(&[0., -1., 0.5, 1., 0., 0.25, 0., 0., 1.]),
+    },
+    Case {
+        name: "similarity",
+        src: SRC_2D,
+        dst: [
+            [-3., 4.],
+            [2.196152422706632, 7.],
+            [-3.2679491924311224, 8.464101615137753],
+            [-5.732050807568877, 4.732050807568878],
+        ],
+        estimate_scale: true,
+        expected: Some(&[
+            1.7320508075688772,
+            -1.,
+            -3.,
+            1.,
+            1.7320508075688772,
+            4.,
+            0.,
+            0.,
+            1.,
+        ]),
+    },
+    Case {
+        name: "reflection",
+        src: [[0., 0.], [2., 0.], [2., 1.], [0., 1.]],
+        dst: [[0., 0.], [-2., 0.], [-2., 1.], [0., 1.]],
+        estimate_scale: true,
+        expected: Some(&[-0.6, 0., -0.4, 0., -0.6, 0.8, 0., 0., 1.]),
+    },
+    Case {
+        name: "coincident",
+        src: [[1., 1.], [1., 1.], [1., 1.], [1., 1.]],
+        dst: SRC_2D,
+        estimate_scale: true,
+        expected: None,
+    },
+];
+
+/// Cases with 5 points in 3 dimensions.
+pub const CASES_3D: &[Case<5, 3>] = &[
+    Case {
+        name: "identity",
+        src: SRC_3D,
+        dst: SRC_3D,
+        estimate_scale: true,
+        expected: Some(&[
+            1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+        ]),
+    },
+    Case {
+        name: "rotation",
+        src: SRC_3D,
+        dst: [
+            [1., 2., 3.],
+            [1., 3., 3.],
+            [-1., 2., 3.],
+            [1., 2., 6.],
+            [0., 3., 4.],
+        ],
+        estimate_scale: false,
+        expected: Some(&[
+            0., -1., 0., 1., 1., 0., 0., 2., 0., 0., 1., 3., 0., 0., 0., 1.,
+        ]),
+    },
+    Case {
+        name: "similarity",
+        src: SRC_3D,
+        dst: [
+            [-1., 0., 2.],
+            [-1., 0.5, 2.],
+            [-1., 0., 3.],
+            [0.5, 0., 2.],
+            [-0.5, 0.5, 2.5],
+        ],
+        estimate_scale: true,
+        expected: Some(&[
+            0., 0., 0.5, -1., 0.5, 0., 0., 0., 0., 0.5, 0., 2., 0., 0., 0., 1.,
+        ]),
+    },
+    Case {
+        name: "coplanar",
+        src: [
+            [0., 0., 0.],
+            [2., 0., 0.],
+            [0., 1., 0.],
+            [1., 3., 0.],
+            [-1., 1., 0.],
+        ],
+        dst: [
+            [0., 1., -1.],
+            [3., 1., -1.],
+            [0., 1., 0.5],
+            [1.5, 1., 3.5],
+            [-1.5, 1., 0.5],
+        ],
+        estimate_scale: true,
+        expected: Some(&[
+            1.5, 0., 0., 0., 0., 0., -1.5, 1., 0., 1.5, 0., -1., 0., 0., 0., 1.,
+        ]),
+    },
+    Case {
+        name: "coincident",
+        src: [[2., 0., 1.]; 5],
+        dst: SRC_3D,
+        estimate_scale: false,
+        expected: None,
+    },
+];
+
+/// Run an estimator against the given cases, returning the ones whose output differs from the
+/// expected matrix by more than `tol` in any entry.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::testdata::{check, CASES_2D, CASES_3D};
+/// use kabsch_umeyama::{estimate, estimate_with_solver, Analytic3x3};
+///
+/// assert!(check(CASES_2D, |src, dst, scale| estimate(src, dst, scale), 1e-9).is_ok());
+/// assert!(check(
+///     CASES_3D,
+///     |src, dst, scale| estimate_with_solver(src, dst, scale, &Analytic3x3),
+///     1e-9
+/// )
+/// .is_ok());
+/// ```
+pub fn check<const R: usize, const C: usize>(
+    cases: &[Case<R, C>],
+    estimator: impl Fn(Array2<R, C>, Array2<R, C>, bool) -> Option<DMatrix<f64>>,
+    tol: f64,
+) -> Result<(), Vec<Mismatch>> {
+    let mismatches = cases
+        .iter()
+        .filter_map(|case| {
+            let expected = case
+                .expected
+                .map(|expected| DMatrix::from_row_slice(C + 1, C + 1, expected));
+            let actual = estimator(
+                Array2::from(case.src),
+                Array2::from(case.dst),
+                case.estimate_scale,
+            );
+            let matches = match (&expected, &actual) {
+                (Some(expected), Some(actual)) => {
+                    actual.shape() == expected.shape() && (actual - expected).abs().max() <= tol
+                }
+                (None, None) => true,
+                _ => false,
+            };
+            (!matches).then_some(Mismatch {
+                name: case.name,
+                expected,
+                actual,
+            })
+        })
+        .collect::<Vec<_>>();
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches)
+    }
+}
@@ -0,0 +1,106 @@
+//! Helpers for property-based tests: random proper rotations and similarities, and assertions that
+//! an estimator recovers them within tolerance.
+pub use crate::rng::Rng;
+use crate::Array2;
+use nalgebra::{DMatrix, DVector, SMatrix};
+
+/// Random proper rotation, uniformly distributed over SO(C).
+pub fn random_rotation<const C: usize>(rng: &mut Rng) -> SMatrix<f64, C, C> {
+    let gaussian = DMatrix::<f64>::from_fn(C, C, |_, _| rng.normal());
+    let qr = gaussian.qr();
+    let mut q = qr.q();
+    let r = qr.r();
+    for (i, mut column) in q.column_iter_mut().enumerate() {
+        if r[(i, i)] < 0. {
+            column.neg_mut();
+        }
+    }
+    if q.determinant() < 0. {
+        q.column_mut(0).neg_mut();
+    }
+    SMatrix::from_column_slice(q.as_slice())
+}
+
+/// Random homogeneous similarity transformation with a proper rotation, a scale in `scale_range`
+/// and translation components in `[-spread, spread)`.
+pub fn random_similarity<const C: usize>(
+    rng: &mut Rng,
+    scale_range: (f64, f64),
+    spread: f64,
+) -> DMatrix<f64> {
+    let rotation = random_rotation::<C>(rng);
+    let scale = rng.range(scale_range.0, scale_range.1);
+    let mut t = DMatrix::identity(C + 1, C + 1);
+    t.view_mut((0, 0), (C, C)).copy_from(&(rotation * scale));
+    t.view_mut((0, C), (C, 1))
+        .copy_from(&DVector::from_fn(C, |_, _| rng.range(-spread, spread)));
+    t
+}
+
+/// Random points with coordinates in `[-spread, spread)`.
+pub fn random_points<const R: usize, const C: usize>(rng: &mut Rng, spread: f64) -> Array2<R, C> {
+    let mut points = [[0.; C]; R];
+    points
+        .as_flattened_mut()
+        .iter_mut()
+        .for_each(|v| *v = rng.range(-spread, spread));
+    Array2::from(points)
+}
+
+/// Map points through a homogeneous transformation matrix.
+pub fn transform_points<const R: usize, const C: usize>(
+    t: &DMatrix<f64>,
+    points: &Array2<R, C>,
+) -> Array2<R, C> {
+    let linear = t.view((0, 0), (C, C));
+    let translation = t.view((0, C), (C, 1));
+    let mut out = [[0.; C]; R];
+    out.iter_mut().zip(points.iter()).for_each(|(o, p)| {
+        let q = linear * DVector::from_column_slice(p) + translation;
+        o.copy_from_slice(q.as_slice());
+    });
+    Array2::from(out)
+}
+
+/// Assert that `estimator` recovers `trials` random transformations applied to random points,
+/// with every entry of the estimated matrix within `tol` of the ground truth.
+/// Without `estimate_scale`, the generated transformations are rigid.
+/// # Panics
+/// Panics on the first transformation that is not recovered, reporting the seed state.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::testing::{assert_recovers, Rng};
+/// use kabsch_umeyama::{estimate, estimate_with_solver, QuaternionEigen};
+///
+/// let mut rng = Rng::new(7);
+/// assert_recovers::<6, 2>(|src, dst, scale| estimate(src, dst, scale), &mut rng, 50, true, 1e-9);
+/// assert_recovers::<6, 3>(
+///     |src, dst, scale| estimate_with_solver(src, dst, scale, &QuaternionEigen),
+///     &mut rng,
+///     50,
+///     false,
+///     1e-9,
+/// );
+/// ```
+pub fn assert_recovers<const R: usize, const C: usize>(
+    estimator: impl Fn(Array2<R, C>, Array2<R, C>, bool) -> Option<DMatrix<f64>>,
+    rng: &mut Rng,
+    trials: usize,
+    estimate_scale: bool,
+    tol: f64,
+) {
+    for trial in 0..trials {
+        let state = rng.clone();
+        let scale_range = if estimate_scale { (0.1, 10.) } else { (1., 1.) };
+        let expected = random_similarity::<C>(rng, scale_range, 10.);
+        let src = random_points::<R, C>(rng, 10.);
+        let dst = transform_points(&expected, &src);
+        match estimator(src, dst, estimate_scale) {
+            Some(actual) if (&actual - &expected).abs().max() <= tol => {}
+            actual => panic!(
+                "trial {trial} ({state:?}): expected {expected}, estimated {:?}",
+                actual
+            ),
+        }
+    }
+}
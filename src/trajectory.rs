@@ -0,0 +1,443 @@
+//! Trajectory alignment: registering the positions of an estimated trajectory (e.g. from SLAM or
+//! visual odometry) to reference positions, in the frame and the scale of the reference.
+use crate::{fit_points, rows_from_slice, DefaultSolver, Transform};
+use nalgebra::{DVector, Matrix3, Rotation3, SMatrix, SVector, Vector3};
+
+/// Align the positions of an estimated trajectory to the time-associated reference positions,
+/// with or without scaling (Sim(3) or SE(3) alignment).
+/// The `None` value is returned if the lengths differ or the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::trajectory::align;
+///
+/// let estimated = [[0., 0., 0.], [1., 0., 0.], [2., 1., 0.], [3., 1., 1.]];
+/// let reference = estimated.map(|[x, y, z]| [2. * x + 1., 2. * y, 2. * z]);
+///
+/// let t = align(&estimated, &reference, true).unwrap();
+/// assert!((t.scale() - 2.).abs() < 1e-9);
+/// ```
+pub fn align(
+    estimated: &[[f64; 3]],
+    reference: &[[f64; 3]],
+    estimate_scale: bool,
+) -> Option<Transform<3>> {
+    if estimated.is_empty() || estimated.len() != reference.len() {
+        return None;
+    }
+    fit_points(
+        rows_from_slice(estimated),
+        rows_from_slice(reference),
+        &DVector::repeat(estimated.len(), 1.),
+        estimate_scale,
+        &DefaultSolver,
+    )
+    .ok()
+    .map(|fit| fit.to_transform())
+}
+
+/// Distance between each aligned estimated position and its reference position (absolute
+/// trajectory error), ready to be plotted over time.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::trajectory::{align, position_errors};
+///
+/// let estimated = [[0., 0., 0.], [1., 0., 0.], [2., 0., 0.], [3., 0., 0.]];
+/// let reference = [[0., 0., 0.], [1., 0., 0.], [2., 0.2, 0.], [3., 0., 0.]];
+///
+/// let t = align(&estimated, &reference, false).unwrap();
+/// let errors = position_errors(&estimated, &reference, &t);
+/// assert_eq!(errors.len(), 4);
+/// assert!(errors[2] > errors[0]);
+/// ```
+pub fn position_errors(
+    estimated: &[[f64; 3]],
+    reference: &[[f64; 3]],
+    transform: &Transform<3>,
+) -> Vec<f64> {
+    estimated
+        .iter()
+        .zip(reference)
+        .map(|(p, q)| {
+            (transform.rotation() * Vector3::from(*p) * transform.scale() + transform.translation()
+                - Vector3::from(*q))
+            .norm()
+        })
+        .collect()
+}
+
+/// GNSS position fix used as a soft prior on a trajectory sample.
+#[derive(Clone, Copy, Debug)]
+pub struct GnssSample {
+    /// Index of the trajectory sample the fix is associated with.
+    pub index: usize,
+    pub position: [f64; 3],
+    /// Covariance of the position, e.g. large vertically and under tree cover.
+    pub covariance: Matrix3<f64>,
+}
+
+/// Options of [`align_to_gnss`].
+#[derive(Clone, Copy, Debug)]
+pub struct GnssOptions {
+    pub estimate_scale: bool,
+    /// Fixes whose Mahalanobis distance to the aligned trajectory exceeds this are downweighted
+    /// (Huber loss), so that local distortions of the trajectory do not bias the alignment.
+    pub huber: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for GnssOptions {
+    fn default() -> Self {
+        Self {
+            estimate_scale: true,
+            huber: 3.,
+            max_iterations: 20,
+        }
+    }
+}
+
+/// Align a trajectory to GNSS fixes, minimizing the robust sum of the squared Mahalanobis
+/// distances between the aligned positions and the fixes under their covariances.
+///
+/// Starting from the alignment weighting each fix by the inverse of its mean variance, the
+/// similarity is refined by iteratively reweighted Gauss-Newton steps.
+/// The `None` value is returned if a fix refers past the end of the trajectory, a covariance is not
+/// invertible, or the fixes do not constrain the alignment.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::trajectory::{align, align_to_gnss, GnssOptions, GnssSample};
+/// use nalgebra::{Matrix3, Rotation3, Vector3};
+///
+/// // SLAM trajectory along a curve, in its own frame and scale
+/// let positions = (0..40)
+///     .map(|i| {
+///         let a = i as f64 * 0.1;
+///         [a.cos() * 5., a.sin() * 5., a * 0.3]
+///     })
+///     .collect::<Vec<_>>();
+///
+/// // GNSS fixes in the world frame, less accurate vertically, with a multipath jump
+/// let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), 0.4);
+/// let covariance = Matrix3::from_diagonal(&Vector3::new(0.01, 0.01, 0.25));
+/// let fixes = positions
+///     .iter()
+///     .enumerate()
+///     .map(|(index, p)| {
+///         let mut world = rotation * Vector3::from(*p) * 3. + Vector3::new(100., 50., 10.);
+///         if (10..14).contains(&index) {
+///             world.x += 8.;
+///         }
+///         GnssSample { index, position: world.into(), covariance }
+///     })
+///     .collect::<Vec<_>>();
+///
+/// let t = align_to_gnss(&positions, &fixes, GnssOptions::default()).unwrap();
+/// let offset = Vector3::new(100., 50., 10.);
+/// assert!((t.scale() - 3.).abs() < 1e-2);
+/// assert!((t.translation() - offset).norm() < 0.05);
+///
+/// // a least-squares alignment is dragged by the jump
+/// let world = fixes.iter().map(|fix| fix.position).collect::<Vec<_>>();
+/// let plain = align(&positions, &world, true).unwrap();
+/// assert!((plain.translation() - offset).norm() > 0.5);
+/// ```
+pub fn align_to_gnss(
+    positions: &[[f64; 3]],
+    fixes: &[GnssSample],
+    options: GnssOptions,
+) -> Option<Transform<3>> {
+    if fixes.iter().any(|fix| fix.index >= positions.len()) {
+        return None;
+    }
+    let information = fixes
+        .iter()
+        .map(|fix| fix.covariance.try_inverse())
+        .collect::<Option<Vec<_>>>()?;
+    let src = fixes
+        .iter()
+        .map(|fix| positions[fix.index])
+        .collect::<Vec<_>>();
+    let dst = fixes.iter().map(|fix| fix.position).collect::<Vec<_>>();
+    let weights = DVector::from_iterator(
+        fixes.len(),
+        fixes.iter().map(|fix| 3. / fix.covariance.trace()),
+    );
+    let mut transform = fit_points(
+        rows_from_slice(&src),
+        rows_from_slice(&dst),
+        &weights,
+        options.estimate_scale,
+        &DefaultSolver,
+    )
+    .ok()?
+    .to_transform::<3>();
+
+    // parameters: rotation vector, translation and log-scale increments
+    for _ in 0..options.max_iterations {
+        let mut h = SMatrix::<f64, 7, 7>::zeros();
+        let mut g = SVector::<f64, 7>::zeros();
+        for ((p, q), information) in src.iter().zip(&dst).zip(&information) {
+            let linear = transform.rotation() * Vector3::from(*p) * transform.scale();
+            let r = linear + transform.translation() - Vector3::from(*q);
+            let mahalanobis = (r.transpose() * information * r)[0].max(0.).sqrt();
+            let weight = if mahalanobis > options.huber {
+                options.huber / mahalanobis
+            } else {
+                1.
+            };
+            let mut jacobian = SMatrix::<f64, 3, 7>::zeros();
+            jacobian
+                .fixed_view_mut::<3, 3>(0, 0)
+                .copy_from(&-linear.cross_matrix());
+            jacobian
+                .fixed_view_mut::<3, 3>(0, 3)
+                .copy_from(&Matrix3::identity());
+            if options.estimate_scale {
+                jacobian.set_column(6, &linear);
+            }
+            h += jacobian.transpose() * information * jacobian * weight;
+            g += jacobian.transpose() * information * r * weight;
+        }
+        if !options.estimate_scale {
+            h[(6, 6)] = 1.;
+        }
+        let delta = -h.cholesky()?.solve(&g);
+        let rotation = Rotation3::new(Vector3::new(delta[0], delta[1], delta[2])).into_inner();
+        transform = Transform::new(
+            rotation * transform.rotation(),
+            transform.translation() + Vector3::new(delta[3], delta[4], delta[5]),
+            transform.scale() * delta[6].exp(),
+        );
+        if delta.norm() < 1e-12 {
+            break;
+        }
+    }
+    Some(transform)
+}
+
+/// Similarity aligning one window of a trajectory.
+#[derive(Clone, Debug)]
+pub struct Segment {
+    /// Index of the first sample of the window.
+    pub start: usize,
+    /// Index past the last sample of the window.
+    pub end: usize,
+    pub transform: Transform<3>,
+}
+
+/// Options of [`align_windowed`].
+#[derive(Clone, Copy, Debug)]
+pub struct WindowedOptions {
+    /// Number of samples per window; consecutive windows share their boundary sample.
+    pub window: usize,
+    pub estimate_scale: bool,
+    /// Weight, relative to a sample, pinning the first sample of a window to where the previous
+    /// window aligned it, so that the piecewise alignment stays continuous.
+    pub continuity: f64,
+}
+
+impl Default for WindowedOptions {
+    fn default() -> Self {
+        Self {
+            window: 20,
+            estimate_scale: true,
+            continuity: 10.,
+        }
+    }
+}
+
+/// Align a trajectory piecewise, with an independent similarity per window of samples.
+///
+/// A single alignment averages the scale drift of a monocular SLAM trajectory away; the per-window
+/// scales expose where it drifts instead.
+/// The `None` value is returned if the lengths differ, a window has fewer than 2 samples, or the
+/// alignment of a window is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::trajectory::{align_windowed, WindowedOptions};
+///
+/// // straight trajectory whose scale drifts from 1 to 1.5 after the 20th sample
+/// let reference = (0..41).map(|i| [i as f64, (i as f64 * 0.3).sin(), 0.]).collect::<Vec<_>>();
+/// let estimated = reference
+///     .iter()
+///     .map(|&[x, y, z]| if x <= 20. { [x, y, z] } else { [20. + (x - 20.) * 1.5, 1.5 * y, 0.] })
+///     .collect::<Vec<_>>();
+///
+/// let options = WindowedOptions { window: 11, ..Default::default() };
+/// let segments = align_windowed(&estimated, &reference, options).unwrap();
+/// assert_eq!(segments.len(), 4);
+/// assert!((segments[0].transform.scale() - 1.).abs() < 1e-6);
+/// assert!((segments[3].transform.scale() - 1. / 1.5).abs() < 1e-3);
+/// ```
+pub fn align_windowed(
+    estimated: &[[f64; 3]],
+    reference: &[[f64; 3]],
+    options: WindowedOptions,
+) -> Option<Vec<Segment>> {
+    if options.window < 2 || estimated.len() != reference.len() {
+        return None;
+    }
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut start = 0;
+    while start + 1 < estimated.len() {
+        let end = (start + options.window).min(estimated.len());
+        let mut src = estimated[start..end].to_vec();
+        let mut dst = reference[start..end].to_vec();
+        let mut weights = vec![1.; end - start];
+        if let Some(previous) = segments.last() {
+            let t = &previous.transform;
+            let pinned =
+                t.rotation() * Vector3::from(estimated[start]) * t.scale() + t.translation();
+            src.push(estimated[start]);
+            dst.push(pinned.into());
+            weights.push(options.continuity);
+        }
+        let transform = fit_points(
+            rows_from_slice(&src),
+            rows_from_slice(&dst),
+            &DVector::from_vec(weights),
+            options.estimate_scale,
+            &DefaultSolver,
+        )
+        .ok()?
+        .to_transform();
+        segments.push(Segment {
+            start,
+            end,
+            transform,
+        });
+        start = end - 1;
+    }
+    Some(segments)
+}
+
+/// Segment lengths (meters) of the KITTI odometry benchmark.
+pub const KITTI_LENGTHS: [f64; 8] = [100., 200., 300., 400., 500., 600., 700., 800.];
+
+/// Relative pose error over one segment of the trajectory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SegmentError {
+    /// Index of the first pose of the segment.
+    pub first: usize,
+    /// Distance traveled along the reference over the segment.
+    pub length: f64,
+    /// Distance traveled along the reference up to the first pose.
+    pub traveled: f64,
+    /// Translation error divided by the segment length.
+    pub translation: f64,
+    /// Rotation error (radians) divided by the segment length.
+    pub rotation: f64,
+}
+
+/// Drift of an estimated trajectory following the KITTI odometry evaluation: the relative pose
+/// errors over segments of fixed lengths starting every few poses.
+#[derive(Clone, Debug)]
+pub struct DriftAnalysis {
+    pub segments: Vec<SegmentError>,
+}
+
+/// Cumulative distance traveled along a trajectory, at each pose.
+pub fn traveled_distances(poses: &[Transform<3>]) -> Vec<f64> {
+    let mut traveled = 0.;
+    let mut previous = poses.first().map(|p| *p.translation());
+    poses
+        .iter()
+        .map(|pose| {
+            traveled += previous.map_or(0., |p| (pose.translation() - p).norm());
+            previous = Some(*pose.translation());
+            traveled
+        })
+        .collect()
+}
+
+/// Rigid motion from `from` to `to`, as a rotation and a translation in the frame of `from`.
+fn motion(from: &Transform<3>, to: &Transform<3>) -> (Matrix3<f64>, Vector3<f64>) {
+    let rotation = from.rotation().transpose();
+    (
+        rotation * to.rotation(),
+        rotation * (to.translation() - from.translation()),
+    )
+}
+
+impl DriftAnalysis {
+    /// Evaluate the segments of the given `lengths` starting every `step` poses, with the poses of
+    /// both trajectories associated by index. Segments running past the end are skipped.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::trajectory::DriftAnalysis;
+    /// use kabsch_umeyama::Transform;
+    /// use nalgebra::{Matrix3, Vector3};
+    ///
+    /// let pose = |x: f64| Transform::new(Matrix3::identity(), Vector3::new(x, 0., 0.), 1.);
+    /// let reference = (0..=100).map(|i| pose(i as f64)).collect::<Vec<_>>();
+    /// // the odometry overestimates every step by 2%
+    /// let estimated = (0..=100).map(|i| pose(i as f64 * 1.02)).collect::<Vec<_>>();
+    ///
+    /// let drift = DriftAnalysis::new(&estimated, &reference, &[10., 50.], 5);
+    /// assert!(drift.segments.iter().all(|s| (s.translation - 0.02).abs() < 1e-9));
+    /// assert!(drift.to_csv().starts_with("first,traveled,length,translation,rotation\n0,0,10,"));
+    /// ```
+    pub fn new(
+        estimated: &[Transform<3>],
+        reference: &[Transform<3>],
+        lengths: &[f64],
+        step: usize,
+    ) -> Self {
+        let n = estimated.len().min(reference.len());
+        let traveled = traveled_distances(&reference[..n]);
+        let mut segments = Vec::new();
+        for first in (0..n).step_by(step.max(1)) {
+            for &length in lengths {
+                let Some(last) = (first..n).find(|&i| traveled[i] >= traveled[first] + length)
+                else {
+                    continue;
+                };
+                let (r_gt, t_gt) = motion(&reference[first], &reference[last]);
+                let (r_est, t_est) = motion(&estimated[first], &estimated[last]);
+                let r_error = r_est.transpose() * r_gt;
+                let t_error = r_est.transpose() * (t_gt - t_est);
+                let cos = ((r_error.trace() - 1.) / 2.).clamp(-1., 1.);
+                segments.push(SegmentError {
+                    first,
+                    length,
+                    traveled: traveled[first],
+                    translation: t_error.norm() / length,
+                    rotation: cos.acos() / length,
+                });
+            }
+        }
+        Self { segments }
+    }
+
+    /// Mean translation and rotation errors of the segments of each length, as
+    /// `(length, translation, rotation)` in increasing length.
+    pub fn per_length(&self) -> Vec<(f64, f64, f64)> {
+        let mut lengths = self.segments.iter().map(|s| s.length).collect::<Vec<_>>();
+        lengths.sort_by(f64::total_cmp);
+        lengths.dedup();
+        lengths
+            .into_iter()
+            .map(|length| {
+                let (count, translation, rotation) = self
+                    .segments
+                    .iter()
+                    .filter(|s| s.length == length)
+                    .fold((0., 0., 0.), |(c, t, r), s| {
+                        (c + 1., t + s.translation, r + s.rotation)
+                    });
+                (length, translation / count, rotation / count)
+            })
+            .collect()
+    }
+
+    /// Segment errors as CSV with a header row, one segment per row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("first,traveled,length,translation,rotation\n");
+        for s in &self.segments {
+            csv += &format!(
+                "{},{},{},{},{}\n",
+                s.first, s.traveled, s.length, s.translation, s.rotation
+            );
+        }
+        csv
+    }
+}
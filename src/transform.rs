@@ -0,0 +1,308 @@
+use crate::{KabschError, NalgebraSvd, Solver};
+use alloc::boxed::Box;
+#[cfg(not(any(feature = "std", test)))]
+use nalgebra::ComplexField;
+use nalgebra::{
+    DMatrix, Isometry3, Rotation3, SMatrix, SVector, Similarity3, Translation3, UnitQuaternion,
+    Vector3,
+};
+
+/// Lazily initialized cell caching the inverse, only `Sync` with the `std` feature.
+#[cfg(feature = "std")]
+type OnceLock<T> = std::sync::OnceLock<T>;
+#[cfg(not(feature = "std"))]
+type OnceLock<T> = core::cell::OnceCell<T>;
+
+/// Alias of [`Transform`] naming the kind of transformation estimated by this crate.
+pub type SimilarityTransform<const C: usize> = Transform<C>;
+
+/// Similarity transformation `x ↦ s R x + t` in `C` dimensions.
+///
+/// The inverse is computed on first use from `1/s` and `Rᵀ`, which is exact and cheaper than a
+/// generic matrix inversion, then cached.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform<const C: usize> {
+    rotation: SMatrix<f64, C, C>,
+    translation: SVector<f64, C>,
+    scale: f64,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    inverse: OnceLock<Box<Transform<C>>>,
+}
+
+impl<const C: usize> Transform<C> {
+    /// New Transform from its rotation, translation and scale
+    pub fn new(rotation: SMatrix<f64, C, C>, translation: SVector<f64, C>, scale: f64) -> Self {
+        Self {
+            rotation,
+            translation,
+            scale,
+            inverse: OnceLock::new(),
+        }
+    }
+
+    /// Identity transformation
+    pub fn identity() -> Self {
+        Self::new(SMatrix::identity(), SVector::zeros(), 1.)
+    }
+
+    /// New Transform from a `(C+1)x(C+1)` homogeneous similarity matrix, such as the output of
+    /// [`crate::estimate`]. The `None` value is returned if the shape does not match.
+    pub fn from_homogeneous(matrix: &DMatrix<f64>) -> Option<Self> {
+        if matrix.shape() != (C + 1, C + 1) {
+            return None;
+        }
+        let linear = matrix.view((0, 0), (C, C));
+        let scale = linear.column(0).norm();
+        let rotation = SMatrix::from_fn(|i, j| linear[(i, j)] / scale);
+        let translation = SVector::from_fn(|i, _| matrix[(i, C)]);
+        Some(Self::new(rotation, translation, scale))
+    }
+
+    /// New Transform from a `(C+1)x(C+1)` homogeneous matrix, checking that it is a similarity
+    /// transformation with a proper rotation: the last row is `[0, …, 0, 1]` and the linear part
+    /// `L` satisfies `LᵀL = s²I` and `det(L) > 0`, each entry within `tol`.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{KabschError, Transform};
+    /// use nalgebra::DMatrix;
+    ///
+    /// let similarity = DMatrix::from_row_slice(3, 3, &[0., -2., 1., 2., 0., 3., 0., 0., 1.]);
+    /// assert!(Transform::<2>::from_matrix_checked(&similarity, 1e-9).is_ok());
+    ///
+    /// let shear = DMatrix::from_row_slice(3, 3, &[1., 1., 0., 0., 1., 0., 0., 0., 1.]);
+    /// assert_eq!(
+    ///     Transform::<2>::from_matrix_checked(&shear, 1e-9).unwrap_err(),
+    ///     KabschError::NotSimilarity
+    /// );
+    /// ```
+    pub fn from_matrix_checked(matrix: &DMatrix<f64>, tol: f64) -> Result<Self, KabschError> {
+        if matrix.shape() != (C + 1, C + 1) {
+            return Err(KabschError::ShapeMismatch {
+                expected: (C + 1) * (C + 1),
+                actual: matrix.len(),
+            });
+        }
+        if matrix.iter().any(|v| !v.is_finite()) {
+            return Err(KabschError::NonFinite);
+        }
+        let homogeneous_row = (0..=C).all(|j| {
+            let expected = if j == C { 1. } else { 0. };
+            (matrix[(C, j)] - expected).abs() <= tol
+        });
+        let linear = matrix.view((0, 0), (C, C)).into_owned();
+        let gram = linear.transpose() * &linear;
+        let scale = (gram.trace() / C as f64).sqrt();
+        let orthogonal = scale > 0.
+            && (gram / (scale * scale) - DMatrix::identity(C, C))
+                .abs()
+                .max()
+                <= tol;
+        if !homogeneous_row || !orthogonal || linear.determinant() <= 0. {
+            return Err(KabschError::NotSimilarity);
+        }
+        Ok(Self::new(
+            SMatrix::from_fn(|i, j| linear[(i, j)] / scale),
+            SVector::from_fn(|i, _| matrix[(i, C)]),
+            scale,
+        ))
+    }
+
+    /// Project the rotation back onto SO(C), e.g. after it drifted through repeated compositions
+    /// or a round trip through `f32`.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::Transform;
+    /// use nalgebra::{Matrix3, Vector3};
+    ///
+    /// let drifted = Matrix3::new(1., 1e-4, 0., 0., 1., 2e-4, 0., 0., 1.00001);
+    /// let mut t = Transform::new(drifted, Vector3::zeros(), 1.);
+    /// t.reorthonormalize();
+    /// assert!((t.rotation().transpose() * t.rotation() - Matrix3::identity()).abs().max() < 1e-12);
+    /// assert!((t.rotation().determinant() - 1.).abs() < 1e-12);
+    /// ```
+    pub fn reorthonormalize(&mut self) {
+        let rotation = DMatrix::from_column_slice(C, C, self.rotation.as_slice());
+        if let Some(rotation) = NalgebraSvd.rotation(&rotation) {
+            self.rotation = SMatrix::from_column_slice(rotation.as_slice());
+            self.inverse = OnceLock::new();
+        }
+    }
+
+    /// Rotation `R`
+    pub fn rotation(&self) -> &SMatrix<f64, C, C> {
+        &self.rotation
+    }
+
+    /// Translation `t`
+    pub fn translation(&self) -> &SVector<f64, C> {
+        &self.translation
+    }
+
+    /// Scale `s`
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Homogeneous `(C+1)x(C+1)` matrix
+    pub fn to_homogeneous(&self) -> DMatrix<f64> {
+        let mut t = DMatrix::identity(C + 1, C + 1);
+        t.view_mut((0, 0), (C, C))
+            .copy_from(&(self.rotation * self.scale));
+        t.view_mut((0, C), (C, 1)).copy_from(&self.translation);
+        t
+    }
+
+    /// Map each row of `points` through the transformation, e.g. an [`Array2`](crate::Array2),
+    /// which the result converts back to.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{estimate_transform, Array2};
+    ///
+    /// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+    /// let dst = Array2::from([[1., 1.], [1., 3.], [-1., 1.]]);
+    ///
+    /// let t = estimate_transform(src, dst, true).unwrap();
+    /// let moved = t.apply(src);
+    /// assert!((moved - nalgebra::SMatrix::from(dst)).abs().max() < 1e-12);
+    /// assert!((Array2::from(moved)[2][0] + 1.).abs() < 1e-12);
+    /// ```
+    pub fn apply<const R: usize>(
+        &self,
+        points: impl Into<SMatrix<f64, R, C>>,
+    ) -> SMatrix<f64, R, C> {
+        let mut points = points.into();
+        points.row_iter_mut().for_each(|mut row| {
+            let moved = self.apply_point(row.transpose());
+            row.copy_from(&moved.transpose());
+        });
+        points
+    }
+
+    /// Map each point of `points` through the transformation in place, e.g. a point cloud read at
+    /// runtime.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::Transform;
+    /// use nalgebra::{Matrix2, Vector2};
+    ///
+    /// let quarter_turn = Transform::new(Matrix2::new(0., -1., 1., 0.), Vector2::new(1., 0.), 2.);
+    /// let mut points = vec![[0., 0.], [1., 0.], [0., 1.]];
+    /// quarter_turn.apply_mut(&mut points);
+    /// assert_eq!(points, [[1., 0.], [1., 2.], [-1., 0.]]);
+    /// ```
+    pub fn apply_mut(&self, points: &mut [[f64; C]]) {
+        for point in points {
+            *point = self.apply_point(SVector::from(*point)).into();
+        }
+    }
+
+    fn apply_point(&self, point: SVector<f64, C>) -> SVector<f64, C> {
+        self.rotation * point * self.scale + self.translation
+    }
+
+    /// Inverse transformation `x ↦ (1/s) Rᵀ (x - t)`, computed once and cached.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{estimate_transform, Array2};
+    ///
+    /// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.]]);
+    /// let dst = Array2::from([[1., 1.], [1., 3.], [-1., 1.]]);
+    ///
+    /// let t = estimate_transform(src, dst, true).unwrap();
+    /// let roundtrip = t.to_homogeneous() * t.inverse().to_homogeneous();
+    /// assert!((roundtrip - nalgebra::DMatrix::identity(3, 3)).abs().max() < 1e-12);
+    /// ```
+    pub fn inverse(&self) -> &Transform<C> {
+        self.inverse.get_or_init(|| {
+            let rotation = self.rotation.transpose();
+            let scale = 1. / self.scale;
+            let translation = -(rotation * self.translation) * scale;
+            Box::new(Self::new(rotation, translation, scale))
+        })
+    }
+}
+
+/// Tolerance on `|s - 1|` of [`Transform::as_isometry3`].
+const UNIT_SCALE_TOL: f64 = 1e-9;
+
+impl Transform<3> {
+    /// Rotation as a unit quaternion, e.g. for robotics messages.
+    ///
+    /// The estimators always return a proper rotation, the reflection being corrected by the sign
+    /// of the determinant; the `None` value is returned for a transformation built from an
+    /// improper rotation (`det(R) < 0`), which has no quaternion.
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{estimate_transform, Array2, Transform};
+    /// use nalgebra::{Matrix3, UnitQuaternion, Vector3};
+    /// use std::f64::consts::FRAC_PI_2;
+    ///
+    /// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+    /// let dst = Array2::from([[0., 0., 0.], [0., 1., 0.], [-1., 0., 0.], [0., 0., 1.]]);
+    /// let t = estimate_transform(src, dst, false).unwrap();
+    ///
+    /// let q = t.as_unit_quaternion().unwrap();
+    /// let expected = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), FRAC_PI_2);
+    /// assert!(q.angle_to(&expected) < 1e-9);
+    ///
+    /// let (axis, angle) = t.as_axis_angle().unwrap();
+    /// assert!((axis - Vector3::z()).norm() < 1e-9 && (angle - FRAC_PI_2).abs() < 1e-9);
+    ///
+    /// let mirror = Transform::new(Matrix3::new(-1., 0., 0., 0., 1., 0., 0., 0., 1.), Vector3::zeros(), 1.);
+    /// assert!(mirror.as_unit_quaternion().is_none());
+    /// ```
+    pub fn as_unit_quaternion(&self) -> Option<UnitQuaternion<f64>> {
+        if self.rotation.determinant() <= 0. {
+            return None;
+        }
+        Some(UnitQuaternion::from_rotation_matrix(
+            &Rotation3::from_matrix(&self.rotation),
+        ))
+    }
+
+    /// Rotation as a unit axis and an angle in `[0, π]` radians, the `x` axis for a zero angle.
+    /// The `None` value is returned for an improper rotation, like [`Transform::as_unit_quaternion`].
+    pub fn as_axis_angle(&self) -> Option<(Vector3<f64>, f64)> {
+        let q = self.as_unit_quaternion()?;
+        Some(q.axis_angle().map_or((Vector3::x(), 0.), |(axis, angle)| {
+            (axis.into_inner(), angle)
+        }))
+    }
+
+    /// Rigid transformation as an isometry, the `None` value if the rotation is improper or if the
+    /// scale is not 1, which an isometry cannot hold (see [`Transform::as_similarity3`]).
+    /// # Examples
+    /// ```
+    /// use kabsch_umeyama::{estimate_transform, Array2};
+    /// use nalgebra::Point3;
+    ///
+    /// let src = Array2::from([[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]]);
+    /// let dst = Array2::from([[1., 2., 3.], [1., 3., 3.], [0., 2., 3.], [1., 2., 4.]]);
+    /// let t = estimate_transform(src, dst, false).unwrap();
+    /// let isometry = t.as_isometry3().unwrap();
+    /// assert!((isometry * Point3::new(1., 0., 0.) - Point3::new(1., 3., 3.)).norm() < 1e-9);
+    ///
+    /// let scaled = estimate_transform(src, Array2::from(dst.map(|p| p.map(|v| v * 2.))), true).unwrap();
+    /// assert!(scaled.as_isometry3().is_none());
+    /// assert!((scaled.as_similarity3().unwrap().scaling() - 2.).abs() < 1e-9);
+    /// ```
+    pub fn as_isometry3(&self) -> Option<Isometry3<f64>> {
+        if (self.scale - 1.).abs() > UNIT_SCALE_TOL {
+            return None;
+        }
+        Some(Isometry3::from_parts(
+            Translation3::from(self.translation),
+            self.as_unit_quaternion()?,
+        ))
+    }
+
+    /// Transformation as a similarity, the `None` value if the rotation is improper.
+    pub fn as_similarity3(&self) -> Option<Similarity3<f64>> {
+        Some(Similarity3::from_parts(
+            Translation3::from(self.translation),
+            self.as_unit_quaternion()?,
+            self.scale,
+        ))
+    }
+}
@@ -0,0 +1,166 @@
+//! Prediction of the target registration error (TRE) of rigid point-based registration under
+//! anisotropic fiducial localization error (FLE), e.g. to plan the fiducial layout of an
+//! image-guided intervention.
+//!
+//! The prediction is the first-order error propagation of Danilchenko and Fitzpatrick, which
+//! extends Fitzpatrick's isotropic formula to any covariance of the FLE of each fiducial, as in
+//! Wiles et al.: the registration is linearized around the fiducials, so that the covariance of
+//! its rotation and translation, then of each displaced target, follows from the FLE covariances.
+use crate::KabschError;
+use nalgebra::{Matrix3, Matrix6, SMatrix, SymmetricEigen, Vector3};
+
+/// Smallest eigenvalue of the normal matrix, relative to the largest, of fiducials constraining
+/// the rotation.
+const RANK_TOL: f64 = 1e-12;
+
+/// Axis-aligned box of targets, sampled on a regular grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegionOfInterest {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    /// Number of samples along each axis, from `min` to `max` included; a single sample is at
+    /// `min`.
+    pub steps: [usize; 3],
+}
+
+/// Root-mean-square TRE of the targets of a [`RegionOfInterest`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreMap {
+    /// Targets, with the x index varying fastest.
+    pub targets: Vec<[f64; 3]>,
+    /// Root-mean-square TRE of each target.
+    pub rms: Vec<f64>,
+}
+
+impl TreMap {
+    /// Largest root-mean-square TRE of the region, `0` if it is empty
+    pub fn max(&self) -> f64 {
+        self.rms.iter().fold(0., |m: f64, v| m.max(*v))
+    }
+
+    /// Target with the largest root-mean-square TRE, `None` if the region is empty
+    pub fn worst(&self) -> Option<[f64; 3]> {
+        let index = (0..self.rms.len()).max_by(|i, j| self.rms[*i].total_cmp(&self.rms[*j]))?;
+        Some(self.targets[index])
+    }
+}
+
+/// First-order TRE predictor of the rigid registration of a fiducial configuration.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::tre::{RegionOfInterest, TrePredictor};
+/// use nalgebra::Matrix3;
+///
+/// let fiducials = [
+///     [1., 0., 0.], [-1., 0., 0.], [0., 1., 0.], [0., -1., 0.], [0., 0., 1.], [0., 0., -1.],
+/// ];
+/// let sigma = 0.1;
+/// let isotropic = [Matrix3::identity() * sigma * sigma; 6];
+/// let predictor = TrePredictor::new(&fiducials, &isotropic).unwrap();
+///
+/// // Fitzpatrick's formula: FLE²/N (1 + 1/3 Σ d²/f²), with f² = 2/3 about each axis
+/// let expected = 3. * sigma * sigma / 6. * (1. + (4. + 4.) / 3. / (2. / 3.));
+/// assert!((predictor.rms(&[2., 0., 0.]).powi(2) - expected).abs() < 1e-12);
+///
+/// // localizing the fiducials poorly along z mostly hurts the targets along z
+/// let anisotropic = [Matrix3::from_diagonal(&[0.01, 0.01, 0.25].into()); 6];
+/// let predictor = TrePredictor::new(&fiducials, &anisotropic).unwrap();
+/// let region = RegionOfInterest { min: [-2.; 3], max: [2.; 3], steps: [5; 3] };
+/// let map = predictor.map(&region);
+/// assert_eq!(map.rms.len(), 125);
+/// assert!(predictor.rms(&[0., 0., 2.]) > predictor.rms(&[2., 0., 0.]));
+/// assert_eq!(map.max(), predictor.rms(&map.worst().unwrap()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TrePredictor {
+    /// Centroid of the fiducials, around which the rotation is linearized.
+    centroid: Vector3<f64>,
+    /// Covariance of the small rotation vector and the translation of the registration.
+    covariance: Matrix6<f64>,
+}
+
+impl TrePredictor {
+    /// New TrePredictor of the fiducials, in the space where they are localized, and the FLE
+    /// covariance of each of them.
+    /// A [`KabschError::ShapeMismatch`] error is returned if the numbers of fiducials and
+    /// covariances differ, a [`KabschError::DegenerateConfiguration`] error if the fiducials are
+    /// fewer than 3 or collinear, and a [`KabschError::NonFinite`] error for non-finite values.
+    pub fn new(fiducials: &[[f64; 3]], fle: &[Matrix3<f64>]) -> Result<Self, KabschError> {
+        if fiducials.len() != fle.len() {
+            return Err(KabschError::ShapeMismatch {
+                expected: fiducials.len(),
+                actual: fle.len(),
+            });
+        }
+        if fiducials.is_empty() {
+            return Err(KabschError::Empty);
+        }
+        let mut values = fiducials.iter().flatten().chain(fle.iter().flatten());
+        if values.any(|v| !v.is_finite()) {
+            return Err(KabschError::NonFinite);
+        }
+        let centroid = fiducials
+            .iter()
+            .map(|p| Vector3::from(*p))
+            .sum::<Vector3<f64>>()
+            / fiducials.len() as f64;
+        let mut normal = Matrix6::zeros();
+        let mut noise = Matrix6::zeros();
+        for (p, sigma) in fiducials.iter().zip(fle) {
+            let j = jacobian(&(Vector3::from(*p) - centroid));
+            normal += j.transpose() * j;
+            noise += j.transpose() * sigma * j;
+        }
+        let eigenvalues = SymmetricEigen::new(normal).eigenvalues;
+        if eigenvalues.min() <= RANK_TOL * eigenvalues.max() {
+            return Err(KabschError::DegenerateConfiguration);
+        }
+        let inverse = normal
+            .try_inverse()
+            .ok_or(KabschError::DegenerateConfiguration)?;
+        Ok(Self {
+            centroid,
+            covariance: inverse * noise * inverse,
+        })
+    }
+
+    /// Covariance of the displacement of `target` by the registration error
+    pub fn covariance(&self, target: &[f64; 3]) -> Matrix3<f64> {
+        let j = jacobian(&(Vector3::from(*target) - self.centroid));
+        j * self.covariance * j.transpose()
+    }
+
+    /// Root-mean-square TRE at `target`, the square root of the trace of its covariance
+    pub fn rms(&self, target: &[f64; 3]) -> f64 {
+        self.covariance(target).trace().max(0.).sqrt()
+    }
+
+    /// Root-mean-square TRE over the grid of a region of interest
+    pub fn map(&self, region: &RegionOfInterest) -> TreMap {
+        let axis = |k: usize, i: usize| {
+            let steps = region.steps[k];
+            if steps <= 1 {
+                region.min[k]
+            } else {
+                region.min[k] + (region.max[k] - region.min[k]) * i as f64 / (steps - 1) as f64
+            }
+        };
+        let [nx, ny, nz] = region.steps;
+        let targets = (0..nz)
+            .flat_map(|k| (0..ny).flat_map(move |j| (0..nx).map(move |i| (i, j, k))))
+            .map(|(i, j, k)| [axis(0, i), axis(1, j), axis(2, k)])
+            .collect::<Vec<_>>();
+        let rms = targets.iter().map(|t| self.rms(t)).collect();
+        TreMap { targets, rms }
+    }
+}
+
+/// Derivative of the registered position of `p`, relative to the centroid, with respect to the
+/// small rotation vector `ω` and the translation `t`: `p + ω × p + t`.
+fn jacobian(p: &Vector3<f64>) -> SMatrix<f64, 3, 6> {
+    let mut j = SMatrix::<f64, 3, 6>::zeros();
+    j.fixed_view_mut::<3, 3>(0, 0).copy_from(&-p.cross_matrix());
+    j.fixed_view_mut::<3, 3>(0, 3)
+        .copy_from(&Matrix3::identity());
+    j
+}
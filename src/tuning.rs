@@ -0,0 +1,231 @@
+//! Automatic tuning of the ICP parameters on a representative pair of point sets, scored against
+//! a ground-truth transformation or, without one, by the consistency of the forward and backward
+//! registrations.
+use crate::icp::{icp_from, IcpOptions};
+use crate::{KabschError, Transform};
+use nalgebra::SVector;
+use std::collections::HashMap;
+
+/// Reference against which the candidate parameters are scored.
+#[derive(Clone, Debug)]
+pub enum Reference<const C: usize> {
+    /// Known transformation mapping the source points onto the destination points.
+    GroundTruth(Transform<C>),
+    /// Registration of `dst` back onto `src`, which should undo the forward registration.
+    SelfConsistency,
+}
+
+/// Candidate parameters of [`tune_icp`].
+#[derive(Clone, Debug)]
+pub struct TuningOptions {
+    /// Candidate [`IcpOptions::trim_fraction`] values, rejecting outliers and partial overlap.
+    pub trim_fractions: Vec<f64>,
+    /// Candidate edge lengths of the voxels the source points are averaged in before
+    /// registration, as fractions of the root-mean-square radius of the source points; `0` keeps
+    /// every point.
+    pub voxel_sizes: Vec<f64>,
+    /// Options of ICP other than the tuned ones.
+    pub icp: IcpOptions,
+}
+
+impl Default for TuningOptions {
+    fn default() -> Self {
+        Self {
+            trim_fractions: vec![0., 0.05, 0.1, 0.2, 0.3, 0.4, 0.5],
+            voxel_sizes: vec![0., 0.02, 0.05, 0.1],
+            icp: IcpOptions::default(),
+        }
+    }
+}
+
+/// Score of a candidate of [`tune_icp`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trial {
+    pub trim_fraction: f64,
+    /// Absolute edge length of the voxels, `0` if the points were kept.
+    pub voxel_size: f64,
+    /// Root-mean-square distance between the source points moved by the registration and by the
+    /// reference, the infinity if the registration failed.
+    pub error: f64,
+    /// Iterations run by ICP, summed over both directions for [`Reference::SelfConsistency`].
+    pub iterations: usize,
+}
+
+/// Result of [`tune_icp`].
+#[derive(Clone, Debug)]
+pub struct Tuning {
+    /// ICP options with the best trimming fraction.
+    pub icp: IcpOptions,
+    /// Best trial, whose absolute voxel size should be used to downsample the source points.
+    pub best: Trial,
+    /// Every trial, from the largest voxels, in the order of the candidate trimming fractions.
+    pub trials: Vec<Trial>,
+}
+
+/// Search the candidate trimming fractions and voxel sizes for the ICP parameters registering
+/// `src` onto `dst` from `initial` with the lowest error against the `reference`, among equal
+/// errors the largest voxels, which are the fastest to register.
+///
+/// A [`KabschError::Empty`] error is returned if a point set or a candidate list is empty.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::testing::Rng;
+/// use kabsch_umeyama::tuning::{tune_icp, Reference, TuningOptions};
+/// use kabsch_umeyama::Transform;
+/// use nalgebra::{Rotation3, Vector3};
+///
+/// let mut rng = Rng::new(7);
+/// let src = (0..300)
+///     .map(|_| [rng.range(-1., 1.), rng.range(-1., 1.), rng.range(-0.5, 0.5)])
+///     .collect::<Vec<_>>();
+/// let rotation = Rotation3::from_euler_angles(0.05, -0.03, 0.1).into_inner();
+/// let truth = Transform::new(rotation, Vector3::new(0.05, -0.02, 0.03), 1.);
+/// let mut dst = src.clone();
+/// truth.apply_mut(&mut dst);
+/// // a quarter of the source points were not seen in the destination scan
+/// dst.truncate(225);
+///
+/// let reference = Reference::GroundTruth(truth);
+/// let options = TuningOptions::default();
+/// let tuning = tune_icp(&src, &dst, &Transform::identity(), &reference, &options).unwrap();
+/// assert_eq!(tuning.trials.len(), 7 * 4);
+/// assert!(tuning.icp.trim_fraction >= 0.2);
+/// let untrimmed = tuning.trials.iter().find(|t| t.trim_fraction == 0. && t.voxel_size == 0.);
+/// assert!(tuning.best.error < untrimmed.unwrap().error / 10.);
+/// ```
+pub fn tune_icp<const C: usize>(
+    src: &[[f64; C]],
+    dst: &[[f64; C]],
+    initial: &Transform<C>,
+    reference: &Reference<C>,
+    options: &TuningOptions,
+) -> Result<Tuning, KabschError> {
+    if src.is_empty()
+        || dst.is_empty()
+        || options.trim_fractions.is_empty()
+        || options.voxel_sizes.is_empty()
+    {
+        return Err(KabschError::Empty);
+    }
+    let radius = rms_radius(src);
+    let mut voxel_sizes = options
+        .voxel_sizes
+        .iter()
+        .map(|v| v * radius)
+        .collect::<Vec<_>>();
+    voxel_sizes.sort_by(|a, b| b.total_cmp(a));
+
+    let mut trials = Vec::with_capacity(options.trim_fractions.len() * voxel_sizes.len());
+    for &voxel_size in &voxel_sizes {
+        let sampled = downsample(src, voxel_size);
+        for &trim_fraction in &options.trim_fractions {
+            let icp = IcpOptions {
+                trim_fraction,
+                ..options.icp
+            };
+            let (error, iterations) = score(src, &sampled, dst, initial, reference, &icp);
+            trials.push(Trial {
+                trim_fraction,
+                voxel_size,
+                error,
+                iterations,
+            });
+        }
+    }
+    // the trials go from the largest voxels, which win ties
+    let tie = 1e-9 * radius;
+    let best = trials.iter().fold(trials[0], |best, t| {
+        if t.error < best.error - tie {
+            *t
+        } else {
+            best
+        }
+    });
+    Ok(Tuning {
+        icp: IcpOptions {
+            trim_fraction: best.trim_fraction,
+            ..options.icp
+        },
+        best,
+        trials,
+    })
+}
+
+/// Error of the registration of `sampled` onto `dst` and its number of iterations
+fn score<const C: usize>(
+    src: &[[f64; C]],
+    sampled: &[[f64; C]],
+    dst: &[[f64; C]],
+    initial: &Transform<C>,
+    reference: &Reference<C>,
+    icp: &IcpOptions,
+) -> (f64, usize) {
+    let Ok(forward) = icp_from(sampled, dst, initial.clone(), icp) else {
+        return (f64::INFINITY, 0);
+    };
+    let mut moved = src.to_vec();
+    forward.transform.apply_mut(&mut moved);
+    match reference {
+        Reference::GroundTruth(truth) => {
+            let mut expected = src.to_vec();
+            truth.apply_mut(&mut expected);
+            (rms_distance(&moved, &expected), forward.rmse.len())
+        }
+        Reference::SelfConsistency => {
+            let backward_initial = forward.transform.inverse().clone();
+            let Ok(backward) = icp_from(dst, sampled, backward_initial, icp) else {
+                return (f64::INFINITY, forward.rmse.len());
+            };
+            backward.transform.apply_mut(&mut moved);
+            (
+                rms_distance(&moved, src),
+                forward.rmse.len() + backward.rmse.len(),
+            )
+        }
+    }
+}
+
+fn rms_distance<const C: usize>(a: &[[f64; C]], b: &[[f64; C]]) -> f64 {
+    let squared = a
+        .iter()
+        .zip(b)
+        .map(|(p, q)| (SVector::from(*p) - SVector::from(*q)).norm_squared())
+        .sum::<f64>();
+    (squared / a.len() as f64).sqrt()
+}
+
+/// Root-mean-square distance of the points to their centroid
+fn rms_radius<const C: usize>(points: &[[f64; C]]) -> f64 {
+    let center = points
+        .iter()
+        .map(|p| SVector::from(*p))
+        .sum::<SVector<f64, C>>()
+        / points.len() as f64;
+    rms_distance(points, &vec![center.into(); points.len()])
+}
+
+/// Centroid of the points in each voxel of the given edge length, the points themselves if it
+/// is not positive.
+fn downsample<const C: usize>(points: &[[f64; C]], voxel_size: f64) -> Vec<[f64; C]> {
+    if voxel_size <= 0. {
+        return points.to_vec();
+    }
+    let mut voxels = HashMap::<[i64; C], (SVector<f64, C>, usize)>::new();
+    let mut order = Vec::new();
+    for p in points {
+        let key = p.map(|v| (v / voxel_size).floor() as i64);
+        let (sum, count) = voxels.entry(key).or_insert_with(|| {
+            order.push(key);
+            (SVector::zeros(), 0)
+        });
+        *sum += SVector::from(*p);
+        *count += 1;
+    }
+    order
+        .iter()
+        .map(|key| {
+            let (sum, count) = voxels[key];
+            (sum / count as f64).into()
+        })
+        .collect()
+}
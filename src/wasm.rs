@@ -0,0 +1,45 @@
+//! JavaScript bindings (requires the `wasm-bindgen` feature), e.g. for landmark alignment in the
+//! browser. Build for `wasm32-unknown-unknown` without the default `lapack` feature:
+//! `cargo build --target wasm32-unknown-unknown --no-default-features --features wasm-bindgen`,
+//! then run `wasm-bindgen` on the output.
+use crate::estimate_dyn;
+use nalgebra::DMatrix;
+use wasm_bindgen::prelude::*;
+
+/// Estimate the similarity transformation between `rows` points of `cols` dimensions stored
+/// row-major in `src` and `dst`, like [`crate::estimate_dyn`], returning the `(cols+1)x(cols+1)`
+/// homogeneous matrix flattened in row-major order.
+/// A JavaScript error is thrown if the arrays do not hold `rows * cols` values, or if the
+/// estimation fails.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::wasm::estimate;
+///
+/// let src = [0., 0., 1., 0., 0., 1.];
+/// let dst = [1., 1., 1., 3., -1., 1.];
+/// let t = estimate(&src, &dst, 3, 2, true).unwrap();
+/// assert_eq!(t.len(), 9);
+/// assert!((t[3] - 2.).abs() < 1e-12 && (t[2] - 1.).abs() < 1e-12);
+/// ```
+#[wasm_bindgen]
+pub fn estimate(
+    src: &[f64],
+    dst: &[f64],
+    rows: usize,
+    cols: usize,
+    scale: bool,
+) -> Result<Vec<f64>, JsError> {
+    let len = rows.checked_mul(cols);
+    for values in [src, dst] {
+        if len != Some(values.len()) {
+            return Err(JsError::new(&format!(
+                "expected {rows} x {cols} values, got {}",
+                values.len()
+            )));
+        }
+    }
+    let src = DMatrix::from_row_slice(rows, cols, src);
+    let dst = DMatrix::from_row_slice(rows, cols, dst);
+    let matrix = estimate_dyn(&src, &dst, scale).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(matrix.transpose().as_slice().to_vec())
+}
@@ -0,0 +1,100 @@
+use crate::{fit, fit_points, rows, DefaultSolver, Transform};
+use nalgebra::{DMatrix, DVector, SMatrix, SVector};
+
+/// Estimate a similarity transformation like [`crate::estimate`], weighting each correspondence,
+/// e.g. by its matching confidence: the means, the cross-covariance and the scale are weighted
+/// averages, so that only the relative weights matter.
+/// The `None` value is returned if a weight is negative or not finite, the weights sum to zero, or
+/// the problem is not well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate, estimate_weighted, Array2};
+///
+/// let src = Array2::from([[0., 0.], [1., 0.], [0., 1.], [1., 1.]]);
+/// // the last correspondence is a low-confidence mismatch
+/// let dst = Array2::from([[1., 0.], [2., 0.], [1., 1.], [3., 3.]]);
+///
+/// let weighted = estimate_weighted(src, dst, &[1., 1., 1., 0.], true).unwrap();
+/// assert!((weighted[(0, 2)] - 1.).abs() < 1e-12 && (weighted[(0, 0)] - 1.).abs() < 1e-12);
+/// assert!((estimate(src, dst, true).unwrap() - &weighted).abs().max() > 0.1);
+///
+/// assert!(estimate_weighted(src, dst, &[0.; 4], true).is_none());
+/// ```
+pub fn estimate_weighted<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    weights: &[f64; R],
+    estimate_scale: bool,
+) -> Option<DMatrix<f64>> {
+    if weights.iter().any(|w| !(w.is_finite() && *w >= 0.)) || weights.iter().sum::<f64>() <= 0. {
+        return None;
+    }
+    fit_points(
+        rows(src.into()),
+        rows(dst.into()),
+        &DVector::from_column_slice(weights),
+        estimate_scale,
+        &DefaultSolver,
+    )
+    .ok()
+    .map(|fit| fit.transform)
+}
+
+/// Estimate a similarity transformation weighting each correspondence by `1/σ²`, where `σ` is the
+/// measurement error of the device at a point of the `dst` frame, e.g. the depth-dependent noise of
+/// an RGB-D camera.
+///
+/// Evaluating the error map at the noisy measurements would bias the weights, so it is evaluated at
+/// the predicted positions of the `src` points instead: starting from the unweighted estimate, the
+/// weights and the estimate are alternately updated `iterations` times.
+/// The `None` value is returned if a `σ` is not positive and finite, or if the problem is not
+/// well-conditioned.
+/// # Examples
+/// ```
+/// use kabsch_umeyama::{estimate_transform, estimate_with_sigma, Array2};
+///
+/// let src = Array2::from([[0., 0., 1.], [1., 0., 1.], [0., 1., 1.], [0., 0., 10.]]);
+/// // the far point is measured with a large error
+/// let dst = Array2::from([[0., 0., 1.], [1., 0., 1.], [0., 1., 1.], [0.5, 0., 10.]]);
+///
+/// // depth noise grows quadratically with range
+/// let weighted = estimate_with_sigma(src, dst, false, |p| 0.01 * p.z * p.z, 3).unwrap();
+/// let unweighted = estimate_transform(src, dst, false).unwrap();
+/// assert!(weighted.translation().norm() < 0.01);
+/// assert!(unweighted.translation().norm() > 0.05);
+/// ```
+pub fn estimate_with_sigma<const R: usize, const C: usize>(
+    src: impl Into<SMatrix<f64, R, C>>,
+    dst: impl Into<SMatrix<f64, R, C>>,
+    estimate_scale: bool,
+    sigma: impl Fn(&SVector<f64, C>) -> f64,
+    iterations: usize,
+) -> Option<Transform<C>> {
+    let src = src.into();
+    let dst = dst.into();
+    let mut transform = fit(src, dst, estimate_scale, &DefaultSolver)
+        .ok()?
+        .to_transform::<C>();
+    for _ in 0..iterations {
+        let mut weights = DVector::<f64>::zeros(R);
+        for (i, weight) in weights.iter_mut().enumerate() {
+            let predicted = transform.rotation() * src.row(i).transpose() * transform.scale()
+                + transform.translation();
+            let sigma = sigma(&predicted);
+            if !(sigma.is_finite() && sigma > 0.) {
+                return None;
+            }
+            *weight = sigma.powi(-2);
+        }
+        transform = fit_points(
+            rows(src),
+            rows(dst),
+            &weights,
+            estimate_scale,
+            &DefaultSolver,
+        )
+        .ok()?
+        .to_transform();
+    }
+    Some(transform)
+}